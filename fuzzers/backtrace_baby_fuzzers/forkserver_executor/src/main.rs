@@ -22,7 +22,7 @@ use libafl_bolts::shmem::StdShMemProvider;
 use libafl_bolts::shmem::UnixShMemProvider;
 use libafl_bolts::{
     rands::StdRand,
-    shmem::{ShMem, ShMemProvider},
+    shmem::{ShMem, ShMemProvider, ShMemPurpose},
     tuples::tuple_list,
     AsSliceMut,
 };
@@ -38,7 +38,9 @@ pub fn main() {
     #[cfg(not(target_vendor = "apple"))]
     let mut shmem_provider = StdShMemProvider::new().unwrap();
 
-    let mut shmem = shmem_provider.new_shmem(MAP_SIZE).unwrap();
+    let mut shmem = shmem_provider
+        .new_shmem_with_purpose(MAP_SIZE, ShMemPurpose::CoverageMap)
+        .unwrap();
     //let the forkserver know the shmid
     shmem.write_to_env("__AFL_SHM_ID").unwrap();
     let shmem_map = shmem.as_slice_mut();
@@ -21,7 +21,7 @@ use libafl::{
 use libafl_bolts::{
     current_nanos,
     rands::StdRand,
-    shmem::{ShMem, ShMemProvider, UnixShMemProvider},
+    shmem::{ShMem, ShMemProvider, ShMemPurpose, UnixShMemProvider},
     tuples::{tuple_list, Handled, Merge},
     AsSliceMut, Truncate,
 };
@@ -95,7 +95,9 @@ pub fn main() {
     let mut shmem_provider = UnixShMemProvider::new().unwrap();
 
     // The coverage map shared between observer and executor
-    let mut shmem = shmem_provider.new_shmem(MAP_SIZE).unwrap();
+    let mut shmem = shmem_provider
+        .new_shmem_with_purpose(MAP_SIZE, ShMemPurpose::CoverageMap)
+        .unwrap();
     // let the forkserver know the shmid
     shmem.write_to_env("__AFL_SHM_ID").unwrap();
     let shmem_buf = shmem.as_slice_mut();
@@ -41,7 +41,7 @@ use libafl_bolts::{
     current_time,
     os::{dup2, unix_signals::Signal},
     rands::StdRand,
-    shmem::{ShMemProvider, StdShMemProvider},
+    shmem::{ShMemProvider, ShMemPurpose, StdShMemProvider},
     tuples::{tuple_list, Merge},
     AsSlice, AsSliceMut,
 };
@@ -208,7 +208,9 @@ fn fuzz(
 
     let mut shmem_provider = StdShMemProvider::new()?;
 
-    let mut edges_shmem = shmem_provider.new_shmem(EDGES_MAP_SIZE_IN_USE).unwrap();
+    let mut edges_shmem = shmem_provider
+        .new_shmem_with_purpose(EDGES_MAP_SIZE_IN_USE, ShMemPurpose::CoverageMap)
+        .unwrap();
     let edges = edges_shmem.as_slice_mut();
     unsafe { EDGES_MAP_PTR = edges.as_mut_ptr() };
 
@@ -22,7 +22,7 @@ use libafl_bolts::{
     core_affinity::Cores,
     os::unix_signals::Signal,
     rands::StdRand,
-    shmem::{ShMemProvider, StdShMemProvider},
+    shmem::{ShMemProvider, ShMemPurpose, StdShMemProvider},
     tuples::tuple_list,
     AsSlice, AsSliceMut,
 };
@@ -156,7 +156,9 @@ pub fn fuzz() -> Result<(), Error> {
         },
     };
 
-    let mut edges_shmem = shmem_provider.new_shmem(EDGES_MAP_SIZE_IN_USE).unwrap();
+    let mut edges_shmem = shmem_provider
+        .new_shmem_with_purpose(EDGES_MAP_SIZE_IN_USE, ShMemPurpose::CoverageMap)
+        .unwrap();
     let edges = edges_shmem.as_slice_mut();
     unsafe { EDGES_MAP_PTR = edges.as_mut_ptr() };
 
@@ -27,6 +27,7 @@ use libafl_bolts::{
     tuples::tuple_list,
 };
 use libafl_qemu::{
+    command::QemuCrashReasonFeedback,
     edges::{edges_map_mut_ptr, QemuEdgeCoverageHelper, EDGES_MAP_SIZE_IN_USE, MAX_EDGES_FOUND},
     emu::Emulator,
     executor::{stateful::StatefulQemuExecutor, QemuExecutorState},
@@ -93,7 +94,12 @@ pub fn fuzz() {
         );
 
         // A feedback to choose if an input is a solution or not
-        let mut objective = feedback_or_fast!(CrashFeedback::new(), TimeoutFeedback::new());
+        // The guest may give a reason for crashing with LIBAFL_QEMU_ASSERT, keep it in the testcase
+        let mut objective = feedback_or_fast!(
+            CrashFeedback::new(),
+            TimeoutFeedback::new(),
+            QemuCrashReasonFeedback::new()
+        );
 
         // If not restarting, create a State from scratch
         let mut state = state.unwrap_or_else(|| {
@@ -12,8 +12,14 @@ use std::{
     path::{Path, PathBuf},
 };
 
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+use libafl_bolts::compress::Compressor;
 #[cfg(feature = "gzip")]
 use libafl_bolts::compress::GzipCompressor;
+#[cfg(feature = "lz4")]
+use libafl_bolts::compress::Lz4Compressor;
+#[cfg(feature = "zstd")]
+use libafl_bolts::compress::ZstdCompressor;
 use libafl_bolts::serdeany::SerdeAnyMap;
 use serde::{Deserialize, Serialize};
 
@@ -438,6 +444,14 @@ where
                 OnDiskMetadataFormat::JsonGzip => {
                     GzipCompressor::new().compress(&serde_json::to_vec_pretty(&ondisk_meta)?)
                 }
+                #[cfg(feature = "zstd")]
+                OnDiskMetadataFormat::JsonZstd => {
+                    ZstdCompressor::new().compress(&serde_json::to_vec_pretty(&ondisk_meta)?)
+                }
+                #[cfg(feature = "lz4")]
+                OnDiskMetadataFormat::JsonLz4 => {
+                    Lz4Compressor::new().compress(&serde_json::to_vec_pretty(&ondisk_meta)?)
+                }
             };
             tmpfile.write_all(&serialized)?;
             fs::rename(&tmpfile_path, &metafile_path)?;
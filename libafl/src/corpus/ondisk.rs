@@ -32,6 +32,12 @@ pub enum OnDiskMetadataFormat {
     /// The same as [`OnDiskMetadataFormat::JsonPretty`], but compressed
     #[cfg(feature = "gzip")]
     JsonGzip,
+    /// The same as [`OnDiskMetadataFormat::JsonPretty`], but compressed using zstd
+    #[cfg(feature = "zstd")]
+    JsonZstd,
+    /// The same as [`OnDiskMetadataFormat::JsonPretty`], but compressed using lz4
+    #[cfg(feature = "lz4")]
+    JsonLz4,
 }
 
 /// The [`Testcase`] metadata that'll be stored to disk
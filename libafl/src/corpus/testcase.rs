@@ -504,7 +504,11 @@ impl SchedulerTestcaseMetadata {
     }
 }
 
-libafl_bolts::impl_serdeany!(SchedulerTestcaseMetadata);
+libafl_bolts::impl_serdeany!(
+    SchedulerTestcaseMetadata,
+    name = "libafl::corpus::SchedulerTestcaseMetadata",
+    version = 1
+);
 
 #[cfg(feature = "std")]
 impl<I> Drop for Testcase<I>
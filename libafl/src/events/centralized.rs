@@ -14,8 +14,8 @@ use core::{marker::PhantomData, num::NonZeroUsize, time::Duration};
 use libafl_bolts::tuples::{Handle, Handled};
 #[cfg(feature = "llmp_compression")]
 use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
+    compress::CompressorSet,
+    llmp::{Flags, LLMP_FLAG_INITIALIZED},
 };
 use libafl_bolts::{
     llmp::{self, LlmpBroker, LlmpClient, LlmpClientDescription, Tag},
@@ -32,6 +32,7 @@ use crate::observers::TimeObserver;
 use crate::state::HasScalabilityMonitor;
 use crate::{
     events::{
+        filters::{BrokerEventFilter, BrokerEventFilters},
         AdaptiveSerializer, BrokerEventResult, CustomBufEventResult, Event, EventConfig,
         EventFirer, EventManager, EventManagerId, EventProcessor, EventRestarter,
         HasCustomBufHandlers, HasEventManagerId, LogSeverity, ProgressReporter,
@@ -39,6 +40,7 @@ use crate::{
     executors::{Executor, HasObservers},
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
     inputs::{Input, UsesInput},
+    monitors::Monitor,
     observers::ObserversTuple,
     state::{HasExecutions, HasLastReportTime, UsesState},
     Error, HasMetadata,
@@ -47,22 +49,26 @@ use crate::{
 const _LLMP_TAG_TO_MAIN: Tag = Tag(0x3453453);
 
 /// An LLMP-backed event manager for scalable multi-processed fuzzing
-pub struct CentralizedLlmpEventBroker<I, SP>
+pub struct CentralizedLlmpEventBroker<I, MT, SP>
 where
     I: Input,
     SP: ShMemProvider + 'static,
+    MT: Monitor,
     //CE: CustomEvent<I>,
 {
+    monitor: MT,
     llmp: LlmpBroker<SP>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CompressorSet,
+    filters: BrokerEventFilters<I>,
     phantom: PhantomData<I>,
 }
 
-impl<I, SP> core::fmt::Debug for CentralizedLlmpEventBroker<I, SP>
+impl<I, MT, SP> core::fmt::Debug for CentralizedLlmpEventBroker<I, MT, SP>
 where
     SP: ShMemProvider + 'static,
     I: Input,
+    MT: Monitor,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut debug_struct = f.debug_struct("CentralizedLlmpEventBroker");
@@ -71,22 +77,27 @@ where
         #[cfg(feature = "llmp_compression")]
         let debug = debug.field("compressor", &self.compressor);
         debug
+            .field("filters", &self.filters)
             .field("phantom", &self.phantom)
             .finish_non_exhaustive()
     }
 }
 
-impl<I, SP> CentralizedLlmpEventBroker<I, SP>
+impl<I, MT, SP> CentralizedLlmpEventBroker<I, MT, SP>
 where
     I: Input,
     SP: ShMemProvider + 'static,
+    MT: Monitor,
 {
     /// Create an event broker from a raw broker.
-    pub fn new(llmp: LlmpBroker<SP>) -> Result<Self, Error> {
+    /// The `monitor` reports how many testcases the filters of this broker dropped.
+    pub fn new(llmp: LlmpBroker<SP>, monitor: MT) -> Result<Self, Error> {
         Ok(Self {
+            monitor,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
+            filters: BrokerEventFilters::default(),
             phantom: PhantomData,
         })
     }
@@ -95,12 +106,14 @@ where
     ///
     /// The port must not be bound yet to have a broker.
     #[cfg(feature = "std")]
-    pub fn on_port(shmem_provider: SP, port: u16) -> Result<Self, Error> {
+    pub fn on_port(shmem_provider: SP, monitor: MT, port: u16) -> Result<Self, Error> {
         Ok(Self {
+            monitor,
             // TODO switch to false after solving the bug
             llmp: LlmpBroker::with_keep_pages_attach_to_tcp(shmem_provider, port, true)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
+            filters: BrokerEventFilters::default(),
             phantom: PhantomData,
         })
     }
@@ -110,11 +123,27 @@ where
         self.llmp.set_exit_cleanly_after(n_clients);
     }
 
+    /// Add a [`BrokerEventFilter`], deciding which new testcases get forwarded to the main node.
+    /// The number of dropped testcases is reported to the monitor, per client and filter.
+    pub fn add_filter<F>(&mut self, filter: F)
+    where
+        F: BrokerEventFilter<I> + 'static,
+    {
+        self.filters.push(filter);
+    }
+
+    /// The filters of this broker, and how many testcases they dropped
+    pub fn filters(&self) -> &BrokerEventFilters<I> {
+        &self.filters
+    }
+
     /// Run forever in the broker
     #[cfg(not(feature = "llmp_broker_timeouts"))]
     pub fn broker_loop(&mut self) -> Result<(), Error> {
+        let monitor = &mut self.monitor;
         #[cfg(feature = "llmp_compression")]
         let compressor = &self.compressor;
+        let filters = &mut self.filters;
         self.llmp.loop_forever(
             &mut |client_id, tag, _flags, msg| {
                 if tag == _LLMP_TAG_TO_MAIN {
@@ -123,14 +152,14 @@ where
                     #[cfg(feature = "llmp_compression")]
                     let compressed;
                     #[cfg(feature = "llmp_compression")]
-                    let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                        compressed = compressor.decompress(msg)?;
+                    let event_bytes = if let Some(algorithm) = _flags.compression() {
+                        compressed = compressor.decompress(algorithm, msg)?;
                         &compressed
                     } else {
                         msg
                     };
                     let event: Event<I> = postcard::from_bytes(event_bytes)?;
-                    match Self::handle_in_broker(client_id, &event)? {
+                    match Self::handle_in_broker(monitor, filters, client_id, &event)? {
                         BrokerEventResult::Forward => Ok(llmp::LlmpMsgHookResult::ForwardToClients),
                         BrokerEventResult::Handled => Ok(llmp::LlmpMsgHookResult::Handled),
                     }
@@ -150,8 +179,10 @@ where
     /// Run in the broker until all clients exit
     #[cfg(feature = "llmp_broker_timeouts")]
     pub fn broker_loop(&mut self) -> Result<(), Error> {
+        let monitor = &mut self.monitor;
         #[cfg(feature = "llmp_compression")]
         let compressor = &self.compressor;
+        let filters = &mut self.filters;
         self.llmp.loop_with_timeouts(
             &mut |msg_or_timeout| {
                 if let Some((client_id, tag, _flags, msg)) = msg_or_timeout {
@@ -161,14 +192,14 @@ where
                         #[cfg(feature = "llmp_compression")]
                         let compressed;
                         #[cfg(feature = "llmp_compression")]
-                        let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                            compressed = compressor.decompress(msg)?;
+                        let event_bytes = if let Some(algorithm) = _flags.compression() {
+                            compressed = compressor.decompress(algorithm, msg)?;
                             &compressed
                         } else {
                             msg
                         };
                         let event: Event<I> = postcard::from_bytes(event_bytes)?;
-                        match Self::handle_in_broker(client_id, &event)? {
+                        match Self::handle_in_broker(monitor, filters, client_id, &event)? {
                             BrokerEventResult::Forward => {
                                 Ok(llmp::LlmpMsgHookResult::ForwardToClients)
                             }
@@ -178,6 +209,7 @@ where
                         Ok(llmp::LlmpMsgHookResult::ForwardToClients)
                     }
                 } else {
+                    monitor.display("Broker Heartbeat", ClientId(0));
                     Ok(llmp::LlmpMsgHookResult::Handled)
                 }
             },
@@ -194,7 +226,9 @@ where
     /// Handle arriving events in the broker
     #[allow(clippy::unnecessary_wraps)]
     fn handle_in_broker(
-        _client_id: ClientId,
+        monitor: &mut MT,
+        filters: &mut BrokerEventFilters<I>,
+        client_id: ClientId,
         event: &Event<I>,
     ) -> Result<BrokerEventResult, Error> {
         match &event {
@@ -207,13 +241,24 @@ where
                 time: _,
                 executions: _,
                 forward_id: _,
-            } => Ok(BrokerEventResult::Forward),
+            } => {
+                if let Some(filter) = filters.filter_and_report(monitor, client_id, event)? {
+                    log::debug!("{filter} dropped a testcase of client {client_id:?}");
+                    Ok(BrokerEventResult::Handled)
+                } else {
+                    Ok(BrokerEventResult::Forward)
+                }
+            }
             _ => Ok(BrokerEventResult::Handled),
         }
     }
 }
 
 /// A wrapper manager to implement a main-secondary architecture witgh another broker
+///
+/// Compression dictionaries are not supported on the channel to the main node.
+/// Dictionaries shared with `LlmpEventManager::share_dictionary` only reach
+/// the inner managers, events to the main node are compressed without them.
 #[derive(Debug)]
 pub struct CentralizedEventManager<EM, SP>
 where
@@ -224,7 +269,7 @@ where
     /// The LLMP client for inter process communication
     client: LlmpClient<SP>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CompressorSet,
     #[cfg(feature = "adaptive_serialization")]
     time_ref: Handle<TimeObserver>,
     is_main: bool,
@@ -475,7 +520,7 @@ where
             inner,
             client,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             is_main,
         })
     }
@@ -492,7 +537,7 @@ where
             inner,
             client,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs.handle(),
             is_main,
         })
@@ -509,7 +554,7 @@ where
             inner,
             client,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             is_main,
         })
     }
@@ -531,7 +576,7 @@ where
             inner,
             client,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs.handle(),
             is_main,
         })
@@ -550,7 +595,7 @@ where
             inner,
             client: LlmpClient::on_existing_from_env(shmem_provider, env_name)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             is_main,
         })
     }
@@ -569,7 +614,7 @@ where
             inner,
             client: LlmpClient::on_existing_from_env(shmem_provider, env_name)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs.handle(),
             is_main,
         })
@@ -587,7 +632,7 @@ where
             inner,
             client: LlmpClient::existing_client_from_description(shmem_provider, description)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             is_main,
         })
     }
@@ -605,7 +650,7 @@ where
             inner,
             client: LlmpClient::existing_client_from_description(shmem_provider, description)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs.handle(),
             is_main,
         })
@@ -642,11 +687,11 @@ where
         let serialized = postcard::to_allocvec(event)?;
         let flags = LLMP_FLAG_INITIALIZED;
 
-        match self.compressor.maybe_compress(&serialized) {
-            Some(comp_buf) => {
+        match self.compressor.maybe_compress(&serialized)? {
+            Some((algorithm, comp_buf)) => {
                 self.client.send_buf_with_flags(
                     _LLMP_TAG_TO_MAIN,
-                    flags | LLMP_FLAG_COMPRESSED,
+                    flags | Flags::compressed_with(algorithm),
                     &comp_buf,
                 )?;
             }
@@ -696,8 +741,8 @@ where
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
+            let event_bytes = if let Some(algorithm) = _flags.compression() {
+                compressed = self.compressor.decompress(algorithm, msg)?;
                 &compressed
             } else {
                 msg
@@ -806,3 +851,63 @@ where
         self.await_restart_safe();
     }
 }*/
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{
+        current_time,
+        llmp::LlmpBroker,
+        shmem::{ShMemProvider, StdShMemProvider},
+        ClientId,
+    };
+    use serial_test::serial;
+
+    use super::CentralizedLlmpEventBroker;
+    use crate::{
+        events::{filters::DedupFilter, BrokerEventResult, Event, EventConfig},
+        executors::ExitKind,
+        inputs::BytesInput,
+        monitors::{Monitor, NopMonitor, UserStatsValue},
+    };
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_centralized_broker_filters() {
+        let llmp = LlmpBroker::new(StdShMemProvider::new().unwrap()).unwrap();
+        let mut broker =
+            CentralizedLlmpEventBroker::<BytesInput, _, _>::new(llmp, NopMonitor::new()).unwrap();
+        broker.add_filter(DedupFilter::new());
+
+        let event = Event::NewTestcase {
+            input: BytesInput::new(b"dup".to_vec()),
+            observers_buf: None,
+            exit_kind: ExitKind::Ok,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: current_time(),
+            executions: 0,
+            forward_id: None,
+        };
+        let mut handle = |client_id| {
+            CentralizedLlmpEventBroker::<BytesInput, NopMonitor, StdShMemProvider>::handle_in_broker(
+                &mut broker.monitor,
+                &mut broker.filters,
+                client_id,
+                &event,
+            )
+            .unwrap()
+        };
+        assert!(matches!(handle(ClientId(1)), BrokerEventResult::Forward));
+        assert!(matches!(handle(ClientId(2)), BrokerEventResult::Handled));
+        assert!(matches!(handle(ClientId(2)), BrokerEventResult::Handled));
+
+        assert_eq!(broker.filters().dropped()["DedupFilter"], 2);
+        let dropped = broker
+            .monitor
+            .client_stats_for(ClientId(2))
+            .get_user_stats("dropped_DedupFilter")
+            .unwrap();
+        assert!(matches!(dropped.value(), UserStatsValue::Number(2)));
+    }
+}
@@ -0,0 +1,386 @@
+//! Broker-side filters for [`Event::NewTestcase`]s, dropping events before they get rebroadcast to all clients.
+//!
+//! In large fleets, many clients find the same inputs. The [`DedupFilter`] drops these duplicates in the broker,
+//! saving bandwidth and evaluation time in all other clients.
+//! Other policies can be plugged in by implementing [`BrokerEventFilter`].
+
+use alloc::{borrow::Cow, boxed::Box, format, vec::Vec};
+use core::{fmt::Debug, hash::Hasher, time::Duration};
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::{current_time, hasher_std, ClientId, Named};
+
+use crate::{
+    events::Event,
+    inputs::Input,
+    monitors::{AggregatorOps, Monitor, UserStats, UserStatsValue},
+    Error,
+};
+
+/// A filter deciding in the broker if a [`Event::NewTestcase`] should be forwarded to the clients.
+pub trait BrokerEventFilter<I>: Named + Debug
+where
+    I: Input,
+{
+    /// Returns `false` if the event should be dropped instead of forwarded.
+    /// `client_id` is the client the broker received the event from.
+    fn should_forward(&mut self, client_id: ClientId, event: &Event<I>) -> Result<bool, Error>;
+}
+
+/// The [`BrokerEventFilter`]s of a broker, and how many events each of them dropped.
+#[derive(Debug)]
+pub struct BrokerEventFilters<I> {
+    filters: Vec<Box<dyn BrokerEventFilter<I>>>,
+    dropped: HashMap<Cow<'static, str>, u64>,
+}
+
+impl<I> Default for BrokerEventFilters<I> {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            dropped: HashMap::new(),
+        }
+    }
+}
+
+impl<I> BrokerEventFilters<I>
+where
+    I: Input,
+{
+    /// Add a filter. Filters run in the order they were added, the first one to drop an event wins.
+    pub fn push<F>(&mut self, filter: F)
+    where
+        F: BrokerEventFilter<I> + 'static,
+    {
+        self.filters.push(Box::new(filter));
+    }
+
+    /// Returns `true` if no filters are set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Run all filters on the event.
+    /// Returns the name of the filter that dropped it, or `None` if it should be forwarded.
+    pub fn filter(
+        &mut self,
+        client_id: ClientId,
+        event: &Event<I>,
+    ) -> Result<Option<Cow<'static, str>>, Error> {
+        for filter in &mut self.filters {
+            if !filter.should_forward(client_id, event)? {
+                let name = filter.name().clone();
+                *self.dropped.entry(name.clone()).or_default() += 1;
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like [`Self::filter`], but also counts a dropped event in the `dropped_<filter>` user stats
+    /// of the client in the `monitor`.
+    pub fn filter_and_report<MT>(
+        &mut self,
+        monitor: &mut MT,
+        client_id: ClientId,
+        event: &Event<I>,
+    ) -> Result<Option<Cow<'static, str>>, Error>
+    where
+        MT: Monitor,
+    {
+        let Some(filter) = self.filter(client_id, event)? else {
+            return Ok(None);
+        };
+        let name: Cow<'static, str> = Cow::from(format!("dropped_{filter}"));
+        monitor.client_stats_insert(client_id);
+        let client = monitor.client_stats_mut_for(client_id);
+        let dropped = match client.get_user_stats(&name).map(UserStats::value) {
+            Some(UserStatsValue::Number(dropped)) => dropped + 1,
+            _ => 1,
+        };
+        client.update_user_stats(
+            name.clone(),
+            UserStats::new(UserStatsValue::Number(dropped), AggregatorOps::Sum),
+        );
+        monitor.aggregate(&name);
+        Ok(Some(filter))
+    }
+
+    /// The number of events each filter dropped so far, by filter name
+    #[must_use]
+    pub fn dropped(&self) -> &HashMap<Cow<'static, str>, u64> {
+        &self.dropped
+    }
+}
+
+/// Drops [`Event::NewTestcase`]s whose input, and observers if they were sent along, the broker has already seen.
+#[derive(Debug)]
+pub struct DedupFilter {
+    seen: HashSet<u64>,
+    /// The hashes, in order of arrival, to forget the oldest ones first
+    order: Vec<u64>,
+    /// The index of the oldest hash in `order`, once it's full
+    oldest: usize,
+    max_entries: usize,
+}
+
+impl DedupFilter {
+    /// The default number of hashes to remember
+    pub const DEFAULT_MAX_ENTRIES: usize = 1 << 20;
+
+    /// Create a new [`DedupFilter`], remembering [`Self::DEFAULT_MAX_ENTRIES`] testcases
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_entries(Self::DEFAULT_MAX_ENTRIES)
+    }
+
+    /// Create a new [`DedupFilter`] remembering at most `max_entries` testcases.
+    /// Once full, the oldest testcases are forgotten, and may be forwarded again.
+    #[must_use]
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        assert!(max_entries > 0, "DedupFilter needs to remember something");
+        Self {
+            seen: HashSet::new(),
+            order: Vec::new(),
+            oldest: 0,
+            max_entries,
+        }
+    }
+
+    fn insert(&mut self, hash: u64) -> bool {
+        if !self.seen.insert(hash) {
+            return false;
+        }
+        if self.order.len() < self.max_entries {
+            self.order.push(hash);
+        } else {
+            let evicted = core::mem::replace(&mut self.order[self.oldest], hash);
+            self.seen.remove(&evicted);
+            self.oldest = (self.oldest + 1) % self.max_entries;
+        }
+        true
+    }
+}
+
+impl Default for DedupFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for DedupFilter {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("DedupFilter");
+        &NAME
+    }
+}
+
+impl<I> BrokerEventFilter<I> for DedupFilter
+where
+    I: Input,
+{
+    fn should_forward(&mut self, _client_id: ClientId, event: &Event<I>) -> Result<bool, Error> {
+        let Event::NewTestcase {
+            input,
+            observers_buf,
+            ..
+        } = event
+        else {
+            return Ok(true);
+        };
+        let mut hasher = hasher_std();
+        hasher.write(&postcard::to_allocvec(input)?);
+        if let Some(observers_buf) = observers_buf {
+            hasher.write(observers_buf);
+        }
+        Ok(self.insert(hasher.finish()))
+    }
+}
+
+/// Drops [`Event::NewTestcase`]s with inputs larger than the given size, in serialized bytes.
+#[derive(Debug, Clone)]
+pub struct MaxSizeFilter {
+    max_size: usize,
+}
+
+impl MaxSizeFilter {
+    /// Create a new [`MaxSizeFilter`], dropping inputs that serialize to more than `max_size` bytes
+    #[must_use]
+    pub fn new(max_size: usize) -> Self {
+        Self { max_size }
+    }
+}
+
+impl Named for MaxSizeFilter {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MaxSizeFilter");
+        &NAME
+    }
+}
+
+impl<I> BrokerEventFilter<I> for MaxSizeFilter
+where
+    I: Input,
+{
+    fn should_forward(&mut self, _client_id: ClientId, event: &Event<I>) -> Result<bool, Error> {
+        match event {
+            Event::NewTestcase { input, .. } => {
+                Ok(postcard::to_allocvec(input)?.len() <= self.max_size)
+            }
+            _ => Ok(true),
+        }
+    }
+}
+
+/// Forwards at most a given number of [`Event::NewTestcase`]s per client and time window,
+/// so a single client in a testcase storm can't flood all the others.
+#[derive(Debug, Clone)]
+pub struct RateLimitFilter {
+    max_per_window: usize,
+    window: Duration,
+    /// Per client: the start of the current window, and the testcases forwarded in it
+    clients: HashMap<ClientId, (Duration, usize)>,
+}
+
+impl RateLimitFilter {
+    /// Create a new [`RateLimitFilter`], forwarding at most `max_per_window` testcases per client every `window`
+    #[must_use]
+    pub fn new(max_per_window: usize, window: Duration) -> Self {
+        Self {
+            max_per_window,
+            window,
+            clients: HashMap::new(),
+        }
+    }
+}
+
+impl Named for RateLimitFilter {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RateLimitFilter");
+        &NAME
+    }
+}
+
+impl<I> BrokerEventFilter<I> for RateLimitFilter
+where
+    I: Input,
+{
+    fn should_forward(&mut self, client_id: ClientId, event: &Event<I>) -> Result<bool, Error> {
+        if !matches!(event, Event::NewTestcase { .. }) {
+            return Ok(true);
+        }
+        let now = current_time();
+        let (window_start, count) = self.clients.entry(client_id).or_insert((now, 0));
+        if now.saturating_sub(*window_start) >= self.window {
+            *window_start = now;
+            *count = 0;
+        }
+        *count += 1;
+        Ok(*count <= self.max_per_window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    use core::{marker::PhantomData, time::Duration};
+
+    use libafl_bolts::{current_time, ClientId};
+
+    use super::{BrokerEventFilters, DedupFilter, MaxSizeFilter, RateLimitFilter};
+    use crate::{
+        events::{Event, EventConfig},
+        executors::ExitKind,
+        inputs::BytesInput,
+    };
+
+    fn new_testcase(input: &[u8], observers_buf: Option<Vec<u8>>) -> Event<BytesInput> {
+        Event::NewTestcase {
+            input: BytesInput::new(input.to_vec()),
+            observers_buf,
+            exit_kind: ExitKind::Ok,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: current_time(),
+            executions: 0,
+            forward_id: None,
+        }
+    }
+
+    #[test]
+    fn test_dedup_filter() {
+        let mut filters = BrokerEventFilters::default();
+        filters.push(DedupFilter::with_max_entries(2));
+
+        assert!(filters
+            .filter(ClientId(1), &new_testcase(b"a", None))
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            filters
+                .filter(ClientId(2), &new_testcase(b"a", None))
+                .unwrap()
+                .as_deref(),
+            Some("DedupFilter")
+        );
+        // Different observers make a different testcase
+        assert!(filters
+            .filter(ClientId(2), &new_testcase(b"a", Some(vec![1])))
+            .unwrap()
+            .is_none());
+        // "a" gets evicted
+        assert!(filters
+            .filter(ClientId(2), &new_testcase(b"b", None))
+            .unwrap()
+            .is_none());
+        assert!(filters
+            .filter(ClientId(2), &new_testcase(b"a", None))
+            .unwrap()
+            .is_none());
+
+        // Other events are never dropped
+        let log = Event::Log {
+            severity_level: crate::events::LogSeverity::Info,
+            message: "hi".into(),
+            phantom: PhantomData,
+        };
+        assert!(filters.filter(ClientId(1), &log).unwrap().is_none());
+        assert!(filters.filter(ClientId(1), &log).unwrap().is_none());
+
+        assert_eq!(filters.dropped()["DedupFilter"], 1);
+    }
+
+    #[test]
+    fn test_size_and_rate_filters() {
+        let mut filters = BrokerEventFilters::default();
+        filters.push(MaxSizeFilter::new(16));
+        filters.push(RateLimitFilter::new(2, Duration::from_secs(60)));
+
+        assert_eq!(
+            filters
+                .filter(ClientId(1), &new_testcase(&[0; 64], None))
+                .unwrap()
+                .as_deref(),
+            Some("MaxSizeFilter")
+        );
+        for _ in 0..2 {
+            assert!(filters
+                .filter(ClientId(1), &new_testcase(b"a", None))
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(
+            filters
+                .filter(ClientId(1), &new_testcase(b"a", None))
+                .unwrap()
+                .as_deref(),
+            Some("RateLimitFilter")
+        );
+        // Limits are per client
+        assert!(filters
+            .filter(ClientId(2), &new_testcase(b"a", None))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,355 @@
+//! Hierarchical broker topologies, formed without any per-node configuration.
+//!
+//! Each node's broker registers itself in a shared rendezvous file using a [`BrokerDiscovery`],
+//! and gets assigned the next free index. The broker with index `0` is the root of the tree,
+//! every other broker connects to its parent via a broker-to-broker (`B2B`) connection,
+//! with at most `fanout` children per broker. A huge `fanout` results in a star around the root.
+//!
+//! Since `B2B` connections forward messages without any loop detection, the brokers always form a tree, never a mesh.
+//!
+//! Each broker periodically reports the stats of its whole subtree to its parent,
+//! so the monitor of each level shows the aggregated stats of all nodes below it.
+
+use alloc::{format, string::String, vec::Vec};
+use core::{num::NonZeroUsize, time::Duration};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
+
+use libafl_bolts::{current_nanos, current_time, ClientId, IP_LOCALHOST};
+use serde::{Deserialize, Serialize};
+
+use crate::{events::Event, inputs::Input, monitors::Monitor, Error};
+
+/// The tag of the [`Event::CustomBuf`] a broker reports its subtree stats to its parent with
+pub const BROKER_STATS_TAG: &str = "libafl_broker_stats";
+
+/// Finds other brokers using a rendezvous file, to form a tree of brokers.
+///
+/// The rendezvous file needs to be on a filesystem all nodes can access,
+/// and it should be removed before starting a new campaign.
+#[derive(Debug, Clone)]
+pub struct BrokerDiscovery {
+    rendezvous: PathBuf,
+    host: IpAddr,
+    fanout: NonZeroUsize,
+    report_interval: Duration,
+}
+
+impl BrokerDiscovery {
+    /// The default number of child brokers per broker
+    pub const DEFAULT_FANOUT: usize = 4;
+
+    /// Create a new [`BrokerDiscovery`], using the given rendezvous file.
+    ///
+    /// By default, brokers announce themselves on localhost, use a fanout of [`Self::DEFAULT_FANOUT`],
+    /// and report their stats every 5 seconds.
+    #[must_use]
+    pub fn new<P>(rendezvous: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            rendezvous: rendezvous.into(),
+            host: IP_LOCALHOST.parse().unwrap(),
+            fanout: NonZeroUsize::new(Self::DEFAULT_FANOUT).unwrap(),
+            report_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// The address other brokers reach this broker on.
+    /// For multi-machine setups, this needs the `llmp_bind_public` feature.
+    #[must_use]
+    pub fn with_host(mut self, host: IpAddr) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// The maximum number of child brokers each broker gets
+    #[must_use]
+    pub fn with_fanout(mut self, fanout: NonZeroUsize) -> Self {
+        self.fanout = fanout;
+        self
+    }
+
+    /// How often each broker reports the stats of its subtree to its parent
+    #[must_use]
+    pub fn with_report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    /// Register a new broker in the rendezvous file, and find its place in the tree.
+    ///
+    /// The broker listens on `base_port` plus its index, so that multiple nodes on the same machine don't clash.
+    pub fn join(&self, base_port: u16) -> Result<BrokerNode, Error> {
+        let token = format!("{}-{}", std::process::id(), current_nanos());
+        // A single, short, appending write is atomic, so brokers registering at the same time don't interleave.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.rendezvous)?
+            .write_all(format!("{} {base_port} {token}\n", self.host).as_bytes())?;
+
+        let entries = fs::read_to_string(&self.rendezvous)?
+            .lines()
+            .map(RendezvousEntry::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        let index = entries
+            .iter()
+            .position(|entry| entry.token == token)
+            .ok_or_else(|| {
+                Error::illegal_state(format!(
+                    "Own entry vanished from rendezvous file {}",
+                    self.rendezvous.display()
+                ))
+            })?;
+
+        let parent = match tree_parent(index, self.fanout) {
+            Some(parent) => Some((parent, entries[parent].addr(parent)?)),
+            None => None,
+        };
+        Ok(BrokerNode {
+            index,
+            addr: entries[index].addr(index)?,
+            parent,
+            report_interval: self.report_interval,
+            last_report: Duration::ZERO,
+        })
+    }
+}
+
+/// A line of the rendezvous file: `<host> <base port> <unique token>`
+#[derive(Debug)]
+struct RendezvousEntry {
+    host: IpAddr,
+    base_port: u16,
+    token: String,
+}
+
+impl RendezvousEntry {
+    fn parse(line: &str) -> Result<Self, Error> {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(host), Some(base_port), Some(token)) => Ok(Self {
+                host: host.parse().map_err(|_| {
+                    Error::illegal_state(format!("Invalid host in rendezvous file: {line}"))
+                })?,
+                base_port: base_port.parse()?,
+                token: token.into(),
+            }),
+            _ => Err(Error::illegal_state(format!(
+                "Invalid line in rendezvous file: {line}"
+            ))),
+        }
+    }
+
+    /// The address the broker with the given index listens on
+    fn addr(&self, index: usize) -> Result<SocketAddr, Error> {
+        let port = u16::try_from(index)
+            .ok()
+            .and_then(|index| self.base_port.checked_add(index))
+            .ok_or_else(|| {
+                Error::illegal_state(format!(
+                    "No port left for broker #{index} on base port {}",
+                    self.base_port
+                ))
+            })?;
+        Ok(SocketAddr::new(self.host, port))
+    }
+}
+
+/// The parent of the broker with the given index, in a tree with the given fanout
+fn tree_parent(index: usize, fanout: NonZeroUsize) -> Option<usize> {
+    index.checked_sub(1).map(|index| index / fanout.get())
+}
+
+/// A broker's place in the tree of brokers, as found by a [`BrokerDiscovery`]
+#[derive(Debug, Clone)]
+pub struct BrokerNode {
+    index: usize,
+    addr: SocketAddr,
+    parent: Option<(usize, SocketAddr)>,
+    report_interval: Duration,
+    last_report: Duration,
+}
+
+/// The stats of a broker's subtree, sent to its parent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct BrokerStatsReport {
+    node: usize,
+    parent: usize,
+    executions: u64,
+    corpus_size: u64,
+    objective_size: u64,
+}
+
+impl BrokerNode {
+    /// The index of this broker in the rendezvous file, `0` for the root
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The address this broker listens on
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The port this broker listens on, and the clients of this node connect to
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// The address of the parent broker, or `None` for the root
+    #[must_use]
+    pub fn parent(&self) -> Option<SocketAddr> {
+        self.parent.map(|(_, addr)| addr)
+    }
+
+    /// Returns the serialized [`Event::CustomBuf`] with the stats of this broker's subtree,
+    /// if this broker has a parent, and the report interval passed.
+    pub(crate) fn report<I, MT>(&mut self, monitor: &MT) -> Result<Option<Vec<u8>>, Error>
+    where
+        I: Input,
+        MT: Monitor,
+    {
+        let Some((parent, _)) = self.parent else {
+            return Ok(None);
+        };
+        let now = current_time();
+        if now.saturating_sub(self.last_report) < self.report_interval {
+            return Ok(None);
+        }
+        self.last_report = now;
+
+        let report = BrokerStatsReport {
+            node: self.index,
+            parent,
+            executions: monitor.total_execs(),
+            corpus_size: monitor.corpus_size(),
+            objective_size: monitor.objective_size(),
+        };
+        let event: Event<I> = Event::CustomBuf {
+            buf: postcard::to_allocvec(&report)?,
+            tag: BROKER_STATS_TAG.into(),
+        };
+        Ok(Some(postcard::to_allocvec(&event)?))
+    }
+
+    /// Handle a stats report received from `client_id`.
+    ///
+    /// Since reports travel along all `B2B` connections, only reports of direct children are accounted,
+    /// as the stats of the `B2B` client of the child.
+    pub(crate) fn handle_report<MT>(
+        &self,
+        monitor: &mut MT,
+        client_id: ClientId,
+        buf: &[u8],
+    ) -> Result<(), Error>
+    where
+        MT: Monitor,
+    {
+        let report: BrokerStatsReport = postcard::from_bytes(buf)?;
+        if report.parent != self.index {
+            return Ok(());
+        }
+        log::trace!("Stats report of child broker #{}", report.node);
+        monitor.client_stats_insert(client_id);
+        let client = monitor.client_stats_mut_for(client_id);
+        client.update_executions(report.executions, current_time());
+        client.update_corpus_size(report.corpus_size);
+        client.update_objective_size(report.objective_size);
+        monitor.display("Broker Stats", client_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::{num::NonZeroUsize, time::Duration};
+    use std::{env, fs};
+
+    use libafl_bolts::ClientId;
+
+    use super::{tree_parent, BrokerDiscovery};
+    use crate::{
+        events::Event,
+        inputs::BytesInput,
+        monitors::{Monitor, NopMonitor},
+    };
+
+    #[test]
+    fn test_tree_parent() {
+        let fanout = NonZeroUsize::new(2).unwrap();
+        let parents: Vec<_> = (0..7).map(|index| tree_parent(index, fanout)).collect();
+        assert_eq!(
+            parents,
+            [None, Some(0), Some(0), Some(1), Some(1), Some(2), Some(2)]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_discovery_and_reports() {
+        let path = env::temp_dir().join(format!("libafl_rendezvous_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let discovery = BrokerDiscovery::new(&path)
+            .with_fanout(NonZeroUsize::new(1).unwrap())
+            .with_report_interval(Duration::ZERO);
+        let root = discovery.join(2000).unwrap();
+        let mut child = discovery.join(2000).unwrap();
+        let mut grandchild = discovery.join(2000).unwrap();
+
+        assert_eq!(root.index(), 0);
+        assert_eq!(root.port(), 2000);
+        assert_eq!(root.parent(), None);
+        assert_eq!(child.parent(), Some(root.addr()));
+        assert_eq!(grandchild.port(), 2002);
+        assert_eq!(grandchild.parent(), Some(child.addr()));
+
+        let mut grandchild_monitor = NopMonitor::new();
+        grandchild_monitor.client_stats_insert(ClientId(1));
+        grandchild_monitor
+            .client_stats_mut_for(ClientId(1))
+            .update_corpus_size(10);
+        let buf = grandchild
+            .report::<BytesInput, _>(&grandchild_monitor)
+            .unwrap()
+            .unwrap();
+        let Event::<BytesInput>::CustomBuf { buf, .. } = postcard::from_bytes(&buf).unwrap() else {
+            panic!("Stats reports need to be custom bufs");
+        };
+
+        // The report arrives at the child, and, via B2B, at the root. Only the child counts it.
+        let mut child_monitor = NopMonitor::new();
+        child
+            .handle_report(&mut child_monitor, ClientId(3), &buf)
+            .unwrap();
+        assert_eq!(child_monitor.corpus_size(), 10);
+        let mut root_monitor = NopMonitor::new();
+        root.handle_report(&mut root_monitor, ClientId(3), &buf)
+            .unwrap();
+        assert_eq!(root_monitor.corpus_size(), 0);
+
+        // The root has no one to report to
+        let mut root = root;
+        assert!(root
+            .report::<BytesInput, _>(&root_monitor)
+            .unwrap()
+            .is_none());
+        assert!(child
+            .report::<BytesInput, _>(&child_monitor)
+            .unwrap()
+            .is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
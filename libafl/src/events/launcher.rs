@@ -8,6 +8,7 @@
 //!
 //! To connect multiple nodes together via TCP, we can use the `remote_broker_addr`.
 //! (this requires the `llmp_bind_public` compile-time feature for `LibAFL`).
+//! Alternatively, with a [`BrokerDiscovery`], all nodes find each other and form a tree of brokers by themselves.
 //!
 //! On `Unix` systems, the [`Launcher`] will use `fork` if the `fork` feature is used for `LibAFL`.
 //! Else, it will start subsequent nodes with the same commandline, and will set special `env` variables accordingly.
@@ -15,16 +16,16 @@
 use alloc::string::ToString;
 #[cfg(feature = "std")]
 use core::marker::PhantomData;
-#[cfg(all(unix, feature = "std", feature = "fork"))]
+#[cfg(feature = "std")]
 use core::time::Duration;
 use core::{
     fmt::{self, Debug, Formatter},
     num::NonZeroUsize,
 };
-#[cfg(feature = "std")]
-use std::net::SocketAddr;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use std::process::Stdio;
+#[cfg(feature = "std")]
+use std::{fs, net::SocketAddr, path::PathBuf};
 #[cfg(all(unix, feature = "std"))]
 use std::{fs::File, os::unix::io::AsRawFd};
 
@@ -55,17 +56,22 @@ use crate::observers::TimeObserver;
 #[cfg(feature = "std")]
 use crate::{
     events::{
+        hierarchy::{BrokerDiscovery, BrokerNode},
         llmp::{LlmpRestartingEventManager, LlmpShouldSaveState, ManagerKind, RestartingMgr},
         EventConfig,
     },
     monitors::Monitor,
-    state::{HasExecutions, State},
+    state::{checkpoint::DEFAULT_CHECKPOINT_INTERVAL, HasExecutions, State, StateCheckpointer},
     Error,
 };
 
 /// The (internal) `env` that indicates we're running as client.
 const _AFL_LAUNCHER_CLIENT: &str = "AFL_LAUNCHER_CLIENT";
 
+/// The (internal) `env` that tells clients the broker port assigned by the [`BrokerDiscovery`]
+#[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
+const _AFL_LAUNCHER_BROKER_PORT: &str = "AFL_LAUNCHER_BROKER_PORT";
+
 /// The env variable to set in order to enable child output
 #[cfg(all(feature = "fork", unix))]
 const LIBAFL_DEBUG_OUTPUT: &str = "LIBAFL_DEBUG_OUTPUT";
@@ -124,6 +130,11 @@ where
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// Find the brokers of other nodes with this [`BrokerDiscovery`], and connect to them in a tree.
+    /// The broker then listens on [`Self::broker_port`] plus its index in the tree.
+    /// Takes precedence over [`Self::remote_broker_addr`], and is ignored if [`Self::spawn_broker`] is `false`.
+    #[builder(default = None)]
+    discovery: Option<BrokerDiscovery>,
     #[cfg(feature = "adaptive_serialization")]
     time_ref: Handle<TimeObserver>,
     /// If this launcher should spawn a new `broker` on `[Self::broker_port]` (default).
@@ -135,6 +146,13 @@ where
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// A directory to periodically write a checkpoint of each client's state to.
+    /// On launch, each client resumes from its last checkpoint, if there is one.
+    #[builder(default = None)]
+    checkpoint_dir: Option<PathBuf>,
+    /// The interval between two checkpoints, if [`Self::checkpoint_dir`] is set
+    #[builder(default = DEFAULT_CHECKPOINT_INTERVAL)]
+    checkpoint_interval: Duration,
     #[builder(setter(skip), default = PhantomData)]
     phantom_data: PhantomData<(&'a S, &'a SP, EMH)>,
 }
@@ -154,7 +172,9 @@ where
             .field("broker_port", &self.broker_port)
             .field("core", &self.cores)
             .field("spawn_broker", &self.spawn_broker)
-            .field("remote_broker_addr", &self.remote_broker_addr);
+            .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("discovery", &self.discovery)
+            .field("checkpoint_dir", &self.checkpoint_dir);
         #[cfg(all(unix, feature = "std"))]
         {
             dbg_struct
@@ -196,6 +216,34 @@ where
     S: State + HasExecutions,
     SP: ShMemProvider + 'static,
 {
+    /// The checkpointer of the client running on `core_id`, if checkpoints are enabled
+    fn checkpointer(&self, core_id: CoreId) -> Result<Option<StateCheckpointer>, Error> {
+        let Some(checkpoint_dir) = &self.checkpoint_dir else {
+            return Ok(None);
+        };
+        fs::create_dir_all(checkpoint_dir)?;
+        Ok(Some(StateCheckpointer::new(
+            checkpoint_dir.join(format!("checkpoint_{}", core_id.0)),
+            self.checkpoint_interval,
+        )))
+    }
+
+    /// Join the tree of brokers if a [`BrokerDiscovery`] is set,
+    /// and switch to the broker port assigned to this node.
+    fn join_broker_tree(&mut self) -> Result<Option<BrokerNode>, Error> {
+        let Some(discovery) = self.discovery.as_ref().filter(|_| self.spawn_broker) else {
+            return Ok(None);
+        };
+        let node = discovery.join(self.broker_port)?;
+        log::info!(
+            "Joined the broker tree as node #{} on port {}",
+            node.index(),
+            node.port()
+        );
+        self.broker_port = node.port();
+        Ok(Some(node))
+    }
+
     /// Launch the broker and the clients and fuzz with a user-supplied hook
     #[cfg(all(unix, feature = "std", feature = "fork"))]
     #[allow(clippy::similar_names)]
@@ -213,6 +261,8 @@ where
             ));
         }
 
+        let broker_node = self.join_broker_tree()?;
+
         let core_ids = get_core_ids().unwrap();
         let num_cores = core_ids.len();
         let mut handles = vec![];
@@ -274,6 +324,7 @@ where
                             })
                             .configuration(self.configuration)
                             .serialize_state(self.serialize_state)
+                            .checkpointer(self.checkpointer(*bind_to)?)
                             .hooks(hooks);
                         #[cfg(feature = "adaptive_serialization")]
                         let builder = builder.time_ref(self.time_ref.clone());
@@ -296,6 +347,7 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .broker_node(broker_node)
                 .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
@@ -337,10 +389,14 @@ where
         use libafl_bolts::core_affinity;
 
         let is_client = std::env::var(_AFL_LAUNCHER_CLIENT);
+        let mut broker_node = None;
 
         let mut handles = match is_client {
             Ok(core_conf) => {
                 let core_id = core_conf.parse()?;
+                if let Ok(broker_port) = std::env::var(_AFL_LAUNCHER_BROKER_PORT) {
+                    self.broker_port = broker_port.parse()?;
+                }
                 // the actual client. do the fuzzing
                 let (state, mgr) = RestartingMgr::<EMH, MT, S, SP>::builder()
                     .shmem_provider(self.shmem_provider.clone())
@@ -350,6 +406,7 @@ where
                     })
                     .configuration(self.configuration)
                     .serialize_state(self.serialize_state)
+                    .checkpointer(self.checkpointer(CoreId(core_id))?)
                     .hooks(hooks)
                     .build()
                     .launch()?;
@@ -360,6 +417,11 @@ where
                 // I am a broker
                 // before going to the broker loop, spawn n clients
 
+                broker_node = self.join_broker_tree()?;
+                if broker_node.is_some() {
+                    std::env::set_var(_AFL_LAUNCHER_BROKER_PORT, self.broker_port.to_string());
+                }
+
                 let core_ids = core_affinity::get_core_ids().unwrap();
                 let num_cores = core_ids.len();
                 let mut handles = vec![];
@@ -435,6 +497,7 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .broker_node(broker_node)
                 .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
@@ -631,9 +694,10 @@ where
                 log::info!("PID: {:#?} I am centralized broker", std::process::id());
                 self.shmem_provider.post_fork(true)?;
 
-                let mut broker: CentralizedLlmpEventBroker<S::Input, SP> =
+                let mut broker: CentralizedLlmpEventBroker<S::Input, MT, SP> =
                     CentralizedLlmpEventBroker::on_port(
                         self.shmem_provider.clone(),
+                        self.monitor.clone(),
                         self.centralized_broker_port,
                     )?;
                 broker.broker_loop()?;
@@ -2,7 +2,7 @@
 
 #[cfg(feature = "std")]
 use alloc::string::ToString;
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, format, vec::Vec};
 #[cfg(all(unix, not(miri), feature = "std"))]
 use core::ptr::addr_of_mut;
 #[cfg(feature = "std")]
@@ -13,6 +13,8 @@ use std::net::TcpStream;
 #[cfg(feature = "std")]
 use std::net::{SocketAddr, ToSocketAddrs};
 
+#[cfg(any(feature = "std", feature = "llmp_compression"))]
+use libafl_bolts::llmp::Flags;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use libafl_bolts::os::startable_self;
 #[cfg(all(unix, feature = "std", not(miri)))]
@@ -20,10 +22,9 @@ use libafl_bolts::os::unix_signals::setup_signal_handler;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 use libafl_bolts::os::{fork, ForkResult};
 #[cfg(feature = "llmp_compression")]
-use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
-};
+use libafl_bolts::{compress::CompressorSet, llmp::LLMP_FLAG_INITIALIZED};
+#[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+use libafl_bolts::{compress::ZstdCompressor, AsSlice};
 #[cfg(feature = "std")]
 use libafl_bolts::{
     core_affinity::CoreId,
@@ -35,16 +36,20 @@ use libafl_bolts::{
     current_time,
     tuples::{Handle, Handled},
 };
-#[cfg(feature = "std")]
-use libafl_bolts::{
-    llmp::LlmpConnection, os::CTRL_C_EXIT, shmem::StdShMemProvider, staterestore::StateRestorer,
-};
 use libafl_bolts::{
     llmp::{self, LlmpClient, LlmpClientDescription, Tag},
     shmem::ShMemProvider,
     tuples::tuple_list,
     ClientId,
 };
+#[cfg(feature = "std")]
+use libafl_bolts::{
+    llmp::{LlmpConnection, LLMP_FLAG_FROM_B2B},
+    llmp_replay::LlmpMsgLog,
+    os::CTRL_C_EXIT,
+    shmem::{ShMemPurpose, StdShMemProvider},
+    staterestore::StateRestorer,
+};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
@@ -56,20 +61,32 @@ use crate::events::AdaptiveSerializer;
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 #[cfg(feature = "adaptive_serialization")]
 use crate::observers::TimeObserver;
+#[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+use crate::{corpus::Corpus, inputs::HasTargetBytes, state::HasCorpus};
+#[cfg(feature = "std")]
+use crate::{
+    events::hierarchy::{BrokerNode, BROKER_STATS_TAG},
+    state::StateCheckpointer,
+};
 use crate::{
     events::{
+        filters::{BrokerEventFilter, BrokerEventFilters},
         BrokerEventResult, Event, EventConfig, EventFirer, EventManager, EventManagerId,
         EventProcessor, EventRestarter, HasCustomBufHandlers, HasEventManagerId, ProgressReporter,
     },
     executors::{Executor, HasObservers},
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
     inputs::{Input, InputConverter, UsesInput},
-    monitors::Monitor,
+    monitors::{record_testcase_size, Monitor},
     observers::ObserversTuple,
     state::{HasExecutions, HasLastReportTime, State, UsesState},
     Error, HasMetadata,
 };
 
+/// How often a broker tries to connect to its parent broker in a tree of brokers, once a second
+#[cfg(feature = "std")]
+const BROKER_PARENT_CONNECT_TRIES: usize = 60;
+
 /// Forward this to the client
 const _LLMP_TAG_EVENT_TO_CLIENT: Tag = Tag(0x2C11E471);
 /// Only handle this in the broker
@@ -77,6 +94,8 @@ const _LLMP_TAG_EVENT_TO_BROKER: Tag = Tag(0x2B80438);
 /// Handle in both
 ///
 const LLMP_TAG_EVENT_TO_BOTH: Tag = Tag(0x2B0741);
+/// A zstd dictionary, shared by one client with the broker and all other clients
+const LLMP_TAG_COMPRESSION_DICTIONARY: Tag = Tag(0xD1C7_10AA);
 const _LLMP_TAG_RESTART: Tag = Tag(0x8357A87);
 const _LLMP_TAG_NO_RESTART: Tag = Tag(0x57A7EE71);
 
@@ -84,6 +103,54 @@ const _LLMP_TAG_NO_RESTART: Tag = Tag(0x57A7EE71);
 #[cfg(any(feature = "llmp_compression", feature = "tcp_compression"))]
 pub const COMPRESS_THRESHOLD: usize = 1024;
 
+/// The zstd dictionary shared with [`LlmpEventManager::share_dictionary`].
+/// It is kept in the state, so that clients keep decompressing after a restart.
+#[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionDictionaryMetadata {
+    dictionary: Vec<u8>,
+}
+
+#[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+libafl_bolts::impl_serdeany!(CompressionDictionaryMetadata);
+
+#[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+impl CompressionDictionaryMetadata {
+    /// The raw zstd dictionary
+    #[must_use]
+    pub fn dictionary(&self) -> &[u8] {
+        &self.dictionary
+    }
+}
+
+/// Trains a zstd dictionary of at most `max_size` bytes on the inputs in the corpus,
+/// to be shared with [`LlmpEventManager::share_dictionary`].
+#[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+pub fn train_corpus_dictionary<S>(state: &S, max_size: usize) -> Result<Vec<u8>, Error>
+where
+    S: HasCorpus,
+    S::Input: HasTargetBytes,
+{
+    let mut samples = Vec::with_capacity(state.corpus().count());
+    for id in state.corpus().ids() {
+        let input = state.corpus().cloned_input_for_id(id)?;
+        samples.push(input.target_bytes().as_slice().to_vec());
+    }
+    ZstdCompressor::train_dictionary(&samples, max_size)
+}
+
+/// Use a zstd dictionary received from another node
+#[cfg(feature = "llmp_compression")]
+fn install_dictionary(compressor: &mut CompressorSet, dictionary: &[u8]) {
+    #[cfg(feature = "zstd")]
+    compressor.zstd_mut().set_dictionary(dictionary.to_vec());
+    #[cfg(not(feature = "zstd"))]
+    {
+        let _ = (compressor, dictionary);
+        log::warn!("Ignoring a shared zstd dictionary, the zstd feature is not enabled");
+    }
+}
+
 /// An LLMP-backed event manager for scalable multi-processed fuzzing
 #[derive(Debug)]
 pub struct LlmpEventBroker<I, MT, SP>
@@ -96,7 +163,12 @@ where
     monitor: MT,
     llmp: llmp::LlmpBroker<SP>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CompressorSet,
+    #[cfg(feature = "std")]
+    msg_log: Option<LlmpMsgLog>,
+    filters: BrokerEventFilters<I>,
+    #[cfg(feature = "std")]
+    hierarchy: Option<BrokerNode>,
     phantom: PhantomData<I>,
 }
 
@@ -112,7 +184,12 @@ where
             monitor,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
+            #[cfg(feature = "std")]
+            msg_log: None,
+            filters: BrokerEventFilters::default(),
+            #[cfg(feature = "std")]
+            hierarchy: None,
             phantom: PhantomData,
         })
     }
@@ -126,7 +203,12 @@ where
             monitor,
             llmp: llmp::LlmpBroker::create_attach_to_tcp(shmem_provider, port)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
+            #[cfg(feature = "std")]
+            msg_log: None,
+            filters: BrokerEventFilters::default(),
+            #[cfg(feature = "std")]
+            hierarchy: None,
             phantom: PhantomData,
         })
     }
@@ -145,28 +227,93 @@ where
         self.llmp.connect_b2b(addr)
     }
 
+    /// The compressors used to decompress incoming events,
+    /// for example to set the zstd dictionary the clients use
+    #[cfg(feature = "llmp_compression")]
+    pub fn compressor_mut(&mut self) -> &mut CompressorSet {
+        &mut self.compressor
+    }
+
+    /// Add a [`BrokerEventFilter`], deciding which new testcases get forwarded to the clients.
+    /// The number of dropped testcases is reported to the monitor, per client and filter.
+    pub fn add_filter<F>(&mut self, filter: F)
+    where
+        F: BrokerEventFilter<I> + 'static,
+    {
+        self.filters.push(filter);
+    }
+
+    /// The filters of this broker, and how many testcases they dropped
+    pub fn filters(&self) -> &BrokerEventFilters<I> {
+        &self.filters
+    }
+
+    /// Record all messages passing this broker to the given [`LlmpMsgLog`],
+    /// to replay them later with [`libafl_bolts::llmp_replay::replay`].
+    #[cfg(feature = "std")]
+    pub fn set_msg_log(&mut self, msg_log: LlmpMsgLog) {
+        self.msg_log = Some(msg_log);
+    }
+
+    /// Make this broker a node in a tree of brokers, as found by a [`BrokerDiscovery`](crate::events::hierarchy::BrokerDiscovery).
+    ///
+    /// Connects to the parent broker, retrying for a while in case it didn't start yet.
+    /// From then on, the broker loop periodically reports the stats of this broker's subtree to the parent.
+    #[cfg(feature = "std")]
+    pub fn join_hierarchy(&mut self, node: BrokerNode) -> Result<(), Error> {
+        if let Some(parent) = node.parent() {
+            log::info!("Broker #{} connecting to its parent {parent}", node.index());
+            let mut tries = 0;
+            while let Err(err) = self.llmp.connect_b2b(parent) {
+                tries += 1;
+                if tries >= BROKER_PARENT_CONNECT_TRIES {
+                    return Err(err);
+                }
+                log::info!("Parent broker not reachable yet ({err}), retrying");
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+        self.hierarchy = Some(node);
+        Ok(())
+    }
+
     /// Run forever in the broker
     #[cfg(not(feature = "llmp_broker_timeouts"))]
     pub fn broker_loop(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        if self.hierarchy.is_some() {
+            return self.hierarchy_loop();
+        }
+
         let monitor = &mut self.monitor;
         #[cfg(feature = "llmp_compression")]
-        let compressor = &self.compressor;
+        let compressor = &mut self.compressor;
+        #[cfg(feature = "std")]
+        let msg_log = &mut self.msg_log;
+        let filters = &mut self.filters;
         self.llmp.loop_forever(
             &mut |client_id, tag, _flags, msg| {
+                #[cfg(feature = "std")]
+                Self::record_msg(msg_log, client_id, tag, _flags, msg);
+                if tag == LLMP_TAG_COMPRESSION_DICTIONARY {
+                    #[cfg(feature = "llmp_compression")]
+                    install_dictionary(compressor, msg);
+                    return Ok(llmp::LlmpMsgHookResult::ForwardToClients);
+                }
                 if tag == LLMP_TAG_EVENT_TO_BOTH {
                     #[cfg(not(feature = "llmp_compression"))]
                     let event_bytes = msg;
                     #[cfg(feature = "llmp_compression")]
                     let compressed;
                     #[cfg(feature = "llmp_compression")]
-                    let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                        compressed = compressor.decompress(msg)?;
+                    let event_bytes = if let Some(algorithm) = _flags.compression() {
+                        compressed = compressor.decompress(algorithm, msg)?;
                         &compressed
                     } else {
                         msg
                     };
                     let event: Event<I> = postcard::from_bytes(event_bytes)?;
-                    match Self::handle_in_broker(monitor, client_id, &event)? {
+                    match Self::handle_in_broker(monitor, filters, client_id, &event, true)? {
                         BrokerEventResult::Forward => Ok(llmp::LlmpMsgHookResult::ForwardToClients),
                         BrokerEventResult::Handled => Ok(llmp::LlmpMsgHookResult::Handled),
                     }
@@ -186,26 +333,41 @@ where
     /// Run in the broker until all clients exit
     #[cfg(feature = "llmp_broker_timeouts")]
     pub fn broker_loop(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        if self.hierarchy.is_some() {
+            return self.hierarchy_loop();
+        }
+
         let monitor = &mut self.monitor;
         #[cfg(feature = "llmp_compression")]
-        let compressor = &self.compressor;
+        let compressor = &mut self.compressor;
+        #[cfg(feature = "std")]
+        let msg_log = &mut self.msg_log;
+        let filters = &mut self.filters;
         self.llmp.loop_with_timeouts(
             &mut |msg_or_timeout| {
                 if let Some((client_id, tag, _flags, msg)) = msg_or_timeout {
+                    #[cfg(feature = "std")]
+                    Self::record_msg(msg_log, client_id, tag, _flags, msg);
+                    if tag == LLMP_TAG_COMPRESSION_DICTIONARY {
+                        #[cfg(feature = "llmp_compression")]
+                        install_dictionary(compressor, msg);
+                        return Ok(llmp::LlmpMsgHookResult::ForwardToClients);
+                    }
                     if tag == LLMP_TAG_EVENT_TO_BOTH {
                         #[cfg(not(feature = "llmp_compression"))]
                         let event_bytes = msg;
                         #[cfg(feature = "llmp_compression")]
                         let compressed;
                         #[cfg(feature = "llmp_compression")]
-                        let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                            compressed = compressor.decompress(msg)?;
+                        let event_bytes = if let Some(algorithm) = _flags.compression() {
+                            compressed = compressor.decompress(algorithm, msg)?;
                             &compressed
                         } else {
                             msg
                         };
                         let event: Event<I> = postcard::from_bytes(event_bytes)?;
-                        match Self::handle_in_broker(monitor, client_id, &event)? {
+                        match Self::handle_in_broker(monitor, filters, client_id, &event, true)? {
                             BrokerEventResult::Forward => {
                                 Ok(llmp::LlmpMsgHookResult::ForwardToClients)
                             }
@@ -229,16 +391,98 @@ where
         Err(Error::shutting_down())
     }
 
-    /// Handle arriving events in the broker
+    /// Run in the broker of a tree node until all clients exit,
+    /// reporting the stats of the subtree to the parent broker in-between.
+    #[cfg(feature = "std")]
+    fn hierarchy_loop(&mut self) -> Result<(), Error> {
+        llmp::LlmpBroker::<SP>::setup_shutdown_handlers();
+
+        while !self.llmp.is_done() {
+            let monitor = &mut self.monitor;
+            #[cfg(feature = "llmp_compression")]
+            let compressor = &mut self.compressor;
+            let msg_log = &mut self.msg_log;
+            let filters = &mut self.filters;
+            let node = self.hierarchy.as_mut().unwrap();
+            self.llmp.once(&mut |client_id, tag, flags, msg| {
+                Self::record_msg(msg_log, client_id, tag, flags, msg);
+                if tag == LLMP_TAG_COMPRESSION_DICTIONARY {
+                    #[cfg(feature = "llmp_compression")]
+                    install_dictionary(compressor, msg);
+                    return Ok(llmp::LlmpMsgHookResult::ForwardToClients);
+                }
+                if tag != LLMP_TAG_EVENT_TO_BOTH {
+                    return Ok(llmp::LlmpMsgHookResult::ForwardToClients);
+                }
+                #[cfg(not(feature = "llmp_compression"))]
+                let event_bytes = msg;
+                #[cfg(feature = "llmp_compression")]
+                let compressed;
+                #[cfg(feature = "llmp_compression")]
+                let event_bytes = if let Some(algorithm) = flags.compression() {
+                    compressed = compressor.decompress(algorithm, msg)?;
+                    &compressed
+                } else {
+                    msg
+                };
+                let event: Event<I> = postcard::from_bytes(event_bytes)?;
+                if let Event::CustomBuf { tag, buf } = &event {
+                    if tag == BROKER_STATS_TAG {
+                        node.handle_report(monitor, client_id, buf)?;
+                        return Ok(llmp::LlmpMsgHookResult::Handled);
+                    }
+                }
+                // Other nodes' stats arrive as reports, so their testcases must not update the stats of the B2B client.
+                let from_b2b = flags & LLMP_FLAG_FROM_B2B == LLMP_FLAG_FROM_B2B;
+                match Self::handle_in_broker(monitor, filters, client_id, &event, !from_b2b)? {
+                    BrokerEventResult::Forward => Ok(llmp::LlmpMsgHookResult::ForwardToClients),
+                    BrokerEventResult::Handled => Ok(llmp::LlmpMsgHookResult::Handled),
+                }
+            })?;
+
+            if let Some(report) = node.report::<I, MT>(monitor)? {
+                self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &report)?;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        self.llmp.send_exiting()?;
+
+        #[cfg(feature = "llmp_debug")]
+        println!("The last client quit. Exiting.");
+
+        Err(Error::shutting_down())
+    }
+
+    /// Append a message to the message log, if any.
+    /// A failing log must not take down the broker, so errors are only reported.
+    #[cfg(feature = "std")]
+    fn record_msg(
+        msg_log: &mut Option<LlmpMsgLog>,
+        client_id: ClientId,
+        tag: Tag,
+        flags: Flags,
+        msg: &[u8],
+    ) {
+        if let Some(msg_log) = msg_log {
+            if let Err(err) = msg_log.record(client_id, tag, flags, msg) {
+                log::warn!("Failed to record LLMP message: {err}");
+            }
+        }
+    }
+
+    /// Handle arriving events in the broker.
+    /// With `testcase_stats` unset, new testcases don't update the stats of their sender.
     #[allow(clippy::unnecessary_wraps)]
     fn handle_in_broker(
         monitor: &mut MT,
+        filters: &mut BrokerEventFilters<I>,
         client_id: ClientId,
         event: &Event<I>,
+        testcase_stats: bool,
     ) -> Result<BrokerEventResult, Error> {
         match &event {
             Event::NewTestcase {
-                input: _,
+                input,
                 client_config: _,
                 exit_kind: _,
                 corpus_size,
@@ -253,16 +497,26 @@ where
                     client_id
                 };
 
-                monitor.client_stats_insert(id);
-                let client = monitor.client_stats_mut_for(id);
-                client.update_corpus_size(*corpus_size as u64);
-                if id == client_id {
-                    // do not update executions for forwarded messages, otherwise we loose the total order
-                    // as a forwarded msg with a lower executions may arrive after a stats msg with an higher executions
-                    // this also means when you wrap this event manger with centralized EM, you will **NOT** get executions update with the new tc message
-                    client.update_executions(*executions, *time);
+                if testcase_stats {
+                    monitor.client_stats_insert(id);
+                    let client = monitor.client_stats_mut_for(id);
+                    client.update_corpus_size(*corpus_size as u64);
+                    if id == client_id {
+                        // do not update executions for forwarded messages, otherwise we loose the total order
+                        // as a forwarded msg with a lower executions may arrive after a stats msg with an higher executions
+                        // this also means when you wrap this event manger with centralized EM, you will **NOT** get executions update with the new tc message
+                        client.update_executions(*executions, *time);
+                    }
+                    record_testcase_size(monitor, id, input);
+                    monitor.display(event.name(), id);
+                }
+
+                if filters
+                    .filter_and_report(monitor, client_id, event)?
+                    .is_some()
+                {
+                    return Ok(BrokerEventResult::Handled);
                 }
-                monitor.display(event.name(), id);
                 Ok(BrokerEventResult::Forward)
             }
             Event::UpdateExecStats {
@@ -355,7 +609,7 @@ where
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CompressorSet,
     /// The configuration defines this specific fuzzer.
     /// A node will not re-use the observer values sent over LLMP
     /// from nodes with other configurations.
@@ -451,7 +705,7 @@ where
             hooks: tuple_list!(),
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             configuration,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
@@ -506,7 +760,7 @@ where
             hooks: tuple_list!(),
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -575,7 +829,7 @@ where
             hooks,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             configuration,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
@@ -636,7 +890,7 @@ where
             hooks,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -737,6 +991,31 @@ where
     pub fn to_env(&self, env_name: &str) {
         self.llmp.to_env(env_name).unwrap();
     }
+
+    /// The compressors used for events, for example to pick another compression algorithm
+    #[cfg(feature = "llmp_compression")]
+    pub fn compressor_mut(&mut self) -> &mut CompressorSet {
+        &mut self.compressor
+    }
+
+    /// Compress with the given zstd `dictionary` from now on, and send it to the broker and all other clients.
+    /// Trained on the corpus with [`train_corpus_dictionary`], it makes small events compress a lot better.
+    ///
+    /// It's only used for zstd, see [`CompressorSet::set_algorithm`].
+    /// Clients attaching after the dictionary was sent won't receive it.
+    /// The centralized event manager does not use it for events sent to the main node.
+    #[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+    pub fn share_dictionary(&mut self, state: &mut S, dictionary: Vec<u8>) -> Result<(), Error>
+    where
+        S: HasMetadata,
+    {
+        // Others must receive the dictionary before our first message compressed with it
+        self.llmp
+            .send_buf(LLMP_TAG_COMPRESSION_DICTIONARY, &dictionary)?;
+        install_dictionary(&mut self.compressor, &dictionary);
+        state.add_metadata(CompressionDictionaryMetadata { dictionary });
+        Ok(())
+    }
 }
 
 impl<EMH, S, SP> LlmpEventManager<EMH, S, SP>
@@ -855,11 +1134,11 @@ where
         let serialized = postcard::to_allocvec(&event)?;
         let flags = LLMP_FLAG_INITIALIZED;
 
-        match self.compressor.maybe_compress(&serialized) {
-            Some(comp_buf) => {
+        match self.compressor.maybe_compress(&serialized)? {
+            Some((algorithm, comp_buf)) => {
                 self.llmp.send_buf_with_flags(
                     LLMP_TAG_EVENT_TO_BOTH,
-                    flags | LLMP_FLAG_COMPRESSED,
+                    flags | Flags::compressed_with(algorithm),
                     &comp_buf,
                 )?;
             }
@@ -938,6 +1217,13 @@ where
     ) -> Result<usize, Error> {
         // TODO: Get around local event copy by moving handle_in_client
         let self_id = self.llmp.sender().id();
+        // After a restart, the shared dictionary is only left in the state
+        #[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+        if self.compressor.zstd().dictionary().is_none() {
+            if let Some(metadata) = state.metadata_map().get::<CompressionDictionaryMetadata>() {
+                install_dictionary(&mut self.compressor, metadata.dictionary());
+            }
+        }
         let mut count = 0;
         while let Some((client_id, tag, _flags, msg)) = self.llmp.recv_buf_with_flags()? {
             assert!(
@@ -948,13 +1234,22 @@ where
             if client_id == self_id {
                 continue;
             }
+            if tag == LLMP_TAG_COMPRESSION_DICTIONARY {
+                #[cfg(feature = "llmp_compression")]
+                install_dictionary(&mut self.compressor, msg);
+                #[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+                state.add_metadata(CompressionDictionaryMetadata {
+                    dictionary: msg.to_vec(),
+                });
+                continue;
+            }
             #[cfg(not(feature = "llmp_compression"))]
             let event_bytes = msg;
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
+            let event_bytes = if let Some(algorithm) = _flags.compression() {
+                compressed = self.compressor.decompress(algorithm, msg)?;
                 &compressed
             } else {
                 msg
@@ -1059,6 +1354,8 @@ where
     staterestorer: StateRestorer<SP>,
     /// Decide if the state restorer must save the serialized state
     save_state: LlmpShouldSaveState,
+    /// Periodically writes the state to disk, if set
+    checkpointer: Option<StateCheckpointer>,
 }
 
 #[cfg(all(feature = "std", feature = "adaptive_serialization"))]
@@ -1208,6 +1505,9 @@ where
     fn process(&mut self, fuzzer: &mut Z, state: &mut S, executor: &mut E) -> Result<usize, Error> {
         let res = self.llmp_mgr.process(fuzzer, state, executor)?;
         self.intermediate_save()?;
+        if let Some(checkpointer) = &mut self.checkpointer {
+            checkpointer.maybe_checkpoint(state)?;
+        }
         Ok(res)
     }
 }
@@ -1254,6 +1554,7 @@ where
             llmp_mgr,
             staterestorer,
             save_state: LlmpShouldSaveState::OnRestart,
+            checkpointer: None,
         }
     }
 
@@ -1267,9 +1568,36 @@ where
             llmp_mgr,
             staterestorer,
             save_state,
+            checkpointer: None,
         }
     }
 
+    /// Periodically write checkpoints of the state to disk, see [`StateCheckpointer`]
+    pub fn set_checkpointer(&mut self, checkpointer: Option<StateCheckpointer>) {
+        self.checkpointer = checkpointer;
+    }
+
+    /// Get the checkpointer, if any
+    pub fn checkpointer(&self) -> Option<&StateCheckpointer> {
+        self.checkpointer.as_ref()
+    }
+
+    /// The compressors used for events, for example to pick another compression algorithm
+    #[cfg(feature = "llmp_compression")]
+    pub fn compressor_mut(&mut self) -> &mut CompressorSet {
+        self.llmp_mgr.compressor_mut()
+    }
+
+    /// Share a zstd dictionary with the broker and all other clients,
+    /// see [`LlmpEventManager::share_dictionary`]
+    #[cfg(all(feature = "llmp_compression", feature = "zstd"))]
+    pub fn share_dictionary(&mut self, state: &mut S, dictionary: Vec<u8>) -> Result<(), Error>
+    where
+        S: HasMetadata,
+    {
+        self.llmp_mgr.share_dictionary(state, dictionary)
+    }
+
     /// Get the staterestorer
     pub fn staterestorer(&self) -> &StateRestorer<SP> {
         &self.staterestorer
@@ -1310,6 +1638,7 @@ pub enum ManagerKind {
 /// Sets up a restarting fuzzer, using the [`StdShMemProvider`], and standard features.
 /// The restarting mgr is a combination of restarter and runner, that can be used on systems with and without `fork` support.
 /// The restarter will spawn a new process each time the child crashes or timeouts.
+/// To also keep the state across reboots, use [`setup_restarting_mgr_std_with_checkpointer`].
 #[cfg(all(feature = "std", not(feature = "adaptive_serialization")))]
 #[allow(clippy::type_complexity)]
 pub fn setup_restarting_mgr_std<MT, S>(
@@ -1337,9 +1666,43 @@ where
         .launch()
 }
 
+/// Sets up a restarting fuzzer like [`setup_restarting_mgr_std`], that also periodically writes
+/// a checkpoint of the state to disk using `checkpointer`.
+/// If no restarted client state is around, the fuzzer resumes from the last checkpoint, for
+/// example after a reboot.
+#[cfg(all(feature = "std", not(feature = "adaptive_serialization")))]
+#[allow(clippy::type_complexity)]
+pub fn setup_restarting_mgr_std_with_checkpointer<MT, S>(
+    monitor: MT,
+    broker_port: u16,
+    configuration: EventConfig,
+    checkpointer: StateCheckpointer,
+) -> Result<
+    (
+        Option<S>,
+        LlmpRestartingEventManager<(), S, StdShMemProvider>,
+    ),
+    Error,
+>
+where
+    MT: Monitor + Clone,
+    S: State + HasExecutions,
+{
+    RestartingMgr::builder()
+        .shmem_provider(StdShMemProvider::new()?)
+        .monitor(Some(monitor))
+        .broker_port(broker_port)
+        .configuration(configuration)
+        .checkpointer(Some(checkpointer))
+        .hooks(tuple_list!())
+        .build()
+        .launch()
+}
+
 /// Sets up a restarting fuzzer, using the [`StdShMemProvider`], and standard features.
 /// The restarting mgr is a combination of restarter and runner, that can be used on systems with and without `fork` support.
 /// The restarter will spawn a new process each time the child crashes or timeouts.
+/// To also keep the state across reboots, use [`setup_restarting_mgr_std_with_checkpointer`].
 #[cfg(all(feature = "std", feature = "adaptive_serialization"))]
 #[allow(clippy::type_complexity)]
 pub fn setup_restarting_mgr_std<MT, S>(
@@ -1369,6 +1732,41 @@ where
         .launch()
 }
 
+/// Sets up a restarting fuzzer like [`setup_restarting_mgr_std`], that also periodically writes
+/// a checkpoint of the state to disk using `checkpointer`.
+/// If no restarted client state is around, the fuzzer resumes from the last checkpoint, for
+/// example after a reboot.
+#[cfg(all(feature = "std", feature = "adaptive_serialization"))]
+#[allow(clippy::type_complexity)]
+pub fn setup_restarting_mgr_std_with_checkpointer<MT, S>(
+    monitor: MT,
+    broker_port: u16,
+    configuration: EventConfig,
+    time_obs: &TimeObserver,
+    checkpointer: StateCheckpointer,
+) -> Result<
+    (
+        Option<S>,
+        LlmpRestartingEventManager<(), S, StdShMemProvider>,
+    ),
+    Error,
+>
+where
+    MT: Monitor + Clone,
+    S: State + HasExecutions,
+{
+    RestartingMgr::builder()
+        .shmem_provider(StdShMemProvider::new()?)
+        .monitor(Some(monitor))
+        .broker_port(broker_port)
+        .configuration(configuration)
+        .checkpointer(Some(checkpointer))
+        .hooks(tuple_list!())
+        .time_ref(time_obs.handle())
+        .build()
+        .launch()
+}
+
 /// Provides a `builder` which can be used to build a [`RestartingMgr`], which is a combination of a
 /// `restarter` and `runner`, that can be used on systems both with and without `fork` support. The
 /// `restarter` will start a new process each time the child crashes or times out.
@@ -1397,6 +1795,10 @@ where
     /// The address to connect to
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The place of this broker in a tree of brokers, see [`LlmpEventBroker::join_hierarchy`].
+    /// Takes precedence over `remote_broker_addr`.
+    #[builder(default = None)]
+    broker_node: Option<BrokerNode>,
     /// The type of manager to build
     #[builder(default = ManagerKind::Any)]
     kind: ManagerKind,
@@ -1411,6 +1813,10 @@ where
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// Periodically write the state to disk, and resume from the last checkpoint if the client
+    /// starts from scratch (for example, after a reboot).
+    #[builder(default = None)]
+    checkpointer: Option<StateCheckpointer>,
     /// The hooks passed to event manager:
     hooks: EMH,
     #[cfg(feature = "adaptive_serialization")]
@@ -1436,7 +1842,9 @@ where
         {
             let broker_things = |mut broker: LlmpEventBroker<S::Input, MT, SP>,
                                  remote_broker_addr| {
-                if let Some(remote_broker_addr) = remote_broker_addr {
+                if let Some(broker_node) = &self.broker_node {
+                    broker.join_hierarchy(broker_node.clone())?;
+                } else if let Some(remote_broker_addr) = remote_broker_addr {
                     log::info!("B2b: Connecting to {:?}", &remote_broker_addr);
                     broker.connect_b2b(remote_broker_addr)?;
                 };
@@ -1529,12 +1937,16 @@ where
 
             // First, create a channel from the current fuzzer to the next to store state between restarts.
             #[cfg(unix)]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP> = StateRestorer::new(
+                self.shmem_provider
+                    .new_shmem_with_purpose(256 * 1024 * 1024, ShMemPurpose::StateRestorer)?,
+            );
 
             #[cfg(not(unix))]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP> = StateRestorer::new(
+                self.shmem_provider
+                    .new_shmem_with_purpose(256 * 1024 * 1024, ShMemPurpose::StateRestorer)?,
+            );
             // Store the information to a map.
             staterestorer.write_to_env(_ENV_FUZZER_SENDER)?;
 
@@ -1659,7 +2071,20 @@ where
                     ),
                 )
             } else {
-                log::info!("First run. Let's set it all up");
+                // Nothing to restore from the last client, but maybe from the last campaign
+                let state_opt = match &self.checkpointer {
+                    Some(checkpointer) => {
+                        let state_opt = checkpointer.resume()?;
+                        if state_opt.is_some() {
+                            log::info!("Resuming from checkpoint {:?}", checkpointer.path());
+                        }
+                        state_opt
+                    }
+                    None => None,
+                };
+                if state_opt.is_none() {
+                    log::info!("First run. Let's set it all up");
+                }
                 // Mgr to send and receive msgs from/to all other fuzzer instances
                 #[cfg(not(feature = "adaptive_serialization"))]
                 let mgr = LlmpEventManager::<EMH, S, SP>::existing_client_from_env_with_hooks(
@@ -1678,7 +2103,7 @@ where
                 )?;
 
                 (
-                    None,
+                    state_opt,
                     LlmpRestartingEventManager::with_save_state(
                         mgr,
                         staterestorer,
@@ -1686,6 +2111,7 @@ where
                     ),
                 )
             };
+        mgr.set_checkpointer(self.checkpointer.clone());
         // We reset the staterestorer, the next staterestorer and receiver (after crash) will reuse the page from the initial message.
         if self.serialize_state.oom_safe() {
             mgr.intermediate_save()?;
@@ -1716,7 +2142,7 @@ where
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
     #[cfg(feature = "llmp_compression")]
-    compressor: GzipCompressor,
+    compressor: CompressorSet,
     converter: Option<IC>,
     converter_back: Option<ICB>,
     phantom: PhantomData<S>,
@@ -1761,7 +2187,7 @@ where
         Ok(Self {
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             converter,
             converter_back,
             phantom: PhantomData,
@@ -1781,7 +2207,7 @@ where
         Ok(Self {
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             converter,
             converter_back,
             phantom: PhantomData,
@@ -1800,7 +2226,7 @@ where
         Ok(Self {
             llmp: LlmpClient::on_existing_from_env(shmem_provider, env_name)?,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: CompressorSet::with_threshold(COMPRESS_THRESHOLD),
             phantom: PhantomData,
             converter,
             converter_back,
@@ -1919,13 +2345,18 @@ where
             if client_id == self_id {
                 continue;
             }
+            if tag == LLMP_TAG_COMPRESSION_DICTIONARY {
+                #[cfg(feature = "llmp_compression")]
+                install_dictionary(&mut self.compressor, msg);
+                continue;
+            }
             #[cfg(not(feature = "llmp_compression"))]
             let event_bytes = msg;
             #[cfg(feature = "llmp_compression")]
             let compressed;
             #[cfg(feature = "llmp_compression")]
-            let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
+            let event_bytes = if let Some(algorithm) = _flags.compression() {
+                compressed = self.compressor.decompress(algorithm, msg)?;
                 &compressed
             } else {
                 msg
@@ -1997,11 +2428,11 @@ where
         let serialized = postcard::to_allocvec(&converted_event)?;
         let flags = LLMP_FLAG_INITIALIZED;
 
-        match self.compressor.maybe_compress(&serialized) {
-            Some(comp_buf) => {
+        match self.compressor.maybe_compress(&serialized)? {
+            Some((algorithm, comp_buf)) => {
                 self.llmp.send_buf_with_flags(
                     LLMP_TAG_EVENT_TO_BOTH,
-                    flags | LLMP_FLAG_COMPRESSED,
+                    flags | Flags::compressed_with(algorithm),
                     &comp_buf,
                 )?;
             }
@@ -1,6 +1,9 @@
 //! An [`EventManager`] manages all events that go to other instances of the fuzzer.
 //! The messages are commonly information about new Testcases as well as stats and other [`Event`]s.
 
+pub mod filters;
+#[cfg(feature = "std")]
+pub mod hierarchy;
 pub mod hooks;
 
 pub mod simple;
@@ -39,8 +42,17 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 use uuid::Uuid;
 
+#[cfg(all(feature = "prometheus_monitor", feature = "std"))]
+use crate::monitors::prometheus::CLIENT_HOST_STAT;
+#[cfg(any(
+    feature = "scalability_introspection",
+    all(feature = "prometheus_monitor", feature = "std")
+))]
+use crate::monitors::{AggregatorOps, UserStatsValue};
 #[cfg(feature = "introspection")]
 use crate::state::HasClientPerfMonitor;
+#[cfg(feature = "scalability_introspection")]
+use crate::state::HasScalabilityMonitor;
 use crate::{
     executors::ExitKind,
     inputs::Input,
@@ -49,11 +61,6 @@ use crate::{
     state::{HasExecutions, HasLastReportTime, State},
     Error, HasMetadata,
 };
-#[cfg(feature = "scalability_introspection")]
-use crate::{
-    monitors::{AggregatorOps, UserStatsValue},
-    state::HasScalabilityMonitor,
-};
 
 /// Check if ctrl-c is sent with this struct
 #[cfg(all(unix, feature = "std"))]
@@ -452,6 +459,19 @@ where
         let Some(last_report_time) = state.last_report_time() else {
             // this is the first time we execute, no need to report progress just yet.
             *state.last_report_time_mut() = Some(current_time());
+            // Tell a `PrometheusMonitor` where this client runs, to label its metrics
+            #[cfg(all(feature = "prometheus_monitor", feature = "std"))]
+            self.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from(CLIENT_HOST_STAT),
+                    value: UserStats::new(
+                        UserStatsValue::String(libafl_bolts::os::hostname().into()),
+                        AggregatorOps::None,
+                    ),
+                    phantom: PhantomData,
+                },
+            )?;
             return Ok(());
         };
         let cur = current_time();
@@ -18,7 +18,11 @@ use libafl_bolts::os::unix_signals::setup_signal_handler;
 use libafl_bolts::os::{fork, ForkResult};
 use libafl_bolts::ClientId;
 #[cfg(feature = "std")]
-use libafl_bolts::{os::CTRL_C_EXIT, shmem::ShMemProvider, staterestore::StateRestorer};
+use libafl_bolts::{
+    os::CTRL_C_EXIT,
+    shmem::{ShMemProvider, ShMemPurpose},
+    staterestore::StateRestorer,
+};
 #[cfg(feature = "std")]
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -31,7 +35,7 @@ use crate::{
         EventRestarter, HasEventManagerId,
     },
     inputs::UsesInput,
-    monitors::Monitor,
+    monitors::{record_testcase_size, Monitor},
     state::{HasExecutions, HasLastReportTime, State, UsesState},
     Error, HasMetadata,
 };
@@ -201,7 +205,7 @@ where
     ) -> Result<BrokerEventResult, Error> {
         match event {
             Event::NewTestcase {
-                input: _,
+                input,
                 client_config: _,
                 exit_kind: _,
                 corpus_size,
@@ -217,6 +221,7 @@ where
                 monitor
                     .client_stats_mut_for(ClientId(0))
                     .update_executions(*executions, *time);
+                record_testcase_size(monitor, ClientId(0), input);
                 monitor.display(event.name(), ClientId(0));
                 Ok(BrokerEventResult::Handled)
             }
@@ -466,11 +471,15 @@ where
         let mut staterestorer = if std::env::var(_ENV_FUZZER_SENDER).is_err() {
             // First, create a place to store state in, for restarts.
             #[cfg(unix)]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP> = StateRestorer::new(
+                shmem_provider
+                    .new_shmem_with_purpose(256 * 1024 * 1024, ShMemPurpose::StateRestorer)?,
+            );
             #[cfg(not(unix))]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP> = StateRestorer::new(
+                shmem_provider
+                    .new_shmem_with_purpose(256 * 1024 * 1024, ShMemPurpose::StateRestorer)?,
+            );
 
             //let staterestorer = { LlmpSender::new(shmem_provider.clone(), 0, false)? };
             staterestorer.write_to_env(_ENV_FUZZER_SENDER)?;
@@ -16,7 +16,7 @@ use std::{
 };
 
 #[cfg(feature = "tcp_compression")]
-use libafl_bolts::compress::GzipCompressor;
+use libafl_bolts::compress::{CompressionAlgorithm, CompressorSet};
 #[cfg(feature = "std")]
 use libafl_bolts::core_affinity::CoreId;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
@@ -27,7 +27,10 @@ use libafl_bolts::os::unix_signals::setup_signal_handler;
 use libafl_bolts::os::{fork, ForkResult};
 use libafl_bolts::{shmem::ShMemProvider, tuples::tuple_list, ClientId};
 #[cfg(feature = "std")]
-use libafl_bolts::{shmem::StdShMemProvider, staterestore::StateRestorer};
+use libafl_bolts::{
+    shmem::{ShMemPurpose, StdShMemProvider},
+    staterestore::StateRestorer,
+};
 use serde::{de::DeserializeOwned, Deserialize};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -49,11 +52,29 @@ use crate::{
     executors::{Executor, HasObservers},
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
     inputs::{Input, UsesInput},
-    monitors::Monitor,
+    monitors::{record_testcase_size, Monitor},
     state::{HasExecutions, HasLastReportTime, State, UsesState},
     Error, HasMetadata,
 };
 
+/// Compresses `buf` with the current algorithm of the `compressor`.
+/// The algorithm id is prepended, so that the other side can pick the right decompressor.
+#[cfg(feature = "tcp_compression")]
+fn compress_tcp(compressor: &CompressorSet, buf: &[u8]) -> Result<Vec<u8>, Error> {
+    let (algorithm, compressed) = compressor.compress(buf)?;
+    let mut tagged = Vec::with_capacity(compressed.len() + 1);
+    tagged.push(algorithm.id());
+    tagged.extend_from_slice(&compressed);
+    Ok(tagged)
+}
+
+/// Decompresses a buffer created by [`compress_tcp`]
+#[cfg(feature = "tcp_compression")]
+fn decompress_tcp(compressor: &CompressorSet, buf: &[u8]) -> Result<Vec<u8>, Error> {
+    let (algorithm, compressed) = buf.split_first().ok_or_else(Error::compression)?;
+    compressor.decompress(CompressionAlgorithm::from_id(*algorithm)?, compressed)
+}
+
 /// Tries to create (synchronously) a [`TcpListener`] that is `nonblocking` (for later use in tokio).
 /// Will error if the port is already in use (or other errors occur)
 fn create_nonblocking_listener<A: ToSocketAddrs>(addr: A) -> Result<TcpListener, Error> {
@@ -272,6 +293,10 @@ where
             }*/
         });
 
+        // Decompresses all enabled algorithms, no matter which one the clients picked.
+        #[cfg(feature = "tcp_compression")]
+        let compressor = CompressorSet::with_threshold(0);
+
         loop {
             let buf = rx_mpsc.recv().await.expect("Could not receive");
 
@@ -285,7 +310,7 @@ where
             let event_bytes = &buf[4..];
 
             #[cfg(feature = "tcp_compression")]
-            let event_bytes = GzipCompressor::new().decompress(event_bytes)?;
+            let event_bytes = decompress_tcp(&compressor, event_bytes)?;
 
             #[allow(clippy::needless_borrow)] // make decompressed vec and slice compatible
             let event: Event<I> = postcard::from_bytes(&event_bytes)?;
@@ -315,7 +340,7 @@ where
     ) -> Result<BrokerEventResult, Error> {
         match &event {
             Event::NewTestcase {
-                input: _,
+                input,
                 client_config: _,
                 exit_kind: _,
                 corpus_size,
@@ -333,6 +358,7 @@ where
                 let client = monitor.client_stats_mut_for(id);
                 client.update_corpus_size(*corpus_size as u64);
                 client.update_executions(*executions, *time);
+                record_testcase_size(monitor, id, input);
                 monitor.display(event.name(), id);
                 Ok(BrokerEventResult::Forward)
             }
@@ -427,7 +453,7 @@ where
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
     #[cfg(feature = "tcp_compression")]
-    compressor: GzipCompressor,
+    compressor: CompressorSet,
     /// The configuration defines this specific fuzzer.
     /// A node will not re-use the observer values sent over TCP
     /// from nodes with other configurations.
@@ -550,7 +576,7 @@ where
             tcp,
             client_id,
             #[cfg(feature = "tcp_compression")]
-            compressor: GzipCompressor::new(),
+            compressor: CompressorSet::with_threshold(0),
             configuration,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
@@ -610,6 +636,13 @@ where
         env::set_var(env_name, format!("{}", self.client_id.0));
     }
 
+    /// The compressors used for events, for example to pick another
+    /// [`libafl_bolts::compress::CompressionAlgorithm`] or to set a zstd dictionary.
+    #[cfg(feature = "tcp_compression")]
+    pub fn compressor_mut(&mut self) -> &mut CompressorSet {
+        &mut self.compressor
+    }
+
     // Handle arriving events in the client
     #[allow(clippy::unused_self)]
     fn handle_in_client<E, Z>(
@@ -719,7 +752,7 @@ where
         let serialized = postcard::to_allocvec(&event)?;
 
         #[cfg(feature = "tcp_compression")]
-        let serialized = self.compressor.compress(&serialized);
+        let serialized = compress_tcp(&self.compressor, &serialized)?;
 
         let size = u32::try_from(serialized.len())?;
         self.tcp.write_all(&size.to_le_bytes())?;
@@ -789,7 +822,7 @@ where
 
                         let buf = &buf[4..];
                         #[cfg(feature = "tcp_compression")]
-                        let buf = self.compressor.decompress(buf)?;
+                        let buf = decompress_tcp(&self.compressor, buf)?;
 
                         // make decompressed vec and slice compatible
                         #[allow(clippy::needless_borrow)]
@@ -1220,12 +1253,16 @@ where
 
             // First, create a channel from the current fuzzer to the next to store state between restarts.
             #[cfg(unix)]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP> = StateRestorer::new(
+                self.shmem_provider
+                    .new_shmem_with_purpose(256 * 1024 * 1024, ShMemPurpose::StateRestorer)?,
+            );
 
             #[cfg(not(unix))]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP> = StateRestorer::new(
+                self.shmem_provider
+                    .new_shmem_with_purpose(256 * 1024 * 1024, ShMemPurpose::StateRestorer)?,
+            );
             // Store the information to a map.
             staterestorer.write_to_env(_ENV_FUZZER_SENDER)?;
 
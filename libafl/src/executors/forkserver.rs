@@ -1255,7 +1255,7 @@ mod tests {
     use std::ffi::OsString;
 
     use libafl_bolts::{
-        shmem::{ShMem, ShMemProvider, UnixShMemProvider},
+        shmem::{ShMem, ShMemProvider, ShMemPurpose, UnixShMemProvider},
         tuples::tuple_list,
         AsSliceMut,
     };
@@ -1277,7 +1277,9 @@ mod tests {
 
         let mut shmem_provider = UnixShMemProvider::new().unwrap();
 
-        let mut shmem = shmem_provider.new_shmem(MAP_SIZE).unwrap();
+        let mut shmem = shmem_provider
+            .new_shmem_with_purpose(MAP_SIZE, ShMemPurpose::CoverageMap)
+            .unwrap();
         shmem.write_to_env("__AFL_SHM_ID").unwrap();
         let shmem_buf = shmem.as_slice_mut();
 
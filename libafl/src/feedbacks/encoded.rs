@@ -0,0 +1,123 @@
+//! Feedback that learns which tokens of a [`TokenVocabulary`] lead to new coverage
+
+use alloc::{borrow::Cow, boxed::Box};
+
+use libafl_bolts::{Error, Named};
+
+use crate::{
+    corpus::{Corpus, Testcase},
+    events::{CustomBufEventResult, Event, EventFirer, HasCustomBufHandlers},
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::{EncodedInput, TokenVocabulary, TOKEN_VOCABULARY_TAG},
+    observers::ObserversTuple,
+    state::{HasCorpus, State},
+    HasMetadata,
+};
+
+/// A feedback that rewards the tokens of each new corpus entry in the [`TokenVocabulary`] of the state.
+///
+/// It is never interesting on its own, combine it with a coverage feedback.
+/// The vocabulary mutators then prefer tokens that found new coverage before.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TokenVocabularyFeedback {
+    sync: bool,
+}
+
+impl TokenVocabularyFeedback {
+    /// Creates a new [`TokenVocabularyFeedback`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self { sync: false }
+    }
+
+    /// Send the rewarded tokens to the other clients, in a `CustomBuf` event tagged [`TOKEN_VOCABULARY_TAG`].
+    ///
+    /// Each client has to receive them with [`TokenVocabularyFeedback::add_sync_handler`].
+    /// Only the counts of tokens the receiving vocabulary knows are merged, see [`TokenVocabulary::merge_counts`],
+    /// so all clients should start from the same vocabulary.
+    #[must_use]
+    pub fn with_sync(mut self) -> Self {
+        self.sync = true;
+        self
+    }
+
+    /// Merge the rewards other clients send into the [`TokenVocabulary`] of the state
+    pub fn add_sync_handler<EM>(manager: &mut EM)
+    where
+        EM: HasCustomBufHandlers,
+        EM::State: HasMetadata,
+    {
+        manager.add_custom_buf_handler(Box::new(|state, tag, buf| {
+            if tag != TOKEN_VOCABULARY_TAG {
+                return Ok(CustomBufEventResult::Next);
+            }
+            let rewarded: TokenVocabulary = postcard::from_bytes(buf)?;
+            state
+                .metadata_or_insert_with(TokenVocabulary::new)
+                .merge_counts(&rewarded);
+            Ok(CustomBufEventResult::Handled)
+        }));
+    }
+}
+
+impl Named for TokenVocabularyFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TokenVocabularyFeedback");
+        &NAME
+    }
+}
+
+impl<S> Feedback<S> for TokenVocabularyFeedback
+where
+    S: HasMetadata + HasCorpus + State<Input = EncodedInput>,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if !state.has_metadata::<TokenVocabulary>() {
+            state.add_metadata(TokenVocabulary::new());
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &EncodedInput,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<EncodedInput>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        state.corpus().load_input_into(testcase)?;
+        let input = testcase.input().as_ref().unwrap();
+        let rewarded = state.metadata_mut::<TokenVocabulary>()?.reward(input);
+        if self.sync && !rewarded.is_empty() {
+            manager.fire(
+                state,
+                Event::CustomBuf {
+                    buf: postcard::to_allocvec(&rewarded)?,
+                    tag: TOKEN_VOCABULARY_TAG.into(),
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
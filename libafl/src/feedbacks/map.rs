@@ -327,7 +327,9 @@ where
 
 libafl_bolts::impl_serdeany!(
     MapFeedbackMetadata<T: Debug + Default + Copy + 'static + Serialize + DeserializeOwned>,
-    <u8>,<u16>,<u32>,<u64>,<i8>,<i16>,<i32>,<i64>,<f32>,<f64>,<bool>,<char>,<usize>
+    <u8>,<u16>,<u32>,<u64>,<i8>,<i16>,<i32>,<i64>,<f32>,<f64>,<bool>,<char>,<usize>,
+    name = "libafl::feedbacks::MapFeedbackMetadata",
+    version = 1
 );
 
 impl<T> MapFeedbackMetadata<T>
@@ -13,6 +13,7 @@ use core::{
 #[cfg(feature = "std")]
 pub use concolic::ConcolicFeedback;
 pub use differential::DiffFeedback;
+pub use encoded::TokenVocabularyFeedback;
 use libafl_bolts::{
     tuples::{Handle, Handled, MatchNameRef},
     Named,
@@ -41,6 +42,7 @@ pub mod map;
 #[cfg(feature = "std")]
 pub mod concolic;
 pub mod differential;
+pub mod encoded;
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "std")]
@@ -1,7 +1,17 @@
 //! Gramatron generator
-use alloc::{string::String, vec::Vec};
+//!
+//! The [`Automaton`] of a grammar is built with [`gnf_grammar`] and [`Automaton::from_gnf_grammar`],
+//! like the `gnf_converter.py` and `construct_automata` tools in `utils/gramatron` do.
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, VecDeque},
+    format,
+    string::String,
+    vec::Vec,
+};
 use core::marker::PhantomData;
 
+use hashbrown::HashMap;
 use libafl_bolts::rands::Rand;
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +22,249 @@ use crate::{
     Error,
 };
 
+/// A grammar in the JSON format of the Gramatron tools, from nonterminals to their rules.
+///
+/// A rule is a list of symbols separated by whitespace, terminals are quoted with `'`, like `"'(' EXPR ')'"`.
+pub type GramatronGrammar = BTreeMap<String, Vec<String>>;
+
+/// The key of a [`GramatronGrammar`] in Greibach normal form that holds the start symbol
+pub const GRAMATRON_START: &str = "Start";
+
+/// The maximum number of states of an [`Automaton`], without a stack limit the construction may not terminate
+const MAX_AUTOMATON_STATES: usize = 1 << 22;
+
+/// The maximum number of rules while converting to Greibach normal form
+const MAX_GNF_RULES: usize = 1 << 22;
+
+/// A rule of a grammar, as list of symbols
+type Symbols = Vec<String>;
+
+/// Split a rule into its symbols, keeping the quotes of terminals
+fn rule_symbols(rule: &str) -> Symbols {
+    let mut symbols = Vec::new();
+    let mut chars = rule.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let end = if c == '\'' || c == '"' {
+            chars
+                .find(|(_, next)| *next == c)
+                .map_or(rule.len(), |(idx, _)| idx + 1)
+        } else {
+            while chars
+                .peek()
+                .is_some_and(|(_, next)| !next.is_whitespace() && *next != '\'' && *next != '"')
+            {
+                chars.next();
+            }
+            chars.peek().map_or(rule.len(), |(idx, _)| *idx)
+        };
+        symbols.push(rule[start..end].to_owned());
+    }
+    if symbols.is_empty() {
+        // The empty rule derives the empty terminal
+        symbols.push("''".to_owned());
+    }
+    symbols
+}
+
+fn is_terminal(symbol: &str) -> bool {
+    symbol.len() >= 2 && symbol.starts_with('\'') && symbol.ends_with('\'')
+}
+
+/// The text of a terminal symbol
+fn terminal_text(symbol: &str) -> String {
+    let text = &symbol[1..symbol.len() - 1];
+    if text == "\\n" {
+        "\n".to_owned()
+    } else {
+        text.to_owned()
+    }
+}
+
+/// A nonterminal name that is not used in the grammar yet
+fn fresh_nonterminal(rules: &BTreeMap<String, Vec<Symbols>>, count: &mut usize) -> String {
+    loop {
+        *count += 1;
+        let name = format!("GeneratedTermVar{count}");
+        if !rules.contains_key(&name) {
+            return name;
+        }
+    }
+}
+
+/// Replace unit rules, like `A -> B`, by the rules of the nonterminal they derive
+fn remove_unit_rules(rules: &BTreeMap<String, Vec<Symbols>>) -> BTreeMap<String, Vec<Symbols>> {
+    fn expand<'a>(
+        rules: &'a BTreeMap<String, Vec<Symbols>>,
+        nonterminal: &'a str,
+        path: &mut Vec<&'a str>,
+        expanded: &mut Vec<Symbols>,
+    ) {
+        for rule in &rules[nonterminal] {
+            match rule.as_slice() {
+                [unit] if !is_terminal(unit) => {
+                    // Cycles of unit rules derive nothing new
+                    if !path.contains(&unit.as_str()) {
+                        path.push(unit);
+                        expand(rules, unit, path, expanded);
+                        path.pop();
+                    }
+                }
+                _ => expanded.push(rule.clone()),
+            }
+        }
+    }
+
+    rules
+        .keys()
+        .map(|nonterminal| {
+            let mut expanded = Vec::new();
+            expand(rules, nonterminal, &mut vec![nonterminal], &mut expanded);
+            (nonterminal.clone(), expanded)
+        })
+        .collect()
+}
+
+/// Replace the terminals after the first symbol of rules by nonterminals that derive them
+fn remove_mixed_rules(
+    rules: BTreeMap<String, Vec<Symbols>>,
+    count: &mut usize,
+) -> BTreeMap<String, Vec<Symbols>> {
+    let mut terminals: BTreeMap<String, String> = BTreeMap::new();
+    let mut mixed = rules.clone();
+    for (nonterminal, list) in rules {
+        let list = list
+            .into_iter()
+            .map(|mut rule| {
+                for symbol in rule.iter_mut().skip(1).filter(|symbol| is_terminal(symbol)) {
+                    let replacement = terminals.entry(symbol.clone()).or_insert_with(|| {
+                        let replacement = fresh_nonterminal(&mixed, count);
+                        mixed.insert(replacement.clone(), vec![vec![symbol.clone()]]);
+                        replacement
+                    });
+                    symbol.clone_from(replacement);
+                }
+                rule
+            })
+            .collect();
+        mixed.insert(nonterminal, list);
+    }
+    mixed
+}
+
+/// Remove direct left recursion, like `A -> A x | y`, by `A -> y A'` and `A' -> x A' | ''`
+fn remove_left_recursion(
+    rules: BTreeMap<String, Vec<Symbols>>,
+    count: &mut usize,
+) -> BTreeMap<String, Vec<Symbols>> {
+    let mut result = rules.clone();
+    for (nonterminal, list) in rules {
+        let (recursive, mut others): (Vec<Symbols>, Vec<Symbols>) =
+            list.into_iter().partition(|rule| rule[0] == nonterminal);
+        if recursive.is_empty() {
+            continue;
+        }
+        let tail = fresh_nonterminal(&result, count);
+        for rule in &mut others {
+            rule.push(tail.clone());
+        }
+        let mut tail_rules: Vec<Symbols> = recursive
+            .into_iter()
+            .filter(|rule| rule.len() > 1)
+            .map(|mut rule| {
+                rule.remove(0);
+                rule.push(tail.clone());
+                rule
+            })
+            .collect();
+        tail_rules.push(vec!["''".to_owned()]);
+        result.insert(nonterminal, others);
+        result.insert(tail, tail_rules);
+    }
+    result
+}
+
+/// Convert a grammar to Greibach normal form, where every rule starts with a terminal, for [`Automaton::from_gnf_grammar`].
+///
+/// This is a port of `gnf_converter.py` in `utils/gramatron`, the result maps [`GRAMATRON_START`] to `start`.
+/// Unlike the script, left recursion ends with the empty terminal instead of a space,
+/// so that seeds can be parsed with [`Automaton::parse`].
+pub fn gnf_grammar(grammar: &GramatronGrammar, start: &str) -> Result<GramatronGrammar, Error> {
+    let mut rules: BTreeMap<String, Vec<Symbols>> = grammar
+        .iter()
+        .map(|(nonterminal, list)| {
+            (
+                nonterminal.clone(),
+                list.iter().map(|rule| rule_symbols(rule)).collect(),
+            )
+        })
+        .collect();
+    if !rules.contains_key(start) {
+        return Err(Error::illegal_argument(format!(
+            "The start symbol {start} has no rules"
+        )));
+    }
+    for (nonterminal, list) in &rules {
+        if let Some(undefined) = list
+            .iter()
+            .flatten()
+            .find(|symbol| !is_terminal(symbol) && !rules.contains_key(symbol.as_str()))
+        {
+            return Err(Error::illegal_argument(format!(
+                "Rule of {nonterminal} references the undefined nonterminal {undefined}"
+            )));
+        }
+    }
+
+    let mut count = 1;
+    rules = remove_unit_rules(&rules);
+    rules = remove_mixed_rules(rules, &mut count);
+    loop {
+        rules = remove_left_recursion(rules, &mut count);
+        let mut substituted = BTreeMap::new();
+        let mut total = 0;
+        for (nonterminal, list) in &rules {
+            let mut expanded = Vec::new();
+            for rule in list {
+                if is_terminal(&rule[0]) {
+                    expanded.push(rule.clone());
+                    continue;
+                }
+                for extension in &rules[&rule[0]] {
+                    let mut extended = extension.clone();
+                    extended.extend_from_slice(&rule[1..]);
+                    expanded.push(extended);
+                }
+            }
+            total += expanded.len();
+            substituted.insert(nonterminal.clone(), expanded);
+        }
+        if total > MAX_GNF_RULES {
+            return Err(Error::illegal_argument(
+                "The grammar grows too large in Greibach normal form",
+            ));
+        }
+        rules = substituted;
+        if rules.values().flatten().all(|rule| is_terminal(&rule[0])) {
+            break;
+        }
+    }
+
+    let mut gnf: GramatronGrammar = rules
+        .into_iter()
+        .map(|(nonterminal, list)| {
+            (
+                nonterminal,
+                list.into_iter().map(|rule| rule.join(" ")).collect(),
+            )
+        })
+        .collect();
+    gnf.insert(GRAMATRON_START.to_owned(), vec![start.to_owned()]);
+    Ok(gnf)
+}
+
 /// A trigger
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Trigger {
@@ -32,6 +285,184 @@ pub struct Automaton {
     pub pda: Vec<Vec<Trigger>>,
 }
 
+impl Automaton {
+    /// Build the [`Automaton`] of a grammar in Greibach normal form, see [`gnf_grammar`].
+    ///
+    /// This is the construction of `construct_automata` in `utils/gramatron`: each state is the stack of nonterminals left to derive,
+    /// states with the same nonterminals on the stack are merged. States with a stack larger than `stack_limit` are abandoned,
+    /// with a `stack_limit` of 0 the stack is unbounded, and the construction fails for grammars that need too many states.
+    pub fn from_gnf_grammar(gnf: &GramatronGrammar, stack_limit: usize) -> Result<Self, Error> {
+        let start = gnf
+            .get(GRAMATRON_START)
+            .and_then(|start| start.first())
+            .ok_or_else(|| Error::illegal_argument("The grammar has no Start symbol"))?;
+        let mut rules: HashMap<&str, Vec<(String, Vec<&str>)>> = HashMap::new();
+        for (nonterminal, list) in gnf.iter().filter(|(nt, _)| *nt != GRAMATRON_START) {
+            let mut parsed = Vec::with_capacity(list.len());
+            for rule in list {
+                let mut symbols = rule_symbols(rule).into_iter();
+                let terminal = symbols.next().unwrap();
+                let rest: Vec<String> = symbols.collect();
+                if !is_terminal(&terminal) || rest.iter().any(|symbol| is_terminal(symbol)) {
+                    return Err(Error::illegal_argument(format!(
+                        "Rule {rule} of {nonterminal} is not in Greibach normal form"
+                    )));
+                }
+                let rest = rest
+                    .iter()
+                    .map(|symbol| {
+                        gnf.get_key_value(symbol.as_str())
+                            .map(|(key, _)| key.as_str())
+                            .ok_or_else(|| {
+                                Error::illegal_argument(format!(
+                                    "Rule {rule} of {nonterminal} references the undefined nonterminal {symbol}"
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+                parsed.push((terminal_text(&terminal), rest));
+            }
+            rules.insert(nonterminal, parsed);
+        }
+
+        let init_state = 0;
+        let mut final_state = None;
+        let mut states: HashMap<Vec<&str>, usize> = HashMap::new();
+        let mut pda: Vec<Vec<Trigger>> = vec![vec![]];
+        let mut worklist = VecDeque::from([(init_state, vec![start.as_str()])]);
+        while let Some((state, stack)) = worklist.pop_front() {
+            let Some((nonterminal, rest)) = stack.split_first() else {
+                continue;
+            };
+            let expansions = rules.get(nonterminal).ok_or_else(|| {
+                Error::illegal_argument(format!("The nonterminal {nonterminal} has no rules"))
+            })?;
+            for (terminal, symbols) in expansions {
+                let next: Vec<&str> = symbols.iter().chain(rest).copied().collect();
+                let mut sorted = next.clone();
+                sorted.sort_unstable();
+                let dest = if let Some(dest) = states.get(&sorted) {
+                    *dest
+                } else {
+                    if stack_limit > 0 && next.len() > stack_limit {
+                        continue;
+                    }
+                    if pda.len() >= MAX_AUTOMATON_STATES {
+                        return Err(Error::illegal_argument(
+                            "The automaton has too many states, set a stack limit",
+                        ));
+                    }
+                    let dest = pda.len();
+                    pda.push(vec![]);
+                    if next.is_empty() {
+                        final_state = Some(dest);
+                    }
+                    states.insert(sorted, dest);
+                    worklist.push_back((dest, next));
+                    dest
+                };
+                pda[state].push(Trigger {
+                    dest,
+                    term: terminal.clone(),
+                });
+            }
+        }
+        let final_state = final_state
+            .ok_or_else(|| Error::illegal_argument("The grammar derives no finite input"))?;
+
+        // Drop the transitions into states that never reach the final state, as a walk would get stuck there
+        let mut alive = vec![false; pda.len()];
+        alive[final_state] = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for state in 0..pda.len() {
+                if !alive[state] && pda[state].iter().any(|trigger| alive[trigger.dest]) {
+                    alive[state] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !alive[init_state] {
+            return Err(Error::illegal_argument(
+                "The grammar has no derivation within the stack limit",
+            ));
+        }
+        for (state, triggers) in pda.iter_mut().enumerate() {
+            if alive[state] {
+                triggers.retain(|trigger| alive[trigger.dest]);
+            } else {
+                triggers.clear();
+            }
+        }
+
+        Ok(Self {
+            final_state,
+            init_state,
+            pda,
+        })
+    }
+
+    /// Build the [`Automaton`] of any grammar, by converting it to Greibach normal form first,
+    /// see [`gnf_grammar`] and [`Automaton::from_gnf_grammar`]
+    pub fn from_grammar(
+        grammar: &GramatronGrammar,
+        start: &str,
+        stack_limit: usize,
+    ) -> Result<Self, Error> {
+        Self::from_gnf_grammar(&gnf_grammar(grammar, start)?, stack_limit)
+    }
+
+    /// Parse bytes into a [`GramatronInput`], a walk over this automaton whose terminals are the bytes.
+    ///
+    /// Of ambiguous walks, the one with the fewest terminals is chosen.
+    pub fn parse(&self, bytes: &[u8]) -> Result<GramatronInput, Error> {
+        // A breadth-first search over states and positions in the input
+        let mut parents: HashMap<(usize, usize), (usize, usize, usize)> = HashMap::new();
+        let mut queue = VecDeque::from([(self.init_state, 0)]);
+        let mut furthest = 0;
+        while let Some((state, pos)) = queue.pop_front() {
+            furthest = furthest.max(pos);
+            if state == self.final_state {
+                if pos == bytes.len() {
+                    let mut terms = Vec::new();
+                    let mut current = (state, pos);
+                    while let Some((parent_state, parent_pos, trigger_idx)) =
+                        parents.get(&current).copied()
+                    {
+                        terms.push(Terminal::new(
+                            parent_state,
+                            trigger_idx,
+                            self.pda[parent_state][trigger_idx].term.clone(),
+                        ));
+                        current = (parent_state, parent_pos);
+                    }
+                    terms.reverse();
+                    return Ok(GramatronInput::new(terms));
+                }
+                continue;
+            }
+            let Some(triggers) = self.pda.get(state) else {
+                continue;
+            };
+            for (trigger_idx, trigger) in triggers.iter().enumerate() {
+                let term = trigger.term.as_bytes();
+                let next = (trigger.dest, pos + term.len());
+                if bytes[pos..].starts_with(term)
+                    && next != (self.init_state, 0)
+                    && !parents.contains_key(&next)
+                {
+                    parents.insert(next, (state, pos, trigger_idx));
+                    queue.push_back(next);
+                }
+            }
+        }
+        Err(Error::illegal_argument(format!(
+            "The input does not match the automaton, parsing failed at byte {furthest}"
+        )))
+    }
+}
+
 #[derive(Clone, Debug)]
 /// Generates random inputs from a grammar automaton
 pub struct GramatronGenerator<'a, S>
@@ -94,3 +525,81 @@ where
         counter
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use super::{gnf_grammar, Automaton, GramatronGrammar};
+    use crate::inputs::{GramatronInput, Terminal};
+
+    /// A random walk over the automaton, like the [`super::GramatronGenerator`] does
+    fn random_walk(automaton: &Automaton, rand: &mut StdRand) -> GramatronInput {
+        let mut terms = Vec::new();
+        let mut state = automaton.init_state;
+        while state != automaton.final_state {
+            let triggers = &automaton.pda[state];
+            let idx = rand.below(triggers.len());
+            terms.push(Terminal::new(state, idx, triggers[idx].term.clone()));
+            state = triggers[idx].dest;
+        }
+        GramatronInput::new(terms)
+    }
+
+    #[test]
+    fn test_gramatron_js_round_trip() {
+        let grammar: GramatronGrammar = serde_json::from_str(include_str!(
+            "../../../utils/gramatron/grammars/js_grammar.json"
+        ))
+        .unwrap();
+        let gnf = gnf_grammar(&grammar, "PROGRAM").unwrap();
+        assert_eq!(gnf["Start"], ["PROGRAM"]);
+        assert!(gnf
+            .iter()
+            .filter(|(nonterminal, _)| *nonterminal != "Start")
+            .flat_map(|(_, rules)| rules)
+            .all(|rule| rule.starts_with('\'')));
+
+        let automaton = Automaton::from_gnf_grammar(&gnf, 5).unwrap();
+        let mut rand = StdRand::with_seed(1337);
+        let mut bytes = Vec::new();
+        let mut reparsed = Vec::new();
+        for _ in 0..64 {
+            let input = random_walk(&automaton, &mut rand);
+            input.unparse(&mut bytes);
+            let parsed = automaton.parse(&bytes).unwrap();
+            assert!(parsed.terminals().len() <= input.terminals().len());
+            parsed.unparse(&mut reparsed);
+            assert_eq!(bytes, reparsed);
+
+            // The walk is valid for the automaton
+            let mut state = automaton.init_state;
+            for term in parsed.terminals() {
+                assert_eq!(term.state, state);
+                let trigger = &automaton.pda[state][term.trigger_idx];
+                assert_eq!(trigger.term, term.symbol);
+                state = trigger.dest;
+            }
+            assert_eq!(state, automaton.final_state);
+        }
+        assert!(automaton.parse(b"\xff").is_err());
+    }
+
+    #[test]
+    fn test_gramatron_left_recursion() {
+        let grammar: GramatronGrammar =
+            serde_json::from_str(include_str!("../../../utils/gramatron/grammars/test1.json"))
+                .unwrap();
+        let automaton = Automaton::from_grammar(&grammar, "A", 0).unwrap();
+        let mut bytes = Vec::new();
+        for len in 1..8 {
+            let input = automaton.parse(&b"a".repeat(len)).unwrap();
+            input.unparse(&mut bytes);
+            assert_eq!(bytes, b"a".repeat(len));
+        }
+        assert!(automaton.parse(b"").is_err());
+        assert!(automaton.parse(b"ab").is_err());
+    }
+}
@@ -0,0 +1,764 @@
+//! Infer an approximate context-free grammar from a corpus, in the style of [Arvada](https://arxiv.org/abs/2108.13340)
+//! and [Mimid](https://arxiv.org/abs/1912.05937).
+//!
+//! The samples are tokenized with a [`Tokenizer`], and nested along `()`, `[]` and `{}` into groups.
+//! Token values of the same shape (numbers, identifiers, string literals), and groups of the same bracket kind, are then
+//! greedily merged into nonterminals, as long as an oracle confirms that swapping one for the other keeps a sample valid.
+//! Finally, runs of the same symbol are turned into recursive list rules.
+//!
+//! The resulting [`InferredGrammar`] can be loaded into a [`crate::generators::NautilusContext`], or turned into a
+//! Gramatron [`Automaton`]. Use the [`crate::stages::GrammarInferenceStage`] to infer a grammar during fuzzing,
+//! with the coverage of the target as oracle.
+
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    generators::gramatron::{Automaton, Trigger},
+    inputs::{GeneralizedInputMetadata, GeneralizedItem, Tokenizer},
+    Error,
+};
+
+/// The name of the start symbol of an [`InferredGrammar`]
+pub const INFERRED_START: &str = "ROOT";
+
+/// The brackets samples are nested along, with the names of their nonterminals
+const BRACKETS: [(&str, &str, &str); 3] = [
+    ("(", ")", "PAREN"),
+    ("[", "]", "BRACKET"),
+    ("{", "}", "BRACE"),
+];
+
+/// A symbol of an [`InferredGrammar`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GrammarSymbol {
+    /// A token, emitted as is
+    Terminal(String),
+    /// A reference to the rules of a nonterminal
+    NonTerminal(String),
+}
+
+/// A context-free grammar inferred by a [`GrammarInference`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InferredGrammar {
+    rules: BTreeMap<String, Vec<Vec<GrammarSymbol>>>,
+    separator: String,
+}
+
+impl_serdeany!(InferredGrammar);
+
+impl InferredGrammar {
+    /// The rules, by nonterminal. The start symbol is [`INFERRED_START`].
+    #[must_use]
+    pub fn rules(&self) -> &BTreeMap<String, Vec<Vec<GrammarSymbol>>> {
+        &self.rules
+    }
+
+    /// The separator emitted between two tokens
+    #[must_use]
+    pub fn separator(&self) -> &str {
+        &self.separator
+    }
+
+    /// The rules in the JSON format of Nautilus grammars, `[["NONTERMINAL", "rule"], ...]`, start symbol first.
+    ///
+    /// Load them with [`crate::generators::NautilusContext::new`].
+    #[must_use]
+    pub fn nautilus_rules(&self) -> Vec<Vec<String>> {
+        let start = self.rules.get_key_value(INFERRED_START);
+        let others = self
+            .rules
+            .iter()
+            .filter(|(nonterminal, _)| *nonterminal != INFERRED_START);
+        start
+            .into_iter()
+            .chain(others)
+            .flat_map(|(nonterminal, alternatives)| {
+                alternatives.iter().map(move |alternative| {
+                    let rule: Vec<String> = alternative
+                        .iter()
+                        .map(|symbol| match symbol {
+                            GrammarSymbol::Terminal(token) => {
+                                token.replace('{', "\\{").replace('}', "\\}")
+                            }
+                            GrammarSymbol::NonTerminal(name) => format!("{{{name}}}"),
+                        })
+                        .collect();
+                    vec![nonterminal.clone(), rule.join(&self.separator)]
+                })
+            })
+            .collect()
+    }
+
+    /// Build a Gramatron [`Automaton`] for this grammar.
+    ///
+    /// Each state of the automaton is a stack of pending symbols, at most `stack_limit` deep,
+    /// so that recursion is unrolled up to this limit. At most `max_states` states are created.
+    /// The separator is emitted by the transitions between tokens.
+    pub fn automaton(&self, stack_limit: usize, max_states: usize) -> Result<Automaton, Error> {
+        let mut ids: BTreeMap<Vec<GrammarSymbol>, usize> = BTreeMap::new();
+        let mut stacks: Vec<Vec<GrammarSymbol>> = vec![vec![]];
+        ids.insert(vec![], 0);
+        let start = vec![GrammarSymbol::NonTerminal(INFERRED_START.to_owned())];
+        ids.insert(start.clone(), 1);
+        stacks.push(start);
+
+        let final_state = 0;
+        let init_state = 1;
+        let mut pda: Vec<Vec<Trigger>> = vec![vec![]];
+        let mut state = 1;
+        while state < stacks.len() {
+            let mut triggers = Vec::new();
+            for expanded in self.expand(&stacks[state], stack_limit) {
+                let trigger = match expanded.split_last() {
+                    None => Trigger {
+                        dest: final_state,
+                        term: String::new(),
+                    },
+                    Some((GrammarSymbol::Terminal(token), rest)) => {
+                        let dest = if let Some(dest) = ids.get(rest) {
+                            *dest
+                        } else if stacks.len() < max_states {
+                            ids.insert(rest.to_vec(), stacks.len());
+                            stacks.push(rest.to_vec());
+                            stacks.len() - 1
+                        } else {
+                            continue;
+                        };
+                        let term = if rest.is_empty() {
+                            token.clone()
+                        } else {
+                            token.clone() + &self.separator
+                        };
+                        Trigger { dest, term }
+                    }
+                    Some((GrammarSymbol::NonTerminal(_), _)) => unreachable!(),
+                };
+                if !triggers.contains(&trigger) {
+                    triggers.push(trigger);
+                }
+            }
+            pda.push(triggers);
+            state += 1;
+        }
+
+        // Drop the states that never reach the final state, as a walk would get stuck there
+        let mut alive = vec![false; pda.len()];
+        alive[final_state] = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for state in 0..pda.len() {
+                if !alive[state] && pda[state].iter().any(|trigger| alive[trigger.dest]) {
+                    alive[state] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !alive[init_state] {
+            return Err(Error::illegal_state(
+                "The inferred grammar has no derivation within the stack limit",
+            ));
+        }
+        let mut renumbered = vec![0; pda.len()];
+        let mut next = 0;
+        for (state, alive) in alive.iter().enumerate() {
+            if *alive {
+                renumbered[state] = next;
+                next += 1;
+            }
+        }
+        let pda = pda
+            .into_iter()
+            .enumerate()
+            .filter(|(state, _)| alive[*state])
+            .map(|(_, triggers)| {
+                triggers
+                    .into_iter()
+                    .filter(|trigger| alive[trigger.dest])
+                    .map(|trigger| Trigger {
+                        dest: renumbered[trigger.dest],
+                        term: trigger.term,
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Automaton {
+            final_state: renumbered[final_state],
+            init_state: renumbered[init_state],
+            pda,
+        })
+    }
+
+    /// Expand the nonterminals on top of `stack`, until a terminal is on top, or the stack is empty
+    fn expand(&self, stack: &[GrammarSymbol], stack_limit: usize) -> Vec<Vec<GrammarSymbol>> {
+        let mut seen = BTreeSet::new();
+        let mut worklist = vec![stack.to_vec()];
+        let mut expanded = Vec::new();
+        while let Some(stack) = worklist.pop() {
+            if !seen.insert(stack.clone()) {
+                continue;
+            }
+            match stack.split_last() {
+                Some((GrammarSymbol::NonTerminal(nonterminal), rest)) => {
+                    for alternative in self.rules.get(nonterminal).into_iter().flatten() {
+                        if rest.len() + alternative.len() <= stack_limit {
+                            let mut next = rest.to_vec();
+                            next.extend(alternative.iter().rev().cloned());
+                            worklist.push(next);
+                        }
+                    }
+                }
+                _ => expanded.push(stack),
+            }
+        }
+        expanded
+    }
+
+    /// Write the rules to a JSON file, loadable with [`crate::generators::NautilusContext::from_file`]
+    #[cfg(feature = "std")]
+    pub fn write_nautilus_grammar<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let json = serde_json::to_vec_pretty(&self.nautilus_rules())?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Write a serialized Gramatron [`Automaton`] for this grammar to a file, as `construct_automata` does
+    #[cfg(feature = "std")]
+    pub fn write_automaton<P>(
+        &self,
+        path: P,
+        stack_limit: usize,
+        max_states: usize,
+    ) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let automaton = self.automaton(stack_limit, max_states)?;
+        fs::write(path, postcard::to_allocvec(&automaton)?)?;
+        Ok(())
+    }
+}
+
+/// A node of a sample, as index of the sample and id of the node
+type Occurrence = (usize, usize);
+
+/// A cluster of interchangeable occurrences, by their kind and representative
+type Cluster<K, T> = (K, Occurrence, Vec<T>);
+
+/// A token or a bracket group of a sample
+#[derive(Clone, Debug)]
+enum Node {
+    Token {
+        id: usize,
+        text: String,
+    },
+    Group {
+        id: usize,
+        kind: usize,
+        children: Vec<Node>,
+    },
+}
+
+impl Node {
+    fn id(&self) -> usize {
+        match self {
+            Self::Token { id, .. } | Self::Group { id, .. } => *id,
+        }
+    }
+}
+
+/// Find the node with the given id
+fn find_node(nodes: &[Node], id: usize) -> Option<&Node> {
+    nodes.iter().find_map(|node| match node {
+        _ if node.id() == id => Some(node),
+        Node::Group { children, .. } => find_node(children, id),
+        Node::Token { .. } => None,
+    })
+}
+
+/// The tokens of `nodes`, with the node with the given id replaced by another one
+fn flatten<'a>(nodes: &'a [Node], replace: Option<(usize, &'a Node)>, tokens: &mut Vec<&'a str>) {
+    for node in nodes {
+        let node = match replace {
+            Some((id, replacement)) if node.id() == id => replacement,
+            _ => node,
+        };
+        match node {
+            Node::Token { text, .. } => tokens.push(text),
+            Node::Group { children, .. } => flatten(children, replace, tokens),
+        }
+    }
+}
+
+/// The shape of a token that may be replaced by other tokens of the same shape
+fn token_class(token: &str) -> Option<&'static str> {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    let first = token.chars().next()?;
+    if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        Some("NUMBER")
+    } else if (first.is_alphabetic() || first == '_')
+        && token.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        Some("IDENT")
+    } else if token.len() >= 2 && (first == '"' || first == '\'') && token.ends_with(first) {
+        Some("STRING")
+    } else {
+        None
+    }
+}
+
+/// Infers an [`InferredGrammar`] from samples, see the [module-level documentation](self)
+#[derive(Debug)]
+pub struct GrammarInference<T> {
+    tokenizer: T,
+    separator: String,
+    max_checks: usize,
+    samples: Vec<Vec<Node>>,
+    next_id: usize,
+}
+
+impl<T> GrammarInference<T>
+where
+    T: Tokenizer,
+{
+    /// Create a new [`GrammarInference`] for samples tokenized by `tokenizer`
+    #[must_use]
+    pub fn new(tokenizer: T) -> Self {
+        Self {
+            tokenizer,
+            separator: " ".to_owned(),
+            max_checks: 4096,
+            samples: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Set the separator emitted between two tokens, a single space by default
+    #[must_use]
+    pub fn with_separator<S>(mut self, separator: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Set the maximum number of oracle queries used to merge tokens and groups
+    #[must_use]
+    pub fn with_max_checks(mut self, max_checks: usize) -> Self {
+        self.max_checks = max_checks;
+        self
+    }
+
+    /// The number of samples added so far
+    #[must_use]
+    pub fn samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Add a sample, and return its index
+    pub fn add_sample(&mut self, bytes: &[u8]) -> Result<usize, Error> {
+        let tokens = self.tokenizer.tokenize(bytes)?;
+        Ok(self.push_sample(tokens))
+    }
+
+    /// Add a sample generalized by the [`crate::stages::GeneralizationStage`], and return its index.
+    ///
+    /// The gaps of the generalized sample separate tokens, the bytes in between are tokenized on their own.
+    pub fn add_generalized_sample(
+        &mut self,
+        generalized: &GeneralizedInputMetadata,
+    ) -> Result<usize, Error> {
+        let mut tokens = Vec::new();
+        for item in generalized.generalized() {
+            if let GeneralizedItem::Bytes(bytes) = item {
+                tokens.append(&mut self.tokenizer.tokenize(bytes)?);
+            }
+        }
+        Ok(self.push_sample(tokens))
+    }
+
+    /// The bytes of the sample with the given index, as the inferred grammar would emit them
+    #[must_use]
+    pub fn sample_bytes(&self, sample: usize) -> Vec<u8> {
+        self.render(sample, None)
+    }
+
+    fn push_sample(&mut self, tokens: Vec<String>) -> usize {
+        let mut split = Vec::new();
+        for token in tokens {
+            if token.starts_with(['"', '\'']) {
+                split.push(token);
+                continue;
+            }
+            let mut rest = token.as_str();
+            while let Some(pos) = rest.find(|c: char| "()[]{}".contains(c)) {
+                if pos > 0 {
+                    split.push(rest[..pos].to_owned());
+                }
+                split.push(rest[pos..=pos].to_owned());
+                rest = &rest[pos + 1..];
+            }
+            if !rest.is_empty() {
+                split.push(rest.to_owned());
+            }
+        }
+
+        // Nest the tokens along matching brackets
+        let mut stack: Vec<(Option<usize>, Vec<Node>)> = vec![(None, Vec::new())];
+        for text in split {
+            let id = self.next_id;
+            self.next_id += 1;
+            let open = BRACKETS.iter().position(|(open, _, _)| *open == text);
+            let close = BRACKETS.iter().position(|(_, close, _)| *close == text);
+            let token = Node::Token { id, text };
+            if open.is_some() {
+                stack.last_mut().unwrap().1.push(token);
+                stack.push((open, Vec::new()));
+            } else if close.is_some() && stack.last().unwrap().0 == close {
+                let (_, children) = stack.pop().unwrap();
+                let id = self.next_id;
+                self.next_id += 1;
+                let parent = &mut stack.last_mut().unwrap().1;
+                parent.push(Node::Group {
+                    id,
+                    kind: close.unwrap(),
+                    children,
+                });
+                parent.push(token);
+            } else {
+                stack.last_mut().unwrap().1.push(token);
+            }
+        }
+        // Unclosed brackets are plain tokens
+        while stack.len() > 1 {
+            let (_, mut children) = stack.pop().unwrap();
+            stack.last_mut().unwrap().1.append(&mut children);
+        }
+        self.samples.push(stack.pop().unwrap().1);
+        self.samples.len() - 1
+    }
+
+    fn render(&self, sample: usize, replace: Option<(usize, &Node)>) -> Vec<u8> {
+        let mut tokens = Vec::new();
+        flatten(&self.samples[sample], replace, &mut tokens);
+        tokens.join(&self.separator).into_bytes()
+    }
+
+    /// Infer a grammar from the samples.
+    ///
+    /// The `oracle` gets the index of the sample a candidate was derived from, and the candidate,
+    /// and returns whether the candidate is still valid. Samples that are not valid themselves, as emitted
+    /// with the separator, are ignored.
+    pub fn infer<O>(&self, mut oracle: O) -> Result<InferredGrammar, Error>
+    where
+        O: FnMut(usize, &[u8]) -> Result<bool, Error>,
+    {
+        let mut valid = Vec::new();
+        for sample in 0..self.samples.len() {
+            if oracle(sample, &self.sample_bytes(sample))? {
+                valid.push(sample);
+            }
+        }
+        if valid.is_empty() {
+            return Err(Error::empty("No valid samples to infer a grammar from"));
+        }
+
+        // All occurrences of tokens with a class, and of groups, by their content
+        let mut tokens: BTreeMap<(&'static str, String), Vec<Occurrence>> = BTreeMap::new();
+        let mut groups: BTreeMap<(usize, Vec<u8>), Vec<Occurrence>> = BTreeMap::new();
+        for &sample in &valid {
+            let mut worklist: Vec<&Node> = self.samples[sample].iter().collect();
+            while let Some(node) = worklist.pop() {
+                match node {
+                    Node::Token { id, text } => {
+                        if let Some(class) = token_class(text) {
+                            tokens
+                                .entry((class, text.clone()))
+                                .or_default()
+                                .push((sample, *id));
+                        }
+                    }
+                    Node::Group { id, kind, children } => {
+                        let mut content = Vec::new();
+                        flatten(children, None, &mut content);
+                        groups
+                            .entry((*kind, content.join(&self.separator).into_bytes()))
+                            .or_default()
+                            .push((sample, *id));
+                        worklist.extend(children);
+                    }
+                }
+            }
+        }
+
+        // Two occurrences are interchangeable if both swaps keep their samples valid
+        let mut checks = 0;
+        let mut interchangeable = |(sample_a, id_a): (usize, usize),
+                                   (sample_b, id_b): (usize, usize)|
+         -> Result<bool, Error> {
+            if checks + 2 > self.max_checks {
+                return Ok(false);
+            }
+            checks += 2;
+            let node_a = find_node(&self.samples[sample_a], id_a).unwrap();
+            let node_b = find_node(&self.samples[sample_b], id_b).unwrap();
+            Ok(
+                oracle(sample_a, &self.render(sample_a, Some((id_a, node_b))))?
+                    && oracle(sample_b, &self.render(sample_b, Some((id_b, node_a))))?,
+            )
+        };
+
+        let mut token_nonterminals: BTreeMap<String, String> = BTreeMap::new();
+        let mut clusters: Vec<Cluster<&'static str, &String>> = Vec::new();
+        for ((class, text), occurrences) in &tokens {
+            let mut joined = false;
+            for (cluster_class, representative, cluster) in &mut clusters {
+                if cluster_class == class && interchangeable(*representative, occurrences[0])? {
+                    cluster.push(text);
+                    joined = true;
+                    break;
+                }
+            }
+            if !joined {
+                clusters.push((*class, occurrences[0], vec![text]));
+            }
+        }
+        let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+        for (class, _, cluster) in clusters.into_iter().filter(|(_, _, c)| c.len() > 1) {
+            let name = nonterminal_name(&mut names, class);
+            for text in cluster {
+                token_nonterminals.insert(text.clone(), name.clone());
+            }
+        }
+
+        let mut group_nonterminals: BTreeMap<usize, String> = BTreeMap::new();
+        let mut clusters: Vec<Cluster<usize, &Vec<Occurrence>>> = Vec::new();
+        for ((kind, _), occurrences) in &groups {
+            let mut joined = false;
+            for (cluster_kind, representative, cluster) in &mut clusters {
+                if cluster_kind == kind && interchangeable(*representative, occurrences[0])? {
+                    cluster.push(occurrences);
+                    joined = true;
+                    break;
+                }
+            }
+            if !joined {
+                clusters.push((*kind, occurrences[0], vec![occurrences]));
+            }
+        }
+        for (kind, _, cluster) in clusters {
+            let name = nonterminal_name(&mut names, BRACKETS[kind].2);
+            for (_, id) in cluster.into_iter().flatten() {
+                group_nonterminals.insert(*id, name.clone());
+            }
+        }
+
+        // Collect the rules from the trees
+        let symbols = |nodes: &[Node]| -> Vec<GrammarSymbol> {
+            nodes
+                .iter()
+                .map(|node| match node {
+                    Node::Token { text, .. } => token_nonterminals.get(text).map_or_else(
+                        || GrammarSymbol::Terminal(text.clone()),
+                        |name| GrammarSymbol::NonTerminal(name.clone()),
+                    ),
+                    Node::Group { id, .. } => {
+                        GrammarSymbol::NonTerminal(group_nonterminals[id].clone())
+                    }
+                })
+                .collect()
+        };
+        let mut rules: BTreeMap<String, Vec<Vec<GrammarSymbol>>> = BTreeMap::new();
+        for &sample in &valid {
+            add_rule(&mut rules, INFERRED_START, symbols(&self.samples[sample]));
+            let mut worklist: Vec<&Node> = self.samples[sample].iter().collect();
+            while let Some(node) = worklist.pop() {
+                if let Node::Group { id, children, .. } = node {
+                    add_rule(&mut rules, &group_nonterminals[id], symbols(children));
+                    worklist.extend(children);
+                }
+            }
+        }
+        for (text, name) in &token_nonterminals {
+            add_rule(
+                &mut rules,
+                name,
+                vec![GrammarSymbol::Terminal(text.clone())],
+            );
+        }
+
+        Ok(InferredGrammar {
+            rules: collapse_repetitions(rules, &mut names),
+            separator: self.separator.clone(),
+        })
+    }
+}
+
+/// A fresh nonterminal name, `BASE`, `BASE_1`, `BASE_2`, ...
+fn nonterminal_name(names: &mut BTreeMap<&str, usize>, base: &'static str) -> String {
+    let count = names.entry(base).or_default();
+    *count += 1;
+    if *count == 1 {
+        base.to_owned()
+    } else {
+        format!("{base}_{}", *count - 1)
+    }
+}
+
+fn add_rule(
+    rules: &mut BTreeMap<String, Vec<Vec<GrammarSymbol>>>,
+    nonterminal: &str,
+    alternative: Vec<GrammarSymbol>,
+) {
+    let alternatives = rules.entry(nonterminal.to_owned()).or_default();
+    if !alternatives.contains(&alternative) {
+        alternatives.push(alternative);
+    }
+}
+
+/// Replace runs of the same symbol by a recursive list nonterminal
+fn collapse_repetitions(
+    rules: BTreeMap<String, Vec<Vec<GrammarSymbol>>>,
+    names: &mut BTreeMap<&str, usize>,
+) -> BTreeMap<String, Vec<Vec<GrammarSymbol>>> {
+    let mut lists: BTreeMap<GrammarSymbol, String> = BTreeMap::new();
+    let mut collapsed: BTreeMap<String, Vec<Vec<GrammarSymbol>>> = BTreeMap::new();
+    for (nonterminal, alternatives) in rules {
+        for alternative in alternatives {
+            let mut symbols: Vec<GrammarSymbol> = Vec::new();
+            let mut idx = 0;
+            while idx < alternative.len() {
+                let symbol = &alternative[idx];
+                let run = alternative[idx..]
+                    .iter()
+                    .take_while(|other| *other == symbol)
+                    .count();
+                if run > 1 {
+                    let list = lists
+                        .entry(symbol.clone())
+                        .or_insert_with(|| nonterminal_name(names, "LIST"))
+                        .clone();
+                    symbols.push(GrammarSymbol::NonTerminal(list));
+                } else {
+                    symbols.push(symbol.clone());
+                }
+                idx += run;
+            }
+            add_rule(&mut collapsed, &nonterminal, symbols);
+        }
+    }
+    for (symbol, list) in lists {
+        add_rule(&mut collapsed, &list, vec![symbol.clone()]);
+        add_rule(
+            &mut collapsed,
+            &list,
+            vec![symbol, GrammarSymbol::NonTerminal(list.clone())],
+        );
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{GrammarInference, GrammarSymbol, INFERRED_START};
+    use crate::{
+        corpus::InMemoryCorpus,
+        feedbacks::ConstFeedback,
+        generators::{Generator, GramatronGenerator},
+        inputs::{GramatronInput, Tokenizer},
+        state::StdState,
+        Error,
+    };
+
+    /// Splits at whitespace
+    struct WhitespaceTokenizer;
+
+    impl Tokenizer for WhitespaceTokenizer {
+        fn tokenize(&self, bytes: &[u8]) -> Result<Vec<String>, Error> {
+            Ok(core::str::from_utf8(bytes)?
+                .split_whitespace()
+                .map(String::from)
+                .collect())
+        }
+    }
+
+    /// Accepts sums and calls of numbers and identifiers, with balanced parentheses, like `f ( 1 + x )`
+    fn valid(bytes: &[u8]) -> bool {
+        let mut depth = 0_i32;
+        let mut operand = false;
+        for token in core::str::from_utf8(bytes).unwrap().split(' ') {
+            match token {
+                "(" => depth += 1,
+                ")" if operand => depth -= 1,
+                "+" if operand => operand = false,
+                _ if !operand && token.chars().all(char::is_alphanumeric) && !token.is_empty() => {
+                    operand = true;
+                }
+                _ => return false,
+            }
+            if depth < 0 {
+                return false;
+            }
+            if token == "(" {
+                operand = false;
+            }
+        }
+        depth == 0 && operand
+    }
+
+    #[test]
+    fn test_grammar_inference() {
+        let mut inference = GrammarInference::new(WhitespaceTokenizer);
+        for sample in ["f(1)", "g(x + 2)", "(a + (b + c))", "h(1) + f(3 + y)"] {
+            inference.add_sample(sample.as_bytes()).unwrap();
+        }
+        let grammar = inference
+            .infer(|_, candidate| Ok(valid(candidate)))
+            .unwrap();
+
+        // Identifiers and numbers are interchangeable in the language, and so are all parenthesized groups
+        let paren = &grammar.rules()["PAREN"];
+        assert!(paren.contains(&vec![
+            GrammarSymbol::NonTerminal("IDENT".into()),
+            GrammarSymbol::Terminal("+".into()),
+            GrammarSymbol::NonTerminal("IDENT".into()),
+        ]));
+        assert!(!grammar.rules().contains_key("PAREN_1"));
+        assert_eq!(grammar.nautilus_rules()[0][0], INFERRED_START);
+
+        // Walks of the automaton are valid inputs
+        let automaton = grammar.automaton(16, 4096).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<GramatronInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        let mut generator = GramatronGenerator::new(&automaton);
+        for _ in 0..32 {
+            let mut bytes = Vec::new();
+            generator.generate(&mut state).unwrap().unparse(&mut bytes);
+            assert!(valid(&bytes), "{}", String::from_utf8_lossy(&bytes));
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Generator for [`MessageInput`]s, from the [`crate::inputs::MessageSchema`] in the state metadata
+use core::marker::PhantomData;
+
+use crate::{
+    generators::Generator,
+    inputs::{message::with_schema, MessageInput},
+    state::HasRand,
+    Error, HasMetadata,
+};
+
+/// Generates random [`MessageInput`]s of the root message type of the [`crate::inputs::MessageSchema`] in the state metadata
+#[derive(Clone, Debug)]
+pub struct MessageGenerator<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<S> Generator<MessageInput, S> for MessageGenerator<S>
+where
+    S: HasRand + HasMetadata,
+{
+    fn generate(&mut self, state: &mut S) -> Result<MessageInput, Error> {
+        with_schema(state, |state, schema| {
+            schema.validate()?;
+            Ok(schema.random_input(state.rand_mut()))
+        })?
+    }
+}
+
+impl<S> MessageGenerator<S> {
+    /// Returns a new [`MessageGenerator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> Default for MessageGenerator<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -14,6 +14,15 @@ use crate::{
 pub mod gramatron;
 pub use gramatron::*;
 
+pub mod grammar_inference;
+pub use grammar_inference::{GrammarInference, InferredGrammar};
+
+pub mod message;
+pub use message::*;
+
+pub mod nautilus_grammar;
+pub use nautilus_grammar::{nautilus_rules_from_antlr4, nautilus_rules_from_python, NautilusRules};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
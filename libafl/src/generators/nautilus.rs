@@ -9,7 +9,14 @@ use std::{fs, io::BufReader, path::Path};
 use grammartec::context::Context;
 pub use grammartec::newtypes::NTermID;
 
-use crate::{generators::Generator, inputs::nautilus::NautilusInput, Error};
+use crate::{
+    generators::{
+        nautilus_grammar::{nautilus_rules_from_antlr4, nautilus_rules_from_python},
+        Generator,
+    },
+    inputs::nautilus::NautilusInput,
+    Error,
+};
 
 /// The nautilus context for a generator
 pub struct NautilusContext {
@@ -80,14 +87,75 @@ impl NautilusContext {
         Some(Self { ctx })
     }
 
-    /// Create a new [`NautilusContext`] from a file
+    /// Create a new [`NautilusContext`] from a file.
+    ///
+    /// Files ending in `.py` are loaded as Python grammar, see [`NautilusContext::from_python`],
+    /// files ending in `.g4` as ANTLR4 grammar, see [`NautilusContext::from_antlr4`],
+    /// and all others as JSON list of `[nonterminal, rule]` pairs.
     #[must_use]
     pub fn from_file<P: AsRef<Path>>(tree_depth: usize, grammar_file: P) -> Self {
-        let file = fs::File::open(grammar_file).expect("Cannot open grammar file");
-        let reader = BufReader::new(file);
-        let rules: Vec<Vec<String>> =
-            serde_json::from_reader(reader).expect("Cannot parse grammar file");
-        Self::new(tree_depth, &rules)
+        let grammar_file = grammar_file.as_ref();
+        match grammar_file.extension().and_then(|ext| ext.to_str()) {
+            Some("py") => {
+                Self::from_python_file(tree_depth, grammar_file).expect("Cannot parse grammar file")
+            }
+            Some("g4") => Self::from_antlr4_files(tree_depth, &[grammar_file], None)
+                .expect("Cannot parse grammar file"),
+            _ => {
+                let file = fs::File::open(grammar_file).expect("Cannot open grammar file");
+                let reader = BufReader::new(file);
+                let rules: Vec<Vec<String>> =
+                    serde_json::from_reader(reader).expect("Cannot parse grammar file");
+                Self::new(tree_depth, &rules)
+            }
+        }
+    }
+
+    /// Create a new [`NautilusContext`] from [`crate::generators::NautilusRules`], the start rule first
+    fn from_rules(tree_depth: usize, rules: &[(String, Vec<u8>)]) -> Result<Self, Error> {
+        let rules: Vec<(&str, &[u8])> = rules
+            .iter()
+            .map(|(symbol, rule)| (symbol.as_str(), rule.as_slice()))
+            .collect();
+        Self::with_rules(tree_depth, &rules).ok_or_else(|| Error::empty("The grammar has no rules"))
+    }
+
+    /// Create a new [`NautilusContext`] from a grammar in the Python DSL of Nautilus,
+    /// consisting of `ctx.rule` and `ctx.regex` calls, see [`nautilus_rules_from_python`]
+    pub fn from_python(tree_depth: usize, source: &str) -> Result<Self, Error> {
+        Self::from_rules(tree_depth, &nautilus_rules_from_python(source)?)
+    }
+
+    /// Create a new [`NautilusContext`] from a Python grammar file, see [`NautilusContext::from_python`]
+    pub fn from_python_file<P: AsRef<Path>>(
+        tree_depth: usize,
+        grammar_file: P,
+    ) -> Result<Self, Error> {
+        Self::from_python(tree_depth, &fs::read_to_string(grammar_file)?)
+    }
+
+    /// Create a new [`NautilusContext`] from ANTLR4 grammars, like a lexer and a parser grammar,
+    /// starting at the rule `start`, or the first parser rule, see [`nautilus_rules_from_antlr4`]
+    pub fn from_antlr4(
+        tree_depth: usize,
+        sources: &[&str],
+        start: Option<&str>,
+    ) -> Result<Self, Error> {
+        Self::from_rules(tree_depth, &nautilus_rules_from_antlr4(sources, start)?)
+    }
+
+    /// Create a new [`NautilusContext`] from ANTLR4 grammar files, see [`NautilusContext::from_antlr4`]
+    pub fn from_antlr4_files<P: AsRef<Path>>(
+        tree_depth: usize,
+        grammar_files: &[P],
+        start: Option<&str>,
+    ) -> Result<Self, Error> {
+        let sources = grammar_files
+            .iter()
+            .map(fs::read_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+        Self::from_antlr4(tree_depth, &sources, start)
     }
 }
 
@@ -122,7 +190,6 @@ impl<'a> NautilusGenerator<'a> {
     }
 
     /// Gets the nonterminal from this input
-    // TODO create from a python grammar
     #[must_use]
     pub fn nonterminal(&self, name: &str) -> NTermID {
         self.ctx.nt_id(name)
@@ -0,0 +1,1451 @@
+//! Load grammars for the [`Nautilus`](https://github.com/RUB-SysSec/nautilus) generator from other formats:
+//! the Python grammar DSL of upstream Nautilus, and ANTLR4 `.g4` grammars.
+//!
+//! Both loaders produce [`NautilusRules`], that `NautilusContext::with_rules` takes,
+//! and do not need the `nautilus` feature themselves.
+//! Regular expressions and character sets are expanded into plain rules, as Nautilus rules are only strings
+//! and references to other nonterminals.
+
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::iter::Peekable;
+
+use crate::Error;
+
+/// The rules of a Nautilus grammar, as pairs of nonterminal and rule, the start rule first
+pub type NautilusRules = Vec<(String, Vec<u8>)>;
+
+/// The start symbol `NautilusContext` adds on its own, so grammars may not use it
+const START: &str = "START";
+
+/// The name grammar nonterminals called [`START`] get instead
+const RENAMED_START: &str = "START_";
+
+/// The maximum number of optional repetitions a bounded repetition, like `a{2,5}`, is expanded to
+const MAX_BOUNDED_REPEAT: usize = 16;
+
+/// The maximum number of required repetitions, like the `2` of `a{2,5}`, a repetition may have
+const MAX_REQUIRED_REPEAT: usize = 1024;
+
+/// The prefix of all nonterminals loaded from ANTLR4 grammars.
+/// Nautilus nonterminals have to start with an uppercase letter, ANTLR4 parser rules start with a lowercase one.
+const ANTLR4_PREFIX: &str = "R_";
+
+/// The maximum number of characters a range, like `\u0000-￿`, is expanded to
+const MAX_RANGE: usize = 256;
+
+/// An expression of a regular expression, or of an ANTLR4 rule
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Vec<u8>),
+    Set(BTreeSet<char>),
+    Ref(String),
+    Seq(Vec<Expr>),
+    Alt(Vec<Expr>),
+    Repeat(Box<Expr>, usize, Option<usize>),
+}
+
+impl Expr {
+    fn empty() -> Self {
+        Self::Seq(Vec::new())
+    }
+
+    /// The characters this expression matches, if it matches exactly one character
+    fn chars(&self) -> Option<BTreeSet<char>> {
+        match self {
+            Self::Set(chars) => Some(chars.clone()),
+            Self::Literal(bytes) => {
+                let literal = core::str::from_utf8(bytes).ok()?;
+                let mut chars = literal.chars();
+                let c = chars.next()?;
+                chars.next().is_none().then(|| BTreeSet::from([c]))
+            }
+            Self::Alt(alternatives) => {
+                let mut chars = BTreeSet::new();
+                for alternative in alternatives {
+                    chars.append(&mut alternative.chars()?);
+                }
+                Some(chars)
+            }
+            _ => None,
+        }
+    }
+
+    fn visit_refs<'a>(&'a self, refs: &mut Vec<&'a str>) {
+        match self {
+            Self::Ref(name) => refs.push(name),
+            Self::Seq(items) | Self::Alt(items) => {
+                for item in items {
+                    item.visit_refs(refs);
+                }
+            }
+            Self::Repeat(item, _, _) => item.visit_refs(refs),
+            Self::Literal(_) | Self::Set(_) => {}
+        }
+    }
+}
+
+/// The characters negated sets and `.` are taken from: printable ASCII and whitespace
+fn universe() -> BTreeSet<char> {
+    (' '..='~').chain(['\t', '\n', '\r']).collect()
+}
+
+/// The characters of the range `from..=to`, at most [`MAX_RANGE`] of them
+fn char_range(from: char, to: char) -> Result<BTreeSet<char>, Error> {
+    if to < from {
+        return Err(Error::illegal_argument(format!(
+            "Invalid character range {from:?}-{to:?}"
+        )));
+    }
+    let len = u32::from(to) - u32::from(from) + 1;
+    if len as usize <= MAX_RANGE {
+        return Ok((from..=to).collect());
+    }
+    // Printable characters, and a sample of the others
+    let mut chars: BTreeSet<char> = universe()
+        .into_iter()
+        .filter(|c| (from..=to).contains(c))
+        .collect();
+    let step = len / 16;
+    chars.extend((0..16).filter_map(|idx| char::from_u32(u32::from(from) + idx * step)));
+    Ok(chars)
+}
+
+fn escape_terminal(bytes: &[u8], rhs: &mut Vec<u8>) {
+    for byte in bytes {
+        if *byte == b'{' || *byte == b'}' {
+            rhs.push(b'\\');
+        }
+        rhs.push(*byte);
+    }
+}
+
+fn nonterminal_ref(name: &str) -> Vec<u8> {
+    format!("{{{name}}}").into_bytes()
+}
+
+/// Builds Nautilus rules from [`Expr`]s, introducing nonterminals for alternatives, sets and repetitions
+#[derive(Default)]
+struct RuleBuilder {
+    rules: NautilusRules,
+    /// Prepended to every nonterminal
+    prefix: &'static str,
+    fresh: BTreeMap<String, usize>,
+    sets: BTreeMap<BTreeSet<char>, String>,
+}
+
+impl RuleBuilder {
+    fn with_prefix(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            ..Self::default()
+        }
+    }
+
+    fn fresh(&mut self, base: &str) -> String {
+        let count = self.fresh.entry(base.to_owned()).or_default();
+        *count += 1;
+        format!("{base}__{count}")
+    }
+
+    fn rule(&mut self, nonterminal: &str, rhs: Vec<u8>) {
+        self.rules
+            .push((format!("{}{nonterminal}", self.prefix), rhs));
+    }
+
+    /// The reference to `nonterminal` in a rule
+    fn reference(&self, nonterminal: &str) -> Vec<u8> {
+        nonterminal_ref(&format!("{}{nonterminal}", self.prefix))
+    }
+
+    /// The rule fragment for `expr`, with `separator` between the items of sequences
+    fn lower(&mut self, expr: &Expr, base: &str, separator: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(match expr {
+            Expr::Literal(bytes) => {
+                let mut rhs = Vec::new();
+                escape_terminal(bytes, &mut rhs);
+                rhs
+            }
+            Expr::Ref(name) => self.reference(name),
+            Expr::Seq(items) => {
+                let mut parts = Vec::new();
+                for item in items {
+                    let part = self.lower(item, base, separator)?;
+                    if !part.is_empty() {
+                        parts.push(part);
+                    }
+                }
+                parts.join(separator)
+            }
+            Expr::Alt(alternatives) if alternatives.len() == 1 => {
+                self.lower(&alternatives[0], base, separator)?
+            }
+            Expr::Alt(alternatives) => {
+                let nonterminal = self.fresh(base);
+                for alternative in alternatives {
+                    let rhs = self.lower(alternative, base, separator)?;
+                    self.rule(&nonterminal, rhs);
+                }
+                self.reference(&nonterminal)
+            }
+            Expr::Set(chars) => {
+                if let Some(nonterminal) = self.sets.get(chars) {
+                    return Ok(self.reference(nonterminal));
+                }
+                let nonterminal = self.fresh(base);
+                for c in chars {
+                    let mut rhs = Vec::new();
+                    escape_terminal(c.encode_utf8(&mut [0; 4]).as_bytes(), &mut rhs);
+                    self.rule(&nonterminal, rhs);
+                }
+                self.sets.insert(chars.clone(), nonterminal.clone());
+                self.reference(&nonterminal)
+            }
+            Expr::Repeat(_, min, _) if *min > MAX_REQUIRED_REPEAT => {
+                return Err(Error::illegal_argument(format!(
+                    "Repetitions of at least {min} items are not supported, the maximum is {MAX_REQUIRED_REPEAT}"
+                )));
+            }
+            Expr::Repeat(item, min, max) => {
+                let item = self.lower(item, base, separator)?;
+                let mut parts = vec![item.clone(); *min];
+                match max {
+                    None => {
+                        let list = self.fresh(base);
+                        let list_ref = self.reference(&list);
+                        self.rule(&list, item.clone());
+                        self.rule(&list, [&item, separator, &list_ref].concat());
+                        if parts.pop().is_some() {
+                            parts.push(list_ref);
+                        } else {
+                            let optional = self.fresh(base);
+                            self.rule(&optional, Vec::new());
+                            self.rule(&optional, list_ref);
+                            parts.push(self.reference(&optional));
+                        }
+                    }
+                    Some(max) => {
+                        let mut tail: Option<String> = None;
+                        for _ in 0..max.saturating_sub(*min).min(MAX_BOUNDED_REPEAT) {
+                            let optional = self.fresh(base);
+                            self.rule(&optional, Vec::new());
+                            let rhs = match &tail {
+                                Some(tail) => [&item, separator, &self.reference(tail)].concat(),
+                                None => item.clone(),
+                            };
+                            self.rule(&optional, rhs);
+                            tail = Some(optional);
+                        }
+                        if let Some(tail) = tail {
+                            parts.push(self.reference(&tail));
+                        }
+                    }
+                }
+                parts.join(separator)
+            }
+        })
+    }
+
+    /// The rules, those of `start` first, with [`START`] renamed
+    fn finish(self, start: &str) -> Result<NautilusRules, Error> {
+        let prefixed = format!("{}{start}", self.prefix);
+        let (mut rules, others): (NautilusRules, NautilusRules) = self
+            .rules
+            .into_iter()
+            .partition(|(nonterminal, _)| *nonterminal == prefixed);
+        if rules.is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "The start symbol {start} has no rules"
+            )));
+        }
+        rules.extend(others);
+        for (nonterminal, rhs) in &mut rules {
+            if nonterminal == START {
+                RENAMED_START.clone_into(nonterminal);
+            }
+            *rhs = rename_start_refs(rhs);
+        }
+        Ok(rules)
+    }
+}
+
+/// Rename the references to [`START`] in a rule, skipping escaped braces
+fn rename_start_refs(rhs: &[u8]) -> Vec<u8> {
+    let reference = nonterminal_ref(START);
+    let mut renamed = Vec::with_capacity(rhs.len());
+    let mut idx = 0;
+    while idx < rhs.len() {
+        if rhs[idx] == b'\\' && idx + 1 < rhs.len() {
+            renamed.extend_from_slice(&rhs[idx..idx + 2]);
+            idx += 2;
+        } else if rhs[idx..].starts_with(&reference) {
+            renamed.extend(nonterminal_ref(RENAMED_START));
+            idx += reference.len();
+        } else {
+            renamed.push(rhs[idx]);
+            idx += 1;
+        }
+    }
+    renamed
+}
+
+/// Parse a regular expression, as used by `ctx.regex` rules, into an [`Expr`]
+struct RegexParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl RegexParser {
+    fn parse(regex: &str) -> Result<Expr, Error> {
+        let mut parser = Self {
+            chars: regex.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("Unbalanced parenthesis"));
+        }
+        Ok(expr)
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        let regex: String = self.chars.iter().collect();
+        Error::illegal_argument(format!("{msg} at {} in regex {regex:?}", self.pos))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next(&mut self) -> Result<char, Error> {
+        let c = self.peek().ok_or_else(|| self.error("Unexpected end"))?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn alternatives(&mut self) -> Result<Expr, Error> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.sequence()?);
+        }
+        Ok(Expr::Alt(alternatives))
+    }
+
+    fn sequence(&mut self) -> Result<Expr, Error> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            items.push(self.quantified(atom)?);
+        }
+        Ok(Expr::Seq(items))
+    }
+
+    fn atom(&mut self) -> Result<Expr, Error> {
+        Ok(match self.next()? {
+            '(' => {
+                if self.eat('?') {
+                    // Non-capturing and named groups, `(?:...)` and `(?P<name>...)`
+                    while self.peek().is_some_and(|c| c != ':' && c != '>') {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                let group = self.alternatives()?;
+                if !self.eat(')') {
+                    return Err(self.error("Missing )"));
+                }
+                group
+            }
+            '[' => Expr::Set(self.class()?),
+            '.' => Expr::Set(universe()),
+            '^' | '$' => Expr::empty(),
+            '\\' => self.escape()?,
+            c => Expr::Literal(c.to_string().into_bytes()),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Expr, Error> {
+        let digits: BTreeSet<char> = ('0'..='9').collect();
+        let word: BTreeSet<char> = ('a'..='z')
+            .chain('A'..='Z')
+            .chain('0'..='9')
+            .chain(['_'])
+            .collect();
+        let space: BTreeSet<char> = [' ', '\t', '\n', '\r'].into();
+        let negate = |set: BTreeSet<char>| universe().difference(&set).copied().collect();
+        Ok(match self.next()? {
+            'd' => Expr::Set(digits),
+            'w' => Expr::Set(word),
+            's' => Expr::Set(space),
+            'D' => Expr::Set(negate(digits)),
+            'W' => Expr::Set(negate(word)),
+            'S' => Expr::Set(negate(space)),
+            'b' | 'B' | 'A' | 'z' | 'Z' => Expr::empty(),
+            c => Expr::Literal(self.escaped_char(c)?.to_string().into_bytes()),
+        })
+    }
+
+    /// The character an escape sequence, of which `c` has been read, stands for
+    fn escaped_char(&mut self, c: char) -> Result<char, Error> {
+        Ok(match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            '0' => '\0',
+            'x' | 'u' => {
+                let len = if c == 'x' { 2 } else { 4 };
+                let hex: String = (0..len).map(|_| self.next()).collect::<Result<_, _>>()?;
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error("Invalid escape"))?
+            }
+            c => c,
+        })
+    }
+
+    fn class(&mut self) -> Result<BTreeSet<char>, Error> {
+        let negated = self.eat('^');
+        let mut chars = BTreeSet::new();
+        let mut first = true;
+        loop {
+            let c = self.next()?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let from = if c == '\\' {
+                match self.escape()? {
+                    Expr::Set(mut set) => {
+                        chars.append(&mut set);
+                        continue;
+                    }
+                    Expr::Literal(bytes) => String::from_utf8(bytes)
+                        .unwrap()
+                        .chars()
+                        .next()
+                        .unwrap_or_default(),
+                    _ => continue,
+                }
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.pos += 1;
+                let mut to = self.next()?;
+                if to == '\\' {
+                    let escaped = self.next()?;
+                    to = self.escaped_char(escaped)?;
+                }
+                chars.append(&mut char_range(from, to)?);
+            } else {
+                chars.insert(from);
+            }
+        }
+        Ok(if negated {
+            universe().difference(&chars).copied().collect()
+        } else {
+            chars
+        })
+    }
+
+    fn quantified(&mut self, atom: Expr) -> Result<Expr, Error> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let Some((min, max, len)) = self.bounds() else {
+                    return Ok(atom);
+                };
+                self.pos += len - 1;
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        // Lazy and possessive quantifiers generate the same strings
+        let _ = self.eat('?') || self.eat('+');
+        self.quantified(Expr::Repeat(Box::new(atom), min, max))
+    }
+
+    /// The bounds of a `{n}`, `{n,}` or `{n,m}` quantifier at the current position, and its length
+    fn bounds(&self) -> Option<(usize, Option<usize>, usize)> {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let end = rest.find('}')?;
+        let inner = &rest[1..end];
+        let (min, max) = match inner.split_once(',') {
+            None => {
+                let n = inner.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        Some((min, max, rest[..=end].chars().count()))
+    }
+}
+
+/// A token of the Python grammar DSL
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PyToken {
+    Ident(String),
+    Str(Vec<u8>),
+    Punct(char),
+}
+
+/// Split a Python grammar file into tokens, with their line numbers
+fn python_tokens(source: &str) -> Result<Vec<(PyToken, usize)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '\\' => {
+                // Line continuation
+                chars.next();
+            }
+            '#' => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '\'' | '"' => {
+                let start = line;
+                tokens.push((
+                    PyToken::Str(python_string(&mut chars, "", &mut line)?),
+                    start,
+                ));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    ident.push(c);
+                    chars.next();
+                }
+                let is_prefix = ident.len() <= 2
+                    && ident
+                        .chars()
+                        .all(|c| matches!(c.to_ascii_lowercase(), 'r' | 'b' | 'u' | 'f'));
+                if is_prefix && matches!(chars.peek(), Some('\'' | '"')) {
+                    let start = line;
+                    tokens.push((
+                        PyToken::Str(python_string(&mut chars, &ident, &mut line)?),
+                        start,
+                    ));
+                } else {
+                    tokens.push((PyToken::Ident(ident), line));
+                }
+            }
+            c => {
+                tokens.push((PyToken::Punct(c), line));
+                chars.next();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a Python string literal with the given prefix, and return its UTF-8 encoded contents
+fn python_string<I>(
+    chars: &mut Peekable<I>,
+    prefix: &str,
+    line: &mut usize,
+) -> Result<Vec<u8>, Error>
+where
+    I: Iterator<Item = char>,
+{
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.contains('f') {
+        return Err(Error::illegal_argument(format!(
+            "Format strings are not supported, in line {line}"
+        )));
+    }
+    let raw = prefix.contains('r');
+    let bytes = prefix.contains('b');
+
+    let quote = chars.next().unwrap();
+    let mut triple = false;
+    if chars.peek() == Some(&quote) {
+        chars.next();
+        if chars.peek() == Some(&quote) {
+            chars.next();
+            triple = true;
+        } else {
+            // The empty string
+            return Ok(Vec::new());
+        }
+    }
+
+    let unterminated = |line: usize| {
+        Error::illegal_argument(format!("Unterminated string literal in line {line}"))
+    };
+    let mut contents = Vec::new();
+    let mut pending_quotes = 0;
+    loop {
+        let c = chars.next().ok_or_else(|| unterminated(*line))?;
+        if c == quote {
+            if !triple {
+                break;
+            }
+            pending_quotes += 1;
+            if pending_quotes == 3 {
+                break;
+            }
+            continue;
+        }
+        for _ in 0..pending_quotes {
+            contents.push(quote as u8);
+        }
+        pending_quotes = 0;
+        match c {
+            '\n' if !triple => return Err(unterminated(*line)),
+            '\n' => {
+                *line += 1;
+                contents.push(b'\n');
+            }
+            '\\' if raw => {
+                contents.push(b'\\');
+                if let Some(next) = chars.next() {
+                    let mut buf = [0; 4];
+                    contents.extend_from_slice(next.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().ok_or_else(|| unterminated(*line))?;
+                let mut hex = |len: usize| -> Result<u32, Error> {
+                    let digits: String = (0..len).filter_map(|_| chars.next()).collect();
+                    u32::from_str_radix(&digits, 16).map_err(|_| {
+                        Error::illegal_argument(format!(
+                            "Invalid escape \\{escaped}{digits} in line {line}"
+                        ))
+                    })
+                };
+                let value = match escaped {
+                    '\n' => {
+                        *line += 1;
+                        continue;
+                    }
+                    'n' => u32::from('\n'),
+                    'r' => u32::from('\r'),
+                    't' => u32::from('\t'),
+                    'a' => 7,
+                    'b' => 8,
+                    'f' => 12,
+                    'v' => 11,
+                    '\\' | '\'' | '"' => u32::from(escaped),
+                    'x' => {
+                        let value = hex(2)?;
+                        if bytes {
+                            #[allow(clippy::cast_possible_truncation)]
+                            contents.push(value as u8);
+                            continue;
+                        }
+                        value
+                    }
+                    'u' if !bytes => hex(4)?,
+                    'U' if !bytes => hex(8)?,
+                    '0'..='7' => {
+                        let mut value = escaped.to_digit(8).unwrap();
+                        for _ in 0..2 {
+                            match chars.peek().and_then(|c| c.to_digit(8)) {
+                                Some(digit) => {
+                                    value = value * 8 + digit;
+                                    chars.next();
+                                }
+                                None => break,
+                            }
+                        }
+                        if bytes {
+                            #[allow(clippy::cast_possible_truncation)]
+                            contents.push(value as u8);
+                            continue;
+                        }
+                        value
+                    }
+                    other => {
+                        contents.push(b'\\');
+                        u32::from(other)
+                    }
+                };
+                let c = char::from_u32(value).ok_or_else(|| {
+                    Error::illegal_argument(format!("Invalid character in line {line}"))
+                })?;
+                contents.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            c => contents.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Ok(contents)
+}
+
+/// Load the rules of a grammar written in the Python DSL of Nautilus.
+///
+/// Only a restricted subset of Python is supported, so that no Python runtime is needed:
+/// the grammar may only consist of `ctx.rule("NONTERMINAL", "rule")` and `ctx.regex("NONTERMINAL", "regex")` calls,
+/// with string or bytes literals as arguments, possibly concatenated with `+`, and comments.
+/// `ctx.script` rules, and any other Python code, are rejected.
+/// The start symbol is `START`, or the first nonterminal, if there is no `START`.
+pub fn nautilus_rules_from_python(source: &str) -> Result<NautilusRules, Error> {
+    let tokens = python_tokens(source)?;
+    let mut tokens = tokens.into_iter().peekable();
+    let mut builder = RuleBuilder::default();
+    let mut first = None;
+
+    while let Some((token, line)) = tokens.next() {
+        let unsupported =
+            || Error::illegal_argument(format!("Unsupported Python code in line {line}"));
+        let mut expect = |expected: PyToken| match tokens.next() {
+            Some((token, _)) if token == expected => Ok(()),
+            _ => Err(unsupported()),
+        };
+        if !matches!(token, PyToken::Ident(_)) {
+            return Err(unsupported());
+        }
+        expect(PyToken::Punct('.'))?;
+        let Some((PyToken::Ident(method), _)) = tokens.next() else {
+            return Err(unsupported());
+        };
+        if tokens.next().map(|(token, _)| token) != Some(PyToken::Punct('(')) {
+            return Err(unsupported());
+        }
+
+        let mut args: Vec<Vec<u8>> = Vec::new();
+        loop {
+            let mut arg = Vec::new();
+            while let Some((PyToken::Str(part), _)) = tokens.peek() {
+                arg.extend_from_slice(part);
+                tokens.next();
+                if tokens.peek().map(|(token, _)| token) == Some(&PyToken::Punct('+')) {
+                    tokens.next();
+                }
+            }
+            match tokens.next() {
+                Some((PyToken::Punct(','), _)) => args.push(arg),
+                Some((PyToken::Punct(')'), _)) => {
+                    if !arg.is_empty() || !args.is_empty() {
+                        args.push(arg);
+                    }
+                    break;
+                }
+                _ => return Err(unsupported()),
+            }
+        }
+        if tokens.peek().map(|(token, _)| token) == Some(&PyToken::Punct(';')) {
+            tokens.next();
+        }
+
+        if args.len() != 2 || args[0].is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "ctx.{method} takes a nonterminal and a string, in line {line}"
+            )));
+        }
+        let nonterminal = String::from_utf8(args[0].clone())
+            .map_err(|_| Error::illegal_argument(format!("Invalid nonterminal in line {line}")))?;
+        first.get_or_insert_with(|| nonterminal.clone());
+        match method.as_str() {
+            "rule" => builder.rule(&nonterminal, args.pop().unwrap()),
+            "regex" => {
+                let regex = String::from_utf8_lossy(&args[1]).into_owned();
+                let expr = RegexParser::parse(&regex)?;
+                let rhs = builder.lower(&expr, &nonterminal, b"")?;
+                builder.rule(&nonterminal, rhs);
+            }
+            "script" => {
+                return Err(Error::illegal_argument(format!(
+                    "Script rules need a Python runtime, in line {line}"
+                )))
+            }
+            _ => return Err(unsupported()),
+        }
+    }
+
+    let start = if builder
+        .rules
+        .iter()
+        .any(|(nonterminal, _)| nonterminal == START)
+    {
+        START.to_owned()
+    } else {
+        first.ok_or_else(|| Error::illegal_argument("The grammar has no rules"))?
+    };
+    builder.finish(&start)
+}
+
+/// A token of an ANTLR4 grammar
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum G4Token {
+    Ident(String),
+    Literal(String),
+    CharSet(String),
+    Action(String),
+    Punct(&'static str),
+}
+
+/// Split an ANTLR4 grammar into tokens
+fn antlr4_tokens(source: &str) -> Result<Vec<G4Token>, Error> {
+    const PUNCTS: [&str; 17] = [
+        "->", "+=", "..", "::", ":", ";", "|", "(", ")", "?", "*", "+", "~", ".", "=", "#", ",",
+    ];
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut pos = 0;
+    let unterminated = |what: &str| Error::illegal_argument(format!("Unterminated {what}"));
+    while pos < chars.len() {
+        let c = chars[pos];
+        let rest: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+        if c.is_whitespace() {
+            pos += 1;
+        } else if rest == "//" {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+        } else if rest == "/*" {
+            pos += 2;
+            while pos + 1 < chars.len() && !(chars[pos] == '*' && chars[pos + 1] == '/') {
+                pos += 1;
+            }
+            pos += 2;
+        } else if c == '\'' || c == '[' {
+            let close = if c == '\'' { '\'' } else { ']' };
+            let mut content = String::new();
+            pos += 1;
+            loop {
+                let c = *chars
+                    .get(pos)
+                    .ok_or_else(|| unterminated("literal or set"))?;
+                pos += 1;
+                if c == close {
+                    break;
+                }
+                content.push(c);
+                if c == '\\' {
+                    content.push(*chars.get(pos).ok_or_else(|| unterminated("escape"))?);
+                    pos += 1;
+                }
+            }
+            tokens.push(if close == '\'' {
+                G4Token::Literal(content)
+            } else {
+                G4Token::CharSet(content)
+            });
+        } else if c == '{' {
+            let mut depth = 0;
+            let start = pos;
+            loop {
+                match chars.get(pos).ok_or_else(|| unterminated("action"))? {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                pos += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            tokens.push(G4Token::Action(chars[start + 1..pos - 1].iter().collect()));
+        } else if c == '<' {
+            // Element options, like `<assoc=right>`
+            while pos < chars.len() && chars[pos] != '>' {
+                pos += 1;
+            }
+            pos += 1;
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(G4Token::Ident(chars[start..pos].iter().collect()));
+        } else if c == '@' {
+            // Named actions, like `@header {...}` or `@lexer::members {...}`
+            pos += 1;
+            while pos < chars.len() && chars[pos] != '{' {
+                pos += 1;
+            }
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| rest.starts_with(**punct)) {
+            tokens.push(G4Token::Punct(punct));
+            pos += punct.len();
+        } else {
+            return Err(Error::illegal_argument(format!(
+                "Unexpected character {c:?} in ANTLR4 grammar"
+            )));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Decode the escapes of an ANTLR4 literal or set, and mark the characters that were escaped
+fn antlr4_unescape(content: &str) -> Result<Vec<(char, bool)>, Error> {
+    let mut chars = Vec::new();
+    let mut iter = content.chars();
+    while let Some(c) = iter.next() {
+        if c != '\\' {
+            chars.push((c, false));
+            continue;
+        }
+        let escaped = iter
+            .next()
+            .ok_or_else(|| Error::illegal_argument("Unterminated escape"))?;
+        let c = match escaped {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'u' => {
+                let hex: String = if iter.clone().next() == Some('{') {
+                    iter.next();
+                    iter.by_ref().take_while(|c| *c != '}').collect()
+                } else {
+                    iter.by_ref().take(4).collect()
+                };
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| Error::illegal_argument(format!("Invalid escape \\u{hex}")))?
+            }
+            'p' | 'P' => {
+                // Unicode properties, approximated by ASCII letters
+                if iter.clone().next() == Some('{') {
+                    iter.by_ref().take_while(|c| *c != '}').for_each(drop);
+                }
+                chars.extend(('a'..='z').chain('A'..='Y').map(|c| (c, true)));
+                'Z'
+            }
+            other => other,
+        };
+        chars.push((c, true));
+    }
+    Ok(chars)
+}
+
+/// Parses the rules of ANTLR4 grammars
+struct Antlr4Parser {
+    tokens: Vec<G4Token>,
+    pos: usize,
+}
+
+/// A rule of an ANTLR4 grammar
+struct Antlr4Rule {
+    name: String,
+    lexer: bool,
+    hidden: bool,
+    expr: Expr,
+}
+
+impl Antlr4Parser {
+    fn peek(&self) -> Option<&G4Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(G4Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::illegal_argument(format!("{msg}, at {:?} in ANTLR4 grammar", self.peek()))
+    }
+
+    fn skip_past(&mut self, punct: &str) {
+        while self.pos < self.tokens.len() && !self.eat(punct) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parse all rules, and the names of the tokens declared in `tokens { ... }`
+    fn grammar(&mut self) -> Result<(Vec<Antlr4Rule>, Vec<String>), Error> {
+        let mut rules = Vec::new();
+        let mut declared = Vec::new();
+        while let Some(token) = self.peek().cloned() {
+            self.pos += 1;
+            let ident = match token {
+                G4Token::Ident(ident) => ident,
+                // Named actions
+                G4Token::Action(_) => continue,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("Expected a rule"));
+                }
+            };
+            match ident.as_str() {
+                "lexer" | "parser" | "grammar" | "import" | "mode" => self.skip_past(";"),
+                "options" | "channels" => {
+                    self.pos += 1;
+                }
+                "tokens" => {
+                    if let Some(G4Token::Action(names)) = self.peek() {
+                        declared.extend(
+                            names
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|name| !name.is_empty())
+                                .map(ToOwned::to_owned),
+                        );
+                    }
+                    self.pos += 1;
+                }
+                "fragment" => {
+                    let Some(G4Token::Ident(name)) = self.peek().cloned() else {
+                        return Err(self.error("Expected a rule name"));
+                    };
+                    self.pos += 1;
+                    rules.push(self.rule(name)?);
+                }
+                _ => rules.push(self.rule(ident)?),
+            }
+        }
+        Ok((rules, declared))
+    }
+
+    fn rule(&mut self, name: String) -> Result<Antlr4Rule, Error> {
+        // Skip arguments, return values, locals, options and actions up to the `:`
+        while !self.eat(":") {
+            if self.pos >= self.tokens.len() {
+                return Err(self.error("Expected :"));
+            }
+            self.pos += 1;
+        }
+        let lexer = name.starts_with(|c: char| c.is_uppercase());
+        let mut hidden = false;
+        let expr = self.alternatives(&mut hidden)?;
+        if !self.eat(";") {
+            return Err(self.error("Expected ;"));
+        }
+        // Exception handlers
+        while matches!(self.peek(), Some(G4Token::Ident(ident)) if ident == "catch" || ident == "finally")
+        {
+            self.pos += 1;
+            while matches!(self.peek(), Some(G4Token::CharSet(_) | G4Token::Action(_))) {
+                self.pos += 1;
+            }
+        }
+        Ok(Antlr4Rule {
+            name,
+            lexer,
+            hidden,
+            expr,
+        })
+    }
+
+    fn alternatives(&mut self, hidden: &mut bool) -> Result<Expr, Error> {
+        let mut alternatives = vec![self.alternative(hidden)?];
+        while self.eat("|") {
+            alternatives.push(self.alternative(hidden)?);
+        }
+        Ok(Expr::Alt(alternatives))
+    }
+
+    fn alternative(&mut self, hidden: &mut bool) -> Result<Expr, Error> {
+        let mut items = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(G4Token::Punct("|" | ";" | ")")) => break,
+                Some(G4Token::Punct("->")) => {
+                    // Lexer commands
+                    self.pos += 1;
+                    while !matches!(self.peek(), None | Some(G4Token::Punct("|" | ";" | ")"))) {
+                        if let Some(G4Token::Ident(command)) = self.peek() {
+                            *hidden |= command == "skip" || command == "channel";
+                        }
+                        self.pos += 1;
+                        if self.eat("(") {
+                            self.skip_past(")");
+                        }
+                    }
+                }
+                Some(G4Token::Punct("#")) => {
+                    // Alternative labels
+                    self.pos += 2;
+                }
+                _ => {
+                    let element = self.element(hidden)?;
+                    items.push(element);
+                }
+            }
+        }
+        Ok(Expr::Seq(items))
+    }
+
+    fn element(&mut self, hidden: &mut bool) -> Result<Expr, Error> {
+        // Labels, like `left=expr` or `args+=expr`
+        if matches!(self.peek(), Some(G4Token::Ident(_)))
+            && matches!(
+                self.tokens.get(self.pos + 1),
+                Some(G4Token::Punct("=" | "+="))
+            )
+        {
+            self.pos += 2;
+        }
+        let atom = self.atom(hidden)?;
+        Ok(self.suffixed(atom))
+    }
+
+    fn suffixed(&mut self, atom: Expr) -> Expr {
+        let (min, max) = if self.eat("?") {
+            (0, Some(1))
+        } else if self.eat("*") {
+            (0, None)
+        } else if self.eat("+") {
+            (1, None)
+        } else {
+            return atom;
+        };
+        // Non-greedy suffixes generate the same strings
+        self.eat("?");
+        Expr::Repeat(Box::new(atom), min, max)
+    }
+
+    fn atom(&mut self, hidden: &mut bool) -> Result<Expr, Error> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("Unexpected end"))?;
+        self.pos += 1;
+        Ok(match token {
+            G4Token::Punct("(") => {
+                let group = self.alternatives(hidden)?;
+                if !self.eat(")") {
+                    return Err(self.error("Expected )"));
+                }
+                group
+            }
+            G4Token::Punct(".") => Expr::Set(universe()),
+            G4Token::Punct("~") => {
+                let negated = self.atom(hidden)?;
+                let chars = negated
+                    .chars()
+                    .ok_or_else(|| self.error("Only sets of characters can be negated"))?;
+                Expr::Set(universe().difference(&chars).copied().collect())
+            }
+            G4Token::Literal(literal) => {
+                let from: Vec<char> = antlr4_unescape(&literal)?
+                    .into_iter()
+                    .map(|(c, _)| c)
+                    .collect();
+                if self.eat("..") {
+                    let Some(G4Token::Literal(to)) = self.peek().cloned() else {
+                        return Err(self.error("Expected the end of a range"));
+                    };
+                    self.pos += 1;
+                    let to: Vec<char> = antlr4_unescape(&to)?.into_iter().map(|(c, _)| c).collect();
+                    match (from.as_slice(), to.as_slice()) {
+                        ([from], [to]) => Expr::Set(char_range(*from, *to)?),
+                        _ => return Err(self.error("Ranges are between single characters")),
+                    }
+                } else {
+                    Expr::Literal(from.into_iter().collect::<String>().into_bytes())
+                }
+            }
+            G4Token::CharSet(set) => {
+                let chars = antlr4_unescape(&set)?;
+                let mut set = BTreeSet::new();
+                let mut idx = 0;
+                while idx < chars.len() {
+                    if idx + 2 < chars.len() && chars[idx + 1] == ('-', false) {
+                        set.append(&mut char_range(chars[idx].0, chars[idx + 2].0)?);
+                        idx += 3;
+                    } else {
+                        set.insert(chars[idx].0);
+                        idx += 1;
+                    }
+                }
+                Expr::Set(set)
+            }
+            G4Token::Ident(name) if name == "EOF" => Expr::empty(),
+            G4Token::Ident(name) => Expr::Ref(name),
+            G4Token::Action(_) => {
+                // Actions and semantic predicates generate nothing
+                self.eat("?");
+                Expr::empty()
+            }
+            G4Token::Punct(_) => return Err(self.error("Unexpected token")),
+        })
+    }
+}
+
+/// Load the rules of ANTLR4 grammars, like a lexer and a parser grammar, or a single combined grammar.
+///
+/// The items of parser rules are separated by a space, so that tokens do not run into each other.
+/// Lexer rules that are skipped, or sent to another channel, like whitespace and comments, are dropped.
+/// Semantic predicates, actions and lexer modes are ignored, and tokens declared in `tokens { ... }`
+/// without a lexer rule generate the empty string.
+/// The start symbol is `start`, or the first parser rule.
+/// All nonterminals are prefixed with `R_`, as Nautilus nonterminals have to start with an uppercase letter,
+/// so the rule `expr` becomes `R_expr`.
+pub fn nautilus_rules_from_antlr4(
+    sources: &[&str],
+    start: Option<&str>,
+) -> Result<NautilusRules, Error> {
+    let mut rules = Vec::new();
+    let mut declared = Vec::new();
+    for source in sources {
+        let mut parser = Antlr4Parser {
+            tokens: antlr4_tokens(source)?,
+            pos: 0,
+        };
+        let (mut file_rules, mut file_declared) = parser.grammar()?;
+        rules.append(&mut file_rules);
+        declared.append(&mut file_declared);
+    }
+
+    let defined: BTreeSet<&str> = rules.iter().map(|rule| rule.name.as_str()).collect();
+    for rule in &rules {
+        let mut refs = Vec::new();
+        rule.expr.visit_refs(&mut refs);
+        if let Some(undefined) = refs
+            .into_iter()
+            .find(|name| !defined.contains(name) && !declared.iter().any(|d| d == name))
+        {
+            return Err(Error::illegal_argument(format!(
+                "Rule {} references the undefined rule {undefined}",
+                rule.name
+            )));
+        }
+    }
+
+    let start = match start {
+        Some(start) => start.to_owned(),
+        None => rules
+            .iter()
+            .find(|rule| !rule.lexer)
+            .or_else(|| rules.first())
+            .map(|rule| rule.name.clone())
+            .ok_or_else(|| Error::illegal_argument("The grammar has no rules"))?,
+    };
+
+    let mut builder = RuleBuilder::with_prefix(ANTLR4_PREFIX);
+    for name in declared
+        .iter()
+        .filter(|name| !defined.contains(name.as_str()))
+    {
+        builder.rule(name, Vec::new());
+    }
+    for rule in rules.iter().filter(|rule| !rule.hidden) {
+        let separator: &[u8] = if rule.lexer { b"" } else { b" " };
+        let alternatives = match &rule.expr {
+            Expr::Alt(alternatives) => alternatives.as_slice(),
+            expr => core::slice::from_ref(expr),
+        };
+        for alternative in alternatives {
+            let rhs = builder.lower(alternative, &rule.name, separator)?;
+            builder.rule(&rule.name, rhs);
+        }
+    }
+    builder.finish(&start)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use super::{nautilus_rules_from_antlr4, nautilus_rules_from_python, NautilusRules};
+    #[cfg(feature = "nautilus")]
+    use crate::generators::{Generator, NautilusContext, NautilusGenerator};
+
+    /// Derive a random string from the rules, preferring rules without references when deep
+    #[allow(clippy::naive_bytecount)]
+    fn derive(
+        rules: &NautilusRules,
+        nonterminal: &str,
+        rand: &mut StdRand,
+        depth: usize,
+    ) -> Vec<u8> {
+        let mut candidates: Vec<&Vec<u8>> = rules
+            .iter()
+            .filter(|(nt, _)| nt == nonterminal)
+            .map(|(_, rhs)| rhs)
+            .collect();
+        assert!(!candidates.is_empty(), "no rules for {nonterminal}");
+        if depth > 8 {
+            let fewest = candidates
+                .iter()
+                .map(|rhs| rhs.iter().filter(|b| **b == b'{').count())
+                .min()
+                .unwrap();
+            candidates.retain(|rhs| rhs.iter().filter(|b| **b == b'{').count() == fewest);
+        }
+        let rhs = rand.choose(&candidates);
+        let mut out = Vec::new();
+        let mut idx = 0;
+        while idx < rhs.len() {
+            match rhs[idx] {
+                b'\\' if matches!(rhs.get(idx + 1), Some(b'{' | b'}')) => {
+                    out.push(rhs[idx + 1]);
+                    idx += 2;
+                }
+                b'{' => {
+                    let end = idx + rhs[idx..].iter().position(|b| *b == b'}').unwrap();
+                    let name = String::from_utf8(rhs[idx + 1..end].to_vec()).unwrap();
+                    out.extend(derive(rules, &name, rand, depth + 1));
+                    idx = end + 1;
+                }
+                byte => {
+                    out.push(byte);
+                    idx += 1;
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_nautilus_python_grammar() {
+        let grammar = r#"
+# A tiny JSON-like grammar
+ctx.rule("START", "{VALUE}")
+ctx.rule(u"VALUE", '{NUMBER}')
+ctx.rule("VALUE", "\{" + "{STRING}: {VALUE}" "\}")
+ctx.rule("VALUE", b"\x00\n")
+ctx.rule("VALUE", """[
+{VALUE}]""")
+ctx.regex("NUMBER", r"-?(0|[1-9]\d{0,2})")
+ctx.regex("STRING", "\"[a-c]*\"");
+"#;
+        let rules = nautilus_rules_from_python(grammar).unwrap();
+        assert_eq!(rules[0], ("START_".into(), b"{VALUE}".to_vec()));
+        assert!(rules.contains(&("VALUE".into(), br"\{{STRING}: {VALUE}\}".to_vec())));
+        assert!(rules.contains(&("VALUE".into(), b"\0\n".to_vec())));
+        assert!(rules.contains(&("VALUE".into(), b"[\n{VALUE}]".to_vec())));
+
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..32 {
+            let number = derive(&rules, "NUMBER", &mut rand, 0);
+            let number = core::str::from_utf8(&number).unwrap();
+            assert!(number.parse::<i32>().unwrap().abs() < 1000, "{number}");
+            let string = derive(&rules, "STRING", &mut rand, 0);
+            assert!(string.starts_with(b"\"") && string.ends_with(b"\""));
+            assert!(string[1..string.len() - 1]
+                .iter()
+                .all(|b| b"abc".contains(b)));
+        }
+
+        // Required repetitions are expanded, so they are limited
+        assert!(nautilus_rules_from_python(r#"ctx.regex("A", "a{1024}")"#).is_ok());
+        assert!(nautilus_rules_from_python(r#"ctx.regex("A", "a{1025,}")"#).is_err());
+        assert!(nautilus_rules_from_python("ctx.script('A', ['B'], lambda b: b)").is_err());
+        assert!(nautilus_rules_from_python("import os").is_err());
+    }
+
+    #[test]
+    fn test_nautilus_antlr4_grammar() {
+        let grammar = r"
+grammar Expr;
+options { language = Java; }
+@header { package expr; }
+
+prog: stat+ EOF ;
+stat: expr ';'                # print
+    | ID '=' value=expr ';'   # assign
+    ;
+expr: <assoc=right> expr '^' expr
+    | left=expr op=('*'|'/') expr
+    | INT
+    | ID
+    | '(' expr ')'
+    ;
+
+ID  : LETTER (LETTER | [0-9])* ;
+INT : '0' | [1-9] DIGIT* ;
+fragment LETTER : 'a'..'z' | [A-Z_] ;
+fragment DIGIT : ~[a-z\u0000-/:-￿] ;
+WS  : [ \t\r\n]+ -> skip ;
+COMMENT : '/*' .*? '*/' -> channel(HIDDEN) ;
+";
+        let rules = nautilus_rules_from_antlr4(&[grammar], None).unwrap();
+        assert_eq!(rules[0].0, "R_prog");
+        assert!(!rules
+            .iter()
+            .any(|(nt, _)| nt == "R_WS" || nt == "R_COMMENT"));
+        assert!(rules.contains(&("R_stat".into(), b"{R_ID} = {R_expr} ;".to_vec())));
+        // All nonterminals are valid Nautilus nonterminals, i.e., start with an uppercase letter
+        assert!(rules.iter().all(|(nt, _)| nt.starts_with("R_")));
+
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..32 {
+            let id = derive(&rules, "R_ID", &mut rand, 0);
+            assert!(id[0].is_ascii_alphabetic() || id[0] == b'_');
+            assert!(id.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_'));
+            let int = derive(&rules, "R_INT", &mut rand, 0);
+            assert!(int.iter().all(u8::is_ascii_digit));
+            assert!(int == b"0" || int[0] != b'0');
+        }
+        let prog = derive(&rules, "R_prog", &mut rand, 0);
+        assert!(prog.ends_with(b";"));
+
+        assert!(nautilus_rules_from_antlr4(&["a: b;"], None).is_err());
+        assert_eq!(
+            nautilus_rules_from_antlr4(&["tokens { INDENT } block: INDENT 'x';"], Some("block"))
+                .unwrap()[0],
+            ("R_block".into(), b"{R_INDENT} x".to_vec())
+        );
+        // A grammar rule called `START` does not clash with the start symbol of Nautilus
+        assert_eq!(
+            nautilus_rules_from_antlr4(&["START: 'x';"], None).unwrap()[0],
+            ("R_START".into(), b"x".to_vec())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "nautilus")]
+    fn test_nautilus_antlr4_context() {
+        let grammar = r"
+list: '[' item (',' item)* ']' ;
+item: list | NUM ;
+NUM : [0-9]+ ;
+";
+        let rules = nautilus_rules_from_antlr4(&[grammar], None).unwrap();
+        let rules: Vec<(&str, &[u8])> = rules
+            .iter()
+            .map(|(nt, rhs)| (nt.as_str(), rhs.as_slice()))
+            .collect();
+        let context = NautilusContext::with_rules(15, &rules).unwrap();
+        let mut generator = NautilusGenerator::new(&context);
+        for _ in 0..32 {
+            let input = generator.generate(&mut ()).unwrap();
+            let mut bytes = Vec::new();
+            input.unparse(&context, &mut bytes);
+            // Nonterminals Nautilus did not recognize would end up in the output verbatim
+            assert!(
+                bytes.starts_with(b"[") && bytes.ends_with(b"]"),
+                "{bytes:?}"
+            );
+            assert!(
+                bytes.iter().all(|b| b"[], 0123456789".contains(b)),
+                "{bytes:?}"
+            );
+        }
+    }
+}
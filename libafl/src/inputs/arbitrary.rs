@@ -0,0 +1,142 @@
+//! An input decoded with the [`arbitrary`](https://docs.rs/arbitrary) crate, like the inputs of `cargo-fuzz` harnesses.
+//!
+//! The input keeps the raw bytes, which get mutated, and lazily decodes them to a `T` with
+//! [`Arbitrary::arbitrary_take_rest`], exactly like `fuzz_target!(|x: T| ...)` does.
+//! Use the mutators in [`crate::mutators::arbitrary`] to mutate the bytes along the layout of the encoding.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::Debug,
+    hash::{BuildHasher, Hasher},
+};
+use std::{fs::File, io::Read, path::Path};
+
+use ::arbitrary::{Arbitrary, Unstructured};
+use ahash::RandomState;
+use libafl_bolts::{fs::write_file_atomic, ownedref::OwnedSlice, Error, HasLen};
+use serde::{Deserialize, Serialize};
+
+use crate::inputs::{HasBytesVec, HasTargetBytes, Input};
+
+/// An input of raw bytes, decoded to a `T` with [`Arbitrary::arbitrary_take_rest`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "")]
+pub struct ArbitraryInput<T> {
+    bytes: Vec<u8>,
+    #[serde(skip)]
+    value: Option<T>,
+}
+
+impl<T> Input for ArbitraryInput<T>
+where
+    T: for<'a> Arbitrary<'a> + Clone + Debug,
+{
+    /// Write the raw bytes of this input to the file, as `cargo-fuzz` would
+    fn to_file<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        write_file_atomic(path, &self.bytes)
+    }
+
+    /// Load the raw bytes of this input from a file
+    fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path)?;
+        let mut bytes: Vec<u8> = vec![];
+        file.read_to_end(&mut bytes)?;
+        Ok(Self::new(bytes))
+    }
+
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        hasher.write(&self.bytes);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl<T> ArbitraryInput<T>
+where
+    T: for<'a> Arbitrary<'a>,
+{
+    /// Creates a new [`ArbitraryInput`] from the given raw bytes
+    #[must_use]
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes, value: None }
+    }
+
+    /// The decoded value, decoding the raw bytes first, if they changed since the last call
+    pub fn value(&mut self) -> Result<&T, Error> {
+        if self.value.is_none() {
+            let value = T::arbitrary_take_rest(Unstructured::new(&self.bytes)).map_err(|err| {
+                Error::illegal_argument(format!("Could not decode arbitrary input: {err}"))
+            })?;
+            self.value = Some(value);
+        }
+        Ok(self.value.as_ref().unwrap())
+    }
+
+    /// The decoded value, if it was decoded since the raw bytes last changed
+    #[must_use]
+    pub fn cached_value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+}
+
+impl<T> HasBytesVec for ArbitraryInput<T> {
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The raw bytes, as mutable borrow. Drops the decoded value, as the bytes may change.
+    #[inline]
+    fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        self.value = None;
+        &mut self.bytes
+    }
+}
+
+impl<T> HasTargetBytes for ArbitraryInput<T> {
+    #[inline]
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(&self.bytes)
+    }
+}
+
+impl<T> HasLen for ArbitraryInput<T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl<T> From<Vec<u8>> for ArbitraryInput<T>
+where
+    T: for<'a> Arbitrary<'a>,
+{
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArbitraryInput;
+    use crate::inputs::HasBytesVec;
+
+    #[test]
+    fn test_arbitrary_input_decode() {
+        let mut input = ArbitraryInput::<u8>::new(vec![7]);
+        assert_eq!(input.cached_value(), None);
+        assert_eq!(*input.value().unwrap(), 7);
+        assert_eq!(input.cached_value(), Some(&7));
+
+        input.bytes_mut()[0] = 9;
+        assert_eq!(input.cached_value(), None);
+        assert_eq!(*input.value().unwrap(), 9);
+    }
+}
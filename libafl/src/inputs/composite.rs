@@ -0,0 +1,126 @@
+//! Inputs composed of heterogeneous parts, such as `{ header: BytesInput, body: NautilusInput, flags: u32 }`.
+//!
+//! Unlike a [`crate::inputs::MultipartInput`], where every part has the same type, a composite input is
+//! a plain struct. Use `#[derive(CompositeInput)]` to implement [`Input`], [`CompositeInput`], and
+//! [`crate::inputs::HasTargetBytes`] for it, and mutate it with a [`crate::mutators::CompositeMutator`].
+//! Integer and `bool` parts can be mutated with a [`crate::mutators::IntPartMutator`].
+//!
+//! A `NautilusInput` part needs its grammar to be unparsed, register it once with [`register_nautilus_context`].
+
+use alloc::{string::String, vec::Vec};
+use core::hash::{BuildHasher, Hasher};
+#[cfg(feature = "nautilus")]
+use std::sync::OnceLock;
+
+use ahash::RandomState;
+use libafl_bolts::rands::Rand;
+use serde::Serialize;
+
+#[cfg(feature = "nautilus")]
+use crate::{generators::nautilus::NautilusContext, inputs::NautilusInput};
+use crate::{
+    inputs::{BytesInput, GramatronInput, HasBytesVec, Input},
+    mutators::MutationResult,
+    Error,
+};
+
+/// The grammar to unparse [`NautilusInput`] parts with, see [`register_nautilus_context`]
+#[cfg(feature = "nautilus")]
+static NAUTILUS_CONTEXT: OnceLock<NautilusContext> = OnceLock::new();
+
+/// Register the grammar that [`NautilusInput`] parts of [`CompositeInput`]s are unparsed with.
+///
+/// There is one such grammar per process. The returned reference can be passed to the
+/// Nautilus mutators of those parts.
+#[cfg(feature = "nautilus")]
+pub fn register_nautilus_context(
+    context: NautilusContext,
+) -> Result<&'static NautilusContext, Error> {
+    NAUTILUS_CONTEXT.set(context).map_err(|_| {
+        Error::illegal_state("A NautilusContext for composite inputs was already registered")
+    })?;
+    Ok(NAUTILUS_CONTEXT.get().unwrap())
+}
+
+/// An [`Input`] made of a fixed number of heterogeneous parts, usually implemented by `#[derive(CompositeInput)]`
+pub trait CompositeInput: Input {
+    /// The names of the parts, in declaration order
+    const PART_NAMES: &'static [&'static str];
+
+    /// Cross over the part at index `part` with a randomly chosen part of `other` of the same type
+    fn crossover_part<R>(
+        &mut self,
+        rand: &mut R,
+        other: &Self,
+        part: usize,
+    ) -> Result<MutationResult, Error>
+    where
+        R: Rand;
+}
+
+/// A part of a [`CompositeInput`] that contributes to its target bytes
+pub trait CompositeInputPart {
+    /// Append the bytes of this part, as the target should see them
+    fn append_target_bytes(&self, bytes: &mut Vec<u8>);
+}
+
+impl CompositeInputPart for BytesInput {
+    fn append_target_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.bytes());
+    }
+}
+
+impl CompositeInputPart for GramatronInput {
+    fn append_target_bytes(&self, bytes: &mut Vec<u8>) {
+        let mut unparsed = Vec::new();
+        self.unparse(&mut unparsed);
+        bytes.extend_from_slice(&unparsed);
+    }
+}
+
+#[cfg(feature = "nautilus")]
+impl CompositeInputPart for NautilusInput {
+    /// Unparse this part with the grammar registered by [`register_nautilus_context`]
+    ///
+    /// # Panics
+    /// Panics if no grammar was registered
+    fn append_target_bytes(&self, bytes: &mut Vec<u8>) {
+        let context = NAUTILUS_CONTEXT
+            .get()
+            .expect("Register a grammar with register_nautilus_context to use NautilusInput parts");
+        let mut unparsed = Vec::new();
+        self.unparse(context, &mut unparsed);
+        bytes.extend_from_slice(&unparsed);
+    }
+}
+
+impl CompositeInputPart for bool {
+    fn append_target_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.push(u8::from(*self));
+    }
+}
+
+macro_rules! impl_composite_input_part_int {
+    ($($int:ty),+) => {
+        $(
+            impl CompositeInputPart for $int {
+                fn append_target_bytes(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )+
+    };
+}
+
+impl_composite_input_part_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// The name of a [`CompositeInput`]: a hash of its serialized parts
+#[must_use]
+pub fn composite_input_name<I>(input: &I) -> String
+where
+    I: Serialize,
+{
+    let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+    hasher.write(&postcard::to_allocvec(input).expect("Could not serialize composite input"));
+    format!("{:016x}", hasher.finish())
+}
@@ -0,0 +1,582 @@
+//! An Earley parser for context-free grammars over bytes.
+//!
+//! It is used to import byte inputs into grammar inputs, like [`crate::inputs::NautilusInput`],
+//! by finding a derivation of the bytes in the grammar. Ambiguous inputs get one of their derivations,
+//! the first one in rule order.
+
+use alloc::{format, vec::Vec};
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::Error;
+
+/// A symbol of an [`EarleyGrammar`] rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EarleySymbol {
+    /// Bytes that have to occur literally
+    Terminal(Vec<u8>),
+    /// A nonterminal, by its index
+    NonTerminal(usize),
+}
+
+/// The default maximum length of the inputs an [`EarleyGrammar`] parses.
+/// Parsing takes up to cubic time in the input length.
+pub const EARLEY_MAX_LEN: usize = 4096;
+
+/// A context-free grammar over bytes, to parse inputs with.
+///
+/// Nonterminals are indices, rules are indices in the order they were added.
+/// A derivation is the list of the rules applied, in preorder.
+#[derive(Clone, Debug)]
+pub struct EarleyGrammar {
+    start: usize,
+    rules: Vec<(usize, Vec<EarleySymbol>)>,
+    rules_for: Vec<Vec<usize>>,
+    max_len: usize,
+}
+
+/// An Earley item: a rule, the position in the rule, and where in the input it started
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// The result of the recognizer
+struct Chart {
+    /// The rules that derive a span of the input, as rule, start, and end
+    completed: HashSet<(usize, usize, usize)>,
+    /// The ends of the spans of the input a nonterminal derives, starting at a position
+    ends: HashMap<(usize, usize), Vec<usize>>,
+    /// The last position of the input that any item reached
+    furthest: usize,
+}
+
+/// A nonterminal whose derivation is being built by a [`DerivationBuilder`]
+struct Frame {
+    nt: usize,
+    start: usize,
+    end: usize,
+    /// The index of the next rule of `nt` to try
+    next_rule: usize,
+    /// The length of the derivation before this nonterminal
+    len: usize,
+    /// Whether a rule was chosen, and its remaining children are in `children`
+    chosen: bool,
+    /// The nonterminal children of the chosen rule left to build, with their spans, in reverse
+    children: Vec<(usize, usize, usize)>,
+}
+
+/// The result of entering a nonterminal in a [`DerivationBuilder`]
+enum Entered {
+    /// The derivation was built right away, or there is none
+    Done(bool),
+    /// The rules of the nonterminal have to be tried
+    Frame(Frame),
+}
+
+impl Default for EarleyGrammar {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl EarleyGrammar {
+    /// Create a new, empty [`EarleyGrammar`] with the given start nonterminal
+    #[must_use]
+    pub fn new(start: usize) -> Self {
+        Self {
+            start,
+            rules: Vec::new(),
+            rules_for: Vec::new(),
+            max_len: EARLEY_MAX_LEN,
+        }
+    }
+
+    /// Set the maximum length of the inputs to parse, defaults to [`EARLEY_MAX_LEN`]
+    #[must_use]
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// The maximum length of the inputs to parse
+    #[must_use]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Add a rule for `nonterminal`, and return its index
+    pub fn add_rule(&mut self, nonterminal: usize, symbols: Vec<EarleySymbol>) -> usize {
+        let max_nonterminal = symbols
+            .iter()
+            .filter_map(|symbol| match symbol {
+                EarleySymbol::NonTerminal(nt) => Some(*nt),
+                EarleySymbol::Terminal(_) => None,
+            })
+            .chain([nonterminal, self.start])
+            .max()
+            .unwrap();
+        if self.rules_for.len() <= max_nonterminal {
+            self.rules_for.resize(max_nonterminal + 1, Vec::new());
+        }
+        self.rules_for[nonterminal].push(self.rules.len());
+        self.rules.push((nonterminal, symbols));
+        self.rules.len() - 1
+    }
+
+    /// The rules of this grammar, as nonterminal and symbols
+    #[must_use]
+    pub fn rules(&self) -> &[(usize, Vec<EarleySymbol>)] {
+        &self.rules
+    }
+
+    /// Find a derivation of `bytes` from the start nonterminal
+    ///
+    /// Inputs longer than [`EarleyGrammar::max_len`] are rejected.
+    pub fn parse(&self, bytes: &[u8]) -> Result<Vec<usize>, Error> {
+        if bytes.len() > self.max_len {
+            return Err(Error::illegal_argument(format!(
+                "The input is {} bytes long, only inputs up to {} bytes are parsed",
+                bytes.len(),
+                self.max_len
+            )));
+        }
+        let (nullable, chart) = self.recognize(bytes);
+        self.derive(bytes, &nullable, &chart, bytes.len())
+            .ok_or_else(|| {
+                Error::illegal_argument(format!(
+                    "The input does not match the grammar, parsing failed at byte {}",
+                    chart.furthest
+                ))
+            })
+    }
+
+    /// Find a derivation of the longest prefix of `bytes` that the start nonterminal derives,
+    /// and return it with the length of the prefix.
+    ///
+    /// Only the first [`EarleyGrammar::max_len`] bytes are parsed.
+    #[must_use]
+    pub fn parse_prefix(&self, bytes: &[u8]) -> Option<(Vec<usize>, usize)> {
+        let bytes = &bytes[..bytes.len().min(self.max_len)];
+        let (nullable, chart) = self.recognize(bytes);
+        let mut ends = chart
+            .ends
+            .get(&(self.start, 0))
+            .cloned()
+            .unwrap_or_default();
+        if nullable.get(self.start).copied().flatten().is_some() {
+            ends.push(0);
+        }
+        ends.sort_unstable();
+        ends.into_iter()
+            .rev()
+            .find_map(|end| Some((self.derive(bytes, &nullable, &chart, end)?, end)))
+    }
+
+    /// For each nonterminal, a rule that derives the empty string without recursion, if it has one
+    fn nullable(&self) -> Vec<Option<usize>> {
+        let mut nullable = vec![None; self.rules_for.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (rule, (nt, symbols)) in self.rules.iter().enumerate() {
+                if nullable[*nt].is_none()
+                    && symbols.iter().all(|symbol| match symbol {
+                        EarleySymbol::Terminal(bytes) => bytes.is_empty(),
+                        EarleySymbol::NonTerminal(nt) => nullable[*nt].is_some(),
+                    })
+                {
+                    nullable[*nt] = Some(rule);
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    /// Run the Earley recognizer over all of `bytes`
+    fn recognize(&self, bytes: &[u8]) -> (Vec<Option<usize>>, Chart) {
+        let nullable = self.nullable();
+        let mut sets: Vec<Vec<Item>> = vec![Vec::new(); bytes.len() + 1];
+        let mut seen: Vec<HashSet<Item>> = vec![HashSet::new(); bytes.len() + 1];
+        let mut chart = Chart {
+            completed: HashSet::new(),
+            ends: HashMap::new(),
+            furthest: 0,
+        };
+
+        let mut add = |sets: &mut Vec<Vec<Item>>, pos: usize, item: Item| {
+            if seen[pos].insert(item) {
+                sets[pos].push(item);
+            }
+        };
+        if self.start < self.rules_for.len() {
+            for rule in &self.rules_for[self.start] {
+                add(
+                    &mut sets,
+                    0,
+                    Item {
+                        rule: *rule,
+                        dot: 0,
+                        origin: 0,
+                    },
+                );
+            }
+        }
+
+        for pos in 0..=bytes.len() {
+            if !sets[pos].is_empty() {
+                chart.furthest = pos;
+            }
+            let mut idx = 0;
+            while idx < sets[pos].len() {
+                let item = sets[pos][idx];
+                idx += 1;
+                let (nt, symbols) = &self.rules[item.rule];
+                match symbols.get(item.dot) {
+                    None => {
+                        // Complete
+                        if chart.completed.insert((item.rule, item.origin, pos)) {
+                            let ends = chart.ends.entry((*nt, item.origin)).or_default();
+                            if !ends.contains(&pos) {
+                                ends.push(pos);
+                            }
+                        }
+                        let mut waiting = 0;
+                        while waiting < sets[item.origin].len() {
+                            let parent = sets[item.origin][waiting];
+                            waiting += 1;
+                            if self.rules[parent.rule].1.get(parent.dot)
+                                == Some(&EarleySymbol::NonTerminal(*nt))
+                            {
+                                add(
+                                    &mut sets,
+                                    pos,
+                                    Item {
+                                        dot: parent.dot + 1,
+                                        ..parent
+                                    },
+                                );
+                            }
+                        }
+                    }
+                    Some(EarleySymbol::NonTerminal(next)) => {
+                        // Predict
+                        for rule in &self.rules_for[*next] {
+                            add(
+                                &mut sets,
+                                pos,
+                                Item {
+                                    rule: *rule,
+                                    dot: 0,
+                                    origin: pos,
+                                },
+                            );
+                        }
+                        if nullable[*next].is_some() {
+                            add(
+                                &mut sets,
+                                pos,
+                                Item {
+                                    dot: item.dot + 1,
+                                    ..item
+                                },
+                            );
+                        }
+                    }
+                    Some(EarleySymbol::Terminal(terminal)) => {
+                        // Scan
+                        if bytes[pos..].starts_with(terminal) {
+                            add(
+                                &mut sets,
+                                pos + terminal.len(),
+                                Item {
+                                    dot: item.dot + 1,
+                                    ..item
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        (nullable, chart)
+    }
+
+    /// Build a derivation of `bytes[..end]` from the chart
+    fn derive(
+        &self,
+        bytes: &[u8],
+        nullable: &[Option<usize>],
+        chart: &Chart,
+        end: usize,
+    ) -> Option<Vec<usize>> {
+        let mut derivation = Vec::new();
+        let mut visiting = HashSet::new();
+        let builder = DerivationBuilder {
+            grammar: self,
+            bytes,
+            nullable,
+            chart,
+        };
+        builder
+            .build(self.start, 0, end, &mut derivation, &mut visiting)
+            .then_some(derivation)
+    }
+}
+
+/// Builds derivations top-down from a [`Chart`]
+struct DerivationBuilder<'a> {
+    grammar: &'a EarleyGrammar,
+    bytes: &'a [u8],
+    nullable: &'a [Option<usize>],
+    chart: &'a Chart,
+}
+
+impl DerivationBuilder<'_> {
+    /// Append a derivation of `nt` for `bytes[start..end]`, returns `false` if there is none.
+    ///
+    /// The tree is built depth-first with an explicit stack, so deep trees do not overflow the call stack.
+    fn build(
+        &self,
+        nt: usize,
+        start: usize,
+        end: usize,
+        derivation: &mut Vec<usize>,
+        visiting: &mut HashSet<(usize, usize, usize)>,
+    ) -> bool {
+        let mut stack = Vec::new();
+        let mut done = match self.enter(nt, start, end, derivation, visiting) {
+            Entered::Done(found) => return found,
+            Entered::Frame(frame) => {
+                stack.push(frame);
+                None
+            }
+        };
+        while let Some(frame) = stack.last_mut() {
+            if done.take() == Some(false) {
+                // A child has no derivation, try the next rule
+                derivation.truncate(frame.len);
+                frame.chosen = false;
+                frame.children.clear();
+            }
+            if let Some((child, child_start, child_end)) = frame.children.pop() {
+                match self.enter(child, child_start, child_end, derivation, visiting) {
+                    Entered::Done(found) => done = Some(found),
+                    Entered::Frame(child) => stack.push(child),
+                }
+                continue;
+            }
+            if !frame.chosen && self.choose(frame, derivation) {
+                continue;
+            }
+            done = Some(frame.chosen);
+            visiting.remove(&(frame.nt, frame.start, frame.end));
+            stack.pop();
+        }
+        done.unwrap_or(false)
+    }
+
+    /// Start building a derivation of `nt` for `bytes[start..end]`
+    fn enter(
+        &self,
+        nt: usize,
+        start: usize,
+        end: usize,
+        derivation: &mut Vec<usize>,
+        visiting: &mut HashSet<(usize, usize, usize)>,
+    ) -> Entered {
+        if start == end {
+            if let Some(rule) = self.nullable.get(nt).copied().flatten() {
+                self.build_empty(rule, derivation);
+                return Entered::Done(true);
+            }
+        }
+        // Cycles of unit rules, like `A -> B`, `B -> A`, may derive the same span forever
+        if !visiting.insert((nt, start, end)) {
+            return Entered::Done(false);
+        }
+        Entered::Frame(Frame {
+            nt,
+            start,
+            end,
+            next_rule: 0,
+            len: derivation.len(),
+            chosen: false,
+            children: Vec::new(),
+        })
+    }
+
+    /// Choose the next rule of the frame that derives its span, returns `false` if there is none
+    fn choose(&self, frame: &mut Frame, derivation: &mut Vec<usize>) -> bool {
+        let rules = &self.grammar.rules_for[frame.nt];
+        while let Some(rule) = rules.get(frame.next_rule).copied() {
+            frame.next_rule += 1;
+            if !self
+                .chart
+                .completed
+                .contains(&(rule, frame.start, frame.end))
+            {
+                continue;
+            }
+            let mut failed = HashSet::new();
+            let Some(spans) = self.split(rule, 0, frame.start, frame.end, &mut failed) else {
+                continue;
+            };
+            derivation.push(rule);
+            frame.chosen = true;
+            frame.children = self.grammar.rules[rule]
+                .1
+                .iter()
+                .zip(spans)
+                .filter_map(|(symbol, (child_start, child_end))| match symbol {
+                    EarleySymbol::NonTerminal(child) => Some((*child, child_start, child_end)),
+                    EarleySymbol::Terminal(_) => None,
+                })
+                .rev()
+                .collect();
+            return true;
+        }
+        false
+    }
+
+    /// Append the derivation of the empty string by a nullable rule
+    fn build_empty(&self, rule: usize, derivation: &mut Vec<usize>) {
+        derivation.push(rule);
+        for symbol in &self.grammar.rules[rule].1 {
+            if let EarleySymbol::NonTerminal(child) = symbol {
+                self.build_empty(self.nullable[*child].unwrap(), derivation);
+            }
+        }
+    }
+
+    /// Split `bytes[pos..end]` into the spans of the symbols of `rule`, from the symbol at `idx` on
+    fn split(
+        &self,
+        rule: usize,
+        idx: usize,
+        pos: usize,
+        end: usize,
+        failed: &mut HashSet<(usize, usize)>,
+    ) -> Option<Vec<(usize, usize)>> {
+        let symbols = &self.grammar.rules[rule].1;
+        if idx == symbols.len() {
+            return (pos == end).then(Vec::new);
+        }
+        if failed.contains(&(idx, pos)) {
+            return None;
+        }
+        let candidates: Vec<usize> = match &symbols[idx] {
+            EarleySymbol::Terminal(terminal) => {
+                if self.bytes[pos..end].starts_with(terminal) {
+                    vec![pos + terminal.len()]
+                } else {
+                    vec![]
+                }
+            }
+            EarleySymbol::NonTerminal(nt) => {
+                let mut ends: Vec<usize> = self
+                    .chart
+                    .ends
+                    .get(&(*nt, pos))
+                    .map(|ends| ends.iter().copied().filter(|e| *e <= end).collect())
+                    .unwrap_or_default();
+                if self.nullable[*nt].is_some() && !ends.contains(&pos) {
+                    ends.push(pos);
+                }
+                ends
+            }
+        };
+        for next in candidates {
+            if let Some(mut spans) = self.split(rule, idx + 1, next, end, failed) {
+                spans.insert(0, (pos, next));
+                return Some(spans);
+            }
+        }
+        failed.insert((idx, pos));
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{EarleyGrammar, EarleySymbol};
+
+    /// Unparse a derivation, like a grammar fuzzer would
+    fn unparse(grammar: &EarleyGrammar, derivation: &[usize], idx: &mut usize, out: &mut Vec<u8>) {
+        let rule = derivation[*idx];
+        *idx += 1;
+        for symbol in &grammar.rules()[rule].1 {
+            match symbol {
+                EarleySymbol::Terminal(bytes) => out.extend_from_slice(bytes),
+                EarleySymbol::NonTerminal(_) => unparse(grammar, derivation, idx, out),
+            }
+        }
+    }
+
+    #[test]
+    fn test_earley_parse() {
+        use EarleySymbol::{NonTerminal as N, Terminal as T};
+        // 0: EXPR -> EXPR + EXPR | ( EXPR ) | NUM, 1: NUM -> DIGIT NUM | DIGIT, 2: DIGIT -> 0 | 1, 3: OPT -> "" | -
+        let mut grammar = EarleyGrammar::new(0);
+        grammar.add_rule(0, vec![N(0), T(b"+".to_vec()), N(0)]);
+        grammar.add_rule(0, vec![T(b"(".to_vec()), N(3), N(0), T(b")".to_vec())]);
+        grammar.add_rule(0, vec![N(1)]);
+        grammar.add_rule(1, vec![N(2), N(1)]);
+        grammar.add_rule(1, vec![N(2)]);
+        grammar.add_rule(2, vec![T(b"0".to_vec())]);
+        grammar.add_rule(2, vec![T(b"1".to_vec())]);
+        grammar.add_rule(3, vec![]);
+        grammar.add_rule(3, vec![T(b"-".to_vec())]);
+
+        for input in [&b"1"[..], b"10+1", b"(1+0)+(-11)+1", b"((0))"] {
+            let derivation = grammar.parse(input).unwrap();
+            let mut out = Vec::new();
+            unparse(&grammar, &derivation, &mut 0, &mut out);
+            assert_eq!(out, input);
+        }
+
+        assert!(grammar.parse(b"1+").is_err());
+        assert!(grammar.parse(b"").is_err());
+        let (derivation, len) = grammar.parse_prefix(b"1+10)+1").unwrap();
+        assert_eq!(len, 4);
+        let mut out = Vec::new();
+        unparse(&grammar, &derivation, &mut 0, &mut out);
+        assert_eq!(out, b"1+10");
+        assert!(grammar.parse_prefix(b"+1").is_none());
+
+        // Cycles of unit rules do not hang the parser
+        let mut grammar = EarleyGrammar::new(0);
+        grammar.add_rule(0, vec![N(1)]);
+        grammar.add_rule(1, vec![N(0)]);
+        grammar.add_rule(1, vec![T(b"x".to_vec())]);
+        assert_eq!(grammar.parse(b"x").unwrap(), [0, 2]);
+    }
+
+    #[test]
+    fn test_earley_deep_and_long() {
+        use EarleySymbol::{NonTerminal as N, Terminal as T};
+        // 0: LIST -> x LIST | x
+        let mut grammar = EarleyGrammar::new(0);
+        grammar.add_rule(0, vec![T(b"x".to_vec()), N(0)]);
+        grammar.add_rule(0, vec![T(b"x".to_vec())]);
+
+        // A tree as deep as the input is long
+        let input = vec![b'x'; 512];
+        let derivation = grammar.parse(&input).unwrap();
+        assert_eq!(derivation.len(), input.len());
+        let mut out = Vec::new();
+        unparse(&grammar, &derivation, &mut 0, &mut out);
+        assert_eq!(out, input);
+
+        let grammar = grammar.with_max_len(16);
+        assert!(grammar.parse(&input).is_err());
+        let (derivation, len) = grammar.parse_prefix(&input).unwrap();
+        assert_eq!(len, 16);
+        assert_eq!(derivation.len(), 16);
+    }
+}
@@ -14,7 +14,7 @@ use core::{
 
 use ahash::RandomState;
 use hashbrown::HashMap;
-use libafl_bolts::{Error, HasLen};
+use libafl_bolts::{rands::Rand, Error, HasLen};
 #[cfg(feature = "regex")]
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -107,6 +107,376 @@ impl Default for TokenInputEncoderDecoder {
     }
 }
 
+/// The class of a token, mutators only replace tokens with tokens of the same class
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenClass {
+    /// Identifiers and keywords, like `foo` or `SELECT`
+    Identifier,
+    /// Number literals, like `42` or `0x10`
+    Number,
+    /// String literals, like `'foo'` or `"bar"`
+    String,
+    /// Operators and punctuation, like `+=` or `;`
+    Punctuation,
+}
+
+impl TokenClass {
+    /// Classify a token as produced by the [`NaiveTokenizer`]
+    #[must_use]
+    pub fn of(token: &str) -> Self {
+        match token.chars().next() {
+            Some('"' | '\'' | '`') => Self::String,
+            Some(c) if c.is_ascii_digit() => Self::Number,
+            Some(_)
+                if token
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '$') =>
+            {
+                Self::Identifier
+            }
+            _ => Self::Punctuation,
+        }
+    }
+}
+
+/// How much more a token counts if it is part of an input that found new coverage, than if it was merely encoded
+pub const TOKEN_COVERAGE_WEIGHT: usize = 4;
+
+/// The tag of the `CustomBuf` events that share [`TokenVocabulary`] rewards between clients
+pub const TOKEN_VOCABULARY_TAG: &str = "libafl_token_vocabulary";
+
+/// Cumulative weights of a list of codes, to choose one of them by weight in `O(log n)`
+#[derive(Clone, Debug, Default)]
+struct WeightIndex {
+    /// The codes, in the order they are lined up by weight
+    codes: Vec<u32>,
+    /// A Fenwick tree over the weights of the codes: entry `i` holds the sum of the `(i + 1) & !i` weights ending at `i`
+    tree: Vec<usize>,
+    /// The sum of all weights
+    total: usize,
+}
+
+impl WeightIndex {
+    /// Appends a code with the given weight, and returns its position
+    fn push(&mut self, code: u32, weight: usize) -> usize {
+        let pos = self.codes.len();
+        // The new entry sums up its own weight and the entries it covers
+        let lowest = (pos + 1) & !pos;
+        let mut sum = weight;
+        let mut child = pos;
+        while child > pos + 1 - lowest {
+            sum += self.tree[child - 1];
+            child &= child - 1;
+        }
+        self.codes.push(code);
+        self.tree.push(sum);
+        self.total += weight;
+        pos
+    }
+
+    /// Adds `delta` to the weight of the code at `pos`
+    fn add(&mut self, pos: usize, delta: usize) {
+        let mut idx = pos;
+        while idx < self.tree.len() {
+            self.tree[idx] += delta;
+            idx |= idx + 1;
+        }
+        self.total += delta;
+    }
+
+    /// The code that `pick`, below `total`, falls on
+    fn find(&self, mut pick: usize) -> Option<u32> {
+        if pick >= self.total {
+            return None;
+        }
+        // Descend the tree to the last position whose weights, and those before it, sum up to at most `pick`
+        let mut pos = 0;
+        let mut step = self.tree.len().checked_next_power_of_two()?;
+        while step > 0 {
+            if pos + step <= self.tree.len() && self.tree[pos + step - 1] <= pick {
+                pos += step;
+                pick -= self.tree[pos - 1];
+            }
+            step >>= 1;
+        }
+        self.codes.get(pos).copied()
+    }
+}
+
+/// The [`WeightIndex`]es of a [`TokenVocabulary`], over all tokens and for each [`TokenClass`]
+#[derive(Clone, Debug, Default)]
+struct TokenWeights {
+    all: WeightIndex,
+    classes: HashMap<TokenClass, WeightIndex>,
+    /// The position of each code in the index of its class
+    class_positions: Vec<usize>,
+}
+
+impl TokenWeights {
+    fn get(&self, class: Option<TokenClass>) -> Option<&WeightIndex> {
+        match class {
+            None => Some(&self.all),
+            Some(class) => self.classes.get(&class),
+        }
+    }
+}
+
+/// The serialized part of a [`TokenVocabulary`], without the [`TokenWeights`] that are rebuilt from it
+#[derive(Deserialize)]
+struct TokenVocabularyCounts {
+    tokens: Vec<String>,
+    classes: Vec<TokenClass>,
+    frequencies: Vec<usize>,
+    coverage: Vec<usize>,
+    token_table: HashMap<String, u32>,
+}
+
+impl From<TokenVocabularyCounts> for TokenVocabulary {
+    fn from(counts: TokenVocabularyCounts) -> Self {
+        let mut vocabulary = Self {
+            tokens: counts.tokens,
+            classes: counts.classes,
+            frequencies: counts.frequencies,
+            coverage: counts.coverage,
+            token_table: counts.token_table,
+            weights: TokenWeights::default(),
+        };
+        for code in 0..vocabulary.tokens.len() as u32 {
+            vocabulary.index_token(code);
+        }
+        vocabulary
+    }
+}
+
+/// A learned token vocabulary, that encodes and decodes [`EncodedInput`]s.
+///
+/// Next to the codes, it records how often each token was encoded, and how often it was part of an input that was added to the corpus.
+/// Mutators choose tokens weighted by these counts.
+/// As metadata, the vocabulary is stored with the state, so clients that start from the same vocabulary agree on the codes.
+/// The rewards are shared between clients with [`crate::feedbacks::TokenVocabularyFeedback::with_sync`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(from = "TokenVocabularyCounts")]
+pub struct TokenVocabulary {
+    /// The tokens, indexed by code
+    tokens: Vec<String>,
+    /// The class of each token
+    classes: Vec<TokenClass>,
+    /// How often each token was encoded
+    frequencies: Vec<usize>,
+    /// How often each token was part of a new corpus entry
+    coverage: Vec<usize>,
+    /// The code of each token
+    token_table: HashMap<String, u32>,
+    /// The cumulative weights, kept up to date with the counts
+    #[serde(skip)]
+    weights: TokenWeights,
+}
+
+libafl_bolts::impl_serdeany!(TokenVocabulary);
+
+impl TokenVocabulary {
+    /// Creates a new, empty [`TokenVocabulary`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of tokens in this vocabulary
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Returns `true` if this vocabulary has no tokens
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Adds a token, if it is new, and returns its code
+    pub fn add_token(&mut self, token: &str) -> u32 {
+        if let Some(code) = self.token_table.get(token) {
+            return *code;
+        }
+        let code = self.tokens.len() as u32;
+        self.tokens.push(token.to_owned());
+        self.classes.push(TokenClass::of(token));
+        self.frequencies.push(0);
+        self.coverage.push(0);
+        self.token_table.insert(token.to_owned(), code);
+        self.index_token(code);
+        code
+    }
+
+    /// Adds the next token to the [`TokenWeights`]
+    fn index_token(&mut self, code: u32) {
+        let weight = self.weight(code);
+        let class = self.classes[code as usize];
+        self.weights.all.push(code, weight);
+        let pos = self
+            .weights
+            .classes
+            .entry(class)
+            .or_default()
+            .push(code, weight);
+        self.weights.class_positions.push(pos);
+    }
+
+    /// Adds to the counts of a token, and updates its weight
+    fn add_counts(&mut self, code: u32, frequency: usize, coverage: usize) {
+        let idx = code as usize;
+        self.frequencies[idx] += frequency;
+        self.coverage[idx] += coverage;
+        let delta = frequency + TOKEN_COVERAGE_WEIGHT * coverage;
+        if delta == 0 {
+            return;
+        }
+        self.weights.all.add(idx, delta);
+        let pos = self.weights.class_positions[idx];
+        if let Some(class) = self.weights.classes.get_mut(&self.classes[idx]) {
+            class.add(pos, delta);
+        }
+    }
+
+    /// The code of a token
+    #[must_use]
+    pub fn code(&self, token: &str) -> Option<u32> {
+        self.token_table.get(token).copied()
+    }
+
+    /// The token for a code
+    #[must_use]
+    pub fn token(&self, code: u32) -> Option<&str> {
+        self.tokens.get(code as usize).map(String::as_str)
+    }
+
+    /// The class of the token for a code
+    #[must_use]
+    pub fn class(&self, code: u32) -> Option<TokenClass> {
+        self.classes.get(code as usize).copied()
+    }
+
+    /// The weight of a token, used to choose it in mutations
+    #[must_use]
+    pub fn weight(&self, code: u32) -> usize {
+        let code = code as usize;
+        if code >= self.tokens.len() {
+            return 0;
+        }
+        1 + self.frequencies[code] + TOKEN_COVERAGE_WEIGHT * self.coverage[code]
+    }
+
+    /// Counts the tokens of an input that found new coverage.
+    ///
+    /// Returns a vocabulary of just the rewarded tokens, to [`Self::merge_counts`] into the vocabularies of other clients.
+    #[must_use]
+    pub fn reward(&mut self, input: &EncodedInput) -> Self {
+        let mut rewarded = Self::new();
+        for code in input.codes() {
+            if (*code as usize) < self.tokens.len() {
+                self.add_counts(*code, 0, 1);
+                let code = rewarded.add_token(&self.tokens[*code as usize]);
+                rewarded.add_counts(code, 0, 1);
+            }
+        }
+        rewarded
+    }
+
+    /// The sum of the weights of the tokens of the given class if any, see [`Self::weight`]
+    #[must_use]
+    pub fn total_weight(&self, class: Option<TokenClass>) -> usize {
+        self.weights.get(class).map_or(0, |index| index.total)
+    }
+
+    /// The code that `pick`, below [`Self::total_weight`], falls on when the tokens of the given class are lined up by weight
+    #[must_use]
+    pub fn weighted_code(&self, class: Option<TokenClass>, pick: usize) -> Option<u32> {
+        self.weights.get(class)?.find(pick)
+    }
+
+    /// Chooses a code weighted by [`Self::weight`], only of the given class if any
+    pub fn choose<R: Rand>(&self, rand: &mut R, class: Option<TokenClass>) -> Option<u32> {
+        let total = self.total_weight(class);
+        if total == 0 {
+            return None;
+        }
+        self.weighted_code(class, rand.below(total))
+    }
+
+    /// Merges another vocabulary into this one.
+    ///
+    /// Codes of known tokens stay the same, new tokens are appended, and the counts are added up.
+    pub fn merge(&mut self, other: &Self) {
+        for (other_code, token) in other.tokens.iter().enumerate() {
+            let code = self.add_token(token);
+            self.add_counts(
+                code,
+                other.frequencies[other_code],
+                other.coverage[other_code],
+            );
+        }
+    }
+
+    /// Adds the counts of another vocabulary for the tokens this one knows, and ignores the others.
+    ///
+    /// Unlike [`Self::merge`], the codes stay valid for the inputs other clients send, if all clients started from the same vocabulary.
+    pub fn merge_counts(&mut self, other: &Self) {
+        for (other_code, token) in other.tokens.iter().enumerate() {
+            if let Some(code) = self.code(token) {
+                self.add_counts(
+                    code,
+                    other.frequencies[other_code],
+                    other.coverage[other_code],
+                );
+            }
+        }
+    }
+}
+
+impl<T> InputEncoder<T> for TokenVocabulary
+where
+    T: Tokenizer,
+{
+    fn encode(&mut self, bytes: &[u8], tokenizer: &mut T) -> Result<EncodedInput, Error> {
+        let tokens = tokenizer.tokenize(bytes)?;
+        let codes = tokens
+            .iter()
+            .map(|tok| {
+                let code = self.add_token(tok);
+                self.add_counts(code, 1, 0);
+                code
+            })
+            .collect();
+        Ok(EncodedInput::new(codes))
+    }
+}
+
+impl InputDecoder for TokenVocabulary {
+    fn decode(&self, input: &EncodedInput, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        for id in input.codes() {
+            let tok = self
+                .token(*id)
+                .ok_or_else(|| Error::illegal_state(format!("Id {id} not in the vocabulary")))?;
+            bytes.extend_from_slice(tok.as_bytes());
+            bytes.push(b' ');
+        }
+        Ok(())
+    }
+}
+
+impl From<&TokenInputEncoderDecoder> for TokenVocabulary {
+    /// Keeps the codes of all tokens encoded so far
+    fn from(encoder_decoder: &TokenInputEncoderDecoder) -> Self {
+        let mut vocabulary = Self::new();
+        for id in 0..encoder_decoder.next_id {
+            if let Some(tok) = encoder_decoder.id_table.get(&id) {
+                vocabulary.add_token(tok);
+            }
+        }
+        vocabulary
+    }
+}
+
 /// A naive tokenizer struct
 #[cfg(feature = "regex")]
 #[derive(Clone, Debug)]
@@ -265,8 +635,11 @@ mod tests {
     use alloc::borrow::ToOwned;
     use core::str::from_utf8;
 
+    use libafl_bolts::rands::{Rand, StdRand};
+
     use crate::inputs::encoded::{
-        InputDecoder, InputEncoder, NaiveTokenizer, TokenInputEncoderDecoder,
+        EncodedInput, InputDecoder, InputEncoder, NaiveTokenizer, TokenClass,
+        TokenInputEncoderDecoder, TokenVocabulary, TOKEN_COVERAGE_WEIGHT,
     };
 
     #[test]
@@ -284,4 +657,145 @@ mod tests {
             "a = 'pippo baudo' ; b = c + a ".to_owned()
         );
     }
+
+    #[test]
+    #[cfg_attr(all(miri, target_arch = "aarch64", target_vendor = "apple"), ignore)] // Regex miri fails on M1
+    fn test_vocabulary() {
+        let t = NaiveTokenizer::default();
+        let mut vocabulary = TokenVocabulary::new();
+        let input = vocabulary
+            .encode(
+                b"SELECT a, b FROM t WHERE a = 'x' AND b > 42",
+                &mut t.clone(),
+            )
+            .unwrap();
+        vocabulary
+            .encode(b"SELECT a FROM t", &mut t.clone())
+            .unwrap();
+        let mut bytes = vec![];
+        vocabulary.decode(&input, &mut bytes).unwrap();
+        assert_eq!(
+            from_utf8(&bytes).unwrap(),
+            "SELECT a , b FROM t WHERE a = 'x' AND b > 42 "
+        );
+        let unknown = EncodedInput::new(vec![vocabulary.len() as u32]);
+        assert!(vocabulary.decode(&unknown, &mut bytes).is_err());
+
+        let select = vocabulary.code("SELECT").unwrap();
+        assert_eq!(vocabulary.class(select), Some(TokenClass::Identifier));
+        assert_eq!(
+            vocabulary.class(vocabulary.code("'x'").unwrap()),
+            Some(TokenClass::String)
+        );
+        assert_eq!(
+            vocabulary.class(vocabulary.code("42").unwrap()),
+            Some(TokenClass::Number)
+        );
+        let gt = vocabulary.code(">").unwrap();
+        assert_eq!(vocabulary.class(gt), Some(TokenClass::Punctuation));
+
+        // Tokens that were encoded more often, or that found coverage, weigh more
+        assert!(vocabulary.weight(select) > vocabulary.weight(gt));
+        let rewarded = vocabulary.reward(&EncodedInput::new(vec![gt]));
+        assert!(vocabulary.weight(gt) > vocabulary.weight(select));
+        assert_eq!(rewarded.len(), 1);
+        assert_eq!(rewarded.weight(0), 1 + TOKEN_COVERAGE_WEIGHT);
+
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..64 {
+            let code = vocabulary
+                .choose(&mut rand, Some(TokenClass::Number))
+                .unwrap();
+            assert_eq!(vocabulary.token(code), Some("42"));
+        }
+
+        // Merging keeps the codes of known tokens
+        let mut other = TokenVocabulary::new();
+        other.encode(b"DELETE FROM t", &mut t.clone()).unwrap();
+        other.merge(&vocabulary);
+        assert_eq!(other.code("DELETE"), Some(0));
+        vocabulary.merge(&other);
+        assert_eq!(vocabulary.code("SELECT"), Some(select));
+        assert!(vocabulary.code("DELETE").is_some());
+
+        // Merging only the counts keeps the codes of all tokens
+        let mut other = TokenVocabulary::new();
+        other.encode(b"a > b", &mut t.clone()).unwrap();
+        let weight = other.weight(1);
+        other.merge_counts(&rewarded);
+        other.merge_counts(&vocabulary);
+        assert_eq!(other.len(), 3);
+        assert_eq!(other.code(">"), Some(1));
+        assert!(other.weight(1) > weight + TOKEN_COVERAGE_WEIGHT);
+    }
+
+    /// The code `pick` falls on, lining up all tokens of `class` one by one
+    fn linear_weighted_code(
+        vocabulary: &TokenVocabulary,
+        class: Option<TokenClass>,
+        mut pick: usize,
+    ) -> Option<u32> {
+        for code in 0..vocabulary.len() as u32 {
+            if class.is_some() && vocabulary.class(code) != class {
+                continue;
+            }
+            let weight = vocabulary.weight(code);
+            if pick < weight {
+                return Some(code);
+            }
+            pick -= weight;
+        }
+        None
+    }
+
+    #[test]
+    #[cfg_attr(all(miri, target_arch = "aarch64", target_vendor = "apple"), ignore)] // Regex miri fails on M1
+    fn test_vocabulary_weights() {
+        let t = NaiveTokenizer::default();
+        let mut vocabulary = TokenVocabulary::new();
+        let mut rand = StdRand::with_seed(1337);
+        for round in 0..37_u64 {
+            let source = format!(
+                "x{} = y{} + {round} ; 'z{}'",
+                round % 5,
+                round % 11,
+                round % 3
+            );
+            let input = vocabulary
+                .encode(source.as_bytes(), &mut t.clone())
+                .unwrap();
+            if rand.below(3) == 0 {
+                let _ = vocabulary.reward(&input);
+            }
+        }
+        let mut other = TokenVocabulary::new();
+        other.encode(b"x1 = 17 ; q = r", &mut t.clone()).unwrap();
+        vocabulary.merge_counts(&other);
+        vocabulary.merge(&other);
+
+        // Serializing drops the cumulative weights, they are rebuilt when loading
+        let loaded: TokenVocabulary =
+            postcard::from_bytes(&postcard::to_allocvec(&vocabulary).unwrap()).unwrap();
+        for vocabulary in [&vocabulary, &loaded] {
+            for class in [
+                None,
+                Some(TokenClass::Identifier),
+                Some(TokenClass::Number),
+                Some(TokenClass::String),
+                Some(TokenClass::Punctuation),
+            ] {
+                let total: usize = (0..vocabulary.len() as u32)
+                    .filter(|code| class.is_none() || vocabulary.class(*code) == class)
+                    .map(|code| vocabulary.weight(code))
+                    .sum();
+                assert_eq!(vocabulary.total_weight(class), total);
+                for pick in 0..=total {
+                    assert_eq!(
+                        vocabulary.weighted_code(class, pick),
+                        linear_weighted_code(vocabulary, class, pick)
+                    );
+                }
+            }
+        }
+    }
 }
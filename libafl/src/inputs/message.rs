@@ -0,0 +1,1557 @@
+//! Structured inputs for targets that take protobuf or JSON messages, driven by a [`MessageSchema`] at runtime.
+//!
+//! A [`MessageSchema`] describes the message types of a target, much like a protobuf descriptor,
+//! and is stored in the state metadata, where the [`crate::generators::MessageGenerator`]
+//! and the mutators in [`crate::mutators::message`] find it.
+//! Schemas are built by hand, loaded from the `FileDescriptorSet` that `protoc` writes,
+//! see [`MessageSchema::from_descriptor_set`], or deserialized from a JSON or TOML description.
+//! Seeds can be decoded from protobuf or, with the `std` feature, from JSON.
+//!
+//! A [`MessageInput`] is self-describing: it keeps the names, numbers and wire types of all its fields,
+//! so that it gets serialized in [`HasTargetBytes`] without the schema, in-process as well as for forkservers.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    any::type_name,
+    fmt::Write,
+    hash::{BuildHasher, Hasher},
+};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use ahash::RandomState;
+use hashbrown::HashMap;
+use libafl_bolts::{ownedref::OwnedSlice, rands::Rand, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    inputs::{HasTargetBytes, Input},
+    HasMetadata,
+};
+
+/// The maximum nesting depth of messages decoded from bytes
+const MAX_DECODE_DEPTH: usize = 64;
+
+/// The maximum length of randomly generated strings and byte fields
+const MAX_RANDOM_LEN: usize = 16;
+
+/// How a message gets serialized to target bytes
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// The protobuf binary wire format
+    #[default]
+    Protobuf,
+    /// The protobuf JSON mapping, which also fits most plain JSON messages
+    Json,
+}
+
+/// The label of a field
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldLabel {
+    /// The field may be missing
+    Optional,
+    /// The field is always present
+    Required,
+    /// The field has any number of values
+    Repeated,
+}
+
+/// The type of a field, and how it is encoded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    /// `bool`
+    Bool,
+    /// `int32`
+    Int32,
+    /// `int64`
+    Int64,
+    /// `uint32`
+    Uint32,
+    /// `uint64`
+    Uint64,
+    /// `sint32`, zigzag encoded
+    Sint32,
+    /// `sint64`, zigzag encoded
+    Sint64,
+    /// `fixed32`
+    Fixed32,
+    /// `fixed64`
+    Fixed64,
+    /// `sfixed32`
+    Sfixed32,
+    /// `sfixed64`
+    Sfixed64,
+    /// `float`
+    Float,
+    /// `double`
+    Double,
+    /// `string`
+    String,
+    /// `bytes`
+    Bytes,
+    /// An enum, with the numbers of its variants
+    Enum(Vec<i32>),
+    /// A nested message, by its index in the [`MessageSchema`]
+    Message(usize),
+}
+
+/// The description of a single field of a message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FieldDescriptor {
+    /// The name of the field, used as key in JSON
+    pub name: String,
+    /// The field number, used as tag in protobuf
+    pub number: u32,
+    /// The type of the field
+    pub ty: FieldType,
+    /// The label of the field
+    pub label: FieldLabel,
+}
+
+impl FieldDescriptor {
+    /// Create a new [`FieldDescriptor`]
+    #[must_use]
+    pub fn new<N>(name: N, number: u32, ty: FieldType, label: FieldLabel) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            number,
+            ty,
+            label,
+        }
+    }
+}
+
+/// The description of a message type
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MessageDescriptor {
+    /// The name of the message type
+    pub name: String,
+    /// The fields of the message type
+    pub fields: Vec<FieldDescriptor>,
+}
+
+/// The message types of a target, stored in the state metadata
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "UncheckedMessageSchema")]
+pub struct MessageSchema {
+    messages: Vec<MessageDescriptor>,
+    root: usize,
+    format: MessageFormat,
+    max_depth: usize,
+}
+
+libafl_bolts::impl_serdeany!(MessageSchema);
+
+/// A [`MessageSchema`] as deserialized, before it is validated
+#[derive(Deserialize)]
+struct UncheckedMessageSchema {
+    messages: Vec<MessageDescriptor>,
+    root: usize,
+    format: MessageFormat,
+    max_depth: usize,
+}
+
+impl TryFrom<UncheckedMessageSchema> for MessageSchema {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedMessageSchema) -> Result<Self, Error> {
+        let schema = Self {
+            messages: unchecked.messages,
+            root: unchecked.root,
+            format: unchecked.format,
+            max_depth: unchecked.max_depth,
+        };
+        schema.validate()?;
+        Ok(schema)
+    }
+}
+
+/// Run `f` with the [`MessageSchema`] taken out of the state metadata, so that `f` can use the state as well,
+/// and put the schema back afterwards
+pub(crate) fn with_schema<S, T, F>(state: &mut S, f: F) -> Result<T, Error>
+where
+    S: HasMetadata,
+    F: FnOnce(&mut S, &MessageSchema) -> T,
+{
+    let schema = state.remove_metadata::<MessageSchema>().ok_or_else(|| {
+        Error::key_not_found(format!("{} not found", type_name::<MessageSchema>()))
+    })?;
+    let result = f(state, &schema);
+    state.metadata_map_mut().insert_boxed(schema);
+    Ok(result)
+}
+
+impl MessageSchema {
+    /// Create a new, empty [`MessageSchema`]. The first message added is the root message by default.
+    #[must_use]
+    pub fn new(format: MessageFormat) -> Self {
+        Self {
+            messages: Vec::new(),
+            root: 0,
+            format,
+            max_depth: 4,
+        }
+    }
+
+    /// Add a message type without fields, and return its index
+    pub fn add_message<N>(&mut self, name: N) -> usize
+    where
+        N: Into<String>,
+    {
+        self.messages.push(MessageDescriptor {
+            name: name.into(),
+            fields: Vec::new(),
+        });
+        self.messages.len() - 1
+    }
+
+    /// Add a field to the message type with the given index
+    ///
+    /// # Panics
+    /// Panics if there is no message type with this index, or if the field is a nested message
+    /// of a type that was not added yet
+    pub fn add_field(&mut self, message: usize, field: FieldDescriptor) -> &mut Self {
+        if let FieldType::Message(nested) = field.ty {
+            assert!(
+                nested < self.messages.len(),
+                "Field {} has an unknown message type {nested}",
+                field.name
+            );
+        }
+        self.messages[message].fields.push(field);
+        self
+    }
+
+    /// Set the message type the target takes
+    ///
+    /// # Panics
+    /// Panics if there is no message type with this index
+    #[must_use]
+    pub fn with_root(mut self, root: usize) -> Self {
+        assert!(
+            root < self.messages.len(),
+            "Unknown root message type {root}"
+        );
+        self.root = root;
+        self
+    }
+
+    /// Check that the root and all nested messages are message types of this schema.
+    ///
+    /// This holds for all deserialized schemas, and for built ones as soon as they have a message type.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.root >= self.messages.len() {
+            return Err(Error::illegal_argument(format!(
+                "Unknown root message type {}",
+                self.root
+            )));
+        }
+        for message in &self.messages {
+            for field in &message.fields {
+                if let FieldType::Message(nested) = field.ty {
+                    if nested >= self.messages.len() {
+                        return Err(Error::illegal_argument(format!(
+                            "Field {}.{} has an unknown message type {nested}",
+                            message.name, field.name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the message types of a protobuf `FileDescriptorSet`, as written by
+    /// `protoc --include_imports --descriptor_set_out`.
+    ///
+    /// Message types are named by their full name, such as `my.package.Request`, and `root` is the full name
+    /// of the message type the target takes. Fields are named by their JSON name. Groups are not supported.
+    pub fn from_descriptor_set(
+        bytes: &[u8],
+        root: &str,
+        format: MessageFormat,
+    ) -> Result<Self, Error> {
+        let set = descriptor_set_schema().decode(bytes)?;
+        // Collect all types first, since fields may refer to types that are declared later
+        let mut messages = Vec::new();
+        let mut enums = HashMap::new();
+        for file in descriptor_messages(set.message(), "file") {
+            let package = descriptor_string(file, "package").unwrap_or_default();
+            let prefix = if package.is_empty() {
+                String::new()
+            } else {
+                format!(".{package}")
+            };
+            collect_descriptor_types(&prefix, file, "message_type", &mut messages, &mut enums);
+        }
+
+        let mut schema = Self::new(format);
+        let indices: HashMap<&str, usize> = messages
+            .iter()
+            .map(|(name, _)| (name.as_str(), schema.add_message(&name[1..])))
+            .collect();
+        for (idx, (_, message)) in messages.iter().enumerate() {
+            for field in descriptor_messages(message, "field") {
+                let name = descriptor_string(field, "json_name")
+                    .or_else(|| descriptor_string(field, "name"))
+                    .unwrap_or_default();
+                let number = descriptor_int(field, "number")
+                    .and_then(|number| u32::try_from(number).ok())
+                    .ok_or_else(|| {
+                        Error::illegal_argument(format!("Field {name} has an invalid number"))
+                    })?;
+                let label = match descriptor_int(field, "label") {
+                    Some(2) => FieldLabel::Required,
+                    Some(3) => FieldLabel::Repeated,
+                    _ => FieldLabel::Optional,
+                };
+                let type_name = descriptor_string(field, "type_name").unwrap_or_default();
+                // The numbers of `FieldDescriptorProto.Type`
+                let ty = match descriptor_int(field, "type") {
+                    Some(1) => FieldType::Double,
+                    Some(2) => FieldType::Float,
+                    Some(3) => FieldType::Int64,
+                    Some(4) => FieldType::Uint64,
+                    Some(5) => FieldType::Int32,
+                    Some(6) => FieldType::Fixed64,
+                    Some(7) => FieldType::Fixed32,
+                    Some(8) => FieldType::Bool,
+                    Some(9) => FieldType::String,
+                    Some(10) => {
+                        return Err(Error::not_implemented(format!(
+                            "Field {name} is a group, which is not supported"
+                        )))
+                    }
+                    Some(12) => FieldType::Bytes,
+                    Some(13) => FieldType::Uint32,
+                    Some(15) => FieldType::Sfixed32,
+                    Some(16) => FieldType::Sfixed64,
+                    Some(17) => FieldType::Sint32,
+                    Some(18) => FieldType::Sint64,
+                    // Messages and enums, by their full type name
+                    _ => match (indices.get(type_name), enums.get(type_name)) {
+                        (Some(nested), _) => FieldType::Message(*nested),
+                        (None, Some(numbers)) => FieldType::Enum(numbers.clone()),
+                        (None, None) => {
+                            return Err(Error::key_not_found(format!(
+                                "Unknown type {type_name} of field {name}"
+                            )))
+                        }
+                    },
+                };
+                schema.add_field(idx, FieldDescriptor::new(name, number, ty, label));
+            }
+        }
+
+        let root = format!(".{}", root.trim_start_matches('.'));
+        let root = *indices
+            .get(root.as_str())
+            .ok_or_else(|| Error::key_not_found(format!("Unknown root message type {root}")))?;
+        Ok(schema.with_root(root))
+    }
+
+    /// Load the message types of a protobuf `FileDescriptorSet` file, see [`Self::from_descriptor_set`]
+    #[cfg(feature = "std")]
+    pub fn from_descriptor_set_file<P>(
+        path: P,
+        root: &str,
+        format: MessageFormat,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_descriptor_set(&fs::read(path)?, root, format)
+    }
+
+    /// Set the maximum nesting depth of generated messages
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The message types
+    #[must_use]
+    pub fn messages(&self) -> &[MessageDescriptor] {
+        &self.messages
+    }
+
+    /// The index of the message type the target takes
+    #[must_use]
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    /// The format messages get serialized in
+    #[must_use]
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    /// The maximum nesting depth of generated messages
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Generate a random message of the root type
+    pub fn random_input<R>(&self, rand: &mut R) -> MessageInput
+    where
+        R: Rand,
+    {
+        MessageInput::new(self.random_message(rand, self.root, 0), self.format)
+    }
+
+    /// Generate a random message of the given type, at the given nesting depth.
+    ///
+    /// Beyond the maximum depth, only required fields get generated.
+    pub fn random_message<R>(&self, rand: &mut R, message: usize, depth: usize) -> MessageValue
+    where
+        R: Rand,
+    {
+        let mut value = MessageValue {
+            message,
+            fields: Vec::new(),
+        };
+        for (idx, field) in self.messages[message].fields.iter().enumerate() {
+            let count = match field.label {
+                FieldLabel::Required => 1,
+                _ if depth >= self.max_depth => 0,
+                FieldLabel::Optional => rand.below(2),
+                FieldLabel::Repeated => rand.below(4),
+            };
+            if count > 0 {
+                let values = (0..count)
+                    .map(|_| self.random_value(rand, &field.ty, depth + 1))
+                    .collect();
+                value.fields.push(FieldValue::new(idx, field, values));
+            }
+        }
+        value
+    }
+
+    /// Generate a random value of the given type, at the given nesting depth
+    pub fn random_value<R>(&self, rand: &mut R, ty: &FieldType, depth: usize) -> Value
+    where
+        R: Rand,
+    {
+        match ty {
+            FieldType::Enum(numbers) if !numbers.is_empty() => Value::Enum(*rand.choose(numbers)),
+            FieldType::Enum(_) => Value::Enum(0),
+            FieldType::Message(message) => {
+                Value::Message(self.random_message(rand, *message, depth))
+            }
+            _ => Value::default_of(ty).randomized(rand),
+        }
+    }
+
+    /// Decode a message of the root type in the format of this schema, for example to import a seed corpus.
+    /// Unknown fields are skipped. Decoding JSON needs the `std` feature.
+    pub fn decode(&self, bytes: &[u8]) -> Result<MessageInput, Error> {
+        self.validate()?;
+        let message = match self.format {
+            MessageFormat::Protobuf => self.decode_message(self.root, bytes, 0)?,
+            #[cfg(feature = "std")]
+            MessageFormat::Json => {
+                let json: serde_json::Value = serde_json::from_slice(bytes)?;
+                self.decode_json_message(self.root, &json, 0)?
+            }
+            #[cfg(not(feature = "std"))]
+            MessageFormat::Json => {
+                return Err(Error::not_implemented(
+                    "Decoding JSON messages needs the std feature",
+                ))
+            }
+        };
+        Ok(MessageInput::new(message, self.format))
+    }
+
+    fn decode_message(
+        &self,
+        message: usize,
+        mut bytes: &[u8],
+        depth: usize,
+    ) -> Result<MessageValue, Error> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(Error::illegal_argument("Message nested too deep"));
+        }
+        let descriptor = &self.messages[message];
+        let mut value = MessageValue {
+            message,
+            fields: Vec::new(),
+        };
+        while !bytes.is_empty() {
+            let key = read_varint(&mut bytes)?;
+            let payload = match key & 7 {
+                0 => Payload::Varint(read_varint(&mut bytes)?),
+                1 => Payload::Fixed64(u64::from_le_bytes(read_array(&mut bytes)?)),
+                2 => {
+                    let len = usize::try_from(read_varint(&mut bytes)?)
+                        .map_err(|_| Error::illegal_argument("Invalid length"))?;
+                    Payload::Delimited(read_slice(&mut bytes, len)?)
+                }
+                5 => Payload::Fixed32(u32::from_le_bytes(read_array(&mut bytes)?)),
+                wire => {
+                    return Err(Error::illegal_argument(format!(
+                        "Unsupported wire type {wire}"
+                    )))
+                }
+            };
+            let number = key >> 3;
+            let Some((idx, field)) = descriptor
+                .fields
+                .iter()
+                .enumerate()
+                .find(|(_, field)| u64::from(field.number) == number)
+            else {
+                continue;
+            };
+
+            let mut values = Vec::new();
+            match payload {
+                // packed repeated scalars
+                Payload::Delimited(mut packed) if Value::default_of(&field.ty).is_packable() => {
+                    while !packed.is_empty() {
+                        let payload = match Value::default_of(&field.ty).wire_type() {
+                            0 => Payload::Varint(read_varint(&mut packed)?),
+                            1 => Payload::Fixed64(u64::from_le_bytes(read_array(&mut packed)?)),
+                            _ => Payload::Fixed32(u32::from_le_bytes(read_array(&mut packed)?)),
+                        };
+                        values.push(self.decode_value(&field.ty, payload, depth)?);
+                    }
+                }
+                payload => values.push(self.decode_value(&field.ty, payload, depth)?),
+            }
+
+            match value.fields.iter_mut().find(|present| present.field == idx) {
+                Some(present) if field.label == FieldLabel::Repeated => {
+                    present.values.append(&mut values);
+                }
+                Some(present) => present.values = values,
+                None => value.fields.push(FieldValue::new(idx, field, values)),
+            }
+        }
+        Ok(value)
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn decode_value(&self, ty: &FieldType, payload: Payload, depth: usize) -> Result<Value, Error> {
+        Ok(match (ty, payload) {
+            (FieldType::Bool, Payload::Varint(v)) => Value::Bool(v != 0),
+            (FieldType::Int32, Payload::Varint(v)) => Value::Int32(v as i32),
+            (FieldType::Int64, Payload::Varint(v)) => Value::Int64(v as i64),
+            (FieldType::Uint32, Payload::Varint(v)) => Value::Uint32(v as u32),
+            (FieldType::Uint64, Payload::Varint(v)) => Value::Uint64(v),
+            (FieldType::Sint32, Payload::Varint(v)) => Value::Sint32(zigzag_decode(v) as i32),
+            (FieldType::Sint64, Payload::Varint(v)) => Value::Sint64(zigzag_decode(v)),
+            (FieldType::Enum(_), Payload::Varint(v)) => Value::Enum(v as i32),
+            (FieldType::Fixed32, Payload::Fixed32(v)) => Value::Fixed32(v),
+            (FieldType::Sfixed32, Payload::Fixed32(v)) => Value::Sfixed32(v as i32),
+            (FieldType::Float, Payload::Fixed32(v)) => Value::Float(f32::from_bits(v)),
+            (FieldType::Fixed64, Payload::Fixed64(v)) => Value::Fixed64(v),
+            (FieldType::Sfixed64, Payload::Fixed64(v)) => Value::Sfixed64(v as i64),
+            (FieldType::Double, Payload::Fixed64(v)) => Value::Double(f64::from_bits(v)),
+            (FieldType::String, Payload::Delimited(v)) => Value::String(
+                core::str::from_utf8(v)
+                    .map_err(|_| Error::illegal_argument("Invalid UTF-8 in string field"))?
+                    .to_string(),
+            ),
+            (FieldType::Bytes, Payload::Delimited(v)) => Value::Bytes(v.to_vec()),
+            (FieldType::Message(message), Payload::Delimited(v)) => {
+                Value::Message(self.decode_message(*message, v, depth + 1)?)
+            }
+            (ty, _) => {
+                return Err(Error::illegal_argument(format!(
+                    "Wire type does not match field type {ty:?}"
+                )))
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl MessageSchema {
+    fn decode_json_message(
+        &self,
+        message: usize,
+        json: &serde_json::Value,
+        depth: usize,
+    ) -> Result<MessageValue, Error> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(Error::illegal_argument("Message nested too deep"));
+        }
+        let serde_json::Value::Object(object) = json else {
+            return Err(Error::illegal_argument(format!(
+                "Expected a JSON object, got {json}"
+            )));
+        };
+        let mut value = MessageValue {
+            message,
+            fields: Vec::new(),
+        };
+        for (idx, field) in self.messages[message].fields.iter().enumerate() {
+            let values = match object.get(&field.name) {
+                None | Some(serde_json::Value::Null) => continue,
+                Some(serde_json::Value::Array(values)) if field.label == FieldLabel::Repeated => {
+                    values
+                        .iter()
+                        .map(|json| self.decode_json_value(&field.ty, json, depth))
+                        .collect::<Result<_, _>>()?
+                }
+                Some(json) => vec![self.decode_json_value(&field.ty, json, depth)?],
+            };
+            value.fields.push(FieldValue::new(idx, field, values));
+        }
+        Ok(value)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn decode_json_value(
+        &self,
+        ty: &FieldType,
+        json: &serde_json::Value,
+        depth: usize,
+    ) -> Result<Value, Error> {
+        let invalid =
+            || Error::illegal_argument(format!("Invalid value {json} for field type {ty:?}"));
+        Ok(match ty {
+            FieldType::Bool => Value::Bool(json.as_bool().ok_or_else(invalid)?),
+            FieldType::Int32 => Value::Int32(json_int(json).ok_or_else(invalid)?),
+            FieldType::Int64 => Value::Int64(json_int(json).ok_or_else(invalid)?),
+            FieldType::Uint32 => Value::Uint32(json_int(json).ok_or_else(invalid)?),
+            FieldType::Uint64 => Value::Uint64(json_int(json).ok_or_else(invalid)?),
+            FieldType::Sint32 => Value::Sint32(json_int(json).ok_or_else(invalid)?),
+            FieldType::Sint64 => Value::Sint64(json_int(json).ok_or_else(invalid)?),
+            FieldType::Fixed32 => Value::Fixed32(json_int(json).ok_or_else(invalid)?),
+            FieldType::Fixed64 => Value::Fixed64(json_int(json).ok_or_else(invalid)?),
+            FieldType::Sfixed32 => Value::Sfixed32(json_int(json).ok_or_else(invalid)?),
+            FieldType::Sfixed64 => Value::Sfixed64(json_int(json).ok_or_else(invalid)?),
+            FieldType::Float => Value::Float(json_float(json).ok_or_else(invalid)? as f32),
+            FieldType::Double => Value::Double(json_float(json).ok_or_else(invalid)?),
+            FieldType::String => Value::String(json.as_str().ok_or_else(invalid)?.to_string()),
+            FieldType::Bytes => {
+                Value::Bytes(json.as_str().and_then(decode_base64).ok_or_else(invalid)?)
+            }
+            FieldType::Enum(_) => Value::Enum(json_int(json).ok_or_else(invalid)?),
+            FieldType::Message(message) => {
+                Value::Message(self.decode_json_message(*message, json, depth + 1)?)
+            }
+        })
+    }
+}
+
+/// An integer from a JSON number, or from a string, as 64 bit integers are in the protobuf JSON mapping
+#[cfg(feature = "std")]
+fn json_int<T>(json: &serde_json::Value) -> Option<T>
+where
+    T: TryFrom<i64> + TryFrom<u64> + core::str::FromStr,
+{
+    match json {
+        serde_json::Value::Number(number) => number
+            .as_i64()
+            .and_then(|number| T::try_from(number).ok())
+            .or_else(|| number.as_u64().and_then(|number| T::try_from(number).ok())),
+        serde_json::Value::String(number) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+/// A float from a JSON number, or from one of the strings the protobuf JSON mapping uses for special values
+#[cfg(feature = "std")]
+fn json_float(json: &serde_json::Value) -> Option<f64> {
+    match json {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(number) => match number.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            number => number.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+/// The parts of protobuf's `descriptor.proto` needed to load a `FileDescriptorSet`
+fn descriptor_set_schema() -> MessageSchema {
+    let mut schema = MessageSchema::new(MessageFormat::Protobuf);
+    let set = schema.add_message("FileDescriptorSet");
+    let file = schema.add_message("FileDescriptorProto");
+    let message = schema.add_message("DescriptorProto");
+    let field = schema.add_message("FieldDescriptorProto");
+    let enumeration = schema.add_message("EnumDescriptorProto");
+    let enum_value = schema.add_message("EnumValueDescriptorProto");
+    let optional = |name: &str, number: u32, ty: FieldType| {
+        FieldDescriptor::new(name, number, ty, FieldLabel::Optional)
+    };
+    let repeated = |name: &str, number: u32, ty: FieldType| {
+        FieldDescriptor::new(name, number, ty, FieldLabel::Repeated)
+    };
+    schema
+        .add_field(set, repeated("file", 1, FieldType::Message(file)))
+        .add_field(file, optional("package", 2, FieldType::String))
+        .add_field(
+            file,
+            repeated("message_type", 4, FieldType::Message(message)),
+        )
+        .add_field(
+            file,
+            repeated("enum_type", 5, FieldType::Message(enumeration)),
+        )
+        .add_field(message, optional("name", 1, FieldType::String))
+        .add_field(message, repeated("field", 2, FieldType::Message(field)))
+        .add_field(
+            message,
+            repeated("nested_type", 3, FieldType::Message(message)),
+        )
+        .add_field(
+            message,
+            repeated("enum_type", 4, FieldType::Message(enumeration)),
+        )
+        .add_field(field, optional("name", 1, FieldType::String))
+        .add_field(field, optional("number", 3, FieldType::Int32))
+        .add_field(field, optional("label", 4, FieldType::Enum(Vec::new())))
+        .add_field(field, optional("type", 5, FieldType::Enum(Vec::new())))
+        .add_field(field, optional("type_name", 6, FieldType::String))
+        .add_field(field, optional("json_name", 10, FieldType::String))
+        .add_field(enumeration, optional("name", 1, FieldType::String))
+        .add_field(
+            enumeration,
+            repeated("value", 2, FieldType::Message(enum_value)),
+        )
+        .add_field(enum_value, optional("number", 2, FieldType::Int32));
+    schema
+}
+
+/// The nested messages of a field of a decoded descriptor
+fn descriptor_messages<'a>(
+    descriptor: &'a MessageValue,
+    name: &'a str,
+) -> impl Iterator<Item = &'a MessageValue> {
+    descriptor
+        .fields
+        .iter()
+        .filter(move |field| field.name == name)
+        .flat_map(|field| &field.values)
+        .filter_map(|value| match value {
+            Value::Message(message) => Some(message),
+            _ => None,
+        })
+}
+
+/// A string field of a decoded descriptor
+fn descriptor_string<'a>(descriptor: &'a MessageValue, name: &str) -> Option<&'a str> {
+    descriptor
+        .fields
+        .iter()
+        .find(|field| field.name == name)
+        .and_then(|field| match field.values.first() {
+            Some(Value::String(value)) => Some(value.as_str()),
+            _ => None,
+        })
+}
+
+/// An integer or enum field of a decoded descriptor
+fn descriptor_int(descriptor: &MessageValue, name: &str) -> Option<i32> {
+    descriptor
+        .fields
+        .iter()
+        .find(|field| field.name == name)
+        .and_then(|field| match field.values.first() {
+            Some(Value::Int32(value) | Value::Enum(value)) => Some(*value),
+            _ => None,
+        })
+}
+
+/// Collect the enum types and the message types in the field `messages_field` of the decoded descriptor `scope`,
+/// and all types nested in them, with their full names
+fn collect_descriptor_types<'a>(
+    prefix: &str,
+    scope: &'a MessageValue,
+    messages_field: &'a str,
+    messages: &mut Vec<(String, &'a MessageValue)>,
+    enums: &mut HashMap<String, Vec<i32>>,
+) {
+    for enumeration in descriptor_messages(scope, "enum_type") {
+        let name = descriptor_string(enumeration, "name").unwrap_or_default();
+        let numbers = descriptor_messages(enumeration, "value")
+            .map(|value| descriptor_int(value, "number").unwrap_or_default())
+            .collect();
+        enums.insert(format!("{prefix}.{name}"), numbers);
+    }
+    for message in descriptor_messages(scope, messages_field) {
+        let name = format!(
+            "{prefix}.{}",
+            descriptor_string(message, "name").unwrap_or_default()
+        );
+        messages.push((name.clone(), message));
+        collect_descriptor_types(&name, message, "nested_type", messages, enums);
+    }
+}
+
+/// A raw value read from the protobuf wire format
+enum Payload<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Delimited(&'a [u8]),
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| Error::illegal_argument("Truncated varint"))?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::illegal_argument("Varint too long"))
+}
+
+fn read_slice<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if bytes.len() < len {
+        return Err(Error::illegal_argument("Truncated message"));
+    }
+    let (slice, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(slice)
+}
+
+fn read_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], Error> {
+    Ok(read_slice(bytes, N)?.try_into().unwrap())
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        #[allow(clippy::cast_possible_truncation)]
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    bytes.push(value as u8);
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// A single value of a field
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `bool`
+    Bool(bool),
+    /// `int32`
+    Int32(i32),
+    /// `int64`
+    Int64(i64),
+    /// `uint32`
+    Uint32(u32),
+    /// `uint64`
+    Uint64(u64),
+    /// `sint32`
+    Sint32(i32),
+    /// `sint64`
+    Sint64(i64),
+    /// `fixed32`
+    Fixed32(u32),
+    /// `fixed64`
+    Fixed64(u64),
+    /// `sfixed32`
+    Sfixed32(i32),
+    /// `sfixed64`
+    Sfixed64(i64),
+    /// `float`
+    Float(f32),
+    /// `double`
+    Double(f64),
+    /// `string`
+    String(String),
+    /// `bytes`
+    Bytes(Vec<u8>),
+    /// The number of an enum variant
+    Enum(i32),
+    /// A nested message
+    Message(MessageValue),
+}
+
+impl Value {
+    /// The default value of a field type
+    #[must_use]
+    pub fn default_of(ty: &FieldType) -> Self {
+        match ty {
+            FieldType::Bool => Self::Bool(false),
+            FieldType::Int32 => Self::Int32(0),
+            FieldType::Int64 => Self::Int64(0),
+            FieldType::Uint32 => Self::Uint32(0),
+            FieldType::Uint64 => Self::Uint64(0),
+            FieldType::Sint32 => Self::Sint32(0),
+            FieldType::Sint64 => Self::Sint64(0),
+            FieldType::Fixed32 => Self::Fixed32(0),
+            FieldType::Fixed64 => Self::Fixed64(0),
+            FieldType::Sfixed32 => Self::Sfixed32(0),
+            FieldType::Sfixed64 => Self::Sfixed64(0),
+            FieldType::Float => Self::Float(0.0),
+            FieldType::Double => Self::Double(0.0),
+            FieldType::String => Self::String(String::new()),
+            FieldType::Bytes => Self::Bytes(Vec::new()),
+            FieldType::Enum(numbers) => Self::Enum(numbers.first().copied().unwrap_or_default()),
+            FieldType::Message(message) => Self::Message(MessageValue {
+                message: *message,
+                fields: Vec::new(),
+            }),
+        }
+    }
+
+    /// A random value of the same kind. Enums and messages, which need the schema, stay unchanged.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn randomized<R>(&self, rand: &mut R) -> Self
+    where
+        R: Rand,
+    {
+        let raw = rand.next();
+        match self {
+            Self::Bool(_) => Self::Bool(raw & 1 == 1),
+            Self::Int32(_) => Self::Int32(raw as i32),
+            Self::Int64(_) => Self::Int64(raw as i64),
+            Self::Uint32(_) => Self::Uint32(raw as u32),
+            Self::Uint64(_) => Self::Uint64(raw),
+            Self::Sint32(_) => Self::Sint32(raw as i32),
+            Self::Sint64(_) => Self::Sint64(raw as i64),
+            Self::Fixed32(_) => Self::Fixed32(raw as u32),
+            Self::Fixed64(_) => Self::Fixed64(raw),
+            Self::Sfixed32(_) => Self::Sfixed32(raw as i32),
+            Self::Sfixed64(_) => Self::Sfixed64(raw as i64),
+            Self::Float(_) => Self::Float(f32::from_bits(raw as u32)),
+            Self::Double(_) => Self::Double(f64::from_bits(raw)),
+            Self::String(_) => Self::String(
+                (0..rand.below(MAX_RANDOM_LEN + 1))
+                    .map(|_| char::from(b' ' + rand.below(95) as u8))
+                    .collect(),
+            ),
+            Self::Bytes(_) => Self::Bytes(
+                (0..rand.below(MAX_RANDOM_LEN + 1))
+                    .map(|_| rand.next() as u8)
+                    .collect(),
+            ),
+            Self::Enum(_) | Self::Message(_) => self.clone(),
+        }
+    }
+
+    /// If this is an integer value
+    #[must_use]
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Self::Int32(_)
+                | Self::Int64(_)
+                | Self::Uint32(_)
+                | Self::Uint64(_)
+                | Self::Sint32(_)
+                | Self::Sint64(_)
+                | Self::Fixed32(_)
+                | Self::Fixed64(_)
+                | Self::Sfixed32(_)
+                | Self::Sfixed64(_)
+        )
+    }
+
+    /// If repeated values of this kind may be packed into a single length-delimited record
+    fn is_packable(&self) -> bool {
+        !matches!(self, Self::String(_) | Self::Bytes(_) | Self::Message(_))
+    }
+
+    /// The protobuf wire type of this value
+    fn wire_type(&self) -> u64 {
+        match self {
+            Self::Fixed64(_) | Self::Sfixed64(_) | Self::Double(_) => 1,
+            Self::String(_) | Self::Bytes(_) | Self::Message(_) => 2,
+            Self::Fixed32(_) | Self::Sfixed32(_) | Self::Float(_) => 5,
+            _ => 0,
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn encode_protobuf(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Self::Bool(v) => write_varint(bytes, u64::from(*v)),
+            Self::Int32(v) | Self::Enum(v) => write_varint(bytes, i64::from(*v) as u64),
+            Self::Int64(v) => write_varint(bytes, *v as u64),
+            Self::Uint32(v) => write_varint(bytes, u64::from(*v)),
+            Self::Uint64(v) => write_varint(bytes, *v),
+            Self::Sint32(v) => write_varint(bytes, zigzag_encode(i64::from(*v))),
+            Self::Sint64(v) => write_varint(bytes, zigzag_encode(*v)),
+            Self::Fixed32(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            Self::Fixed64(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            Self::Sfixed32(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            Self::Sfixed64(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            Self::Float(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            Self::Double(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            Self::String(v) => {
+                write_varint(bytes, v.len() as u64);
+                bytes.extend_from_slice(v.as_bytes());
+            }
+            Self::Bytes(v) => {
+                write_varint(bytes, v.len() as u64);
+                bytes.extend_from_slice(v);
+            }
+            Self::Message(v) => {
+                let mut nested = Vec::new();
+                v.encode_protobuf(&mut nested);
+                write_varint(bytes, nested.len() as u64);
+                bytes.append(&mut nested);
+            }
+        }
+    }
+
+    fn encode_json(&self, json: &mut String) {
+        // Writing to a `String` never fails
+        let _ = match self {
+            Self::Bool(v) => write!(json, "{v}"),
+            Self::Int32(v) | Self::Sint32(v) | Self::Sfixed32(v) | Self::Enum(v) => {
+                write!(json, "{v}")
+            }
+            Self::Uint32(v) | Self::Fixed32(v) => write!(json, "{v}"),
+            // 64 bit integers are strings in the protobuf JSON mapping
+            Self::Int64(v) | Self::Sint64(v) | Self::Sfixed64(v) => write!(json, "\"{v}\""),
+            Self::Uint64(v) | Self::Fixed64(v) => write!(json, "\"{v}\""),
+            Self::Float(v) => encode_json_float(json, f64::from(*v)),
+            Self::Double(v) => encode_json_float(json, *v),
+            Self::String(v) => {
+                encode_json_string(json, v);
+                Ok(())
+            }
+            Self::Bytes(v) => {
+                json.push('"');
+                encode_base64(json, v);
+                json.push('"');
+                Ok(())
+            }
+            Self::Message(v) => {
+                v.encode_json(json);
+                Ok(())
+            }
+        };
+    }
+}
+
+fn encode_json_float(json: &mut String, value: f64) -> core::fmt::Result {
+    if value.is_nan() {
+        write!(json, "\"NaN\"")
+    } else if value.is_infinite() {
+        let sign = if value < 0.0 { "-" } else { "" };
+        write!(json, "\"{sign}Infinity\"")
+    } else {
+        write!(json, "{value}")
+    }
+}
+
+fn encode_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn encode_base64(json: &mut String, bytes: &[u8]) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in bytes.chunks(3) {
+        let block = chunk.iter().enumerate().fold(0_u32, |block, (idx, byte)| {
+            block | u32::from(*byte) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                json.push(char::from(
+                    ALPHABET[(block >> (18 - 6 * idx)) as usize & 0x3f],
+                ));
+            } else {
+                json.push('=');
+            }
+        }
+    }
+}
+
+/// Decode standard or URL-safe base64, with or without padding
+#[cfg(feature = "std")]
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    let mut block = 0_u32;
+    let mut bits = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        block = (block << 6) | u32::from(sextet);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            #[allow(clippy::cast_possible_truncation)]
+            bytes.push((block >> bits) as u8);
+            block &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// The values of a field present in a message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldValue {
+    /// The index of the field in its [`MessageDescriptor`]
+    pub field: usize,
+    /// The name of the field
+    pub name: String,
+    /// The field number
+    pub number: u32,
+    /// If the field is repeated
+    pub repeated: bool,
+    /// The values, exactly one for fields that are not repeated
+    pub values: Vec<Value>,
+}
+
+impl FieldValue {
+    /// Create a new [`FieldValue`] for the field with the given index and descriptor
+    #[must_use]
+    pub fn new(field: usize, descriptor: &FieldDescriptor, values: Vec<Value>) -> Self {
+        Self {
+            field,
+            name: descriptor.name.clone(),
+            number: descriptor.number,
+            repeated: descriptor.label == FieldLabel::Repeated,
+            values,
+        }
+    }
+}
+
+/// A message, with all the fields present in it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MessageValue {
+    /// The index of the message type in the [`MessageSchema`]
+    pub message: usize,
+    /// The fields present in this message
+    pub fields: Vec<FieldValue>,
+}
+
+impl MessageValue {
+    /// The number of values in this message and all nested messages, for which `pred` holds
+    pub fn count_values<P>(&self, pred: &P) -> usize
+    where
+        P: Fn(&Value) -> bool,
+    {
+        self.fields
+            .iter()
+            .flat_map(|field| &field.values)
+            .map(|value| {
+                let nested = match value {
+                    Value::Message(message) => message.count_values(pred),
+                    _ => 0,
+                };
+                usize::from(pred(value)) + nested
+            })
+            .sum()
+    }
+
+    /// The `n`th value in this message and all nested messages, in pre-order, for which `pred` holds
+    pub fn nth_value_mut<P>(&mut self, n: &mut usize, pred: &P) -> Option<&mut Value>
+    where
+        P: Fn(&Value) -> bool,
+    {
+        for value in self.fields.iter_mut().flat_map(|field| &mut field.values) {
+            if pred(value) {
+                if *n == 0 {
+                    return Some(value);
+                }
+                *n -= 1;
+            }
+            if let Value::Message(message) = value {
+                if let Some(found) = message.nth_value_mut(n, pred) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    /// The number of messages, including this one, for which `pred` holds
+    pub fn count_messages<P>(&self, pred: &P) -> usize
+    where
+        P: Fn(&MessageValue) -> bool,
+    {
+        usize::from(pred(self))
+            + self.count_values(&|value| matches!(value, Value::Message(message) if pred(message)))
+    }
+
+    /// The `n`th message, including this one, in pre-order, for which `pred` holds
+    pub fn nth_message_mut<P>(&mut self, n: usize, pred: &P) -> Option<&mut MessageValue>
+    where
+        P: Fn(&MessageValue) -> bool,
+    {
+        if pred(self) {
+            if n == 0 {
+                return Some(self);
+            }
+            let mut n = n - 1;
+            self.nth_nested_message_mut(&mut n, pred)
+        } else {
+            let mut n = n;
+            self.nth_nested_message_mut(&mut n, pred)
+        }
+    }
+
+    fn nth_nested_message_mut<P>(&mut self, n: &mut usize, pred: &P) -> Option<&mut MessageValue>
+    where
+        P: Fn(&MessageValue) -> bool,
+    {
+        match self.nth_value_mut(
+            n,
+            &|value| matches!(value, Value::Message(message) if pred(message)),
+        ) {
+            Some(Value::Message(message)) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// The values of the field with the given index, if present
+    #[must_use]
+    pub fn field(&self, field: usize) -> Option<&FieldValue> {
+        self.fields.iter().find(|present| present.field == field)
+    }
+
+    fn encode_protobuf(&self, bytes: &mut Vec<u8>) {
+        for field in &self.fields {
+            for value in &field.values {
+                write_varint(bytes, u64::from(field.number) << 3 | value.wire_type());
+                value.encode_protobuf(bytes);
+            }
+        }
+    }
+
+    fn encode_json(&self, json: &mut String) {
+        json.push('{');
+        for (idx, field) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+            encode_json_string(json, &field.name);
+            json.push(':');
+            if field.repeated {
+                json.push('[');
+                for (idx, value) in field.values.iter().enumerate() {
+                    if idx > 0 {
+                        json.push(',');
+                    }
+                    value.encode_json(json);
+                }
+                json.push(']');
+            } else if let Some(value) = field.values.first() {
+                value.encode_json(json);
+            } else {
+                json.push_str("null");
+            }
+        }
+        json.push('}');
+    }
+}
+
+/// A structured message input, serialized to protobuf or JSON when it is sent to the target
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MessageInput {
+    message: MessageValue,
+    format: MessageFormat,
+}
+
+impl Input for MessageInput {
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        hasher.write(&self.encode());
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl HasTargetBytes for MessageInput {
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(self.encode())
+    }
+}
+
+impl MessageInput {
+    /// Create a new [`MessageInput`]
+    #[must_use]
+    pub fn new(message: MessageValue, format: MessageFormat) -> Self {
+        Self { message, format }
+    }
+
+    /// The root message
+    #[must_use]
+    pub fn message(&self) -> &MessageValue {
+        &self.message
+    }
+
+    /// The root message, mutable
+    pub fn message_mut(&mut self) -> &mut MessageValue {
+        &mut self.message
+    }
+
+    /// The format this input gets serialized in
+    #[must_use]
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    /// Serialize this input, as the target sees it
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        match self.format {
+            MessageFormat::Protobuf => {
+                let mut bytes = Vec::new();
+                self.message.encode_protobuf(&mut bytes);
+                bytes
+            }
+            MessageFormat::Json => {
+                let mut json = String::new();
+                self.message.encode_json(&mut json);
+                json.into_bytes()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        write_varint, FieldDescriptor, FieldLabel, FieldType, FieldValue, MessageFormat,
+        MessageInput, MessageSchema, MessageValue, Value,
+    };
+
+    /// `message Inner { repeated sint32 values = 1; }`
+    /// `message Outer { required string name = 1; optional Inner inner = 2; optional bytes data = 3; }`
+    fn schema(format: MessageFormat) -> MessageSchema {
+        let mut schema = MessageSchema::new(format);
+        let outer = schema.add_message("Outer");
+        let inner = schema.add_message("Inner");
+        schema
+            .add_field(
+                outer,
+                FieldDescriptor::new("name", 1, FieldType::String, FieldLabel::Required),
+            )
+            .add_field(
+                outer,
+                FieldDescriptor::new("inner", 2, FieldType::Message(inner), FieldLabel::Optional),
+            )
+            .add_field(
+                outer,
+                FieldDescriptor::new("data", 3, FieldType::Bytes, FieldLabel::Optional),
+            )
+            .add_field(
+                inner,
+                FieldDescriptor::new("values", 1, FieldType::Sint32, FieldLabel::Repeated),
+            );
+        schema
+    }
+
+    #[test]
+    fn test_message_protobuf_roundtrip() {
+        let schema = schema(MessageFormat::Protobuf);
+        // name: "hi", inner: { values: [-1, 2] (packed) }, unknown field 9: 1
+        let bytes = [
+            0x0a, 0x02, b'h', b'i', 0x12, 0x04, 0x0a, 0x02, 0x01, 0x04, 0x48, 0x01,
+        ];
+        let input = schema.decode(&bytes).unwrap();
+        let inner = input.message().field(1).unwrap();
+        let Value::Message(inner) = &inner.values[0] else {
+            panic!("inner is a message");
+        };
+        assert_eq!(
+            inner.field(0).unwrap().values,
+            [Value::Sint32(-1), Value::Sint32(2)]
+        );
+        // Repeated values are written unpacked, unknown fields are dropped
+        assert_eq!(
+            input.encode(),
+            [0x0a, 0x02, b'h', b'i', 0x12, 0x04, 0x08, 0x01, 0x08, 0x04]
+        );
+
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..32 {
+            let random = schema.random_input(&mut rand);
+            assert_eq!(schema.decode(&random.encode()).unwrap(), random);
+        }
+    }
+
+    #[test]
+    fn test_message_json() {
+        let schema = schema(MessageFormat::Json);
+        let outer = &schema.messages()[schema.root()];
+        let message = MessageValue {
+            message: schema.root(),
+            fields: vec![
+                FieldValue::new(0, &outer.fields[0], vec![Value::String("a\"b".into())]),
+                FieldValue::new(2, &outer.fields[2], vec![Value::Bytes(b"hello".to_vec())]),
+            ],
+        };
+        let input = MessageInput::new(message, MessageFormat::Json);
+        assert_eq!(input.encode(), br#"{"name":"a\"b","data":"aGVsbG8="}"#);
+        assert_eq!(schema.decode(&input.encode()).unwrap(), input);
+
+        let decoded = schema
+            .decode(br#"{"name":"x","inner":{"values":[1,"-2"]},"data":"_-8","unknown":1}"#)
+            .unwrap();
+        assert_eq!(
+            decoded.encode(),
+            br#"{"name":"x","inner":{"values":[1,-2]},"data":"/+8="}"#
+        );
+        assert!(schema.decode(br#"{"name":1}"#).is_err());
+
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..32 {
+            let random = schema.random_input(&mut rand);
+            assert_eq!(schema.decode(&random.encode()).unwrap(), random);
+        }
+    }
+
+    #[test]
+    fn test_message_schema_validation() {
+        let schema = schema(MessageFormat::Protobuf);
+        let json = serde_json::to_string(&schema).unwrap();
+        assert_eq!(
+            serde_json::from_str::<MessageSchema>(&json).unwrap(),
+            schema
+        );
+
+        let invalid = json.replace(r#""root":0"#, r#""root":7"#);
+        assert!(serde_json::from_str::<MessageSchema>(&invalid).is_err());
+        let invalid = json.replace(r#"{"Message":1}"#, r#"{"Message":7}"#);
+        assert!(serde_json::from_str::<MessageSchema>(&invalid).is_err());
+
+        assert!(MessageSchema::new(MessageFormat::Protobuf)
+            .validate()
+            .is_err());
+    }
+
+    /// A length-delimited protobuf field
+    fn delimited(number: u64, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, number << 3 | 2);
+        write_varint(&mut bytes, payload.len() as u64);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// A varint protobuf field
+    fn varint(number: u64, value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, number << 3);
+        write_varint(&mut bytes, value);
+        bytes
+    }
+
+    /// A `FieldDescriptorProto`
+    fn field(name: &str, number: u64, label: u64, ty: u64, type_name: &str) -> Vec<u8> {
+        let mut field = delimited(1, name.as_bytes());
+        field.extend(varint(3, number));
+        field.extend(varint(4, label));
+        field.extend(varint(5, ty));
+        if !type_name.is_empty() {
+            field.extend(delimited(6, type_name.as_bytes()));
+        }
+        field.extend(delimited(10, name.as_bytes()));
+        delimited(2, &field)
+    }
+
+    #[test]
+    fn test_message_schema_from_descriptor_set() {
+        // package test;
+        // message Request {
+        //   message Header { optional sint32 length = 1; }
+        //   enum Kind { A = 0; B = 5; }
+        //   required Header header = 1;
+        //   repeated uint64 ids = 2;
+        //   optional Kind kind = 3;
+        // }
+        let mut header = delimited(1, b"Header");
+        header.extend(field("length", 1, 1, 17, ""));
+        let mut kind = delimited(1, b"Kind");
+        kind.extend(delimited(2, &delimited(1, b"A")));
+        let mut b = delimited(1, b"B");
+        b.extend(varint(2, 5));
+        kind.extend(delimited(2, &b));
+
+        let mut request = delimited(1, b"Request");
+        request.extend(field("header", 1, 2, 11, ".test.Request.Header"));
+        request.extend(field("ids", 2, 3, 4, ""));
+        request.extend(field("kind", 3, 1, 14, ".test.Request.Kind"));
+        request.extend(delimited(3, &header));
+        request.extend(delimited(4, &kind));
+
+        let mut file = delimited(1, b"test.proto");
+        file.extend(delimited(2, b"test"));
+        file.extend(delimited(4, &request));
+        let set = delimited(1, &file);
+
+        let schema =
+            MessageSchema::from_descriptor_set(&set, "test.Request", MessageFormat::Protobuf)
+                .unwrap();
+        let names: Vec<&str> = schema
+            .messages()
+            .iter()
+            .map(|message| message.name.as_str())
+            .collect();
+        assert_eq!(names, ["test.Request", "test.Request.Header"]);
+        assert_eq!(schema.root(), 0);
+        let request = &schema.messages()[0];
+        assert_eq!(
+            request.fields[0],
+            FieldDescriptor::new("header", 1, FieldType::Message(1), FieldLabel::Required)
+        );
+        assert_eq!(request.fields[1].ty, FieldType::Uint64);
+        assert_eq!(request.fields[1].label, FieldLabel::Repeated);
+        assert_eq!(request.fields[2].ty, FieldType::Enum(vec![0, 5]));
+        assert_eq!(schema.messages()[1].fields[0].ty, FieldType::Sint32);
+
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..32 {
+            let random = schema.random_input(&mut rand);
+            assert_eq!(schema.decode(&random.encode()).unwrap(), random);
+        }
+
+        assert!(
+            MessageSchema::from_descriptor_set(&set, "test.Missing", MessageFormat::Protobuf)
+                .is_err()
+        );
+    }
+}
@@ -3,12 +3,28 @@
 pub mod bytes;
 pub use bytes::BytesInput;
 
+pub mod composite;
+#[cfg(feature = "nautilus")]
+pub use composite::register_nautilus_context;
+pub use composite::{CompositeInput, CompositeInputPart};
+
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(feature = "arbitrary")]
+pub use self::arbitrary::ArbitraryInput;
+
+pub mod earley;
+pub use earley::{EarleyGrammar, EarleySymbol};
+
 pub mod encoded;
 pub use encoded::*;
 
 pub mod gramatron;
 pub use gramatron::*;
 
+pub mod message;
+pub use message::{MessageFormat, MessageInput, MessageSchema};
+
 pub mod generalized;
 pub use generalized::*;
 
@@ -2,7 +2,8 @@
 //!
 //! Unfortunately, since both [`serde::de::Deserialize`] and [`Clone`] require [`Sized`], it is not
 //! possible to dynamically define a single input with dynamic typing. As such, [`MultipartInput`]
-//! requires that each subcomponent be the same subtype.
+//! requires that each subcomponent be the same subtype. For a fixed set of parts with different
+//! types, see [`crate::inputs::composite`].
 
 use alloc::{
     string::{String, ToString},
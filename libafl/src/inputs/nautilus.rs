@@ -5,20 +5,25 @@
 //use core::hash::Hasher;
 
 use alloc::{rc::Rc, string::String, vec::Vec};
-use core::cell::RefCell;
-use std::hash::{Hash, Hasher};
+use core::{cell::RefCell, fmt::Debug};
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+};
 
 use grammartec::{
-    newtypes::NodeID,
-    rule::RuleIDOrCustom,
+    newtypes::{NTermID, NodeID, RuleID},
+    rule::{Rule, RuleChild, RuleIDOrCustom},
     tree::{Tree, TreeLike},
 };
+use hashbrown::HashMap;
 use libafl_bolts::HasLen;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     generators::nautilus::NautilusContext,
-    inputs::{BytesInput, Input, InputConverter},
+    inputs::{BytesInput, EarleyGrammar, EarleySymbol, HasBytesVec, Input, InputConverter},
     Error,
 };
 
@@ -134,3 +139,194 @@ impl<'a> InputConverter for NautilusToBytesInputConverter<'a> {
         Ok(BytesInput::new(bytes))
     }
 }
+
+/// Parses bytes into [`NautilusInput`] trees, to import seed files and inputs of byte-level fuzzers.
+///
+/// The grammar of the [`NautilusContext`] is parsed with an [`EarleyGrammar`], so ambiguous inputs get one of their derivations.
+/// Only plain rules are parsed, regex and script rules never match.
+pub struct NautilusParser<'a> {
+    ctx: &'a NautilusContext,
+    grammar: EarleyGrammar,
+    rules: Vec<RuleID>,
+}
+
+impl Debug for NautilusParser<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NautilusParser {{}}")
+    }
+}
+
+impl<'a> NautilusParser<'a> {
+    /// Creates a new [`NautilusParser`] for the grammar of a context
+    #[must_use]
+    pub fn new(context: &'a NautilusContext) -> Self {
+        let start = context.ctx.nt_id("START");
+        let mut nonterminals: HashMap<NTermID, usize> = HashMap::new();
+        nonterminals.insert(start, 0);
+        let mut queue = vec![start];
+        let mut grammar = EarleyGrammar::new(0);
+        let mut rules = vec![];
+        while let Some(nt) = queue.pop() {
+            for rule_id in context.ctx.get_rules_for_nt(nt) {
+                let Rule::Plain(rule) = context.ctx.get_rule(*rule_id) else {
+                    continue;
+                };
+                let symbols = rule
+                    .children
+                    .iter()
+                    .map(|child| match child {
+                        RuleChild::Term(bytes) => EarleySymbol::Terminal(bytes.clone()),
+                        RuleChild::NTerm(child) => {
+                            let next = nonterminals.len();
+                            let idx = *nonterminals.entry(*child).or_insert_with(|| {
+                                queue.push(*child);
+                                next
+                            });
+                            EarleySymbol::NonTerminal(idx)
+                        }
+                    })
+                    .collect();
+                grammar.add_rule(nonterminals[&nt], symbols);
+                rules.push(*rule_id);
+            }
+        }
+        Self {
+            ctx: context,
+            grammar,
+            rules,
+        }
+    }
+
+    /// Set the maximum length of the inputs to parse, defaults to [`crate::inputs::earley::EARLEY_MAX_LEN`].
+    /// Longer files are loaded up to this length.
+    #[must_use]
+    pub fn with_max_len(self, max_len: usize) -> Self {
+        Self {
+            grammar: self.grammar.with_max_len(max_len),
+            ..self
+        }
+    }
+
+    fn input(&self, derivation: Vec<usize>) -> NautilusInput {
+        let rules = derivation
+            .into_iter()
+            .map(|rule| RuleIDOrCustom::Rule(self.rules[rule]))
+            .collect();
+        NautilusInput::new(Tree::from_rule_vec(rules, &self.ctx.ctx))
+    }
+
+    /// Parse bytes into a [`NautilusInput`], that unparses to the same bytes
+    pub fn parse(&self, bytes: &[u8]) -> Result<NautilusInput, Error> {
+        Ok(self.input(self.grammar.parse(bytes)?))
+    }
+
+    /// Parse the longest prefix of the bytes the grammar derives, and return it with the length of the prefix
+    #[must_use]
+    pub fn parse_prefix(&self, bytes: &[u8]) -> Option<(NautilusInput, usize)> {
+        let (derivation, len) = self.grammar.parse_prefix(bytes)?;
+        Some((self.input(derivation), len))
+    }
+
+    /// Load a file as [`NautilusInput`].
+    /// If the file does not match the grammar, its longest prefix that does is loaded instead.
+    pub fn load_file<P: AsRef<Path>>(&self, path: P) -> Result<NautilusInput, Error> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        match self.parse(&bytes) {
+            Ok(input) => Ok(input),
+            Err(err) => match self.parse_prefix(&bytes) {
+                Some((input, len)) if len > 0 => {
+                    log::info!(
+                        "Loaded the first {len} of {} bytes of {}, the rest does not match the grammar",
+                        bytes.len(),
+                        path.display()
+                    );
+                    Ok(input)
+                }
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// A load callback for a [`crate::stages::SyncFromDiskStage`], to sync with byte-level fuzzers.
+    /// It loads files with [`NautilusParser::load_file`].
+    /// Files from byte-level fuzzers often do not match the grammar at all,
+    /// use [`crate::stages::SyncFromDiskStage::skip_unloadable`] to skip them instead of failing the stage.
+    pub fn sync_callback<S, Z>(
+        &self,
+    ) -> impl FnMut(&mut Z, &mut S, &Path) -> Result<NautilusInput, Error> + '_ {
+        move |_fuzzer, _state, path| self.load_file(path)
+    }
+}
+
+impl InputConverter for NautilusParser<'_> {
+    type From = BytesInput;
+    type To = NautilusInput;
+
+    fn convert(&mut self, input: Self::From) -> Result<Self::To, Error> {
+        self.parse(input.bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use grammartec::rule::RuleIDOrCustom;
+
+    use super::{NautilusInput, NautilusParser};
+    use crate::generators::{Generator, NautilusContext, NautilusGenerator};
+
+    fn rule_ids(input: &NautilusInput) -> Vec<grammartec::newtypes::RuleID> {
+        input
+            .tree()
+            .rules
+            .iter()
+            .map(|rule| match rule {
+                RuleIDOrCustom::Rule(id) | RuleIDOrCustom::Custom(id, _) => *id,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nautilus_parser_roundtrip() {
+        // An unambiguous grammar, so parsing has to find the generated tree
+        let rules = [
+            ("EXPR", &b"{NUM}+{EXPR}"[..]),
+            ("EXPR", b"({EXPR})"),
+            ("EXPR", b"{NUM}"),
+            ("NUM", b"{DIGIT}{NUM}"),
+            ("NUM", b"{DIGIT}"),
+            ("DIGIT", b"0"),
+            ("DIGIT", b"1"),
+        ];
+        let context = NautilusContext::with_rules(15, &rules).unwrap();
+        let parser = NautilusParser::new(&context);
+        let mut generator = NautilusGenerator::new(&context);
+
+        for _ in 0..100 {
+            let input = generator.generate(&mut ()).unwrap();
+            let mut bytes = Vec::new();
+            input.unparse(&context, &mut bytes);
+
+            let parsed = parser.parse(&bytes).unwrap();
+            assert_eq!(rule_ids(&parsed), rule_ids(&input));
+            assert_eq!(parsed.tree().sizes, input.tree().sizes);
+            assert_eq!(parsed.tree().paren, input.tree().paren);
+            let mut unparsed = Vec::new();
+            parsed.unparse(&context, &mut unparsed);
+            assert_eq!(unparsed, bytes);
+        }
+
+        assert!(parser.parse(b"1+").is_err());
+        let (prefix, len) = parser.parse_prefix(b"10+1)").unwrap();
+        assert_eq!(len, 4);
+        let mut unparsed = Vec::new();
+        prefix.unparse(&context, &mut unparsed);
+        assert_eq!(unparsed, b"10+1");
+
+        let parser = parser.with_max_len(2);
+        assert!(parser.parse(b"10+1").is_err());
+        assert_eq!(parser.parse_prefix(b"10+1").unwrap().1, 2);
+    }
+}
@@ -113,6 +113,14 @@ exec_sec = {}
 
         self.base.display(event_msg, sender_id);
     }
+
+    fn record_testcase_size(&mut self, sender_id: ClientId, size: usize) {
+        self.base.record_testcase_size(sender_id, size);
+    }
+
+    fn records_testcase_sizes(&self) -> bool {
+        self.base.records_testcase_sizes()
+    }
 }
 
 impl<M> OnDiskTOMLMonitor<M>
@@ -208,6 +216,10 @@ where
         self.base.set_start_time(time);
     }
 
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
     fn display(&mut self, event_msg: &str, sender_id: ClientId) {
         if (self.log_record)(&mut self.base) {
             let file = OpenOptions::new()
@@ -228,4 +240,12 @@ where
         }
         self.base.display(event_msg, sender_id);
     }
+
+    fn record_testcase_size(&mut self, sender_id: ClientId, size: usize) {
+        self.base.record_testcase_size(sender_id, size);
+    }
+
+    fn records_testcase_sizes(&self) -> bool {
+        self.base.records_testcase_sizes()
+    }
 }
@@ -0,0 +1,489 @@
+//! A monitor that keeps the history of a campaign on disk, and a reader to analyze it afterwards.
+//!
+//! The [`HistoryMonitor`] wraps a base monitor and periodically appends a [`HistoryRecord`] to a JSON lines file:
+//! all aggregated stats, all aggregated user stats, and the full [`ClientStats`] of each client,
+//! including introspection timings with the `introspection` feature.
+//! Once the file grows too large, it gets rotated, keeping a limited number of old files, see [`HistoryRetention`].
+//!
+//! After the campaign, [`History::read`] loads all records again, to plot them or to export them as CSV.
+
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::time::Duration;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, ClientId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    monitors::{Aggregator, ClientStats, Monitor, NopMonitor, UserStatsValue},
+    Error,
+};
+
+/// How much history a [`HistoryMonitor`] keeps on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryRetention {
+    /// Rotate the history file once it grows larger than this, in bytes
+    pub max_file_size: u64,
+    /// The maximum number of files to keep, including the current one
+    pub max_files: usize,
+    /// Remove rotated files older than this, if set
+    pub max_age: Option<Duration>,
+}
+
+impl Default for HistoryRetention {
+    /// Up to 8 files of 64 MiB each, regardless of their age
+    fn default() -> Self {
+        Self {
+            max_file_size: 64 * 1024 * 1024,
+            max_files: 8,
+            max_age: None,
+        }
+    }
+}
+
+/// A single entry of the history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryRecord {
+    /// When this record was taken, since the UNIX epoch
+    pub time: Duration,
+    /// The run time of the campaign
+    pub run_time: Duration,
+    /// The number of clients
+    pub clients: usize,
+    /// The combined corpus size of all clients
+    pub corpus_size: u64,
+    /// The combined objective size of all clients
+    pub objective_size: u64,
+    /// The combined executions of all clients
+    pub executions: u64,
+    /// The combined executions per second of all clients
+    pub execs_per_sec: f64,
+    /// The user stats, aggregated over all clients
+    pub user_stats: HashMap<String, UserStatsValue>,
+    /// The stats of each client, by client id
+    pub client_stats: BTreeMap<u32, ClientStats>,
+}
+
+impl HistoryRecord {
+    /// The aggregated user stat with the given name, as number, if it is numeric
+    #[must_use]
+    pub fn user_stat(&self, name: &str) -> Option<f64> {
+        self.user_stats.get(name).and_then(user_stats_value_as_f64)
+    }
+}
+
+/// A numeric user stats value as `f64`, ratios and percentages as fraction of 1
+#[allow(clippy::cast_precision_loss)]
+fn user_stats_value_as_f64(value: &UserStatsValue) -> Option<f64> {
+    match value {
+        UserStatsValue::Number(n) => Some(*n as f64),
+        UserStatsValue::Float(f) | UserStatsValue::Percent(f) => Some(*f),
+        UserStatsValue::Ratio(a, b) if *b != 0 => Some(*a as f64 / *b as f64),
+        _ => None,
+    }
+}
+
+/// Wraps a base monitor, and keeps the history of all stats in a rotating JSON lines file.
+#[derive(Debug, Clone)]
+pub struct HistoryMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    path: PathBuf,
+    aggregator: Aggregator,
+    last_record: Duration,
+    interval: Duration,
+    retention: HistoryRetention,
+}
+
+impl<M> HistoryMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`HistoryMonitor`], writing a record to `path` every 15 seconds,
+    /// with the default [`HistoryRetention`]
+    #[must_use]
+    pub fn new<P>(path: P, base: M) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            base,
+            path: path.into(),
+            aggregator: Aggregator::new(),
+            last_record: Duration::ZERO,
+            interval: Duration::from_secs(15),
+            retention: HistoryRetention::default(),
+        }
+    }
+
+    /// Write a record at most every `interval`
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set how much history to keep on disk
+    #[must_use]
+    pub fn with_retention(mut self, retention: HistoryRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// The current record, as it would be written to disk
+    pub fn record(&mut self) -> HistoryRecord {
+        let time = current_time();
+        HistoryRecord {
+            time,
+            run_time: time.saturating_sub(self.base.start_time()),
+            clients: self.base.client_stats_count(),
+            corpus_size: self.base.corpus_size(),
+            objective_size: self.base.objective_size(),
+            executions: self.base.total_execs(),
+            execs_per_sec: self.base.execs_per_sec(),
+            user_stats: self.aggregator.aggregated.clone(),
+            client_stats: (0..)
+                .zip(self.base.client_stats())
+                .filter(|(_, client)| client.enabled)
+                .map(|(id, client)| (id, client.clone()))
+                .collect(),
+        }
+    }
+
+    /// Append the current record to the history, rotating the file first if it grew too large
+    fn write_record(&mut self) -> Result<(), Error> {
+        if fs::metadata(&self.path).is_ok_and(|meta| meta.len() >= self.retention.max_file_size) {
+            self.rotate()?;
+        }
+        let mut line = serde_json::to_vec(&self.record())?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Move `path` to `path.1`, `path.1` to `path.2`, and so on, and drop the files exceeding the retention
+    fn rotate(&self) -> Result<(), Error> {
+        let oldest = self.retention.max_files.saturating_sub(1);
+        if oldest == 0 {
+            fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+        let _ = fs::remove_file(rotated_path(&self.path, oldest));
+        for idx in (1..oldest).rev() {
+            let from = rotated_path(&self.path, idx);
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.path, idx + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+
+        if let Some(max_age) = self.retention.max_age {
+            let now = SystemTime::now();
+            for idx in 1..=oldest {
+                let path = rotated_path(&self.path, idx);
+                let too_old = fs::metadata(&path)
+                    .and_then(|meta| meta.modified())
+                    .is_ok_and(|modified| {
+                        now.duration_since(modified).is_ok_and(|age| age > max_age)
+                    });
+                if too_old {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HistoryMonitor<NopMonitor> {
+    /// Create a new [`HistoryMonitor`] without a base
+    #[must_use]
+    pub fn nop<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(path, NopMonitor::new())
+    }
+}
+
+impl<M> Monitor for HistoryMonitor<M>
+where
+    M: Monitor,
+{
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.aggregator.aggregate(name, self.base.client_stats());
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        let cur_time = current_time();
+        if cur_time.saturating_sub(self.last_record) >= self.interval {
+            self.last_record = cur_time;
+            if let Err(err) = self.write_record() {
+                log::error!("Failed to write history to {}: {err}", self.path.display());
+            }
+        }
+        self.base.display(event_msg, sender_id);
+    }
+
+    fn record_testcase_size(&mut self, sender_id: ClientId, size: usize) {
+        self.base.record_testcase_size(sender_id, size);
+    }
+
+    fn records_testcase_sizes(&self) -> bool {
+        self.base.records_testcase_sizes()
+    }
+}
+
+/// The path of the `idx`th rotated history file
+fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{idx}"));
+    rotated.into()
+}
+
+/// The history of a campaign, as written by a [`HistoryMonitor`]
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    records: Vec<HistoryRecord>,
+}
+
+impl History {
+    /// Read the history at `path`, including all rotated files, oldest records first.
+    ///
+    /// Truncated or malformed lines, for example from a crash while writing, are skipped.
+    pub fn read<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut files = vec![path.to_path_buf()];
+        loop {
+            let rotated = rotated_path(path, files.len());
+            if !rotated.exists() {
+                break;
+            }
+            files.push(rotated);
+        }
+        files.reverse();
+
+        let mut records = Vec::new();
+        for file in files {
+            // Split on raw bytes, a line cut off by a crash may end in the middle of a utf-8 char
+            for line in BufReader::new(File::open(&file)?).split(b'\n') {
+                let line = line?;
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                match serde_json::from_slice(&line) {
+                    Ok(record) => records.push(record),
+                    Err(err) => log::warn!(
+                        "Skipping malformed history line in {}: {err}",
+                        file.display()
+                    ),
+                }
+            }
+        }
+        Ok(Self { records })
+    }
+
+    /// All records, oldest first
+    #[must_use]
+    pub fn records(&self) -> &[HistoryRecord] {
+        &self.records
+    }
+
+    /// The run time and value of each record, skipping records without a value
+    pub fn series<F>(&self, mut value: F) -> Vec<(Duration, f64)>
+    where
+        F: FnMut(&HistoryRecord) -> Option<f64>,
+    {
+        self.records
+            .iter()
+            .filter_map(|record| value(record).map(|value| (record.run_time, value)))
+            .collect()
+    }
+
+    /// The run time and value of an aggregated user stat
+    #[must_use]
+    pub fn user_stat_series(&self, name: &str) -> Vec<(Duration, f64)> {
+        self.series(|record| record.user_stat(name))
+    }
+
+    /// The run time and a value of a single client, skipping records in which the client is missing
+    pub fn client_series<F>(&self, client_id: ClientId, mut value: F) -> Vec<(Duration, f64)>
+    where
+        F: FnMut(&ClientStats) -> Option<f64>,
+    {
+        self.series(|record| record.client_stats.get(&client_id.0).and_then(&mut value))
+    }
+
+    /// Write the aggregated stats and all numeric user stats as CSV, one row per record
+    pub fn write_csv<W>(&self, mut writer: W) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        let user_stats: BTreeSet<&String> = self
+            .records
+            .iter()
+            .flat_map(|record| record.user_stats.keys())
+            .collect();
+
+        write!(
+            writer,
+            "run_time,clients,corpus_size,objective_size,executions,execs_per_sec"
+        )?;
+        for name in &user_stats {
+            write!(writer, ",{}", name.replace([',', '"', '\n'], "_"))?;
+        }
+        writeln!(writer)?;
+
+        for record in &self.records {
+            write!(
+                writer,
+                "{},{},{},{},{},{}",
+                record.run_time.as_secs_f64(),
+                record.clients,
+                record.corpus_size,
+                record.objective_size,
+                record.executions,
+                record.execs_per_sec
+            )?;
+            for name in &user_stats {
+                write!(writer, ",")?;
+                if let Some(value) = record.user_stat(name) {
+                    write!(writer, "{value}")?;
+                }
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, string::String, vec::Vec};
+    use core::time::Duration;
+    use std::{env, fs};
+
+    use libafl_bolts::{current_time, ClientId};
+
+    use super::{rotated_path, History, HistoryMonitor, HistoryRetention};
+    use crate::monitors::{AggregatorOps, Monitor, UserStats, UserStatsValue};
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[allow(clippy::cast_precision_loss, clippy::float_cmp)]
+    fn test_history_rotation_and_read() {
+        let path = env::temp_dir().join(format!("libafl_history_{}.jsonl", std::process::id()));
+        let cleanup = || {
+            for idx in 1..4 {
+                let _ = fs::remove_file(rotated_path(&path, idx));
+            }
+            let _ = fs::remove_file(&path);
+        };
+        cleanup();
+
+        let mut monitor = HistoryMonitor::nop(&path)
+            .with_interval(Duration::ZERO)
+            .with_retention(HistoryRetention {
+                max_file_size: 1,
+                max_files: 3,
+                max_age: None,
+            });
+        for round in 1..=5_u64 {
+            monitor.client_stats_insert(ClientId(1));
+            let client = monitor.client_stats_mut_for(ClientId(1));
+            client.update_corpus_size(round);
+            client.update_executions(round * 100, current_time());
+            client.update_user_stats(
+                Cow::from("stability"),
+                UserStats::new(UserStatsValue::Ratio(round, 10), AggregatorOps::Avg),
+            );
+            monitor.aggregate("stability");
+            monitor.display("Test", ClientId(1));
+        }
+
+        // Every record rotates the previous file, only the last three survive
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+        let history = History::read(&path).unwrap();
+        let corpus: Vec<_> = history
+            .series(|record| Some(record.corpus_size as f64))
+            .into_iter()
+            .map(|(_, corpus)| corpus)
+            .collect();
+        assert_eq!(corpus, [3.0, 4.0, 5.0]);
+        assert_eq!(history.user_stat_series("stability").last().unwrap().1, 0.5);
+        assert_eq!(
+            history
+                .client_series(ClientId(1), |client| Some(client.executions as f64))
+                .last()
+                .unwrap()
+                .1,
+            500.0
+        );
+
+        let mut csv = Vec::new();
+        history.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "run_time,clients,corpus_size,objective_size,executions,execs_per_sec,stability"
+        );
+        assert!(lines.last().unwrap().ends_with(",1,5,0,500,0,0.5"));
+
+        // A truncated last line is skipped
+        let record = fs::read(&path).unwrap();
+        let mut bytes = record.clone();
+        bytes.truncate(bytes.len() - 10);
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(History::read(&path).unwrap().records().len(), 2);
+
+        // So are malformed lines in the middle, e.g., when a restarted monitor appended to the file
+        bytes.extend_from_slice(b"\n\xf0\x9f\n");
+        bytes.extend_from_slice(&record);
+        fs::write(&path, &bytes).unwrap();
+        let history = History::read(&path).unwrap();
+        assert_eq!(history.records().len(), 3);
+        assert_eq!(history.records().last().unwrap().corpus_size, 5);
+
+        cleanup();
+    }
+}
@@ -16,12 +16,16 @@ use alloc::string::ToString;
 pub use prometheus::PrometheusMonitor;
 #[cfg(feature = "std")]
 pub mod disk;
+#[cfg(feature = "std")]
+pub mod history;
 use alloc::{borrow::Cow, fmt::Debug, string::String, vec::Vec};
 use core::{fmt, fmt::Write, time::Duration};
 
 #[cfg(feature = "std")]
 pub use disk::{OnDiskJSONMonitor, OnDiskTOMLMonitor};
 use hashbrown::HashMap;
+#[cfg(feature = "std")]
+pub use history::{History, HistoryMonitor, HistoryRecord, HistoryRetention};
 use libafl_bolts::{current_time, format_duration_hms, ClientId};
 use serde::{Deserialize, Serialize};
 
@@ -581,6 +585,32 @@ pub trait Monitor {
 
     /// Aggregate the results in case there're multiple clients
     fn aggregate(&mut self, _name: &str) {}
+
+    /// Record the size of a new testcase of this client, as serialized input, in bytes.
+    /// Monitors tracking the distribution of testcase sizes override this.
+    fn record_testcase_size(&mut self, _sender_id: ClientId, _size: usize) {}
+
+    /// Returns `true` if this monitor wants to know the sizes of new testcases, see [`Self::record_testcase_size`].
+    /// Measuring costs an extra serialization of each testcase in the broker, so it's skipped unless asked for.
+    /// Monitors wrapping another monitor must forward this.
+    fn records_testcase_sizes(&self) -> bool {
+        false
+    }
+}
+
+/// Record the serialized size of a new testcase `input` in the `monitor`, if it [`Monitor::records_testcase_sizes`].
+pub(crate) fn record_testcase_size<MT, I>(monitor: &mut MT, sender_id: ClientId, input: &I)
+where
+    MT: Monitor,
+    I: Serialize,
+{
+    if !monitor.records_testcase_sizes() {
+        return;
+    }
+    match postcard::to_allocvec(input) {
+        Ok(serialized) => monitor.record_testcase_size(sender_id, serialized.len()),
+        Err(err) => log::warn!("Could not measure the testcase of {sender_id:?}: {err}"),
+    }
 }
 
 /// Monitor that print exactly nothing.
@@ -21,8 +21,18 @@
 // ```
 // When using docker, you may need to point prometheus.yml to the docker0 interface or host.docker.internal
 // ====================
+//
+// == per-client metrics ==
+// Next to the campaign-wide metrics, all `client_*` metrics are labelled with the client id,
+// so that alerts can catch a single stuck client. The host of each client is exported as the `host` label
+// of the `client_info` metric, join on `client` to group clients by host. Clients report their host as the `hostname` user stat;
+// until it arrives, or for clients that don't report it, the host of the monitor is used.
+// Hostnames are not unique across nodes, so the client id is the key to tell clients apart. In a tree of brokers, child brokers show up as clients of their parent.
+// ====================
 
 use alloc::{borrow::Cow, fmt::Debug, string::String, vec::Vec};
+#[cfg(feature = "introspection")]
+use alloc::{format, string::ToString};
 use core::{fmt, time::Duration};
 use std::{
     sync::{atomic::AtomicU64, Arc},
@@ -31,17 +41,24 @@ use std::{
 
 // using thread in order to start the HTTP server in a separate thread
 use futures::executor::block_on;
-use libafl_bolts::{current_time, format_duration_hms, ClientId};
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, format_duration_hms, os::hostname, ClientId};
 // using the official rust client library for Prometheus: https://github.com/prometheus/client_rust
 use prometheus_client::{
     encoding::{text::encode, EncodeLabelSet},
-    metrics::{family::Family, gauge::Gauge},
+    metrics::{
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
     registry::Registry,
 };
 // using tide for the HTTP server library (fast, async, simple)
 use tide::Request;
 
-use crate::monitors::{ClientStats, Monitor, UserStatsValue};
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+use crate::monitors::{ClientStats, Monitor, UserStats, UserStatsValue};
 
 /// Tracking monitor during fuzzing.
 #[derive(Clone)]
@@ -59,6 +76,13 @@ where
     runtime: Family<Labels, Gauge>,
     clients_count: Family<Labels, Gauge>,
     custom_stat: Family<Labels, Gauge<f64, AtomicU64>>,
+    client_metrics: ClientMetrics,
+    /// The host exported for clients that did not report their host
+    host: Cow<'static, str>,
+    /// Per client: the host currently exported in `client_info`
+    client_hosts: HashMap<ClientId, Cow<'static, str>>,
+    /// Per client: the executions and time when we last observed its exec time
+    last_executions: HashMap<ClientId, (u64, Duration)>,
 }
 
 impl<F> Debug for PrometheusMonitor<F>
@@ -69,6 +93,7 @@ where
         f.debug_struct("PrometheusMonitor")
             .field("start_time", &self.start_time)
             .field("client_stats", &self.client_stats)
+            .field("host", &self.host)
             .finish_non_exhaustive()
     }
 }
@@ -164,6 +189,8 @@ where
         );
         (self.print_fn)(&fmt);
 
+        self.update_client_metrics(sender_id);
+
         self.client_stats_insert(sender_id);
         let cur_client = self.client_stats_mut_for(sender_id);
         let cur_client_clone = cur_client.clone();
@@ -172,6 +199,10 @@ where
             // Update metrics added to the user_stats hashmap by feedback event-fires
             // You can filter for each custom stat in promQL via labels of both the stat name and client id
             log::info!("{key}: {val}");
+            if key == CLIENT_HOST_STAT {
+                // Exported as the host label of the per-client metrics instead
+                continue;
+            }
             #[allow(clippy::cast_precision_loss)]
             let value: f64 = match val.value() {
                 UserStatsValue::Number(n) => *n as f64,
@@ -188,6 +219,20 @@ where
                 .set(value);
         }
     }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn record_testcase_size(&mut self, sender_id: ClientId, size: usize) {
+        self.client_metrics
+            .testcase_size
+            .get_or_create(&ClientLabels {
+                client: sender_id.0,
+            })
+            .observe(size as f64);
+    }
+
+    fn records_testcase_sizes(&self) -> bool {
+        true
+    }
 }
 
 impl<F> PrometheusMonitor<F>
@@ -195,50 +240,9 @@ where
     F: FnMut(&str),
 {
     pub fn new(listener: String, print_fn: F) -> Self {
-        // Gauge's implementation of clone uses Arc
-        let corpus_count = Family::<Labels, Gauge>::default();
-        let corpus_count_clone = corpus_count.clone();
-        let objective_count = Family::<Labels, Gauge>::default();
-        let objective_count_clone = objective_count.clone();
-        let executions = Family::<Labels, Gauge>::default();
-        let executions_clone = executions.clone();
-        let exec_rate = Family::<Labels, Gauge<f64, AtomicU64>>::default();
-        let exec_rate_clone = exec_rate.clone();
-        let runtime = Family::<Labels, Gauge>::default();
-        let runtime_clone = runtime.clone();
-        let clients_count = Family::<Labels, Gauge>::default();
-        let clients_count_clone = clients_count.clone();
-        let custom_stat = Family::<Labels, Gauge<f64, AtomicU64>>::default();
-        let custom_stat_clone = custom_stat.clone();
-
-        // Need to run the metrics server in a different thread to avoid blocking
-        thread::spawn(move || {
-            block_on(serve_metrics(
-                listener,
-                corpus_count_clone,
-                objective_count_clone,
-                executions_clone,
-                exec_rate_clone,
-                runtime_clone,
-                clients_count_clone,
-                custom_stat_clone,
-            ))
-            .map_err(|err| log::error!("{err:?}"))
-            .ok();
-        });
-        Self {
-            print_fn,
-            start_time: current_time(),
-            client_stats: vec![],
-            corpus_count,
-            objective_count,
-            executions,
-            exec_rate,
-            runtime,
-            clients_count,
-            custom_stat,
-        }
+        Self::with_time(listener, print_fn, current_time())
     }
+
     /// Creates the monitor with a given `start_time`.
     pub fn with_time(listener: String, print_fn: F, start_time: Duration) -> Self {
         let corpus_count = Family::<Labels, Gauge>::default();
@@ -255,7 +259,10 @@ where
         let clients_count_clone = clients_count.clone();
         let custom_stat = Family::<Labels, Gauge<f64, AtomicU64>>::default();
         let custom_stat_clone = custom_stat.clone();
+        let client_metrics = ClientMetrics::new();
+        let client_metrics_clone = client_metrics.clone();
 
+        // Need to run the metrics server in a different thread to avoid blocking
         thread::spawn(move || {
             block_on(serve_metrics(
                 listener,
@@ -266,6 +273,7 @@ where
                 runtime_clone,
                 clients_count_clone,
                 custom_stat_clone,
+                client_metrics_clone,
             ))
             .map_err(|err| log::error!("{err:?}"))
             .ok();
@@ -281,6 +289,129 @@ where
             runtime,
             clients_count,
             custom_stat,
+            client_metrics,
+            host: hostname().into(),
+            client_hosts: HashMap::new(),
+            last_executions: HashMap::new(),
+        }
+    }
+
+    /// Update the per-client metrics of the sender.
+    /// The time since the last find is updated for all clients, so that it keeps growing for clients that got stuck.
+    #[allow(clippy::cast_precision_loss)]
+    fn update_client_metrics(&mut self, sender_id: ClientId) {
+        let cur_time = current_time();
+        let metrics = &self.client_metrics;
+        for (client_id, client) in (0..).map(ClientId).zip(self.client_stats.iter()) {
+            if !client.enabled {
+                continue;
+            }
+            // Without any find yet, count from the start of the client
+            let last_find = client.last_corpus_time.max(client.start_time);
+            metrics
+                .since_last_find
+                .get_or_create(&ClientLabels {
+                    client: client_id.0,
+                })
+                .set(cur_time.saturating_sub(last_find).as_secs_f64());
+        }
+
+        let Some(client) = self.client_stats.get_mut(sender_id.0 as usize) else {
+            return;
+        };
+        if !client.enabled {
+            return;
+        }
+        let labels = ClientLabels {
+            client: sender_id.0,
+        };
+
+        // The host may change, e.g., once the client reports it. Drop the old series, so that only one remains.
+        let host = client_host(client).unwrap_or_else(|| self.host.clone());
+        if self.client_hosts.get(&sender_id) != Some(&host) {
+            if let Some(old_host) = self.client_hosts.insert(sender_id, host.clone()) {
+                metrics.info.remove(&ClientInfoLabels {
+                    client: sender_id.0,
+                    host: old_host,
+                });
+            }
+            metrics
+                .info
+                .get_or_create(&ClientInfoLabels {
+                    client: sender_id.0,
+                    host,
+                })
+                .set(1);
+        }
+
+        metrics
+            .exec_rate
+            .get_or_create(&labels)
+            .set(client.execs_per_sec(cur_time));
+        metrics
+            .executions
+            .get_or_create(&labels)
+            .set(client.executions.try_into().unwrap_or(i64::MAX));
+        metrics
+            .corpus_count
+            .get_or_create(&labels)
+            .set(client.corpus_size.try_into().unwrap_or(i64::MAX));
+        metrics
+            .objective_count
+            .get_or_create(&labels)
+            .set(client.objective_size.try_into().unwrap_or(i64::MAX));
+        let stability =
+            client
+                .get_user_stats("stability")
+                .and_then(|stability| match stability.value() {
+                    UserStatsValue::Ratio(a, b) if *b > 0 => Some(*a as f64 / *b as f64),
+                    UserStatsValue::Percent(p) | UserStatsValue::Float(p) => Some(*p),
+                    _ => None,
+                });
+        if let Some(stability) = stability {
+            metrics.stability.get_or_create(&labels).set(stability);
+        }
+
+        #[cfg(feature = "introspection")]
+        {
+            let perf = &client.introspection_monitor;
+            let elapsed = perf.elapsed_cycles() as f64;
+            if elapsed > 0.0 {
+                let set = |stage: String, feature: String, cycles: u64| {
+                    metrics
+                        .perf
+                        .get_or_create(&PerfLabels {
+                            client: sender_id.0,
+                            stage,
+                            feature,
+                        })
+                        .set(cycles as f64 / elapsed);
+                };
+                set(String::new(), "scheduler".into(), perf.scheduler_cycles());
+                set(String::new(), "manager".into(), perf.manager_cycles());
+                for (stage, features) in perf.used_stages() {
+                    for (feature, cycles) in features.iter().enumerate() {
+                        let feature: PerfFeature = feature.into();
+                        set(stage.to_string(), format!("{feature:?}"), *cycles);
+                    }
+                }
+                for (feedback, cycles) in perf.feedbacks() {
+                    set("feedback".into(), feedback.clone(), *cycles);
+                }
+            }
+        }
+
+        // Only the sender has new executions, observe its average exec time since the last update
+        let executions = client.executions;
+        if let Some((last_executions, last_time)) = self
+            .last_executions
+            .insert(sender_id, (executions, cur_time))
+        {
+            let elapsed = cur_time.saturating_sub(last_time);
+            if executions > last_executions && !elapsed.is_zero() {
+                let exec_time = elapsed.as_secs_f64() / (executions - last_executions) as f64;
+                metrics.exec_time.get_or_create(&labels).observe(exec_time);
+            }
         }
     }
 }
@@ -296,6 +427,7 @@ pub async fn serve_metrics(
     runtime: Family<Labels, Gauge>,
     clients_count: Family<Labels, Gauge>,
     custom_stat: Family<Labels, Gauge<f64, AtomicU64>>,
+    client_metrics: ClientMetrics,
 ) -> Result<(), std::io::Error> {
     tide::log::start();
 
@@ -328,6 +460,7 @@ pub async fn serve_metrics(
         "A metric to contain custom stats returned by feedbacks, filterable by label",
         custom_stat,
     );
+    client_metrics.register(&mut registry);
 
     let mut app = tide::with_state(State {
         registry: Arc::new(registry),
@@ -349,13 +482,234 @@ pub async fn serve_metrics(
     Ok(())
 }
 
+/// The name of the user stat in which clients report their host, see [`ClientInfoLabels`]
+pub const CLIENT_HOST_STAT: &str = "hostname";
+
+/// The host a client reported, if any
+fn client_host(client: &ClientStats) -> Option<Cow<'static, str>> {
+    match client
+        .get_user_stats(CLIENT_HOST_STAT)
+        .map(UserStats::value)
+    {
+        Some(UserStatsValue::String(host)) => Some(host.clone()),
+        _ => None,
+    }
+}
+
 #[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
 pub struct Labels {
     client: u32, // sender_id: u32, to differentiate between clients when multiple are spawned.
     stat: Cow<'static, str>, // for custom_stat filtering.
 }
 
+/// The labels of the per-client metrics
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+pub struct ClientLabels {
+    client: u32,
+}
+
+/// The labels of the `client_info` metric
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+pub struct ClientInfoLabels {
+    client: u32,
+    host: Cow<'static, str>, // the host of the client, reported as the `hostname` user stat, or of the monitor
+}
+
+/// The labels of the per-client performance metrics
+#[cfg(feature = "introspection")]
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+pub struct PerfLabels {
+    client: u32,
+    stage: String, // the stage index, `feedback` for feedbacks, or empty for the scheduler and manager
+    feature: String, // the measured feature, or the feedback name
+}
+
+/// A histogram family, with the buckets set by the constructor
+type HistogramFamily = Family<ClientLabels, Histogram, fn() -> Histogram>;
+
+/// The metrics of each client, labelled with [`ClientLabels`]
+#[derive(Clone, Debug)]
+pub struct ClientMetrics {
+    info: Family<ClientInfoLabels, Gauge>,
+    exec_rate: Family<ClientLabels, Gauge<f64, AtomicU64>>,
+    executions: Family<ClientLabels, Gauge>,
+    corpus_count: Family<ClientLabels, Gauge>,
+    objective_count: Family<ClientLabels, Gauge>,
+    stability: Family<ClientLabels, Gauge<f64, AtomicU64>>,
+    since_last_find: Family<ClientLabels, Gauge<f64, AtomicU64>>,
+    exec_time: HistogramFamily,
+    testcase_size: HistogramFamily,
+    #[cfg(feature = "introspection")]
+    perf: Family<PerfLabels, Gauge<f64, AtomicU64>>,
+}
+
+impl ClientMetrics {
+    fn new() -> Self {
+        Self {
+            info: Family::default(),
+            exec_rate: Family::default(),
+            executions: Family::default(),
+            corpus_count: Family::default(),
+            objective_count: Family::default(),
+            stability: Family::default(),
+            since_last_find: Family::default(),
+            // 1us to 8s
+            exec_time: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.000_001, 2.0, 24))
+            }),
+            // 1 byte to 8 MiB
+            testcase_size: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(1.0, 2.0, 24))
+            }),
+            #[cfg(feature = "introspection")]
+            perf: Family::default(),
+        }
+    }
+
+    fn register(self, registry: &mut Registry) {
+        registry.register(
+            "client_info",
+            "Always 1, labelled with the current host of each client",
+            self.info,
+        );
+        registry.register(
+            "client_execution_rate",
+            "Rate of executions per second of each client",
+            self.exec_rate,
+        );
+        registry.register(
+            "client_executions",
+            "Number of executions of each client",
+            self.executions,
+        );
+        registry.register(
+            "client_corpus_count",
+            "Number of test cases in the corpus of each client",
+            self.corpus_count,
+        );
+        registry.register(
+            "client_objective_count",
+            "Number of objectives of each client",
+            self.objective_count,
+        );
+        registry.register(
+            "client_stability",
+            "Ratio of stable map entries of each client, between 0 and 1",
+            self.stability,
+        );
+        registry.register(
+            "client_seconds_since_last_find",
+            "Seconds since each client last found a new testcase",
+            self.since_last_find,
+        );
+        registry.register(
+            "client_exec_time_seconds",
+            "Average time of a single execution of each client, between two updates",
+            self.exec_time,
+        );
+        registry.register(
+            "client_testcase_size_bytes",
+            "Size of the new testcases of each client, as serialized input",
+            self.testcase_size,
+        );
+        #[cfg(feature = "introspection")]
+        registry.register(
+            "client_perf_ratio",
+            "Ratio of time each client spends in the scheduler, manager, stages and feedbacks",
+            self.perf,
+        );
+    }
+}
+
 #[derive(Clone)]
 struct State {
     registry: Arc<Registry>,
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        format,
+        string::{String, ToString},
+    };
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use libafl_bolts::{current_time, ClientId};
+
+    use super::{PrometheusMonitor, CLIENT_HOST_STAT};
+    use crate::monitors::{AggregatorOps, Monitor, UserStats, UserStatsValue};
+
+    /// Scrape the metrics endpoint, waiting for the server thread to come up
+    fn scrape(addr: &str) -> String {
+        for _ in 0..100 {
+            if let Ok(mut stream) = TcpStream::connect(addr) {
+                stream
+                    .write_all(
+                        b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                    )
+                    .unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                return response;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Prometheus endpoint did not come up on {addr}");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_prometheus_client_metrics() {
+        // Let the OS pick a free port
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut monitor = PrometheusMonitor::new(addr.clone(), |_| {});
+        for (client, corpus_size) in [(ClientId(1), 5), (ClientId(2), 7)] {
+            monitor.client_stats_insert(client);
+            let stats = monitor.client_stats_mut_for(client);
+            stats.update_corpus_size(corpus_size);
+            stats.update_executions(1000, current_time());
+            monitor.record_testcase_size(client, 100);
+            monitor.display("Testcase", client);
+        }
+        // A client on another node reports its host after its first update
+        let remote = ClientId(3);
+        monitor.client_stats_insert(remote);
+        monitor.display("Testcase", remote);
+        monitor.client_stats_mut_for(remote).update_user_stats(
+            CLIENT_HOST_STAT.into(),
+            UserStats::new(UserStatsValue::String("node-2".into()), AggregatorOps::None),
+        );
+        monitor.record_testcase_size(remote, 100);
+        monitor.display("Testcase", remote);
+        let host = monitor.host.clone();
+
+        let response = scrape(&addr);
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        for expected in [
+            "client_corpus_count{client=\"1\"} 5".into(),
+            "client_corpus_count{client=\"2\"} 7".into(),
+            "client_executions{client=\"2\"} 1000".into(),
+            "client_testcase_size_bytes_count{client=\"1\"} 1".into(),
+            "client_seconds_since_last_find{client=\"1\"}".into(),
+            format!("client_info{{client=\"1\",host=\"{host}\"}} 1"),
+            "client_corpus_count{client=\"3\"} 0".into(),
+            "client_testcase_size_bytes_count{client=\"3\"} 1".into(),
+            "client_info{client=\"3\",host=\"node-2\"} 1".into(),
+            "corpus_count{client=\"2\",stat=\"\"} 12".into(),
+        ] {
+            assert!(response.contains(&expected), "{expected} not in {response}");
+        }
+        // The series with the host used before the client reported its own is gone
+        let stale = format!("client_info{{client=\"3\",host=\"{host}\"}}");
+        assert!(!response.contains(&stale), "{stale} in {response}");
+    }
+}
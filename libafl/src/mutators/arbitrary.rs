@@ -0,0 +1,350 @@
+//! Mutators that know the byte layout the [`arbitrary`](https://docs.rs/arbitrary) crate decodes values from.
+//!
+//! `arbitrary` reads most values, like integers, `bool`s and enum variants, front to back,
+//! but takes the lengths of byte slices and strings from the end of the data.
+//! With `arbitrary_take_rest`, as used by `fuzz_target!(|x: T| ...)`, the last field consumes whatever
+//! data remains in between.
+//!
+//! The mutators here work on any input with a bytes vector, so they also help the plain [`crate::inputs::BytesInput`]
+//! of a `libafl_libfuzzer` fuzzer that runs `arbitrary` harnesses.
+//! With the `arbitrary` feature, wrap them in an [`ArbitraryValidMutator`] for an [`crate::inputs::arbitrary::ArbitraryInput`],
+//! to drop mutations the harness type can not decode.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::cmp::min;
+
+use libafl_bolts::{
+    rands::Rand,
+    tuples::{tuple_list, tuple_list_type},
+    Named,
+};
+
+use crate::{
+    inputs::HasBytesVec,
+    mutators::{MutationResult, Mutator},
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+
+/// The number of length slots at the end of the data an [`ArbitraryLenMutator`] mutates
+const ARBITRARY_LEN_SLOTS: usize = 4;
+
+/// The maximum number of bytes an [`ArbitraryTakeRestMutator`] inserts or removes at once
+const ARBITRARY_MAX_CHUNK: usize = 16;
+
+/// The number of bytes `arbitrary` takes from the end of `len` bytes of data, to decode a length
+fn arbitrary_len_width(len: usize) -> usize {
+    let len = len as u64;
+    if len <= u64::from(u8::MAX) + 1 {
+        1
+    } else if len <= u64::from(u16::MAX) + 1 {
+        2
+    } else if len <= u64::from(u32::MAX) + 1 {
+        4
+    } else {
+        8
+    }
+}
+
+/// Mutates the lengths `arbitrary` takes from the end of the data, to grow or shrink byte slices and strings.
+///
+/// Lengths are decoded big endian and taken modulo the remaining size, so each mutation stays decodable.
+/// The width of each length depends on the size of the data, which is exact for the last length slot,
+/// and a good guess for the ones before it.
+#[derive(Default, Debug)]
+pub struct ArbitraryLenMutator;
+
+impl<I, S> Mutator<I, S> for ArbitraryLenMutator
+where
+    S: HasRand,
+    I: HasBytesVec,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let len = input.bytes().len();
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let width = arbitrary_len_width(len);
+        let slot = state
+            .rand_mut()
+            .below(min(ARBITRARY_LEN_SLOTS, len / width));
+        let end = len - slot * width;
+        let start = end - width;
+
+        let current = input.bytes()[start..end]
+            .iter()
+            .fold(0_u64, |value, byte| (value << 8) | u64::from(*byte));
+        let max = start as u64;
+        let value = match state.rand_mut().below(3) {
+            0 => state.rand_mut().below(start + 1) as u64,
+            1 => min(current % (max + 1) + 1, max),
+            _ => (current % (max + 1)).saturating_sub(1),
+        };
+        if value == current {
+            return Ok(MutationResult::Skipped);
+        }
+
+        for (idx, byte) in input.bytes_mut()[start..end].iter_mut().enumerate() {
+            #[allow(clippy::cast_possible_truncation)]
+            let shift = (width - 1 - idx) * 8;
+            *byte = (value >> shift) as u8;
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for ArbitraryLenMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ArbitraryLenMutator");
+        &NAME
+    }
+}
+
+impl ArbitraryLenMutator {
+    /// Creates a new [`ArbitraryLenMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Mutates the choices `arbitrary` decodes from the front of the data.
+///
+/// `bool`s, `Option`s, and the continuation of collections only look at the lowest bit of a byte,
+/// while enum variants are chosen by the most significant byte of a little endian `u32`.
+#[derive(Default, Debug)]
+pub struct ArbitraryChoiceMutator;
+
+impl<I, S> Mutator<I, S> for ArbitraryChoiceMutator
+where
+    S: HasRand,
+    I: HasBytesVec,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let len = input.bytes().len();
+        if len == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        if len < 4 || state.rand_mut().coinflip(0.5) {
+            *state.rand_mut().choose(input.bytes_mut()) ^= 1;
+        } else {
+            let offset = state.rand_mut().below(len - 3);
+            #[allow(clippy::cast_possible_truncation)]
+            let choice = state.rand_mut().next() as u8;
+            if input.bytes()[offset + 3] == choice {
+                return Ok(MutationResult::Skipped);
+            }
+            input.bytes_mut()[offset + 3] = choice;
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for ArbitraryChoiceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ArbitraryChoiceMutator");
+        &NAME
+    }
+}
+
+impl ArbitraryChoiceMutator {
+    /// Creates a new [`ArbitraryChoiceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Grows or shrinks the data the last field consumes with `arbitrary_take_rest`.
+///
+/// Copies or removes a chunk of bytes, but keeps the end of the data in place,
+/// so that the lengths `arbitrary` takes from there still decode to the same values.
+#[derive(Default, Debug)]
+pub struct ArbitraryTakeRestMutator;
+
+impl<I, S> Mutator<I, S> for ArbitraryTakeRestMutator
+where
+    S: HasRand + HasMaxSize,
+    I: HasBytesVec,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let len = input.bytes().len();
+        let tail = min(len, ARBITRARY_LEN_SLOTS * arbitrary_len_width(len));
+        let body = len - tail;
+
+        if state.rand_mut().coinflip(0.5) {
+            if len >= max_size {
+                return Ok(MutationResult::Skipped);
+            }
+            let size = 1 + state
+                .rand_mut()
+                .below(min(ARBITRARY_MAX_CHUNK, max_size - len));
+            let chunk: Vec<u8> = if body == 0 {
+                #[allow(clippy::cast_possible_truncation)]
+                (0..size).map(|_| state.rand_mut().next() as u8).collect()
+            } else {
+                let size = min(size, body);
+                let from = state.rand_mut().below(body - size + 1);
+                input.bytes()[from..from + size].to_vec()
+            };
+            let to = state.rand_mut().below(body + 1);
+            input.bytes_mut().splice(to..to, chunk);
+        } else {
+            if body == 0 {
+                return Ok(MutationResult::Skipped);
+            }
+            let size = 1 + state.rand_mut().below(min(ARBITRARY_MAX_CHUNK, body));
+            let from = state.rand_mut().below(body - size + 1);
+            input.bytes_mut().drain(from..from + size);
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for ArbitraryTakeRestMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ArbitraryTakeRestMutator");
+        &NAME
+    }
+}
+
+impl ArbitraryTakeRestMutator {
+    /// Creates a new [`ArbitraryTakeRestMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations that know the layout of the `arbitrary` encoding
+pub type ArbitraryMutationsType = tuple_list_type!(
+    ArbitraryLenMutator,
+    ArbitraryChoiceMutator,
+    ArbitraryTakeRestMutator
+);
+
+/// Get the mutations that know the layout of the `arbitrary` encoding
+#[must_use]
+pub fn arbitrary_mutations() -> ArbitraryMutationsType {
+    tuple_list!(
+        ArbitraryLenMutator::new(),
+        ArbitraryChoiceMutator::new(),
+        ArbitraryTakeRestMutator::new(),
+    )
+}
+
+#[cfg(feature = "arbitrary")]
+pub use valid::ArbitraryValidMutator;
+
+#[cfg(feature = "arbitrary")]
+mod valid {
+    use alloc::borrow::Cow;
+    use core::fmt::Debug;
+
+    use ::arbitrary::Arbitrary;
+    use libafl_bolts::Named;
+
+    use crate::{
+        corpus::CorpusId,
+        inputs::{arbitrary::ArbitraryInput, HasBytesVec},
+        mutators::{MutationResult, Mutator},
+        Error,
+    };
+
+    /// Wraps a mutator for an [`ArbitraryInput`], and reverts all mutations that do not decode anymore.
+    #[derive(Debug)]
+    pub struct ArbitraryValidMutator<M> {
+        mutator: M,
+    }
+
+    impl<M> ArbitraryValidMutator<M> {
+        /// Creates a new [`ArbitraryValidMutator`], wrapping the given mutator
+        #[must_use]
+        pub fn new(mutator: M) -> Self {
+            Self { mutator }
+        }
+    }
+
+    impl<M, S, T> Mutator<ArbitraryInput<T>, S> for ArbitraryValidMutator<M>
+    where
+        M: Mutator<ArbitraryInput<T>, S>,
+        T: for<'a> Arbitrary<'a>,
+    {
+        fn mutate(
+            &mut self,
+            state: &mut S,
+            input: &mut ArbitraryInput<T>,
+        ) -> Result<MutationResult, Error> {
+            let backup = input.bytes().to_vec();
+            let result = self.mutator.mutate(state, input)?;
+            if result == MutationResult::Mutated && input.value().is_err() {
+                *input.bytes_mut() = backup;
+                return Ok(MutationResult::Skipped);
+            }
+            Ok(result)
+        }
+
+        fn post_exec(
+            &mut self,
+            state: &mut S,
+            new_corpus_idx: Option<CorpusId>,
+        ) -> Result<(), Error> {
+            self.mutator.post_exec(state, new_corpus_idx)
+        }
+    }
+
+    impl<M> Named for ArbitraryValidMutator<M> {
+        fn name(&self) -> &Cow<'static, str> {
+            static NAME: Cow<'static, str> = Cow::Borrowed("ArbitraryValidMutator");
+            &NAME
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{ArbitraryLenMutator, ArbitraryTakeRestMutator};
+    use crate::{
+        corpus::InMemoryCorpus,
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasBytesVec},
+        mutators::{MutationResult, Mutator},
+        state::StdState,
+    };
+
+    #[test]
+    fn test_arbitrary_mutations_keep_tail() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+
+        // The lengths at the end survive resizing the rest
+        let tail = [7, 6, 5, 4];
+        let mut input = BytesInput::new([[0_u8; 16].as_slice(), &tail].concat());
+        let mut take_rest = ArbitraryTakeRestMutator::new();
+        for _ in 0..64 {
+            take_rest.mutate(&mut state, &mut input).unwrap();
+            assert!(input.bytes().ends_with(&tail));
+        }
+
+        // Lengths never exceed the data they are taken from
+        let mut len = ArbitraryLenMutator::new();
+        let mut input = BytesInput::new(vec![0; 8]);
+        for _ in 0..64 {
+            if len.mutate(&mut state, &mut input).unwrap() == MutationResult::Mutated {
+                let (rest, lens) = input.bytes().split_at(8 - super::ARBITRARY_LEN_SLOTS);
+                for (slot, len) in lens.iter().rev().enumerate() {
+                    assert!(usize::from(*len) < rest.len() + lens.len() - slot);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Mutators for [`CompositeInput`]s, see [`crate::inputs::composite`] for details.
+
+use alloc::borrow::Cow;
+use core::{cmp::min, marker::PhantomData};
+
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    inputs::{BytesInput, CompositeInput, EncodedInput, GramatronInput, HasBytesVec},
+    mutators::{
+        mutations::{ARITH_MAX, INTERESTING_32},
+        MutationResult, Mutator,
+    },
+    random_corpus_id,
+    state::{HasCorpus, HasRand},
+    Error,
+};
+
+/// A part of a [`CompositeInput`] that can be crossed over with another part of the same type.
+///
+/// By default, the part is replaced by the other part as a whole.
+pub trait PartCrossover: Clone {
+    /// Cross over this part with `other`
+    fn crossover<R>(&mut self, _rand: &mut R, other: &Self) -> Result<MutationResult, Error>
+    where
+        R: Rand,
+    {
+        *self = other.clone();
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl PartCrossover for BytesInput {
+    /// Replace a random range of this input with a random range of `other`
+    fn crossover<R>(&mut self, rand: &mut R, other: &Self) -> Result<MutationResult, Error>
+    where
+        R: Rand,
+    {
+        let other = other.bytes();
+        if other.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let from = rand.below(other.len());
+        let len = 1 + rand.below(other.len() - from);
+        let to = rand.below(self.bytes().len() + 1);
+        let end = min(to + len, self.bytes().len());
+        self.bytes_mut()
+            .splice(to..end, other[from..from + len].iter().copied());
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl PartCrossover for EncodedInput {}
+
+impl PartCrossover for GramatronInput {}
+
+#[cfg(feature = "nautilus")]
+impl PartCrossover for crate::inputs::NautilusInput {}
+
+impl PartCrossover for bool {}
+
+macro_rules! impl_part_crossover_int {
+    ($($int:ty),+) => {
+        $(impl PartCrossover for $int {})+
+    };
+}
+
+impl_part_crossover_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// A `tuple_list` of one mutator for each part of a [`CompositeInput`], usually implemented by
+/// `#[derive(CompositeInput)]`
+pub trait PartMutatorsTuple<I, S> {
+    /// Mutate the part at index `part` with its mutator
+    fn mutate_part(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        part: usize,
+    ) -> Result<MutationResult, Error>;
+
+    /// Run the `post_exec` of the mutators of all parts
+    fn post_exec_parts(
+        &mut self,
+        state: &mut S,
+        new_corpus_idx: Option<CorpusId>,
+    ) -> Result<(), Error>;
+}
+
+/// Mutates a random part of a [`CompositeInput`], using the mutator of that part
+#[derive(Debug)]
+pub struct CompositeMutator<MT> {
+    mutators: MT,
+}
+
+impl<MT> CompositeMutator<MT> {
+    /// Create a new [`CompositeMutator`], from a `tuple_list` of one mutator for each part,
+    /// in the order the parts are declared in.
+    #[must_use]
+    pub fn new(mutators: MT) -> Self {
+        Self { mutators }
+    }
+}
+
+impl<MT> Named for CompositeMutator<MT> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CompositeMutator");
+        &NAME
+    }
+}
+
+impl<I, MT, S> Mutator<I, S> for CompositeMutator<MT>
+where
+    I: CompositeInput,
+    MT: PartMutatorsTuple<I, S>,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        if I::PART_NAMES.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let part = state.rand_mut().below(I::PART_NAMES.len());
+        self.mutators.mutate_part(state, input, part)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        self.mutators.post_exec_parts(state, new_corpus_idx)
+    }
+}
+
+/// Mutates an integer or `bool` part of a [`CompositeInput`].
+///
+/// Integers get a bit flipped, a small value added or subtracted, or are replaced by an interesting or random value.
+/// A `bool` is negated.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IntPartMutator;
+
+impl IntPartMutator {
+    /// Creates a new [`IntPartMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for IntPartMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("IntPartMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<bool, S> for IntPartMutator {
+    fn mutate(&mut self, _state: &mut S, input: &mut bool) -> Result<MutationResult, Error> {
+        *input = !*input;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+macro_rules! impl_int_part_mutator {
+    ($($int:ty),+) => {
+        $(
+            impl<S> Mutator<$int, S> for IntPartMutator
+            where
+                S: HasRand,
+            {
+                #[allow(
+                    trivial_numeric_casts,
+                    clippy::cast_possible_truncation,
+                    clippy::cast_possible_wrap,
+                    clippy::cast_sign_loss
+                )]
+                fn mutate(&mut self, state: &mut S, input: &mut $int) -> Result<MutationResult, Error> {
+                    let rand = state.rand_mut();
+                    let one: $int = 1;
+                    let old = *input;
+                    *input = match rand.below(5) {
+                        0 => old ^ (one << rand.below(<$int>::BITS as usize)),
+                        1 => old.wrapping_add(1 + rand.below(ARITH_MAX) as $int),
+                        2 => old.wrapping_sub(1 + rand.below(ARITH_MAX) as $int),
+                        3 => *rand.choose(&INTERESTING_32) as $int,
+                        _ => rand.next() as $int,
+                    };
+                    if *input == old {
+                        Ok(MutationResult::Skipped)
+                    } else {
+                        Ok(MutationResult::Mutated)
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_int_part_mutator!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Crosses over a random part of a [`CompositeInput`] with a part of the same type of another corpus entry
+#[derive(Debug)]
+pub struct CompositeCrossoverMutator<I> {
+    phantom: PhantomData<I>,
+}
+
+impl<I> CompositeCrossoverMutator<I> {
+    /// Create a new [`CompositeCrossoverMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I> Default for CompositeCrossoverMutator<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> Named for CompositeCrossoverMutator<I> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CompositeCrossoverMutator");
+        &NAME
+    }
+}
+
+impl<I, S> Mutator<I, S> for CompositeCrossoverMutator<I>
+where
+    I: CompositeInput,
+    S: HasCorpus<Input = I> + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        if I::PART_NAMES.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = random_corpus_id!(state.corpus(), state.rand_mut());
+        let other = {
+            let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+            other_testcase.load_input(state.corpus())?.clone()
+        };
+        let part = state.rand_mut().below(I::PART_NAMES.len());
+        input.crossover_part(state.rand_mut(), &other, part)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+    use serde::{Deserialize, Serialize};
+
+    use super::{CompositeCrossoverMutator, CompositeMutator, IntPartMutator, Mutator};
+    use crate as libafl;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, CompositeInput, HasBytesVec, HasTargetBytes, Input},
+        mutators::{BitFlipMutator, MutationResult, StdScheduledMutator},
+        state::{HasCorpus, StdState},
+        CompositeInput,
+    };
+
+    #[derive(CompositeInput, Clone, Debug, Serialize, Deserialize)]
+    struct ProtocolInput {
+        header: BytesInput,
+        body: BytesInput,
+        flags: u32,
+    }
+
+    fn input(header: &[u8], body: &[u8], flags: u32) -> ProtocolInput {
+        ProtocolInput {
+            header: BytesInput::new(header.into()),
+            body: BytesInput::new(body.into()),
+            flags,
+        }
+    }
+
+    #[test]
+    fn test_composite_input() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<ProtocolInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        state
+            .corpus_mut()
+            .add(Testcase::new(input(b"HDR", b"BODY", 0)))
+            .unwrap();
+
+        let mut composite = input(b"GET", b"", 0);
+        assert_eq!(ProtocolInput::PART_NAMES, ["header", "body", "flags"]);
+        assert_eq!(composite.target_bytes().len(), 3 + 4);
+
+        let mut mutator = CompositeMutator::new(tuple_list!(
+            StdScheduledMutator::new(tuple_list!(BitFlipMutator::new())),
+            BitFlipMutator::new(),
+            IntPartMutator::new(),
+        ));
+        for _ in 0..16 {
+            mutator.mutate(&mut state, &mut composite).unwrap();
+        }
+        assert_eq!(composite.target_bytes().len(), 3 + 4);
+
+        // Crossover only mixes bytes into the byte parts, never into the flags
+        let mut crossover = CompositeCrossoverMutator::new();
+        let flags = composite.flags;
+        for _ in 0..64 {
+            crossover.mutate(&mut state, &mut composite).unwrap();
+            assert!(composite.flags == flags || composite.flags == 0);
+        }
+        assert!(composite
+            .header
+            .bytes()
+            .iter()
+            .chain(composite.body.bytes())
+            .any(|byte| b"HDRBODY".contains(byte)));
+        assert_ne!(
+            composite.generate_name(0),
+            input(b"", b"", 0).generate_name(0)
+        );
+    }
+
+    #[test]
+    fn test_int_part_mutator() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<ProtocolInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        let mut mutator = IntPartMutator::new();
+
+        let mut flags = 0_u32;
+        let mut small = i8::MAX;
+        let mut changed = 0;
+        for _ in 0..64 {
+            let (old_flags, old_small) = (flags, small);
+            let result = mutator.mutate(&mut state, &mut flags).unwrap();
+            assert_eq!(result == MutationResult::Mutated, flags != old_flags);
+            let result = mutator.mutate(&mut state, &mut small).unwrap();
+            assert_eq!(result == MutationResult::Mutated, small != old_small);
+            changed += usize::from(flags != old_flags);
+        }
+        assert!(changed > 32);
+
+        let mut flag = false;
+        mutator.mutate(&mut state, &mut flag).unwrap();
+        assert!(flag);
+    }
+}
@@ -10,14 +10,14 @@ use libafl_bolts::{
 
 use crate::{
     corpus::Corpus,
-    inputs::{EncodedInput, UsesInput},
+    inputs::{EncodedInput, TokenVocabulary, UsesInput},
     mutators::{
         mutations::{buffer_copy, buffer_self_copy, ARITH_MAX},
         MutationResult, Mutator, Named,
     },
     random_corpus_id_with_disabled,
     state::{HasCorpus, HasMaxSize, HasRand},
-    Error,
+    Error, HasMetadata,
 };
 
 /// Set a code in the input as a random value
@@ -414,6 +414,193 @@ impl EncodedCrossoverReplaceMutator {
     }
 }
 
+/// The max number of tokens an [`EncodedNgramSpliceMutator`] splices
+pub const ENCODED_NGRAM_MAX: usize = 8;
+
+/// Replace a random code with a token of the same class from the [`TokenVocabulary`], weighted by frequency and coverage
+#[derive(Debug, Default)]
+pub struct EncodedVocabularyReplaceMutator;
+
+impl<S> Mutator<EncodedInput, S> for EncodedVocabularyReplaceMutator
+where
+    S: HasRand + HasMetadata,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut EncodedInput) -> Result<MutationResult, Error> {
+        if input.codes().is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(input.codes().len());
+        let old = input.codes()[idx];
+
+        // Codes that are not in the vocabulary, e.g. from an `EncodedRandMutator`, may be replaced by any token
+        let vocabulary = state.metadata::<TokenVocabulary>()?;
+        let class = vocabulary.class(old);
+        let total = vocabulary.total_weight(class);
+        if total == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let pick = state.rand_mut().below(total);
+        let Some(new) = state
+            .metadata::<TokenVocabulary>()?
+            .weighted_code(class, pick)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        if new == old {
+            return Ok(MutationResult::Skipped);
+        }
+        input.codes_mut()[idx] = new;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for EncodedVocabularyReplaceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("EncodedVocabularyReplaceMutator");
+        &NAME
+    }
+}
+
+impl EncodedVocabularyReplaceMutator {
+    /// Creates a new [`EncodedVocabularyReplaceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Insert a token from the [`TokenVocabulary`], weighted by frequency and coverage
+#[derive(Debug, Default)]
+pub struct EncodedVocabularyInsertMutator;
+
+impl<S> Mutator<EncodedInput, S> for EncodedVocabularyInsertMutator
+where
+    S: HasRand + HasMaxSize + HasMetadata,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut EncodedInput) -> Result<MutationResult, Error> {
+        let size = input.codes().len();
+        if size >= state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(size + 1);
+
+        let total = state.metadata::<TokenVocabulary>()?.total_weight(None);
+        if total == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let pick = state.rand_mut().below(total);
+        let Some(code) = state
+            .metadata::<TokenVocabulary>()?
+            .weighted_code(None, pick)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        input.codes_mut().insert(idx, code);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for EncodedVocabularyInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("EncodedVocabularyInsertMutator");
+        &NAME
+    }
+}
+
+impl EncodedVocabularyInsertMutator {
+    /// Creates a new [`EncodedVocabularyInsertMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Splice an n-gram from another corpus entry.
+///
+/// Picks a code in the input, and a place where the other entry has the same code.
+/// The tokens after it in the input are replaced by up to [`ENCODED_NGRAM_MAX`] tokens following it in the other entry,
+/// so the spliced tokens fit the context they came from.
+#[derive(Debug, Default)]
+pub struct EncodedNgramSpliceMutator;
+
+impl<S> Mutator<S::Input, S> for EncodedNgramSpliceMutator
+where
+    S: UsesInput<Input = EncodedInput> + HasRand + HasCorpus + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut EncodedInput) -> Result<MutationResult, Error> {
+        let size = input.codes().len();
+        if size == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // We don't want to use the testcase we're already using for splicing
+        let idx = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let pos = state.rand_mut().below(size);
+        let anchor = input.codes()[pos];
+        let max_size = state.max_size();
+
+        // The positions after the anchor in the other entry, it needs the corpus and the rand at different times
+        let (other_size, starts) = {
+            let mut other_testcase = state.corpus().get_from_all(idx)?.borrow_mut();
+            let other = other_testcase.load_input(state.corpus())?.codes();
+            let starts: Vec<usize> = other
+                .iter()
+                .enumerate()
+                .filter(|(i, code)| **code == anchor && i + 1 < other.len())
+                .map(|(i, _)| i + 1)
+                .collect();
+            (other.len(), starts)
+        };
+        if starts.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let from = *state.rand_mut().choose(&starts);
+        let len = 1 + state
+            .rand_mut()
+            .below(min(ENCODED_NGRAM_MAX, other_size - from));
+        let to = pos + 1;
+        let end = min(to + len, size);
+
+        if size - (end - to) + len > max_size {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let other_testcase = state.corpus().get_from_all(idx)?.borrow_mut();
+        // no need to load the input again, it'll already be present at this point.
+        let other = other_testcase.input().as_ref().unwrap().codes();
+        if input.codes()[to..end] == other[from..from + len] {
+            return Ok(MutationResult::Skipped);
+        }
+        input
+            .codes_mut()
+            .splice(to..end, other[from..from + len].iter().copied());
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for EncodedNgramSpliceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("EncodedNgramSpliceMutator");
+        &NAME
+    }
+}
+
+impl EncodedNgramSpliceMutator {
+    /// Creates a new [`EncodedNgramSpliceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
 /// Get the mutations that compose the encoded mutator
 #[must_use]
 pub fn encoded_mutations() -> tuple_list_type!(
@@ -439,3 +626,86 @@ pub fn encoded_mutations() -> tuple_list_type!(
         EncodedCrossoverReplaceMutator::new(),
     )
 }
+
+/// Get the mutations that use the learned [`TokenVocabulary`] and splice n-grams of other corpus entries
+#[must_use]
+pub fn encoded_vocabulary_mutations() -> tuple_list_type!(
+    EncodedVocabularyReplaceMutator,
+    EncodedVocabularyInsertMutator,
+    EncodedNgramSpliceMutator,
+) {
+    tuple_list!(
+        EncodedVocabularyReplaceMutator::new(),
+        EncodedVocabularyInsertMutator::new(),
+        EncodedNgramSpliceMutator::new(),
+    )
+}
+
+#[cfg(feature = "regex")]
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::encoded_vocabulary_mutations;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{InputDecoder, InputEncoder, NaiveTokenizer, TokenClass, TokenVocabulary},
+        mutators::{MutationResult, Mutator, StdScheduledMutator},
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    #[cfg_attr(all(miri, target_arch = "aarch64", target_vendor = "apple"), ignore)] // Regex miri fails on M1
+    fn test_vocabulary_mutations() {
+        let mut tokenizer = NaiveTokenizer::default();
+        let mut vocabulary = TokenVocabulary::new();
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        for seed in [
+            "var a = b + 1;",
+            "function f(x) { return x * 2; }",
+            "if (a < 3) { f(a); }",
+        ] {
+            let input = vocabulary.encode(seed.as_bytes(), &mut tokenizer).unwrap();
+            state.corpus_mut().add(Testcase::new(input)).unwrap();
+        }
+
+        let mut input = vocabulary.encode(b"var c = f(b);", &mut tokenizer).unwrap();
+        state.add_metadata(vocabulary.clone());
+
+        let mut mutator = StdScheduledMutator::new(encoded_vocabulary_mutations());
+        let mut mutated = 0;
+        for _ in 0..256 {
+            let mut next = input.clone();
+            if mutator.mutate(&mut state, &mut next).unwrap() == MutationResult::Skipped {
+                continue;
+            }
+            mutated += 1;
+            // All codes stay in the vocabulary, so they decode to known tokens
+            let mut bytes = vec![];
+            vocabulary.decode(&next, &mut bytes).unwrap();
+            assert!(next
+                .codes()
+                .iter()
+                .all(|code| vocabulary.token(*code).is_some()));
+            input = next;
+        }
+        assert!(mutated > 0);
+
+        // Replacements keep the class, so a number stays a number
+        let mut input = vocabulary.encode(b"1", &mut tokenizer).unwrap();
+        let mut mutator = super::EncodedVocabularyReplaceMutator::new();
+        for _ in 0..64 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            assert_eq!(vocabulary.class(input.codes()[0]), Some(TokenClass::Number));
+        }
+    }
+}
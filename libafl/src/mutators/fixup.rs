@@ -0,0 +1,601 @@
+//! Fixups for checksums and length fields, applied to mutated inputs right before they are executed.
+//!
+//! Binary formats like PNG, ZIP or network packets reject most mutants on their checksum and length checks.
+//! A [`FixupMetadata`] declares where these fields are, and how to compute them.
+//! Mutating a [`FixupInput`] instead of a [`BytesInput`] applies the fixups after each mutation.
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use libafl_bolts::{impl_serdeany, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{CorpusId, HasTestcase, Testcase},
+    inputs::{BytesInput, HasBytesVec},
+    stages::mutational::{MutatedTransform, MutatedTransformPost},
+    state::HasCorpus,
+    HasMetadata,
+};
+
+/// The byte order of a fixup field
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FixupEndian {
+    /// Most significant byte first, as in network packets
+    Big,
+    /// Least significant byte first
+    Little,
+}
+
+/// A position in an input, used to place fixup fields and the data they cover
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FixupPos {
+    /// Bytes from the start of the input
+    Start(usize),
+    /// Bytes back from the end of the input
+    End(usize),
+    /// Bytes after the start of the field of an earlier fixup, given by its index
+    Field {
+        /// The index of the earlier fixup
+        fixup: usize,
+        /// The offset from the start of its field
+        offset: usize,
+    },
+    /// Like [`FixupPos::Field`], but also skips as many bytes as the field of the earlier fixup says.
+    ///
+    /// For length-prefixed chunks, this finds the bytes after the chunk data.
+    Skip {
+        /// The index of the earlier fixup
+        fixup: usize,
+        /// The offset from the start of its field, added to its value
+        offset: usize,
+    },
+}
+
+/// How the value of a fixup field is computed from the data it covers
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FixupAlgorithm {
+    /// The CRC-32 of zlib, PNG and ZIP
+    Crc32,
+    /// The Adler-32 checksum of zlib
+    Adler32,
+    /// The 16 bit ones' complement checksum of IP, TCP and UDP (RFC 1071)
+    IpChecksum,
+    /// The length of the data
+    Length {
+        /// The width of the field in bytes, from 1 up to [`FixupAlgorithm::MAX_WIDTH`]
+        width: usize,
+        /// Added to the length, e.g. if the length counts a header that is not part of the data
+        adjust: i64,
+    },
+    /// A field that is not changed, but that other fixups refer to with [`FixupPos::Skip`]
+    Field {
+        /// The width of the field in bytes, from 1 up to [`FixupAlgorithm::MAX_WIDTH`]
+        width: usize,
+    },
+}
+
+impl FixupAlgorithm {
+    /// The widest field, in bytes, that holds a computed value
+    pub const MAX_WIDTH: usize = 8;
+
+    /// Returns `true` if the field is at least one and at most [`FixupAlgorithm::MAX_WIDTH`] bytes wide
+    #[must_use]
+    pub fn has_valid_width(&self) -> bool {
+        (1..=Self::MAX_WIDTH).contains(&self.width())
+    }
+
+    /// The width of the field in bytes
+    #[must_use]
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Crc32 | Self::Adler32 => 4,
+            Self::IpChecksum => 2,
+            Self::Length { width, .. } | Self::Field { width } => *width,
+        }
+    }
+
+    /// Computes the value of the field for the given data, or `None` for a [`FixupAlgorithm::Field`]
+    #[must_use]
+    pub fn compute(&self, data: &[u8]) -> Option<u64> {
+        match self {
+            Self::Length { width, adjust } => {
+                let len = (data.len() as u64).wrapping_add_signed(*adjust);
+                Some(len & width_mask(*width))
+            }
+            Self::Field { .. } => None,
+            _ => {
+                let mut hasher = FixupHasher::new(*self);
+                for byte in data {
+                    hasher.update(*byte);
+                }
+                Some(hasher.finish())
+            }
+        }
+    }
+}
+
+/// The mask for the values of fields with the given width
+#[must_use]
+pub(crate) fn width_mask(width: usize) -> u64 {
+    if width >= 8 {
+        u64::MAX
+    } else {
+        (1 << (width * 8)) - 1
+    }
+}
+
+/// Reads a field of the given width, if it is in bounds
+#[must_use]
+pub(crate) fn read_field(
+    bytes: &[u8],
+    pos: usize,
+    width: usize,
+    endian: FixupEndian,
+) -> Option<u64> {
+    let field = bytes.get(pos..pos.checked_add(width)?)?;
+    Some(match endian {
+        FixupEndian::Big => field
+            .iter()
+            .fold(0, |value, byte| (value << 8) | u64::from(*byte)),
+        FixupEndian::Little => field
+            .iter()
+            .rev()
+            .fold(0, |value, byte| (value << 8) | u64::from(*byte)),
+    })
+}
+
+/// Writes a field of the given width, the caller checks the bounds.
+///
+/// Bytes beyond the 8 bytes of the value are zeroed.
+fn write_field(bytes: &mut [u8], pos: usize, width: usize, endian: FixupEndian, value: u64) {
+    for i in 0..width {
+        let byte = value.checked_shr((8 * i) as u32).unwrap_or(0) as u8;
+        match endian {
+            FixupEndian::Big => bytes[pos + width - 1 - i] = byte,
+            FixupEndian::Little => bytes[pos + i] = byte,
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes a checksum byte by byte, so that all prefixes of some data can be checked in one pass
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum FixupHasher {
+    /// The inverted CRC so far
+    Crc32(u32),
+    /// The two sums of Adler-32
+    Adler32(u32, u32),
+    /// The sum so far, and if the next byte is the low byte of a word
+    IpChecksum(u64, bool),
+}
+
+impl FixupHasher {
+    /// Starts a checksum, the algorithm must not be a length or a plain field
+    pub(crate) fn new(algorithm: FixupAlgorithm) -> Self {
+        match algorithm {
+            FixupAlgorithm::Crc32 => Self::Crc32(u32::MAX),
+            FixupAlgorithm::Adler32 => Self::Adler32(1, 0),
+            FixupAlgorithm::IpChecksum => Self::IpChecksum(0, false),
+            FixupAlgorithm::Length { .. } | FixupAlgorithm::Field { .. } => {
+                panic!("{algorithm:?} is not a checksum")
+            }
+        }
+    }
+
+    /// Adds the next byte
+    pub(crate) fn update(&mut self, byte: u8) {
+        match self {
+            Self::Crc32(crc) => {
+                *crc = CRC32_TABLE[((*crc ^ u32::from(byte)) & 0xff) as usize] ^ (*crc >> 8);
+            }
+            Self::Adler32(a, b) => {
+                *a = (*a + u32::from(byte)) % 65521;
+                *b = (*b + *a) % 65521;
+            }
+            Self::IpChecksum(sum, low) => {
+                *sum += if *low {
+                    u64::from(byte)
+                } else {
+                    u64::from(byte) << 8
+                };
+                *low = !*low;
+            }
+        }
+    }
+
+    /// The checksum of all bytes so far
+    pub(crate) fn finish(&self) -> u64 {
+        match self {
+            Self::Crc32(crc) => u64::from(!crc),
+            Self::Adler32(a, b) => u64::from((b << 16) | a),
+            Self::IpChecksum(sum, _) => {
+                let mut sum = *sum;
+                while sum >> 16 != 0 {
+                    sum = (sum & 0xffff) + (sum >> 16);
+                }
+                !sum & 0xffff
+            }
+        }
+    }
+}
+
+/// A checksum or length field, and the data it is computed from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fixup {
+    algorithm: FixupAlgorithm,
+    endian: FixupEndian,
+    field: FixupPos,
+    data: Range<FixupPos>,
+}
+
+impl Fixup {
+    /// Creates a new big-endian [`Fixup`], that writes the value computed from `data` to `field`
+    #[must_use]
+    pub fn new(algorithm: FixupAlgorithm, field: FixupPos, data: Range<FixupPos>) -> Self {
+        Self {
+            algorithm,
+            endian: FixupEndian::Big,
+            field,
+            data,
+        }
+    }
+
+    /// Sets the byte order of the field
+    #[must_use]
+    pub fn with_endian(mut self, endian: FixupEndian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// The algorithm that computes the field
+    #[must_use]
+    pub fn algorithm(&self) -> FixupAlgorithm {
+        self.algorithm
+    }
+
+    /// The byte order of the field
+    #[must_use]
+    pub fn endian(&self) -> FixupEndian {
+        self.endian
+    }
+
+    /// The position of the field
+    #[must_use]
+    pub fn field(&self) -> FixupPos {
+        self.field
+    }
+
+    /// The data the field is computed from
+    #[must_use]
+    pub fn data(&self) -> &Range<FixupPos> {
+        &self.data
+    }
+}
+
+/// A list of [`Fixup`]s, applied in order.
+///
+/// As state metadata, it declares the fixups of the format, which apply to all inputs.
+/// As testcase metadata, it holds the fixups the [`crate::stages::FixupDetectionStage`] found for this testcase.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FixupMetadata {
+    fixups: Vec<Fixup>,
+}
+
+impl_serdeany!(FixupMetadata);
+
+impl FixupMetadata {
+    /// Creates a new, empty [`FixupMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a fixup, if it is not in the list yet, and returns its index
+    pub fn add(&mut self, fixup: Fixup) -> usize {
+        if let Some(idx) = self.fixups.iter().position(|other| *other == fixup) {
+            return idx;
+        }
+        self.fixups.push(fixup);
+        self.fixups.len() - 1
+    }
+
+    /// The fixups, in the order they are applied
+    #[must_use]
+    pub fn fixups(&self) -> &[Fixup] {
+        &self.fixups
+    }
+
+    /// Returns `true` if there are no fixups
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.fixups.is_empty()
+    }
+
+    /// Applies all fixups to the bytes, and returns how many applied.
+    ///
+    /// Fixups whose field or data is out of bounds, e.g. after the input was truncated, are skipped.
+    /// So are fixups whose width is not valid, see [`FixupAlgorithm::has_valid_width`].
+    /// If the field lies within its own data, as the checksum of an IPv4 header does, it is zeroed before computing it.
+    pub fn apply(&self, bytes: &mut [u8]) -> usize {
+        let mut fields: Vec<Option<usize>> = Vec::with_capacity(self.fixups.len());
+        let mut applied = 0;
+        for fixup in &self.fixups {
+            let width = fixup.algorithm.width();
+            let field = self
+                .resolve(fixup.field, bytes, &fields)
+                .filter(|field| fixup.algorithm.has_valid_width() && field + width <= bytes.len());
+            fields.push(field);
+            let (Some(field), Some(start), Some(end)) = (
+                field,
+                self.resolve(fixup.data.start, bytes, &fields),
+                self.resolve(fixup.data.end, bytes, &fields),
+            ) else {
+                continue;
+            };
+            if start > end || matches!(fixup.algorithm, FixupAlgorithm::Field { .. }) {
+                continue;
+            }
+            if field < end && field + width > start {
+                bytes[field..field + width].fill(0);
+            }
+            if let Some(value) = fixup.algorithm.compute(&bytes[start..end]) {
+                write_field(bytes, field, width, fixup.endian, value);
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    /// Resolves a position against the bytes and the fields of the earlier fixups
+    fn resolve(&self, pos: FixupPos, bytes: &[u8], fields: &[Option<usize>]) -> Option<usize> {
+        let pos = match pos {
+            FixupPos::Start(offset) => offset,
+            FixupPos::End(offset) => bytes.len().checked_sub(offset)?,
+            FixupPos::Field { fixup, offset } => (*fields.get(fixup)?)?.checked_add(offset)?,
+            FixupPos::Skip { fixup, offset } => {
+                let field = (*fields.get(fixup)?)?;
+                let other = &self.fixups[fixup];
+                let skip = read_field(bytes, field, other.algorithm.width(), other.endian)?;
+                field
+                    .checked_add(offset)?
+                    .checked_add(usize::try_from(skip).ok()?)?
+            }
+        };
+        (pos <= bytes.len()).then_some(pos)
+    }
+}
+
+/// Input which applies the fixups of its testcase, and of the state, after it was mutated.
+///
+/// Crossover mutators work on the corpus input type, so use it with [`crate::mutators::havoc_mutations_no_crossover`].
+pub type FixupInput = (BytesInput, FixupMetadata);
+
+impl HasBytesVec for FixupInput {
+    fn bytes(&self) -> &[u8] {
+        self.0.bytes()
+    }
+
+    fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        self.0.bytes_mut()
+    }
+}
+
+impl<S> MutatedTransform<BytesInput, S> for FixupInput
+where
+    S: HasCorpus<Input = BytesInput> + HasTestcase + HasMetadata,
+{
+    type Post = FixupMetadata;
+
+    fn try_transform_from(base: &mut Testcase<BytesInput>, state: &S) -> Result<Self, Error> {
+        let input = base.load_input(state.corpus())?.clone();
+        let metadata = base
+            .metadata::<FixupMetadata>()
+            .cloned()
+            .unwrap_or_default();
+        Ok((input, metadata))
+    }
+
+    /// Applies the fixups of the testcase first, then the ones declared in the state
+    fn try_transform_into(self, state: &S) -> Result<(BytesInput, Self::Post), Error> {
+        let (mut input, metadata) = self;
+        metadata.apply(input.bytes_mut());
+        if let Ok(declared) = state.metadata::<FixupMetadata>() {
+            declared.apply(input.bytes_mut());
+        }
+        Ok((input, metadata))
+    }
+}
+
+impl<S> MutatedTransformPost<S> for FixupMetadata
+where
+    S: HasTestcase,
+{
+    fn post_exec(self, state: &mut S, corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        if let Some(corpus_idx) = corpus_idx {
+            if !self.is_empty() {
+                let mut tc = state.testcase_mut(corpus_idx)?;
+                tc.add_metadata(self);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{Fixup, FixupAlgorithm, FixupEndian, FixupInput, FixupMetadata, FixupPos};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasBytesVec},
+        mutators::{havoc_mutations_no_crossover, Mutator, StdScheduledMutator},
+        stages::mutational::MutatedTransform,
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_fixup_png_chunks() {
+        // A PNG chunk: length, type, data, and the CRC of type and data
+        let mut chunk = vec![0, 0, 0, 0];
+        chunk.extend_from_slice(b"IEND");
+        chunk.extend_from_slice(&[0, 0, 0, 0]);
+
+        let mut metadata = FixupMetadata::new();
+        let length = metadata.add(Fixup::new(
+            FixupAlgorithm::Field { width: 4 },
+            FixupPos::Start(0),
+            FixupPos::Start(0)..FixupPos::Start(0),
+        ));
+        metadata.add(Fixup::new(
+            FixupAlgorithm::Crc32,
+            FixupPos::Skip {
+                fixup: length,
+                offset: 8,
+            },
+            FixupPos::Field {
+                fixup: length,
+                offset: 4,
+            }..FixupPos::Skip {
+                fixup: length,
+                offset: 8,
+            },
+        ));
+        assert_eq!(metadata.apply(&mut chunk), 1);
+        assert_eq!(&chunk[8..], &[0xae, 0x42, 0x60, 0x82]);
+
+        // Truncated inputs are left alone
+        let mut truncated = chunk[..10].to_vec();
+        assert_eq!(metadata.apply(&mut truncated), 0);
+    }
+
+    #[test]
+    fn test_fixup_algorithms() {
+        assert_eq!(
+            FixupAlgorithm::Crc32.compute(b"123456789"),
+            Some(0xcbf4_3926)
+        );
+        assert_eq!(
+            FixupAlgorithm::Adler32.compute(b"Wikipedia"),
+            Some(0x11e6_0398)
+        );
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(FixupAlgorithm::IpChecksum.compute(&header), Some(0xb861));
+
+        // A little-endian length field counting the whole input
+        let mut bytes = vec![0xff, 0xff, 1, 2, 3];
+        let mut metadata = FixupMetadata::new();
+        metadata.add(
+            Fixup::new(
+                FixupAlgorithm::Length {
+                    width: 2,
+                    adjust: 0,
+                },
+                FixupPos::Start(0),
+                FixupPos::Start(0)..FixupPos::End(0),
+            )
+            .with_endian(FixupEndian::Little),
+        );
+        metadata.apply(&mut bytes);
+        assert_eq!(bytes, [5, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fixup_ipv4_header() {
+        // The header checksum covers the whole header, the checksum field included
+        let mut header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x12, 0x34, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        let mut metadata = FixupMetadata::new();
+        metadata.add(Fixup::new(
+            FixupAlgorithm::IpChecksum,
+            FixupPos::Start(10),
+            FixupPos::Start(0)..FixupPos::Start(20),
+        ));
+        assert_eq!(metadata.apply(&mut header), 1);
+        assert_eq!(&header[10..12], &[0xb8, 0x61]);
+        // A header with a valid checksum sums up to zero, and applying again keeps it
+        assert_eq!(FixupAlgorithm::IpChecksum.compute(&header), Some(0));
+        assert_eq!(metadata.apply(&mut header), 1);
+        assert_eq!(&header[10..12], &[0xb8, 0x61]);
+    }
+
+    #[test]
+    fn test_fixup_invalid_width() {
+        let mut bytes = vec![0xff; 16];
+        let mut metadata = FixupMetadata::new();
+        for width in [0, 9] {
+            metadata.add(Fixup::new(
+                FixupAlgorithm::Length { width, adjust: 0 },
+                FixupPos::Start(0),
+                FixupPos::Start(0)..FixupPos::End(0),
+            ));
+        }
+        assert_eq!(metadata.apply(&mut bytes), 0);
+        assert_eq!(bytes, [0xff; 16]);
+    }
+
+    #[test]
+    fn test_fixup_input() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        // A trailing CRC-32 of everything before it
+        let mut metadata = FixupMetadata::new();
+        metadata.add(Fixup::new(
+            FixupAlgorithm::Crc32,
+            FixupPos::End(4),
+            FixupPos::Start(0)..FixupPos::End(4),
+        ));
+        let mut testcase = Testcase::new(BytesInput::new(b"some data\0\0\0\0".to_vec()));
+        testcase.add_metadata(metadata);
+        let idx = state.corpus_mut().add(testcase).unwrap();
+
+        let mut mutator = StdScheduledMutator::new(havoc_mutations_no_crossover());
+        for _ in 0..64 {
+            let mut testcase = state.corpus().get(idx).unwrap().borrow_mut();
+            let mut input = FixupInput::try_transform_from(&mut testcase, &state).unwrap();
+            drop(testcase);
+            mutator.mutate(&mut state, &mut input).unwrap();
+            let (input, _) = input.try_transform_into(&state).unwrap();
+            let bytes = input.bytes();
+            if bytes.len() >= 4 {
+                let (data, crc) = bytes.split_at(bytes.len() - 4);
+                assert_eq!(
+                    u64::from(u32::from_be_bytes(crc.try_into().unwrap())),
+                    FixupAlgorithm::Crc32.compute(data).unwrap()
+                );
+            }
+        }
+    }
+}
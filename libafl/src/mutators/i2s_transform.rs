@@ -0,0 +1,742 @@
+//! Input-to-state replacement through the transforms that targets commonly apply to input bytes before comparing them.
+//!
+//! [`I2SRandReplace`](crate::mutators::I2SRandReplace) only finds comparison operands that are copied verbatim from the input.
+//! A target that decodes base64, parses a decimal number, or lowercases its input compares a transformed copy instead.
+//! The [`I2STransformReplace`] mutator finds the operand under such a transform, and writes the transformed other operand.
+use alloc::{borrow::Cow, string::ToString, vec::Vec};
+use core::ops::Range;
+
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    inputs::HasBytesVec,
+    mutators::{MutationResult, Mutator},
+    observers::cmp::{AFLppCmpValuesMetadata, CmpValues, CmpValuesMetadata},
+    state::{HasMaxSize, HasRand},
+    Error, HasMetadata,
+};
+
+/// Shorter operands match by chance too often under the transforms that infer a key, or that decode
+const MIN_TRANSFORM_LEN: usize = 2;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A transform that a target may apply to input bytes before it compares them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum I2STransform {
+    /// The input holds the base64 encoding of the compared bytes
+    Base64,
+    /// The input holds the hex encoding of the compared bytes
+    Hex,
+    /// The input holds the URL encoding of the compared bytes
+    Url,
+    /// The input holds a compared integer as ASCII decimal, like `atoi` parses it
+    Decimal,
+    /// The input holds the compared bytes as UTF-16, or the target compares the input widened to UTF-16
+    Utf16,
+    /// The input holds the compared bytes, XOR-ed with a constant
+    Xor,
+    /// The input holds the compared bytes, minus a constant
+    Add,
+    /// The target compares the input case-insensitively, or after changing its case
+    CaseFold,
+}
+
+impl I2STransform {
+    /// All transforms
+    pub const ALL: [Self; 8] = [
+        Self::Base64,
+        Self::Hex,
+        Self::Url,
+        Self::Decimal,
+        Self::Utf16,
+        Self::Xor,
+        Self::Add,
+        Self::CaseFold,
+    ];
+
+    /// Finds where the input holds one operand of the comparison under this transform, at or after `off`.
+    ///
+    /// Returns the range in the input, and the bytes that hold the other operand under this transform instead.
+    #[must_use]
+    pub fn find_replacement(
+        self,
+        bytes: &[u8],
+        off: usize,
+        cmp: &CmpValues,
+    ) -> Option<(Range<usize>, Vec<u8>)> {
+        if let CmpValues::Bytes((left, right)) = cmp {
+            // Logged operands of string functions are cut at a fixed size, after the terminator
+            let (trimmed_left, trimmed_right) = (trim_terminator(left), trim_terminator(right));
+            return self
+                .find_operands(bytes, off, trimmed_left, trimmed_right)
+                .or_else(|| self.find_operands(bytes, off, trimmed_right, trimmed_left))
+                .or_else(|| self.find_operands(bytes, off, left, right))
+                .or_else(|| self.find_operands(bytes, off, right, left));
+        }
+        let width = match cmp {
+            CmpValues::U8(_) => 1,
+            CmpValues::U16(_) => 2,
+            CmpValues::U32(_) => 4,
+            _ => 8,
+        };
+        let (left, right) = cmp.to_u64_tuple()?;
+        self.find_integers(bytes, off, width, left, right)
+            .or_else(|| self.find_integers(bytes, off, width, right, left))
+    }
+
+    /// Finds the integer `pattern` of the given width under this transform, to replace it with `repl`
+    fn find_integers(
+        self,
+        bytes: &[u8],
+        off: usize,
+        width: usize,
+        pattern: u64,
+        repl: u64,
+    ) -> Option<(Range<usize>, Vec<u8>)> {
+        match self {
+            Self::Decimal => {
+                let shift = 64 - 8 * width as u32;
+                // Negative numbers are logged as their two's complement
+                let signed = |value: u64| {
+                    (i64::from_ne_bytes((value << shift).to_ne_bytes()) >> shift).to_string()
+                };
+                find_decimal(bytes, off, &pattern.to_string(), &repl.to_string())
+                    .or_else(|| find_decimal(bytes, off, &signed(pattern), &signed(repl)))
+            }
+            Self::Xor | Self::Add => {
+                if width < MIN_TRANSFORM_LEN || pattern == repl {
+                    return None;
+                }
+                // Any word is the pattern under some key, so prefer a word whose bytes share one key byte.
+                // Else take the little-endian word at `off`.
+                [false, true]
+                    .into_iter()
+                    .find_map(|big_endian| {
+                        let pattern = word_bytes(pattern, width, big_endian);
+                        find_keyed(bytes, off, &pattern, |input, cmp| self.byte_key(input, cmp))
+                            .map(|(pos, _)| (pos, big_endian))
+                    })
+                    .or_else(|| {
+                        find_from(bytes, off, |window| window.len() >= width)
+                            .map(|pos| (pos, false))
+                    })
+                    .and_then(|(pos, big_endian)| {
+                        self.replace_word(bytes, pos, width, big_endian, pattern, repl)
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    /// Infers the key of the word at `pos` from `pattern`, and returns the word that holds `repl` under that key
+    fn replace_word(
+        self,
+        bytes: &[u8],
+        pos: usize,
+        width: usize,
+        big_endian: bool,
+        pattern: u64,
+        repl: u64,
+    ) -> Option<(Range<usize>, Vec<u8>)> {
+        let window = &bytes[pos..pos + width];
+        let mut word = [0; 8];
+        let input = if big_endian {
+            word[8 - width..].copy_from_slice(window);
+            u64::from_be_bytes(word)
+        } else {
+            word[..width].copy_from_slice(window);
+            u64::from_le_bytes(word)
+        };
+        let mask = u64::MAX >> (64 - 8 * width);
+        let (key, repl) = if self == Self::Add {
+            let key = pattern.wrapping_sub(input) & mask;
+            (key, repl.wrapping_sub(key) & mask)
+        } else {
+            let key = input ^ pattern;
+            (key, repl ^ key)
+        };
+        // A zero key is a plain input-to-state match
+        (key != 0).then(|| (pos..pos + width, word_bytes(repl, width, big_endian)))
+    }
+
+    /// The key that transforms an input byte into the compared byte, under [`I2STransform::Xor`] or [`I2STransform::Add`]
+    fn byte_key(self, input: u8, cmp: u8) -> u8 {
+        if self == Self::Add {
+            cmp.wrapping_sub(input)
+        } else {
+            input ^ cmp
+        }
+    }
+
+    /// Finds the bytes `pattern` under this transform, to replace them with `repl`
+    fn find_operands(
+        self,
+        bytes: &[u8],
+        off: usize,
+        pattern: &[u8],
+        repl: &[u8],
+    ) -> Option<(Range<usize>, Vec<u8>)> {
+        if pattern.len() < MIN_TRANSFORM_LEN || pattern == repl {
+            return None;
+        }
+        match self {
+            Self::Base64 => find_decoded(
+                bytes,
+                off,
+                pattern,
+                repl,
+                |byte| BASE64_ALPHABET.contains(&byte),
+                Some(b'='),
+                base64_decode,
+                base64_encode,
+            ),
+            Self::Hex => find_decoded(
+                bytes,
+                off,
+                pattern,
+                repl,
+                |byte| byte.is_ascii_hexdigit(),
+                None,
+                hex_decode,
+                hex_encode,
+            ),
+            Self::Url => {
+                let encoded = url_encode(pattern);
+                // Without anything to encode, this is a plain input-to-state match
+                if encoded == pattern {
+                    return None;
+                }
+                find_from(bytes, off, |window| window.starts_with(&encoded))
+                    .map(|pos| (pos..pos + encoded.len(), url_encode(repl)))
+            }
+            Self::Utf16 => {
+                if let (Some(pattern), Some(repl)) = (utf16_narrow(pattern), utf16_narrow(repl)) {
+                    // The target widened the input
+                    return find_from(bytes, off, |window| window.starts_with(&pattern))
+                        .map(|pos| (pos..pos + pattern.len(), repl));
+                }
+                [false, true].into_iter().find_map(|big_endian| {
+                    let wide = utf16_widen(pattern, big_endian);
+                    find_from(bytes, off, |window| window.starts_with(&wide))
+                        .map(|pos| (pos..pos + wide.len(), utf16_widen(repl, big_endian)))
+                })
+            }
+            Self::Xor => find_keyed(bytes, off, pattern, |input, cmp| self.byte_key(input, cmp))
+                .map(|(pos, key)| {
+                    let repl = repl.iter().map(|byte| byte ^ key).collect();
+                    (pos..pos + pattern.len(), repl)
+                }),
+            Self::Add => find_keyed(bytes, off, pattern, |input, cmp| self.byte_key(input, cmp))
+                .map(|(pos, key)| {
+                    let repl = repl.iter().map(|byte| byte.wrapping_sub(key)).collect();
+                    (pos..pos + pattern.len(), repl)
+                }),
+            Self::CaseFold => {
+                if !pattern.iter().any(u8::is_ascii_alphabetic) {
+                    return None;
+                }
+                find_from(bytes, off, |window| {
+                    window.len() >= pattern.len()
+                        && window[..pattern.len()].eq_ignore_ascii_case(pattern)
+                        && &window[..pattern.len()] != pattern
+                })
+                .map(|pos| (pos..pos + pattern.len(), repl.to_vec()))
+            }
+            Self::Decimal => None,
+        }
+    }
+}
+
+/// Cuts a logged string operand at its terminator, which is two bytes for UTF-16
+fn trim_terminator(bytes: &[u8]) -> &[u8] {
+    let end = if bytes.len() >= 2 && bytes[0] != 0 && bytes[1] == 0 {
+        bytes
+            .chunks_exact(2)
+            .position(|pair| pair == [0, 0])
+            .map_or(bytes.len(), |pos| pos * 2)
+    } else {
+        bytes
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(bytes.len())
+    };
+    &bytes[..end]
+}
+
+/// The first position at or after `off` where `matches` holds for the rest of the input, wrapping around to the start
+fn find_from<F>(bytes: &[u8], off: usize, matches: F) -> Option<usize>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    (off..bytes.len())
+        .chain(0..off.min(bytes.len()))
+        .find(|pos| matches(&bytes[*pos..]))
+}
+
+/// The bytes of an integer of the given width
+fn word_bytes(value: u64, width: usize, big_endian: bool) -> Vec<u8> {
+    if big_endian {
+        value.to_be_bytes()[8 - width..].to_vec()
+    } else {
+        value.to_le_bytes()[..width].to_vec()
+    }
+}
+
+/// Finds a decimal number that is not part of a longer one
+fn find_decimal(
+    bytes: &[u8],
+    off: usize,
+    pattern: &str,
+    repl: &str,
+) -> Option<(Range<usize>, Vec<u8>)> {
+    let pattern = pattern.as_bytes();
+    let is_digit = |pos: usize| bytes.get(pos).is_some_and(u8::is_ascii_digit);
+    find_from(bytes, off, |window| {
+        let pos = bytes.len() - window.len();
+        window.starts_with(pattern)
+            && (pos == 0 || !is_digit(pos - 1))
+            && !is_digit(pos + pattern.len())
+    })
+    .map(|pos| (pos..pos + pattern.len(), repl.as_bytes().to_vec()))
+}
+
+/// Finds the pattern, transformed with a key that is inferred from the first byte
+fn find_keyed<F>(bytes: &[u8], off: usize, pattern: &[u8], key: F) -> Option<(usize, u8)>
+where
+    F: Fn(u8, u8) -> u8,
+{
+    let pos = find_from(bytes, off, |window| {
+        window.len() >= pattern.len() && {
+            let k = key(window[0], pattern[0]);
+            // A zero key is a plain input-to-state match
+            k != 0
+                && window
+                    .iter()
+                    .zip(pattern)
+                    .all(|(input, cmp)| key(*input, *cmp) == k)
+        }
+    })?;
+    Some((pos, key(bytes[pos], pattern[0])))
+}
+
+/// Decodes each run of encoded bytes, and replaces `pattern` where it is found in the decoded run.
+///
+/// Runs that end after `off` are tried first, then the ones before it, like [`find_from`] wraps around.
+#[allow(clippy::too_many_arguments)]
+fn find_decoded<A, D, E>(
+    bytes: &[u8],
+    off: usize,
+    pattern: &[u8],
+    repl: &[u8],
+    in_alphabet: A,
+    padding: Option<u8>,
+    decode: D,
+    encode: E,
+) -> Option<(Range<usize>, Vec<u8>)>
+where
+    A: Fn(u8) -> bool,
+    D: Fn(&[u8]) -> Option<Vec<u8>>,
+    E: Fn(&[u8], &[u8]) -> Vec<u8>,
+{
+    for wrapped in [false, true] {
+        let mut start = 0;
+        while start < bytes.len() {
+            if !in_alphabet(bytes[start]) {
+                start += 1;
+                continue;
+            }
+            let mut end = start
+                + bytes[start..]
+                    .iter()
+                    .position(|byte| !in_alphabet(*byte))
+                    .unwrap_or(bytes.len() - start);
+            while end < bytes.len() && Some(bytes[end]) == padding {
+                end += 1;
+            }
+            let run = &bytes[start..end];
+            if (end > off) != wrapped {
+                if let Some(mut decoded) = decode(run) {
+                    if let Some(pos) = decoded
+                        .windows(pattern.len())
+                        .position(|window| window == pattern)
+                    {
+                        decoded.splice(pos..pos + pattern.len(), repl.iter().copied());
+                        return Some((start..end, encode(&decoded, run)));
+                    }
+                }
+            }
+            start = end;
+        }
+    }
+    None
+}
+
+fn base64_decode(run: &[u8]) -> Option<Vec<u8>> {
+    let run = &run[..run
+        .iter()
+        .position(|byte| *byte == b'=')
+        .unwrap_or(run.len())];
+    let mut decoded = Vec::with_capacity(run.len() * 3 / 4);
+    for quantum in run.chunks(4) {
+        if quantum.len() < 2 {
+            break;
+        }
+        let mut bits = 0u32;
+        for (i, byte) in quantum.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|c| c == byte)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        decoded.extend_from_slice(&bits.to_be_bytes()[1..quantum.len()]);
+    }
+    Some(decoded)
+}
+
+/// Encodes in base64, padded if the original run was
+fn base64_encode(decoded: &[u8], run: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(decoded.len().div_ceil(3) * 4);
+    for chunk in decoded.chunks(3) {
+        let mut bits = [0; 4];
+        bits[1..=chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes(bits);
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64_ALPHABET[((bits >> (18 - 6 * i)) & 0x3f) as usize]);
+        }
+        if run.ends_with(b"=") {
+            encoded.resize(encoded.len() + 3 - chunk.len(), b'=');
+        }
+    }
+    // A single character at the end of the run was not decoded, keep it
+    let unpadded = run
+        .iter()
+        .position(|byte| *byte == b'=')
+        .unwrap_or(run.len());
+    if unpadded % 4 == 1 {
+        encoded.push(run[unpadded - 1]);
+    }
+    encoded
+}
+
+fn hex_decode(run: &[u8]) -> Option<Vec<u8>> {
+    let digit = |byte: u8| char::from(byte).to_digit(16).map(|digit| digit as u8);
+    run.chunks_exact(2)
+        .map(|pair| Some((digit(pair[0])? << 4) | digit(pair[1])?))
+        .collect()
+}
+
+/// Encodes in hex, in the case of the original run
+fn hex_encode(decoded: &[u8], run: &[u8]) -> Vec<u8> {
+    let digits: &[u8; 16] = if run.iter().any(u8::is_ascii_uppercase) {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    let mut encoded: Vec<u8> = decoded
+        .iter()
+        .flat_map(|byte| {
+            [
+                digits[usize::from(byte >> 4)],
+                digits[usize::from(byte & 0xf)],
+            ]
+        })
+        .collect();
+    // An odd digit at the end of the run was not decoded, keep it
+    if run.len() % 2 == 1 {
+        encoded.push(run[run.len() - 1]);
+    }
+    encoded
+}
+
+/// Percent-encodes all but the unreserved characters
+fn url_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len());
+    for byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(byte) {
+            encoded.push(*byte);
+        } else {
+            encoded.push(b'%');
+            encoded.extend_from_slice(&hex_encode(&[*byte], b"AA"));
+        }
+    }
+    encoded
+}
+
+fn utf16_widen(bytes: &[u8], big_endian: bool) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| if big_endian { [0, *byte] } else { [*byte, 0] })
+        .collect()
+}
+
+/// Narrows UTF-16LE with only ASCII characters
+fn utf16_narrow(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.len() % 2 == 1 {
+        return None;
+    }
+    bytes
+        .chunks_exact(2)
+        .map(|pair| (pair[0] != 0 && pair[1] == 0).then_some(pair[0]))
+        .collect()
+}
+
+/// An input-to-state [`Mutator`] that replaces a random comparison operand, found under one of the [`I2STransform`]s.
+///
+/// It uses the [`CmpValuesMetadata`] of a `CmpLogObserver`, or the [`AFLppCmpValuesMetadata`] of an `AFLppCmpLogObserver`.
+#[derive(Debug)]
+pub struct I2STransformReplace {
+    transforms: Vec<I2STransform>,
+}
+
+impl Default for I2STransformReplace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl I2STransformReplace {
+    /// Creates a new [`I2STransformReplace`], that tries all transforms.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_transforms(I2STransform::ALL.to_vec())
+    }
+
+    /// Creates a new [`I2STransformReplace`], that tries only the given transforms.
+    #[must_use]
+    pub fn with_transforms(transforms: Vec<I2STransform>) -> Self {
+        Self { transforms }
+    }
+}
+
+impl<I, S> Mutator<I, S> for I2STransformReplace
+where
+    S: HasMetadata + HasRand + HasMaxSize,
+    I: HasBytesVec,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let size = input.bytes().len();
+        if size == 0 || self.transforms.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let (cmplog_len, aflpp_len) = {
+            let cmplog_len = state
+                .metadata_map()
+                .get::<CmpValuesMetadata>()
+                .map_or(0, |meta| meta.list.len());
+            let aflpp_len = state
+                .metadata_map()
+                .get::<AFLppCmpValuesMetadata>()
+                .map_or(0, |meta| meta.orig_cmpvals().values().map(Vec::len).sum());
+            (cmplog_len, aflpp_len)
+        };
+        let cmp_values = if cmplog_len > 0 {
+            let idx = state.rand_mut().below(cmplog_len);
+            state.metadata::<CmpValuesMetadata>()?.list[idx].clone()
+        } else if aflpp_len > 0 {
+            let idx = state.rand_mut().below(aflpp_len);
+            let meta = state.metadata::<AFLppCmpValuesMetadata>()?;
+            meta.orig_cmpvals()
+                .values()
+                .flatten()
+                .nth(idx)
+                .unwrap()
+                .clone()
+        } else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let transform = *state.rand_mut().choose(&self.transforms);
+        let off = state.rand_mut().below(size);
+        let Some((range, repl)) = transform.find_replacement(input.bytes(), off, &cmp_values)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        if size - range.len() + repl.len() > state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        input.bytes_mut().splice(range, repl);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for I2STransformReplace {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("I2STransformReplace");
+        &NAME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{I2STransform, I2STransformReplace};
+    use crate::{
+        corpus::InMemoryCorpus,
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasBytesVec},
+        mutators::{MutationResult, Mutator},
+        observers::cmp::{AFLppCmpValuesMetadata, CmpValues, CmpValuesMetadata},
+        state::StdState,
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_i2s_transforms() {
+        let cases: [(I2STransform, &[u8], CmpValues, &[u8]); 11] = [
+            // base64 of "hello world" is compared to "HELLO world"
+            (
+                I2STransform::Base64,
+                b"data=aGVsbG8gd29ybGQ=;",
+                CmpValues::Bytes((b"hello world\0abc".to_vec(), b"HELLO world\0".to_vec())),
+                b"data=SEVMTE8gd29ybGQ=;",
+            ),
+            (
+                I2STransform::Hex,
+                b"key: 00c0ffee!",
+                CmpValues::Bytes((vec![0xc0, 0xff], vec![0xde, 0xad])),
+                b"key: 00deadee!",
+            ),
+            (
+                I2STransform::Url,
+                b"GET /?q=a%20b%26c",
+                CmpValues::Bytes((b"a b&c".to_vec(), b"x=y".to_vec())),
+                b"GET /?q=x%3Dy",
+            ),
+            (
+                I2STransform::Decimal,
+                b"len: -5, n: 12345",
+                CmpValues::U32((0xffff_fffb, 1234)),
+                b"len: 1234, n: 12345",
+            ),
+            // Only the number on its own is replaced, wherever the search starts
+            (
+                I2STransform::Decimal,
+                b"id: 12345, n: 123",
+                CmpValues::U32((123, 77)),
+                b"id: 12345, n: 77",
+            ),
+            (
+                I2STransform::Utf16,
+                b"\xff\xfea\0d\0m\0i\0n\0",
+                CmpValues::Bytes((b"admin".to_vec(), b"root".to_vec())),
+                b"\xff\xfer\0o\0o\0t\0",
+            ),
+            // The target widens the input, and compares wide strings
+            (
+                I2STransform::Utf16,
+                b"user=guest",
+                CmpValues::Bytes((b"g\0u\0e\0s\0t\0".to_vec(), b"r\0o\0o\0t\0".to_vec())),
+                b"user=root",
+            ),
+            (
+                I2STransform::Xor,
+                &[0x42, 0x13 ^ 0xaa, 0x37 ^ 0xaa, 0x42],
+                CmpValues::U16((0x3713, 0xbeef)),
+                &[0x42, 0xef ^ 0xaa, 0xbe ^ 0xaa, 0x42],
+            ),
+            (
+                I2STransform::Add,
+                b"\x01HELLO",
+                CmpValues::Bytes((b"IFMMP".to_vec(), b"XPSME".to_vec())),
+                b"\x01WORLD",
+            ),
+            (
+                I2STransform::CaseFold,
+                b"cmd=SeLeCt",
+                CmpValues::Bytes((b"select".to_vec(), b"delete".to_vec())),
+                b"cmd=delete",
+            ),
+            // Big-endian integers under XOR
+            (
+                I2STransform::Xor,
+                &[0x12 ^ 0x55, 0x34 ^ 0x55, 0x56 ^ 0x55, 0x78 ^ 0x55],
+                CmpValues::U32((0x1234_5678, 0xcafe_babe)),
+                &[0xca ^ 0x55, 0xfe ^ 0x55, 0xba ^ 0x55, 0xbe ^ 0x55],
+            ),
+        ];
+
+        for (aflpp, (transform, input, cmp, expected)) in cases.into_iter().enumerate() {
+            let mut state = StdState::new(
+                StdRand::with_seed(1337),
+                InMemoryCorpus::<BytesInput>::new(),
+                InMemoryCorpus::new(),
+                &mut ConstFeedback::new(false),
+                &mut ConstFeedback::new(false),
+            )
+            .unwrap();
+            // Use the metadata of both cmplog observers
+            if aflpp % 2 == 0 {
+                let mut meta = CmpValuesMetadata::new();
+                meta.list.push(cmp.clone());
+                state.add_metadata(meta);
+            } else {
+                let mut meta = AFLppCmpValuesMetadata::new();
+                meta.orig_cmpvals.insert(0, vec![cmp.clone()]);
+                state.add_metadata(meta);
+            }
+
+            let mut mutator = I2STransformReplace::with_transforms(vec![transform]);
+            let mut mutant = BytesInput::new(input.to_vec());
+            assert_eq!(
+                mutator.mutate(&mut state, &mut mutant).unwrap(),
+                MutationResult::Mutated,
+                "{transform:?} did not find {cmp:?}"
+            );
+            assert_eq!(mutant.bytes(), expected, "{transform:?}");
+        }
+    }
+
+    #[test]
+    fn test_i2s_integer_keys() {
+        // Keys wider than a byte, with carries between the bytes under addition
+        let input = 0x1234_5678_u32.wrapping_sub(0x0001_0280).to_le_bytes();
+        let (range, repl) = I2STransform::Add
+            .find_replacement(&input, 2, &CmpValues::U32((0x1234_5678, 0xcafe_babe)))
+            .unwrap();
+        assert_eq!(range, 0..4);
+        assert_eq!(
+            repl,
+            0xcafe_babe_u32.wrapping_sub(0x0001_0280).to_le_bytes()
+        );
+
+        let input = [0x00, 0x1234_u16 ^ 0xbeef, 0x42]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        let (range, repl) = I2STransform::Xor
+            .find_replacement(&input, 2, &CmpValues::U16((0x1234, 0xabcd)))
+            .unwrap();
+        assert_eq!(range, 2..4);
+        assert_eq!(repl, (0xabcd_u16 ^ 0xbeef).to_le_bytes());
+
+        let input = (0x0102_0304_0506_0708_u64 ^ 0xdead_beef_0bad_f00d).to_le_bytes();
+        let (range, repl) = I2STransform::Xor
+            .find_replacement(&input, 0, &CmpValues::U64((0x0102_0304_0506_0708, 7)))
+            .unwrap();
+        assert_eq!(range, 0..8);
+        assert_eq!(repl, (7 ^ 0xdead_beef_0bad_f00d_u64).to_le_bytes());
+    }
+
+    #[test]
+    fn test_i2s_decoded_runs() {
+        let cmp = CmpValues::Bytes((b"hello".to_vec(), b"HELLO".to_vec()));
+        // The search wraps around to runs before the offset
+        let input = b"aGVsbG8= ...";
+        let (range, repl) = I2STransform::Base64
+            .find_replacement(input, input.len() - 1, &cmp)
+            .unwrap();
+        assert_eq!(range, 0..8);
+        assert_eq!(repl, b"SEVMTE8=");
+
+        // A trailing character that does not decode on its own is kept
+        let (range, repl) = I2STransform::Base64
+            .find_replacement(b"aGVsbG8hQ", 0, &cmp)
+            .unwrap();
+        assert_eq!(range, 0..9);
+        assert_eq!(repl, b"SEVMTE8hQ");
+    }
+}
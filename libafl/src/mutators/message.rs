@@ -0,0 +1,685 @@
+//! Mutators for [`MessageInput`]s, that follow the [`MessageSchema`] in the state metadata.
+//!
+//! Mutations pick a random message anywhere in the tree of nested messages, so that deeply nested fields
+//! get mutated as often as top-level ones. Crossover mutations take fields and sub-messages from other corpus entries.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::{
+    rands::Rand,
+    tuples::{tuple_list, tuple_list_type},
+    Named,
+};
+
+use crate::{
+    corpus::Corpus,
+    inputs::{
+        message::{with_schema, FieldLabel, FieldType, FieldValue, MessageValue, Value},
+        MessageInput, MessageSchema,
+    },
+    mutators::{MutationResult, Mutator},
+    random_corpus_id,
+    state::{HasCorpus, HasRand},
+    Error, HasMetadata,
+};
+
+/// Integers around the boundaries of the integer types, cast to the type of the mutated field
+const INTERESTING_INTS: [i64; 15] = [
+    0,
+    1,
+    -1,
+    -128,
+    127,
+    255,
+    -32768,
+    32767,
+    65535,
+    i32::MIN as i64,
+    i32::MAX as i64,
+    u32::MAX as i64,
+    u32::MAX as i64 + 1,
+    i64::MIN,
+    i64::MAX,
+];
+
+/// Choose a random message in the tree below `message`, including `message` itself, for which `pred` holds
+fn choose_message_mut<'a, R, P>(
+    rand: &mut R,
+    message: &'a mut MessageValue,
+    pred: &P,
+) -> Option<&'a mut MessageValue>
+where
+    R: Rand,
+    P: Fn(&MessageValue) -> bool,
+{
+    let count = message.count_messages(pred);
+    if count == 0 {
+        return None;
+    }
+    let n = rand.below(count);
+    message.nth_message_mut(n, pred)
+}
+
+/// Choose a random value in the tree below `message`, for which `pred` holds
+fn choose_value_mut<'a, R, P>(
+    rand: &mut R,
+    message: &'a mut MessageValue,
+    pred: &P,
+) -> Option<&'a mut Value>
+where
+    R: Rand,
+    P: Fn(&Value) -> bool,
+{
+    let count = message.count_values(pred);
+    if count == 0 {
+        return None;
+    }
+    let mut n = rand.below(count);
+    message.nth_value_mut(&mut n, pred)
+}
+
+/// Load the input of a random corpus entry, or `None` if the corpus is empty
+fn random_corpus_input<S>(state: &mut S) -> Result<Option<MessageInput>, Error>
+where
+    S: HasCorpus<Input = MessageInput> + HasRand,
+{
+    if state.corpus().count() == 0 {
+        return Ok(None);
+    }
+    let idx = random_corpus_id!(state.corpus(), state.rand_mut());
+    let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+    Ok(Some(other_testcase.load_input(state.corpus())?.clone()))
+}
+
+/// The fields that can be added to `message`: the missing and the repeated ones
+fn addable_fields<'a>(
+    schema: &'a MessageSchema,
+    message: &'a MessageValue,
+) -> impl Iterator<Item = usize> + 'a {
+    schema
+        .messages()
+        .get(message.message)
+        .into_iter()
+        .flat_map(|descriptor| descriptor.fields.iter().enumerate())
+        .filter(|(idx, field)| field.label == FieldLabel::Repeated || message.field(*idx).is_none())
+        .map(|(idx, _)| idx)
+}
+
+/// If a field can be added to `message`
+fn can_add_field(schema: &MessageSchema, message: &MessageValue) -> bool {
+    addable_fields(schema, message).next().is_some()
+}
+
+/// If `field` of `message` can be removed, which only required fields can't
+fn is_removable(schema: &MessageSchema, message: &MessageValue, field: &FieldValue) -> bool {
+    !matches!(
+        schema
+            .messages()
+            .get(message.message)
+            .and_then(|descriptor| descriptor.fields.get(field.field)),
+        Some(descriptor) if descriptor.label == FieldLabel::Required
+    )
+}
+
+/// If a field can be removed from `message`
+fn can_remove_field(schema: &MessageSchema, message: &MessageValue) -> bool {
+    message
+        .fields
+        .iter()
+        .any(|field| is_removable(schema, message, field))
+}
+
+/// The variants of `field` of `message`, if it is an enum
+fn enum_variants<'a>(
+    schema: &'a MessageSchema,
+    message: &MessageValue,
+    field: &FieldValue,
+) -> Option<&'a [i32]> {
+    match schema
+        .messages()
+        .get(message.message)
+        .and_then(|descriptor| descriptor.fields.get(field.field))
+        .map(|descriptor| &descriptor.ty)
+    {
+        Some(FieldType::Enum(numbers)) => Some(numbers),
+        _ => None,
+    }
+}
+
+/// If `message` has an enum field
+fn has_enum_field(schema: &MessageSchema, message: &MessageValue) -> bool {
+    message
+        .fields
+        .iter()
+        .any(|field| enum_variants(schema, message, field).is_some())
+}
+
+/// Adds a field that is missing, or another value to a repeated field, to a random message
+#[derive(Default, Debug)]
+pub struct MessageFieldAddMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageFieldAddMutator
+where
+    S: HasRand + HasMetadata,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let count = {
+            let schema = state.metadata::<MessageSchema>()?;
+            input
+                .message()
+                .count_messages(&|message| can_add_field(schema, message))
+        };
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let n = state.rand_mut().below(count);
+
+        let schema = state.metadata::<MessageSchema>()?;
+        let Some(message) = input
+            .message_mut()
+            .nth_message_mut(n, &|message| can_add_field(schema, message))
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let candidates: Vec<usize> = addable_fields(schema, message).collect();
+        let idx = *state.rand_mut().choose(&candidates);
+
+        let schema = state.metadata::<MessageSchema>()?;
+        let field = schema.messages()[message.message].fields[idx].clone();
+        let depth = schema.max_depth() / 2;
+        // Generating a value needs the schema and the rand at the same time
+        let value = with_schema(state, |state, schema| {
+            schema.random_value(state.rand_mut(), &field.ty, depth)
+        })?;
+
+        if let Some(present) = message.fields.iter_mut().find(|f| f.field == idx) {
+            let pos = state.rand_mut().below(present.values.len() + 1);
+            present.values.insert(pos, value);
+        } else {
+            message
+                .fields
+                .push(FieldValue::new(idx, &field, vec![value]));
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageFieldAddMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageFieldAddMutator");
+        &NAME
+    }
+}
+
+impl MessageFieldAddMutator {
+    /// Creates a new [`MessageFieldAddMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Removes a field, or a single value of a repeated field, from a random message.
+///
+/// Required fields are never removed.
+#[derive(Default, Debug)]
+pub struct MessageFieldRemoveMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageFieldRemoveMutator
+where
+    S: HasRand + HasMetadata,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let count = {
+            let schema = state.metadata::<MessageSchema>()?;
+            input
+                .message()
+                .count_messages(&|message| can_remove_field(schema, message))
+        };
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let n = state.rand_mut().below(count);
+
+        let schema = state.metadata::<MessageSchema>()?;
+        let Some(message) = input
+            .message_mut()
+            .nth_message_mut(n, &|message| can_remove_field(schema, message))
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let candidates: Vec<usize> = message
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| is_removable(schema, message, field))
+            .map(|(pos, _)| pos)
+            .collect();
+        let pos = *state.rand_mut().choose(&candidates);
+        let field = &mut message.fields[pos];
+        if field.values.len() > 1 {
+            let value = state.rand_mut().below(field.values.len());
+            field.values.remove(value);
+        } else {
+            message.fields.remove(pos);
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageFieldRemoveMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageFieldRemoveMutator");
+        &NAME
+    }
+}
+
+impl MessageFieldRemoveMutator {
+    /// Creates a new [`MessageFieldRemoveMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random scalar value with a random value of the same type
+#[derive(Default, Debug)]
+pub struct MessageFieldReplaceMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageFieldReplaceMutator
+where
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let pred = |value: &Value| !matches!(value, Value::Enum(_) | Value::Message(_));
+        let Some(value) = choose_value_mut(state.rand_mut(), input.message_mut(), &pred) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let replacement = value.randomized(state.rand_mut());
+        if *value == replacement {
+            return Ok(MutationResult::Skipped);
+        }
+        *value = replacement;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageFieldReplaceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageFieldReplaceMutator");
+        &NAME
+    }
+}
+
+impl MessageFieldReplaceMutator {
+    /// Creates a new [`MessageFieldReplaceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Switches a random enum value to another variant, or, rarely, to a number that is no variant at all
+#[derive(Default, Debug)]
+pub struct MessageEnumMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageEnumMutator
+where
+    S: HasRand + HasMetadata,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let count = {
+            let schema = state.metadata::<MessageSchema>()?;
+            input
+                .message()
+                .count_messages(&|message| has_enum_field(schema, message))
+        };
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let n = state.rand_mut().below(count);
+
+        let schema = state.metadata::<MessageSchema>()?;
+        let Some(message) = input
+            .message_mut()
+            .nth_message_mut(n, &|message| has_enum_field(schema, message))
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let candidates: Vec<(usize, Vec<i32>)> = message
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, field)| {
+                enum_variants(schema, message, field).map(|numbers| (pos, numbers.to_vec()))
+            })
+            .collect();
+        let rand = state.rand_mut();
+        let (pos, numbers) = rand.choose(&candidates).clone();
+        let values = &mut message.fields[pos].values;
+        let value = rand.choose(values);
+        #[allow(clippy::cast_possible_truncation)]
+        let number = if numbers.is_empty() || rand.below(8) == 0 {
+            rand.next() as i32
+        } else {
+            *rand.choose(&numbers)
+        };
+        if *value == Value::Enum(number) {
+            return Ok(MutationResult::Skipped);
+        }
+        *value = Value::Enum(number);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageEnumMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageEnumMutator");
+        &NAME
+    }
+}
+
+impl MessageEnumMutator {
+    /// Creates a new [`MessageEnumMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Sets a random integer value to a boundary value, such as `0`, `-1`, or the minimum and maximum of an integer type
+#[derive(Default, Debug)]
+pub struct MessageIntBoundaryMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageIntBoundaryMutator
+where
+    S: HasRand,
+{
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_lossless
+    )]
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let Some(value) =
+            choose_value_mut(state.rand_mut(), input.message_mut(), &Value::is_integer)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let int = *state.rand_mut().choose(&INTERESTING_INTS);
+        let boundary = match value {
+            Value::Int32(_) => Value::Int32(int as i32),
+            Value::Int64(_) => Value::Int64(int),
+            Value::Uint32(_) => Value::Uint32(int as u32),
+            Value::Uint64(_) => Value::Uint64(int as u64),
+            Value::Sint32(_) => Value::Sint32(int as i32),
+            Value::Sint64(_) => Value::Sint64(int),
+            Value::Fixed32(_) => Value::Fixed32(int as u32),
+            Value::Fixed64(_) => Value::Fixed64(int as u64),
+            Value::Sfixed32(_) => Value::Sfixed32(int as i32),
+            Value::Sfixed64(_) => Value::Sfixed64(int),
+            _ => return Ok(MutationResult::Skipped),
+        };
+        if *value == boundary {
+            return Ok(MutationResult::Skipped);
+        }
+        *value = boundary;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageIntBoundaryMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageIntBoundaryMutator");
+        &NAME
+    }
+}
+
+impl MessageIntBoundaryMutator {
+    /// Creates a new [`MessageIntBoundaryMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a range of the values of a repeated field with a range of the values of the same field
+/// in another corpus entry, or in the input itself if the corpus is empty
+#[derive(Default, Debug)]
+pub struct MessageRepeatedSpliceMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageRepeatedSpliceMutator
+where
+    S: HasCorpus<Input = MessageInput> + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let pred = |message: &MessageValue| message.fields.iter().any(|field| field.repeated);
+        let count = input.message().count_messages(&pred);
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let n = state.rand_mut().below(count);
+        let (message_type, field) = {
+            let message = input.message_mut().nth_message_mut(n, &pred).unwrap();
+            let repeated: Vec<usize> = message
+                .fields
+                .iter()
+                .filter(|field| field.repeated)
+                .map(|field| field.field)
+                .collect();
+            (message.message, *state.rand_mut().choose(&repeated))
+        };
+
+        let mut donor = match random_corpus_input(state)? {
+            Some(other) => other,
+            None => input.clone(),
+        };
+        let donor_pred = |message: &MessageValue| {
+            message.message == message_type && message.field(field).is_some()
+        };
+        let Some(donor) = choose_message_mut(state.rand_mut(), donor.message_mut(), &donor_pred)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let donor = &donor.field(field).unwrap().values;
+        if donor.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let from = state.rand_mut().below(donor.len());
+        let len = 1 + state.rand_mut().below(donor.len() - from);
+        let splice = donor[from..from + len].to_vec();
+
+        let message = input.message_mut().nth_message_mut(n, &pred).unwrap();
+        let values = &mut message
+            .fields
+            .iter_mut()
+            .find(|present| present.field == field)
+            .unwrap()
+            .values;
+        let to = state.rand_mut().below(values.len() + 1);
+        let end = to + state.rand_mut().below(values.len() - to + 1);
+        values.splice(to..end, splice);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageRepeatedSpliceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageRepeatedSpliceMutator");
+        &NAME
+    }
+}
+
+impl MessageRepeatedSpliceMutator {
+    /// Creates a new [`MessageRepeatedSpliceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random nested message with a message of the same type from another corpus entry
+#[derive(Default, Debug)]
+pub struct MessageSubmessageSwapMutator;
+
+impl<S> Mutator<MessageInput, S> for MessageSubmessageSwapMutator
+where
+    S: HasCorpus<Input = MessageInput> + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut MessageInput) -> Result<MutationResult, Error> {
+        let is_message = |value: &Value| matches!(value, Value::Message(_));
+        let count = input.message().count_values(&is_message);
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let n = state.rand_mut().below(count);
+        let Some(mut other) = random_corpus_input(state)? else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let mut nth = n;
+        let Some(Value::Message(target)) = input.message_mut().nth_value_mut(&mut nth, &is_message)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let message_type = target.message;
+        let Some(donor) = choose_message_mut(state.rand_mut(), other.message_mut(), &|message| {
+            message.message == message_type
+        }) else {
+            return Ok(MutationResult::Skipped);
+        };
+        if donor == target {
+            return Ok(MutationResult::Skipped);
+        }
+        *target = donor.clone();
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageSubmessageSwapMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MessageSubmessageSwapMutator");
+        &NAME
+    }
+}
+
+impl MessageSubmessageSwapMutator {
+    /// Creates a new [`MessageSubmessageSwapMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations for [`MessageInput`]s
+pub type MessageMutationsType = tuple_list_type!(
+    MessageFieldAddMutator,
+    MessageFieldRemoveMutator,
+    MessageFieldReplaceMutator,
+    MessageEnumMutator,
+    MessageIntBoundaryMutator,
+    MessageRepeatedSpliceMutator,
+    MessageSubmessageSwapMutator
+);
+
+/// Get the mutations for [`MessageInput`]s
+#[must_use]
+pub fn message_mutations() -> MessageMutationsType {
+    tuple_list!(
+        MessageFieldAddMutator::new(),
+        MessageFieldRemoveMutator::new(),
+        MessageFieldReplaceMutator::new(),
+        MessageEnumMutator::new(),
+        MessageIntBoundaryMutator::new(),
+        MessageRepeatedSpliceMutator::new(),
+        MessageSubmessageSwapMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::message_mutations;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        generators::{Generator, MessageGenerator},
+        inputs::{
+            message::{FieldDescriptor, FieldLabel, FieldType},
+            MessageFormat, MessageInput, MessageSchema,
+        },
+        mutators::{Mutator, StdScheduledMutator},
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_message_mutations() {
+        let mut schema = MessageSchema::new(MessageFormat::Protobuf);
+        let request = schema.add_message("Request");
+        let header = schema.add_message("Header");
+        schema
+            .add_field(
+                request,
+                FieldDescriptor::new(
+                    "header",
+                    1,
+                    FieldType::Message(header),
+                    FieldLabel::Required,
+                ),
+            )
+            .add_field(
+                request,
+                FieldDescriptor::new("ids", 2, FieldType::Uint64, FieldLabel::Repeated),
+            )
+            .add_field(
+                request,
+                FieldDescriptor::new(
+                    "headers",
+                    3,
+                    FieldType::Message(header),
+                    FieldLabel::Repeated,
+                ),
+            )
+            .add_field(
+                header,
+                FieldDescriptor::new(
+                    "kind",
+                    1,
+                    FieldType::Enum(vec![0, 1, 5]),
+                    FieldLabel::Required,
+                ),
+            )
+            .add_field(
+                header,
+                FieldDescriptor::new("length", 2, FieldType::Sfixed32, FieldLabel::Optional),
+            );
+
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<MessageInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        state.add_metadata(schema.clone());
+
+        let mut generator = MessageGenerator::new();
+        for _ in 0..4 {
+            let input = generator.generate(&mut state).unwrap();
+            state.corpus_mut().add(Testcase::new(input)).unwrap();
+        }
+
+        let mut input = generator.generate(&mut state).unwrap();
+        let mut mutator = StdScheduledMutator::new(message_mutations());
+        for _ in 0..256 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            // Mutated messages always decode, and keep their required fields
+            let decoded = schema.decode(&input.encode()).unwrap();
+            assert_eq!(decoded.encode(), input.encode());
+            assert!(input.message().field(0).is_some());
+        }
+    }
+}
@@ -11,6 +11,10 @@ use serde::{Deserialize, Serialize};
 pub use token_mutations::*;
 pub mod encoded_mutations;
 pub use encoded_mutations::*;
+pub mod fixup;
+pub use fixup::*;
+pub mod i2s_transform;
+pub use i2s_transform::*;
 pub mod mopt_mutator;
 pub use mopt_mutator::*;
 pub mod gramatron;
@@ -19,6 +23,12 @@ pub mod grimoire;
 pub use grimoire::*;
 pub mod tuneable;
 pub use tuneable::*;
+pub mod composite;
+pub use composite::*;
+pub mod arbitrary;
+pub use self::arbitrary::*;
+pub mod message;
+pub use message::*;
 
 #[cfg(feature = "unicode")]
 pub mod string;
@@ -21,7 +21,11 @@ use crate::{
 /// The n fuzz size
 pub const N_FUZZ_SIZE: usize = 1 << 21;
 
-libafl_bolts::impl_serdeany!(SchedulerMetadata);
+libafl_bolts::impl_serdeany!(
+    SchedulerMetadata,
+    name = "libafl::schedulers::SchedulerMetadata",
+    version = 1
+);
 
 /// The metadata used for power schedules
 #[derive(Serialize, Deserialize, Clone, Debug)]
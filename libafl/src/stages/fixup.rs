@@ -0,0 +1,380 @@
+//! Stage which detects checksum and length fields, and proposes [`Fixup`]s for them.
+//!
+//! In the spirit of taint-guided checksum detection, it looks at the comparisons logged by cmplog.
+//! A comparison whose one operand is read from the input, and whose other operand is a checksum or a length of a part of the input,
+//! is most likely a check of a checksum or length field.
+//! If the [`ColorizationStage`](crate::stages::ColorizationStage) and the `AFLppCmplogTracingStage` ran before,
+//! candidates are confirmed on the colorized input as well.
+
+use alloc::vec::Vec;
+use core::{marker::PhantomData, ops::Range};
+
+use hashbrown::HashSet;
+use libafl_bolts::Error;
+
+use crate::{
+    corpus::HasTestcase,
+    inputs::{BytesInput, HasBytesVec},
+    mutators::{
+        fixup::{read_field, width_mask, FixupHasher},
+        Fixup, FixupAlgorithm, FixupEndian, FixupMetadata, FixupPos,
+    },
+    observers::{AFLppCmpValuesMetadata, CmpValues, CmpValuesMetadata},
+    stages::{Stage, TaintMetadata},
+    state::{HasCorpus, HasCurrentTestcase, State, UsesState},
+    HasMetadata,
+};
+
+/// Checksums over less data than this are not reported, they match by chance too often
+const MIN_CHECKSUM_DATA: usize = 4;
+
+/// The data of a checksum may start up to this many bytes after the start of the input, or after the checksum field
+const MAX_CHECKSUM_GAP: usize = 16;
+
+/// Comparison values from the original input, and from the colorized input, if available
+type CmpPair = (CmpValues, Option<CmpValues>);
+
+/// Encodes a position from the closer end of the input, so it is stable under most insertions and deletions
+fn fixup_pos(pos: usize, len: usize) -> FixupPos {
+    if pos <= len - pos {
+        FixupPos::Start(pos)
+    } else {
+        FixupPos::End(len - pos)
+    }
+}
+
+/// The width of the operands of a comparison
+fn cmp_width(values: &CmpValues) -> usize {
+    match values {
+        CmpValues::U8(_) => 1,
+        CmpValues::U16(_) => 2,
+        CmpValues::U32(_) => 4,
+        CmpValues::U64(_) => 8,
+        CmpValues::Bytes(_) => 0,
+    }
+}
+
+/// The places where `value` is stored in the input, and also in the colorized input, if it was colorized there
+fn stored_at<'a>(
+    bytes: &'a [u8],
+    colorized: Option<(&'a [u8], u64)>,
+    value: u64,
+    width: usize,
+) -> impl Iterator<Item = (usize, FixupEndian)> + 'a {
+    let endians = if width == 1 {
+        &[FixupEndian::Big][..]
+    } else {
+        &[FixupEndian::Big, FixupEndian::Little][..]
+    };
+    (0..=bytes.len().saturating_sub(width))
+        .flat_map(move |pos| endians.iter().map(move |endian| (pos, *endian)))
+        .filter(move |(pos, endian)| {
+            read_field(bytes, *pos, width, *endian) == Some(value)
+                && match colorized {
+                    Some((colorized, new)) => {
+                        read_field(colorized, *pos, width, *endian) == Some(new & width_mask(width))
+                    }
+                    None => true,
+                }
+        })
+}
+
+/// Finds the data range that a checksum field at `field` is computed from
+fn checksum_range(
+    algorithm: FixupAlgorithm,
+    bytes: &[u8],
+    colorized: Option<(&[u8], u64)>,
+    taint: &[Range<usize>],
+    field: usize,
+    computed: u64,
+) -> Option<Range<usize>> {
+    let field_end = field + algorithm.width();
+    let mut starts: Vec<usize> = (0..=MAX_CHECKSUM_GAP)
+        .chain(field_end..=field_end + MAX_CHECKSUM_GAP)
+        .chain(taint.iter().flat_map(|range| [range.start, range.end]))
+        .filter(|start| *start <= bytes.len() && (*start <= field || *start >= field_end))
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+
+    for start in starts {
+        // The data does not cover the field itself
+        let max_end = if start <= field { field } else { bytes.len() };
+        let mut hasher = FixupHasher::new(algorithm);
+        for (end, byte) in bytes.iter().enumerate().take(max_end).skip(start) {
+            hasher.update(*byte);
+            if end + 1 - start < MIN_CHECKSUM_DATA || hasher.finish() != computed {
+                continue;
+            }
+            let range = start..end + 1;
+            let confirmed = match colorized {
+                Some((colorized, new)) => algorithm.compute(&colorized[range.clone()]) == Some(new),
+                None => true,
+            };
+            if confirmed {
+                return Some(range);
+            }
+        }
+    }
+    None
+}
+
+/// Proposes fixups for the comparisons logged while running `bytes`.
+///
+/// `colorized` is the colorized input, and `taint` the colorized ranges, if the [`crate::stages::ColorizationStage`] ran.
+pub(crate) fn detect_fixups(
+    bytes: &[u8],
+    colorized: Option<&[u8]>,
+    taint: &[Range<usize>],
+    cmps: &[CmpPair],
+) -> FixupMetadata {
+    let mut metadata = FixupMetadata::new();
+    let mut seen = HashSet::new();
+    let len = bytes.len();
+    // The colorized input has to line up with the original one
+    let colorized = colorized.filter(|colorized| colorized.len() == len);
+
+    for (orig, new) in cmps {
+        let Some((o0, o1)) = orig.to_u64_tuple() else {
+            continue;
+        };
+        let new = new.as_ref().and_then(CmpValues::to_u64_tuple);
+        if !seen.insert((o0, o1, new)) {
+            continue;
+        }
+        let cmp_width = cmp_width(orig);
+
+        for (stored, computed, new_values) in
+            [(o0, o1, new), (o1, o0, new.map(|(n0, n1)| (n1, n0)))]
+        {
+            if stored == 0 || computed == 0 {
+                continue;
+            }
+            let colorized_stored = colorized.zip(new_values.map(|(stored, _)| stored));
+            let colorized_computed = colorized.zip(new_values.map(|(_, computed)| computed));
+
+            for algorithm in [
+                FixupAlgorithm::Crc32,
+                FixupAlgorithm::Adler32,
+                FixupAlgorithm::IpChecksum,
+            ] {
+                let width = algorithm.width();
+                if width > cmp_width || stored > width_mask(width) || computed > width_mask(width) {
+                    continue;
+                }
+                for (field, endian) in stored_at(bytes, colorized_stored, stored, width) {
+                    if let Some(data) =
+                        checksum_range(algorithm, bytes, colorized_computed, taint, field, computed)
+                    {
+                        metadata.add(
+                            Fixup::new(
+                                algorithm,
+                                fixup_pos(field, len),
+                                fixup_pos(data.start, len)..fixup_pos(data.end, len),
+                            )
+                            .with_endian(endian),
+                        );
+                    }
+                }
+            }
+
+            // Single bytes that happen to match the length are too common
+            if cmp_width < 2 {
+                continue;
+            }
+            for (field, endian) in stored_at(bytes, colorized_stored, stored, cmp_width) {
+                for start in [field + cmp_width, field, 0] {
+                    if (len - start) as u64 == computed {
+                        metadata.add(
+                            Fixup::new(
+                                FixupAlgorithm::Length {
+                                    width: cmp_width,
+                                    adjust: 0,
+                                },
+                                fixup_pos(field, len),
+                                fixup_pos(start, len)..FixupPos::End(0),
+                            )
+                            .with_endian(endian),
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    metadata
+}
+
+/// Stage which detects checksum and length fields in the current testcase, and stores [`Fixup`]s for them in its [`FixupMetadata`].
+///
+/// It does not run the target, so place it after the stages that log comparisons for the current testcase:
+/// the `AFLppCmplogTracingStage` after a [`crate::stages::ColorizationStage`], or a [`crate::stages::TracingStage`] with a cmplog observer.
+/// Then mutate [`crate::mutators::FixupInput`]s, to apply the fixups to each mutant.
+#[derive(Debug)]
+pub struct FixupDetectionStage<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<S> Default for FixupDetectionStage<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> FixupDetectionStage<S> {
+    /// Creates a new [`FixupDetectionStage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> UsesState for FixupDetectionStage<S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<S, E, EM, Z> Stage<E, EM, Z> for FixupDetectionStage<S>
+where
+    S: HasTestcase<Input = BytesInput> + HasCorpus + HasMetadata + State,
+    E: UsesState<State = S>,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let mut tc = state.current_testcase_mut()?;
+        if tc.has_metadata::<FixupMetadata>() {
+            return Ok(()); // skip recompute
+        }
+        let bytes = tc.load_input(state.corpus())?.bytes();
+
+        let metadata = if let Some(cmp_meta) = state.metadata_map().get::<AFLppCmpValuesMetadata>()
+        {
+            let taint_meta = state.metadata_map().get::<TaintMetadata>();
+            let mut cmps = Vec::new();
+            for (idx, orig) in cmp_meta.orig_cmpvals() {
+                let new = cmp_meta.new_cmpvals().get(idx);
+                for (hit, orig) in orig.iter().enumerate() {
+                    let new = new.and_then(|new| new.get(hit)).cloned();
+                    cmps.push((orig.clone(), new));
+                }
+            }
+            detect_fixups(
+                bytes,
+                taint_meta.map(|taint| taint.input_vec().as_slice()),
+                taint_meta.map_or(&[], |taint| taint.ranges().as_slice()),
+                &cmps,
+            )
+        } else if let Some(cmp_meta) = state.metadata_map().get::<CmpValuesMetadata>() {
+            let cmps: Vec<CmpPair> = cmp_meta
+                .list
+                .iter()
+                .map(|cmp| (cmp.clone(), None))
+                .collect();
+            detect_fixups(bytes, None, &[], &cmps)
+        } else {
+            return Ok(());
+        };
+        tc.add_metadata(metadata);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Stage does not run the target. No reset helper needed.
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Stage does not run the target. No reset helper needed.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect_fixups;
+    use crate::{
+        mutators::{Fixup, FixupAlgorithm, FixupEndian, FixupPos},
+        observers::CmpValues,
+    };
+
+    #[test]
+    fn test_detect_fixups() {
+        // A header with a big-endian CRC-32 of the payload and a little-endian length of the payload
+        let payload = b"some payload bytes";
+        let crc = FixupAlgorithm::Crc32.compute(payload).unwrap();
+        let mut bytes = vec![0x13, 0x37, 0, 0, 0, 0];
+        bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(payload);
+        bytes[2..6].copy_from_slice(&0xdead_beef_u32.to_be_bytes());
+
+        let mut colorized = bytes.clone();
+        colorized[2..6].copy_from_slice(&0x0bad_f00d_u32.to_be_bytes());
+        colorized[12..16].copy_from_slice(b"XXXX");
+        let new_crc = FixupAlgorithm::Crc32.compute(&colorized[8..]).unwrap();
+
+        let cmps = vec![
+            (
+                CmpValues::U32((0xdead_beef, crc as u32)),
+                Some(CmpValues::U32((0x0bad_f00d, new_crc as u32))),
+            ),
+            (
+                CmpValues::U16((payload.len() as u16, payload.len() as u16)),
+                Some(CmpValues::U16((payload.len() as u16, payload.len() as u16))),
+            ),
+            // Not a checksum, the magic value is compared to a constant
+            (
+                CmpValues::U16((0x1337, 0x4242)),
+                Some(CmpValues::U16((0x1337, 0x4242))),
+            ),
+        ];
+        let metadata = detect_fixups(
+            &bytes,
+            Some(&colorized),
+            core::slice::from_ref(&(8..bytes.len())),
+            &cmps,
+        );
+        assert_eq!(
+            metadata.fixups(),
+            [
+                Fixup::new(
+                    FixupAlgorithm::Crc32,
+                    FixupPos::Start(2),
+                    FixupPos::Start(8)..FixupPos::End(0)
+                ),
+                Fixup::new(
+                    FixupAlgorithm::Length {
+                        width: 2,
+                        adjust: 0
+                    },
+                    FixupPos::Start(6),
+                    FixupPos::Start(8)..FixupPos::End(0)
+                )
+                .with_endian(FixupEndian::Little),
+            ]
+        );
+
+        // The detected fixups repair a mutant
+        let mut mutant = bytes.clone();
+        mutant.extend_from_slice(b" and more");
+        metadata.apply(&mut mutant);
+        assert_eq!(
+            u32::from_be_bytes(mutant[2..6].try_into().unwrap()),
+            FixupAlgorithm::Crc32.compute(&mutant[8..]).unwrap() as u32
+        );
+        assert_eq!(usize::from(mutant[6]), mutant.len() - 8);
+    }
+}
@@ -40,6 +40,49 @@ fn find_next_char(list: &[Option<u8>], mut idx: usize, ch: u8) -> usize {
     idx
 }
 
+/// Run `input`, and check that it still covers all the `novelties` of the corpus entry it was derived from,
+/// in the map of the observer with the given handle
+pub(crate) fn verify_input<C, E, EM, O, Z>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    state: &mut EM::State,
+    manager: &mut EM,
+    map_observer_handle: &Handle<C>,
+    novelties: &[usize],
+    input: &BytesInput,
+) -> Result<bool, Error>
+where
+    O: MapObserver,
+    C: AsRef<O> + Named,
+    E: Executor<EM, Z> + HasObservers<State = EM::State>,
+    E::Observers: ObserversTuple<EM::State>,
+    EM: UsesState,
+    EM::State: UsesInput<Input = BytesInput> + HasExecutions,
+    Z: UsesState<State = EM::State>,
+{
+    start_timer!(state);
+    executor.observers_mut().pre_exec_all(state, input)?;
+    mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+    start_timer!(state);
+    let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
+    mark_feature_time!(state, PerfFeature::TargetExecution);
+
+    *state.executions_mut() += 1;
+
+    start_timer!(state);
+    executor
+        .observers_mut()
+        .post_exec_all(state, input, &exit_kind)?;
+    mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+    let cnt = executor.observers()[map_observer_handle]
+        .as_ref()
+        .how_many_set(novelties);
+
+    Ok(cnt == novelties.len())
+}
+
 /// A stage that runs a tracer executor
 #[derive(Clone, Debug)]
 pub struct GeneralizationStage<C, EM, O, OT, Z> {
@@ -118,7 +161,15 @@ where
         };
 
         // Do not generalized unstable inputs
-        if !self.verify_input(fuzzer, executor, state, manager, &novelties, &original)? {
+        if !verify_input(
+            fuzzer,
+            executor,
+            state,
+            manager,
+            &self.map_observer_handle,
+            &novelties,
+            &original,
+        )? {
             return Ok(());
         }
 
@@ -352,42 +403,6 @@ where
         }
     }
 
-    fn verify_input<E>(
-        &self,
-        fuzzer: &mut Z,
-        executor: &mut E,
-        state: &mut EM::State,
-        manager: &mut EM,
-        novelties: &[usize],
-        input: &BytesInput,
-    ) -> Result<bool, Error>
-    where
-        E: Executor<EM, Z> + HasObservers<Observers = OT, State = EM::State>,
-        Z: UsesState<State = EM::State>,
-    {
-        start_timer!(state);
-        executor.observers_mut().pre_exec_all(state, input)?;
-        mark_feature_time!(state, PerfFeature::PreExecObservers);
-
-        start_timer!(state);
-        let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
-        mark_feature_time!(state, PerfFeature::TargetExecution);
-
-        *state.executions_mut() += 1;
-
-        start_timer!(state);
-        executor
-            .observers_mut()
-            .post_exec_all(state, input, &exit_kind)?;
-        mark_feature_time!(state, PerfFeature::PostExecObservers);
-
-        let cnt = executor.observers()[&self.map_observer_handle]
-            .as_ref()
-            .how_many_set(novelties);
-
-        Ok(cnt == novelties.len())
-    }
-
     fn trim_payload(payload: &mut Vec<Option<u8>>) {
         let mut previous = false;
         payload.retain(|&x| !(x.is_none() & core::mem::replace(&mut previous, x.is_none())));
@@ -423,7 +438,15 @@ where
                 .bytes_mut()
                 .extend(payload[end..].iter().flatten());
 
-            if self.verify_input(fuzzer, executor, state, manager, novelties, &candidate)? {
+            if verify_input(
+                fuzzer,
+                executor,
+                state,
+                manager,
+                &self.map_observer_handle,
+                novelties,
+                &candidate,
+            )? {
                 for item in &mut payload[start..end] {
                     *item = None;
                 }
@@ -476,7 +499,15 @@ where
                         .bytes_mut()
                         .extend(payload[end..].iter().flatten());
 
-                    if self.verify_input(fuzzer, executor, state, manager, novelties, &candidate)? {
+                    if verify_input(
+                        fuzzer,
+                        executor,
+                        state,
+                        manager,
+                        &self.map_observer_handle,
+                        novelties,
+                        &candidate,
+                    )? {
                         for item in &mut payload[start..end] {
                             *item = None;
                         }
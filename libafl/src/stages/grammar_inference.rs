@@ -0,0 +1,239 @@
+//! The grammar inference stage infers a grammar from the corpus, using the coverage of the target as oracle.
+//!
+//! See [`crate::generators::grammar_inference`] for how the grammar is inferred.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{fmt::Debug, marker::PhantomData};
+use std::{fs, path::PathBuf};
+
+use libafl_bolts::{
+    tuples::{Handle, Handled},
+    AsSlice, Named,
+};
+
+use crate::{
+    corpus::Corpus,
+    executors::{Executor, HasObservers},
+    feedbacks::map::MapNoveltiesMetadata,
+    generators::{GrammarInference, InferredGrammar},
+    inputs::{BytesInput, GeneralizedInputMetadata, HasBytesVec, Tokenizer, UsesInput},
+    observers::{CanTrack, MapObserver, ObserversTuple},
+    require_novelties_tracking,
+    stages::{generalization::verify_input, RetryRestartHelper, Stage},
+    state::{HasCorpus, HasExecutions, UsesState},
+    Error, HasMetadata, HasNamedMetadata,
+};
+
+/// A stage that infers an [`InferredGrammar`] from the corpus, once it has enough entries, and stores it in the state metadata.
+///
+/// A candidate derived from a corpus entry is valid if it still covers all the novelties of the entry,
+/// so the map observer has to track novelties. Entries generalized by a [`crate::stages::GeneralizationStage`]
+/// contribute their generalized form.
+/// Optionally, the stage writes the grammar as `grammar.json` for Nautilus, and as `automaton.postcard` for Gramatron.
+#[derive(Clone, Debug)]
+pub struct GrammarInferenceStage<C, EM, O, OT, T, Z> {
+    map_observer_handle: Handle<C>,
+    tokenizer: T,
+    min_samples: usize,
+    max_samples: usize,
+    max_checks: usize,
+    stack_limit: usize,
+    max_states: usize,
+    output_dir: Option<PathBuf>,
+    attempted_at: Option<usize>,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(EM, O, OT, Z)>,
+}
+
+impl<C, EM, O, OT, T, Z> Named for GrammarInferenceStage<C, EM, O, OT, T, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("GrammarInferenceStage");
+        &NAME
+    }
+}
+
+impl<C, EM, O, OT, T, Z> UsesState for GrammarInferenceStage<C, EM, O, OT, T, Z>
+where
+    EM: UsesState,
+    EM::State: UsesInput<Input = BytesInput>,
+{
+    type State = EM::State;
+}
+
+impl<C, E, EM, O, T, Z> Stage<E, EM, Z> for GrammarInferenceStage<C, EM, O, E::Observers, T, Z>
+where
+    O: MapObserver,
+    C: CanTrack + AsRef<O> + Named,
+    E: Executor<EM, Z> + HasObservers,
+    E::Observers: ObserversTuple<E::State>,
+    E::State:
+        UsesInput<Input = BytesInput> + HasExecutions + HasMetadata + HasCorpus + HasNamedMetadata,
+    EM: UsesState<State = E::State>,
+    T: Tokenizer + Clone,
+    Z: UsesState<State = E::State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut E::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let count = state.corpus().count();
+        let retry_at = self
+            .attempted_at
+            .map_or(self.min_samples, |attempted| attempted + self.min_samples);
+        if state.has_metadata::<InferredGrammar>() || count < retry_at {
+            return Ok(());
+        }
+        self.attempted_at = Some(count);
+
+        let mut inference =
+            GrammarInference::new(self.tokenizer.clone()).with_max_checks(self.max_checks);
+        let mut novelties = Vec::new();
+        let mut next = state.corpus().first();
+        while let Some(corpus_idx) = next {
+            if inference.samples() >= self.max_samples {
+                break;
+            }
+            next = state.corpus().next(corpus_idx);
+
+            let mut testcase = state.corpus().get(corpus_idx)?.borrow_mut();
+            let Some(meta) = testcase.metadata_map().get::<MapNoveltiesMetadata>() else {
+                continue;
+            };
+            if meta.as_slice().is_empty() {
+                continue;
+            }
+            let sample_novelties = meta.as_slice().to_vec();
+            let generalized = testcase
+                .metadata_map()
+                .get::<GeneralizedInputMetadata>()
+                .cloned();
+            let added = match generalized {
+                Some(generalized) => inference.add_generalized_sample(&generalized),
+                None => inference.add_sample(testcase.load_input(state.corpus())?.bytes()),
+            };
+            // Entries the tokenizer can not handle are no samples
+            if added.is_ok() {
+                novelties.push(sample_novelties);
+            }
+        }
+
+        let grammar = match inference.infer(|sample, candidate| {
+            let candidate = BytesInput::new(candidate.to_vec());
+            verify_input(
+                fuzzer,
+                executor,
+                state,
+                manager,
+                &self.map_observer_handle,
+                &novelties[sample],
+                &candidate,
+            )
+        }) {
+            Ok(grammar) => grammar,
+            Err(Error::Empty(..)) => {
+                log::info!("No valid samples to infer a grammar from, retrying later");
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        log::info!(
+            "Inferred a grammar with {} nonterminals",
+            grammar.rules().len()
+        );
+
+        if let Some(output_dir) = &self.output_dir {
+            fs::create_dir_all(output_dir)?;
+            grammar.write_nautilus_grammar(output_dir.join("grammar.json"))?;
+            if let Err(err) = grammar.write_automaton(
+                output_dir.join("automaton.postcard"),
+                self.stack_limit,
+                self.max_states,
+            ) {
+                log::warn!("Could not build a Gramatron automaton for the inferred grammar: {err}");
+            }
+        }
+        state.add_metadata(grammar);
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        RetryRestartHelper::restart_progress_should_run(state, self, 3)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryRestartHelper::clear_restart_progress(state, self)
+    }
+}
+
+impl<C, EM, O, OT, T, Z> GrammarInferenceStage<C, EM, O, OT, T, Z>
+where
+    EM: UsesState,
+    O: MapObserver,
+    C: CanTrack + AsRef<O> + Named,
+    OT: ObserversTuple<EM::State>,
+    EM::State: UsesInput<Input = BytesInput> + HasExecutions + HasMetadata + HasCorpus,
+{
+    /// Create a new [`GrammarInferenceStage`], tokenizing the corpus entries with `tokenizer`.
+    #[must_use]
+    pub fn new(map_observer: &C, tokenizer: T) -> Self {
+        require_novelties_tracking!("GrammarInferenceStage", C);
+        Self {
+            map_observer_handle: map_observer.handle(),
+            tokenizer,
+            min_samples: 16,
+            max_samples: 64,
+            max_checks: 4096,
+            stack_limit: 16,
+            max_states: 1 << 16,
+            output_dir: None,
+            attempted_at: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Set the number of corpus entries needed before inferring a grammar, 16 by default.
+    /// If no grammar could be inferred, the stage retries once the corpus grew by this number again.
+    #[must_use]
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Set the maximum number of corpus entries to infer the grammar from, 64 by default
+    #[must_use]
+    pub fn with_max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples;
+        self
+    }
+
+    /// Set the maximum number of target executions used to merge tokens and groups, 4096 by default
+    #[must_use]
+    pub fn with_max_checks(mut self, max_checks: usize) -> Self {
+        self.max_checks = max_checks;
+        self
+    }
+
+    /// Write the inferred grammar to this directory
+    #[must_use]
+    pub fn with_output_dir<P>(mut self, output_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// Set the stack limit and the maximum number of states of the written Gramatron automaton,
+    /// see [`InferredGrammar::automaton`]
+    #[must_use]
+    pub fn with_automaton_limits(mut self, stack_limit: usize, max_states: usize) -> Self {
+        self.stack_limit = stack_limit;
+        self.max_states = max_states;
+        self
+    }
+}
@@ -15,7 +15,10 @@ pub use concolic::ConcolicTracingStage;
 pub use concolic::SimpleConcolicMutationalStage;
 #[cfg(feature = "std")]
 pub use dump::*;
+pub use fixup::FixupDetectionStage;
 pub use generalization::GeneralizationStage;
+#[cfg(feature = "std")]
+pub use grammar_inference::GrammarInferenceStage;
 use hashbrown::HashSet;
 use libafl_bolts::{
     impl_serdeany,
@@ -26,6 +29,8 @@ pub use logics::*;
 pub use mutational::{MutationalStage, StdMutationalStage};
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 use serde::{Deserialize, Serialize};
+#[cfg(all(unix, feature = "std"))]
+pub use shmem_usage::ShMemUsageStage;
 pub use stats::AflStatsStage;
 #[cfg(feature = "unicode")]
 pub use string::*;
@@ -62,11 +67,16 @@ pub mod colorization;
 pub mod concolic;
 #[cfg(feature = "std")]
 pub mod dump;
+pub mod fixup;
 pub mod generalization;
 /// The [`generation::GenStage`] generates a single input and evaluates it.
 pub mod generation;
+#[cfg(feature = "std")]
+pub mod grammar_inference;
 pub mod logics;
 pub mod power;
+#[cfg(all(unix, feature = "std"))]
+pub mod shmem_usage;
 pub mod stats;
 #[cfg(feature = "unicode")]
 pub mod string;
@@ -0,0 +1,98 @@
+//! Stage to report the shared memory usage of a campaign to the monitor
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{current_time, shmem::ShMemRegistry};
+
+use crate::{
+    events::{Event, EventFirer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    stages::Stage,
+    state::UsesState,
+    Error,
+};
+
+/// The [`ShMemUsageStage`] periodically reads a [`ShMemRegistry`]
+/// and reports the registered and leaked shared memory as user stats.
+///
+/// Since the registry is shared by the whole campaign, all clients report the same numbers.
+#[derive(Debug, Clone)]
+pub struct ShMemUsageStage<E, EM, Z> {
+    registry: ShMemRegistry,
+    // the last time that we report the usage
+    last_report_time: Duration,
+    // the interval that we report the usage
+    report_interval: Duration,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for ShMemUsageStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for ShMemUsageStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = E::State>,
+    Z: UsesState<State = E::State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut E::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let cur = current_time();
+        if cur.checked_sub(self.last_report_time).unwrap_or_default() < self.report_interval {
+            return Ok(());
+        }
+        self.last_report_time = cur;
+
+        let usage = self.registry.usage()?;
+        for (name, value) in [
+            ("shmem_segments", usage.total.segments),
+            ("shmem_bytes", usage.total.bytes),
+            ("shmem_leaked_bytes", usage.leaked.bytes),
+        ] {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from(name),
+                    value: UserStats::new(UserStatsValue::Number(value as u64), AggregatorOps::Max),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not running the target so we won't crash/timeout and, hence, don't need to restore anything
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not running the target so we won't crash/timeout and, hence, don't need to restore anything
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> ShMemUsageStage<E, EM, Z> {
+    /// Create a new [`ShMemUsageStage`], reporting the usage of the given registry every `interval`
+    #[must_use]
+    pub fn new(registry: ShMemRegistry, interval: Duration) -> Self {
+        Self {
+            registry,
+            last_report_time: Duration::ZERO,
+            report_interval: interval,
+            phantom: PhantomData,
+        }
+    }
+}
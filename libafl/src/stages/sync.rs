@@ -50,6 +50,7 @@ impl SyncFromDiskMetadata {
 pub struct SyncFromDiskStage<CB, E, EM, Z> {
     sync_dir: PathBuf,
     load_callback: CB,
+    skip_unloadable: bool,
     phantom: PhantomData<(E, EM, Z)>,
 }
 
@@ -140,10 +141,19 @@ where
         Self {
             sync_dir,
             load_callback,
+            skip_unloadable: false,
             phantom: PhantomData,
         }
     }
 
+    /// Log and skip files the load callback fails on, instead of failing the stage.
+    /// Skipped files are not retried in later syncs.
+    #[must_use]
+    pub fn skip_unloadable(mut self) -> Self {
+        self.skip_unloadable = true;
+        self
+    }
+
     fn load_from_directory(
         &mut self,
         in_dir: &Path,
@@ -173,7 +183,17 @@ where
                         }
                     }
                     max_time = Some(max_time.map_or(time, |t: SystemTime| t.max(time)));
-                    let input = (self.load_callback)(fuzzer, state, &path)?;
+                    let input = match (self.load_callback)(fuzzer, state, &path) {
+                        Ok(input) => input,
+                        Err(err) if self.skip_unloadable => {
+                            log::warn!(
+                                "Skipping {}, it could not be loaded: {err}",
+                                path.display()
+                            );
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    };
                     fuzzer.evaluate_input(state, executor, manager, input)?;
                 }
             } else if attr.is_dir() {
@@ -213,6 +233,7 @@ where
         Self {
             sync_dir,
             load_callback: load_callback::<_, _>,
+            skip_unloadable: false,
             phantom: PhantomData,
         }
     }
@@ -0,0 +1,317 @@
+//! Durable checkpoints of the fuzzer state on disk.
+//!
+//! The [`libafl_bolts::staterestore::StateRestorer`] only keeps the state alive between restarts
+//! of a client. A [`StateCheckpointer`] periodically writes the whole state (corpus, metadata,
+//! rng, executions, scheduler data, ...) to a file, so that a campaign can be resumed after the
+//! broker, or the whole machine, went down.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use libafl_bolts::current_time;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// The magic bytes every checkpoint starts with
+const CHECKPOINT_MAGIC: &[u8; 8] = b"LIBAFLCP";
+
+/// The version of the checkpoint format.
+/// Bump it whenever the layout of the checkpoint changes in an incompatible way.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// The default interval between two checkpoints
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Serializes `state` into the checkpoint format.
+pub fn serialize_checkpoint<S>(state: &S) -> Result<Vec<u8>, Error>
+where
+    S: Serialize,
+{
+    let mut buf = Vec::from(&CHECKPOINT_MAGIC[..]);
+    buf.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+    buf.extend_from_slice(&postcard::to_allocvec(state)?);
+    Ok(buf)
+}
+
+/// Deserializes a state from the checkpoint format.
+/// Fails if the checkpoint was written by an incompatible version.
+pub fn deserialize_checkpoint<S>(buf: &[u8]) -> Result<S, Error>
+where
+    S: DeserializeOwned,
+{
+    let header_len = CHECKPOINT_MAGIC.len() + 4;
+    if buf.len() < header_len || &buf[..CHECKPOINT_MAGIC.len()] != CHECKPOINT_MAGIC {
+        return Err(Error::illegal_state("Not a LibAFL checkpoint"));
+    }
+
+    let version = u32::from_le_bytes(buf[CHECKPOINT_MAGIC.len()..header_len].try_into().unwrap());
+    if version != CHECKPOINT_VERSION {
+        return Err(Error::illegal_state(format!(
+            "Checkpoint version {version} is not supported (expected {CHECKPOINT_VERSION})"
+        )));
+    }
+
+    Ok(postcard::from_bytes(&buf[header_len..])?)
+}
+
+/// Atomically writes a checkpoint of `state` to `path`.
+///
+/// The checkpoint is written to a temporary file next to `path` first, and then renamed, so that
+/// a crash while writing never leaves a truncated checkpoint behind.
+pub fn save_checkpoint<S>(path: &Path, state: &S) -> Result<(), Error>
+where
+    S: Serialize,
+{
+    let buf = serialize_checkpoint(state)?;
+
+    let mut tmp_name = path
+        .file_name()
+        .ok_or_else(|| Error::illegal_argument(format!("Invalid checkpoint path {path:?}")))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&buf)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)?;
+
+    // Make sure the rename itself hits the disk
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Loads the checkpoint stored at `path`.
+pub fn load_checkpoint<S>(path: &Path) -> Result<S, Error>
+where
+    S: DeserializeOwned,
+{
+    deserialize_checkpoint(&fs::read(path)?)
+}
+
+/// Periodically writes checkpoints of the state to a file, and resumes from it.
+#[derive(Debug, Clone)]
+pub struct StateCheckpointer {
+    path: PathBuf,
+    interval: Duration,
+    last_checkpoint: Option<Duration>,
+}
+
+impl StateCheckpointer {
+    /// Creates a new [`StateCheckpointer`] writing a checkpoint to `path` every `interval`
+    #[must_use]
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        Self {
+            path,
+            interval,
+            last_checkpoint: None,
+        }
+    }
+
+    /// The file the checkpoints are written to
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The interval between two checkpoints
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Writes a checkpoint of `state` now
+    pub fn checkpoint<S>(&mut self, state: &S) -> Result<(), Error>
+    where
+        S: Serialize,
+    {
+        save_checkpoint(&self.path, state)?;
+        self.last_checkpoint = Some(current_time());
+        log::debug!("Wrote checkpoint to {:?}", self.path);
+        Ok(())
+    }
+
+    /// The time the checkpoint file was last written, if it exists
+    fn last_written(&self) -> Option<Duration> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+    }
+
+    /// Writes a checkpoint of `state`, if `interval` passed since the last one.
+    ///
+    /// A fresh checkpointer, for example in a restarted client, counts from the time the
+    /// checkpoint file was last written, and writes right away if there is none yet. This way,
+    /// clients restarting more often than `interval` still write checkpoints.
+    /// Returns `true` if a checkpoint was written.
+    pub fn maybe_checkpoint<S>(&mut self, state: &S) -> Result<bool, Error>
+    where
+        S: Serialize,
+    {
+        let cur = current_time();
+        let Some(last_checkpoint) = self.last_checkpoint.or_else(|| self.last_written()) else {
+            self.checkpoint(state)?;
+            return Ok(true);
+        };
+        self.last_checkpoint = Some(last_checkpoint);
+
+        // default to 0 here to avoid crashes on clock skew
+        if cur.checked_sub(last_checkpoint).unwrap_or_default() < self.interval {
+            return Ok(false);
+        }
+
+        self.checkpoint(state)?;
+        Ok(true)
+    }
+
+    /// Loads the last checkpoint, if any was written yet
+    pub fn resume<S>(&self) -> Result<Option<S>, Error>
+    where
+        S: DeserializeOwned,
+    {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        load_checkpoint(&self.path).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        env::temp_dir,
+        fs::{self, File},
+        time::SystemTime,
+    };
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use super::{
+        deserialize_checkpoint, serialize_checkpoint, StateCheckpointer, CHECKPOINT_VERSION,
+    };
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::BytesInput,
+        state::{test::test_std_state, HasCorpus, HasExecutions, HasRand, StdState},
+        HasMetadata, HasNamedMetadata,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+    struct TestMetadata(u64);
+    libafl_bolts::impl_serdeany!(TestMetadata);
+
+    fn populated_state() -> TestState {
+        let mut state: TestState = test_std_state();
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"hello".to_vec())))
+            .unwrap();
+        *state.executions_mut() = 1337;
+        state.add_metadata(TestMetadata(42));
+        state.add_named_metadata("test", TestMetadata(7));
+        state.rand_mut().next();
+        state
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut state = populated_state();
+
+        let buf = serialize_checkpoint(&state).unwrap();
+        let mut resumed: TestState = deserialize_checkpoint(&buf).unwrap();
+
+        assert_eq!(resumed.corpus().count(), 1);
+        assert_eq!(*resumed.executions(), 1337);
+        assert_eq!(
+            resumed.metadata::<TestMetadata>().unwrap(),
+            &TestMetadata(42)
+        );
+        assert_eq!(
+            resumed.named_metadata::<TestMetadata>("test").unwrap(),
+            &TestMetadata(7)
+        );
+        assert_eq!(resumed.rand_mut().next(), state.rand_mut().next());
+    }
+
+    #[test]
+    fn test_checkpoint_version() {
+        let state = populated_state();
+        let mut buf = serialize_checkpoint(&state).unwrap();
+
+        buf[8..12].copy_from_slice(&(CHECKPOINT_VERSION + 1).to_le_bytes());
+        assert!(deserialize_checkpoint::<TestState>(&buf).is_err());
+
+        assert!(deserialize_checkpoint::<TestState>(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_checkpointer() {
+        let path = temp_dir().join(format!("libafl_checkpoint_test_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut checkpointer = StateCheckpointer::new(path.clone(), Duration::ZERO);
+        assert!(checkpointer.resume::<TestState>().unwrap().is_none());
+
+        let state = populated_state();
+        // Without a checkpoint on disk, the first call writes one
+        assert!(checkpointer.maybe_checkpoint(&state).unwrap());
+        assert!(checkpointer.maybe_checkpoint(&state).unwrap());
+
+        let resumed: TestState = checkpointer.resume().unwrap().unwrap();
+        assert_eq!(*resumed.executions(), 1337);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkpointer_restart() {
+        let path = temp_dir().join(format!(
+            "libafl_checkpoint_restart_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        // The restarting manager hands a clone of this to every new client
+        let template = StateCheckpointer::new(path.clone(), Duration::from_secs(60 * 60));
+        let state = populated_state();
+
+        // The first client writes the first checkpoint
+        assert!(template.clone().maybe_checkpoint(&state).unwrap());
+
+        // A client restarting right after does not write again
+        let mut restarted = template.clone();
+        assert!(!restarted.maybe_checkpoint(&state).unwrap());
+        assert!(!restarted.maybe_checkpoint(&state).unwrap());
+
+        // Once the interval passed since the last write, a restarted client writes right away
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+            .unwrap();
+        let mut restarted = template.clone();
+        assert!(restarted.maybe_checkpoint(&state).unwrap());
+        assert!(!restarted.maybe_checkpoint(&state).unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -37,6 +37,11 @@ use crate::{
     Error, HasMetadata, HasNamedMetadata,
 };
 
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub use checkpoint::StateCheckpointer;
+
 /// The maximum size of a testcase
 pub const DEFAULT_MAX_SIZE: usize = 1_048_576;
 
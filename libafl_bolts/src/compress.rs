@@ -1,23 +1,119 @@
 //! Compression of events passed between a broker and clients.
-//! Currently we use the gzip compression algorithm for its fast decompression performance.
+//! By default, we use the gzip compression algorithm for its fast decompression performance.
+//! With the `zstd` and `lz4` features, [`ZstdCompressor`] and [`Lz4Compressor`] can be used instead.
 
-use alloc::vec::Vec;
-use core::fmt::Debug;
+use alloc::{format, vec::Vec};
+use core::fmt::{self, Debug};
+#[cfg(feature = "zstd")]
+use std::io::Read;
 
+#[cfg(feature = "gzip")]
 use miniz_oxide::{
     deflate::{compress_to_vec, CompressionLevel},
-    inflate::decompress_to_vec,
+    inflate::decompress_to_vec_with_limit,
 };
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "zstd")]
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
 
 use crate::Error;
 
+/// The compression algorithms supported by `LibAFL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompressionAlgorithm {
+    /// Gzip (deflate), see [`GzipCompressor`]
+    Gzip,
+    /// Zstandard, see [`ZstdCompressor`]
+    Zstd,
+    /// LZ4, see [`Lz4Compressor`]
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// The id of this algorithm, as sent over the wire
+    #[must_use]
+    pub fn id(self) -> u8 {
+        match self {
+            CompressionAlgorithm::Gzip => 0,
+            CompressionAlgorithm::Zstd => 1,
+            CompressionAlgorithm::Lz4 => 2,
+        }
+    }
+
+    /// The algorithm with the given wire `id`
+    pub fn from_id(id: u8) -> Result<Self, Error> {
+        match id {
+            0 => Ok(CompressionAlgorithm::Gzip),
+            1 => Ok(CompressionAlgorithm::Zstd),
+            2 => Ok(CompressionAlgorithm::Lz4),
+            _ => Err(Error::illegal_argument(format!(
+                "Unknown compression algorithm id {id}"
+            ))),
+        }
+    }
+}
+
+/// The largest buffer the [`Compressor`]s decompress to by default.
+///
+/// This is the size of a fresh LLMP page, no single message can be larger than that.
+/// Compressed buffers that would grow larger than this are rejected.
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 1 << 28;
+
+/// The [`CompressionAlgorithm`] a [`CompressorSet`] compresses with by default.
+///
+/// The `llmp_compression_lz4` and `llmp_compression_zstd` features pick lz4 or zstd.
+/// Otherwise, this is the first enabled algorithm out of gzip, zstd, and lz4.
+pub const DEFAULT_COMPRESSION_ALGORITHM: CompressionAlgorithm =
+    if cfg!(feature = "llmp_compression_lz4") {
+        CompressionAlgorithm::Lz4
+    } else if cfg!(feature = "llmp_compression_zstd") {
+        CompressionAlgorithm::Zstd
+    } else if cfg!(feature = "gzip") {
+        CompressionAlgorithm::Gzip
+    } else if cfg!(feature = "zstd") {
+        CompressionAlgorithm::Zstd
+    } else {
+        CompressionAlgorithm::Lz4
+    };
+
 /// Compression for your stream compression needs.
-#[derive(Debug)]
+pub trait Compressor: Debug {
+    /// The [`CompressionAlgorithm`] of this compressor
+    fn algorithm(&self) -> CompressionAlgorithm;
+
+    /// If less bytes than threshold are being passed to [`Compressor::maybe_compress`],
+    /// the payload is not getting compressed.
+    fn threshold(&self) -> usize;
+
+    /// Force compression.
+    /// Will ignore the preset threshold, and always compress.
+    fn compress(&self, buf: &[u8]) -> Vec<u8>;
+
+    /// Decompression.
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Compression.
+    /// If the buffer is smaller than the threshold of this compressor, `None` will be returned.
+    /// Else, the buffer is compressed.
+    fn maybe_compress(&self, buf: &[u8]) -> Option<Vec<u8>> {
+        if buf.len() >= self.threshold() {
+            //compress if the buffer is large enough
+            Some(self.compress(buf))
+        } else {
+            None
+        }
+    }
+}
+
+/// Compression using gzip
+#[cfg(feature = "gzip")]
+#[derive(Debug, Clone)]
 pub struct GzipCompressor {
     /// If less bytes than threshold are being passed to `compress`, the payload is not getting compressed.
     threshold: usize,
 }
 
+#[cfg(feature = "gzip")]
 impl GzipCompressor {
     /// If the buffer is at least larger as large as the `threshold` value, we compress the buffer.
     /// When given a `threshold` of `0`, the `GzipCompressor` will always compress.
@@ -33,12 +129,14 @@ impl GzipCompressor {
     }
 }
 
+#[cfg(feature = "gzip")]
 impl Default for GzipCompressor {
     fn default() -> Self {
         Self::new()
     }
 }
 
+#[cfg(feature = "gzip")]
 impl GzipCompressor {
     /// Compression.
     /// If the buffer is smaller than the threshold of this compressor, `None` will be returned.
@@ -63,19 +161,392 @@ impl GzipCompressor {
     /// Decompression.
     #[allow(clippy::unused_self)]
     pub fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
-        let decompressed = decompress_to_vec(buf);
+        decompress_to_vec_with_limit(buf, DEFAULT_MAX_DECOMPRESSED_SIZE)
+            .map_err(|_| Error::compression())
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Compressor for GzipCompressor {
+    fn algorithm(&self) -> CompressionAlgorithm {
+        CompressionAlgorithm::Gzip
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        GzipCompressor::compress(self, buf)
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        GzipCompressor::decompress(self, buf)
+    }
+}
+
+/// A zstd dictionary, prepared for compression and decompression
+#[cfg(feature = "zstd")]
+struct ZstdDictionary {
+    raw: Vec<u8>,
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+#[cfg(feature = "zstd")]
+impl Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionary")
+            .field("len", &self.raw.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Compression using zstd, optionally with a dictionary trained on typical payloads.
+///
+/// Small payloads, such as testcases of a single target, compress a lot better with a dictionary.
+/// All parties need to use the same dictionary, see [`ZstdCompressor::train_dictionary`].
+#[cfg(feature = "zstd")]
+#[derive(Debug)]
+pub struct ZstdCompressor {
+    /// If less bytes than threshold are being passed to `compress`, the payload is not getting compressed.
+    threshold: usize,
+    level: i32,
+    max_size: usize,
+    dictionary: Option<ZstdDictionary>,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCompressor {
+    /// The compression level used by default, favoring speed over ratio
+    pub const DEFAULT_LEVEL: i32 = 1;
+
+    /// If the buffer is at least larger as large as the `threshold` value, we compress the buffer.
+    /// When given a `threshold` of `0`, the `ZstdCompressor` will always compress.
+    #[must_use]
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self {
+            threshold,
+            level: Self::DEFAULT_LEVEL,
+            max_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+            dictionary: None,
+        }
+    }
+
+    /// Refuse to decompress buffers to more than `max_size` bytes,
+    /// see [`DEFAULT_MAX_DECOMPRESSED_SIZE`]
+    #[must_use]
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Create a [`ZstdCompressor`] that will always compress
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_threshold(0)
+    }
+
+    /// Sets the zstd compression `level`
+    #[must_use]
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level;
+        if let Some(dictionary) = self.dictionary.take() {
+            self.set_dictionary(dictionary.raw);
+        }
+        self
+    }
+
+    /// Compress and decompress using the given `dictionary`
+    #[must_use]
+    pub fn with_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.set_dictionary(dictionary);
+        self
+    }
+
+    /// Compress and decompress using the given `dictionary` from now on
+    pub fn set_dictionary(&mut self, dictionary: Vec<u8>) {
+        self.dictionary = Some(ZstdDictionary {
+            encoder: EncoderDictionary::copy(&dictionary, self.level),
+            decoder: DecoderDictionary::copy(&dictionary),
+            raw: dictionary,
+        });
+    }
+
+    /// The dictionary used by this compressor, if any
+    #[must_use]
+    pub fn dictionary(&self) -> Option<&[u8]> {
+        self.dictionary
+            .as_ref()
+            .map(|dictionary| &dictionary.raw[..])
+    }
+
+    /// Trains a dictionary of at most `max_size` bytes on the given samples,
+    /// for example testcases of the corpus.
+    pub fn train_dictionary<T>(samples: &[T], max_size: usize) -> Result<Vec<u8>, Error>
+    where
+        T: AsRef<[u8]>,
+    {
+        zstd::dict::from_samples(samples, max_size).map_err(|err| {
+            Error::illegal_argument(format!("Could not train zstd dictionary: {err}"))
+        })
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for ZstdCompressor {
+    fn algorithm(&self) -> CompressionAlgorithm {
+        CompressionAlgorithm::Zstd
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
 
-        match decompressed {
-            Ok(buf) => Ok(buf),
-            Err(_) => Err(Error::compression()),
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        let compressed = match &self.dictionary {
+            Some(dictionary) => {
+                zstd::bulk::Compressor::with_prepared_dictionary(&dictionary.encoder)
+                    .and_then(|mut compressor| compressor.compress(buf))
+            }
+            None => zstd::bulk::compress(buf, self.level),
+        };
+        // This can only fail for invalid parameters, or if we run out of memory.
+        compressed.expect("zstd compression failed")
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut decompressed = Vec::new();
+        // Read one byte more than allowed, to notice oversized buffers
+        let limit = self.max_size as u64 + 1;
+        let res = match &self.dictionary {
+            Some(dictionary) => {
+                zstd::stream::read::Decoder::with_prepared_dictionary(buf, &dictionary.decoder)
+                    .and_then(|decoder| decoder.take(limit).read_to_end(&mut decompressed))
+            }
+            None => zstd::stream::read::Decoder::with_buffer(buf)
+                .and_then(|decoder| decoder.take(limit).read_to_end(&mut decompressed)),
+        };
+
+        match res {
+            Ok(len) if len <= self.max_size => Ok(decompressed),
+            _ => Err(Error::compression()),
+        }
+    }
+}
+
+/// Compression using lz4, trading compression ratio for (a lot of) speed
+#[cfg(feature = "lz4")]
+#[derive(Debug, Clone)]
+pub struct Lz4Compressor {
+    /// If less bytes than threshold are being passed to `compress`, the payload is not getting compressed.
+    threshold: usize,
+    max_size: usize,
+}
+
+#[cfg(feature = "lz4")]
+impl Lz4Compressor {
+    /// If the buffer is at least larger as large as the `threshold` value, we compress the buffer.
+    /// When given a `threshold` of `0`, the `Lz4Compressor` will always compress.
+    #[must_use]
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self {
+            threshold,
+            max_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+
+    /// Create a [`Lz4Compressor`] that will always compress
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_threshold(0)
+    }
+
+    /// Refuse to decompress buffers to more than `max_size` bytes,
+    /// see [`DEFAULT_MAX_DECOMPRESSED_SIZE`]
+    #[must_use]
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl Default for Lz4Compressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl Compressor for Lz4Compressor {
+    fn algorithm(&self) -> CompressionAlgorithm {
+        CompressionAlgorithm::Lz4
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn compress(&self, buf: &[u8]) -> Vec<u8> {
+        lz4_flex::block::compress_prepend_size(buf)
+    }
+
+    fn decompress(&self, buf: &[u8]) -> Result<Vec<u8>, Error> {
+        // The size is prepended by the sender, so don't trust it before allocating.
+        if buf.len() < 4 {
+            return Err(Error::compression());
+        }
+        let (size, compressed) = buf.split_at(4);
+        let size = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+        if size > self.max_size {
+            return Err(Error::compression());
+        }
+        lz4_flex::block::decompress(compressed, size).map_err(|_| Error::compression())
+    }
+}
+
+/// All enabled [`Compressor`]s.
+///
+/// Compresses with one [`CompressionAlgorithm`], but decompresses all of them.
+/// This is used by LLMP, so that clients and brokers using different compression can be mixed.
+pub struct CompressorSet {
+    algorithm: CompressionAlgorithm,
+    #[cfg(feature = "gzip")]
+    gzip: GzipCompressor,
+    #[cfg(feature = "zstd")]
+    zstd: ZstdCompressor,
+    #[cfg(feature = "lz4")]
+    lz4: Lz4Compressor,
+}
+
+impl Debug for CompressorSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("CompressorSet");
+        debug.field("algorithm", &self.algorithm);
+        #[cfg(feature = "gzip")]
+        debug.field("gzip", &self.gzip);
+        #[cfg(feature = "zstd")]
+        debug.field("zstd", &self.zstd);
+        #[cfg(feature = "lz4")]
+        debug.field("lz4", &self.lz4);
+        debug.finish()
+    }
+}
+
+impl CompressorSet {
+    /// Create a new [`CompressorSet`], compressing buffers of at least `threshold` bytes
+    /// using the [`DEFAULT_COMPRESSION_ALGORITHM`].
+    #[must_use]
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self {
+            algorithm: DEFAULT_COMPRESSION_ALGORITHM,
+            #[cfg(feature = "gzip")]
+            gzip: GzipCompressor::with_threshold(threshold),
+            #[cfg(feature = "zstd")]
+            zstd: ZstdCompressor::with_threshold(threshold),
+            #[cfg(feature = "lz4")]
+            lz4: Lz4Compressor::with_threshold(threshold),
         }
     }
+
+    /// The [`CompressionAlgorithm`] used to compress
+    #[must_use]
+    pub fn algorithm(&self) -> CompressionAlgorithm {
+        self.algorithm
+    }
+
+    /// Compress using `algorithm` from now on.
+    /// Fails if the feature for this algorithm is not enabled.
+    pub fn set_algorithm(&mut self, algorithm: CompressionAlgorithm) -> Result<(), Error> {
+        self.get(algorithm)?;
+        self.algorithm = algorithm;
+        Ok(())
+    }
+
+    /// The [`ZstdCompressor`]
+    #[cfg(feature = "zstd")]
+    #[must_use]
+    pub fn zstd(&self) -> &ZstdCompressor {
+        &self.zstd
+    }
+
+    /// The [`ZstdCompressor`], for example to set a dictionary
+    #[cfg(feature = "zstd")]
+    pub fn zstd_mut(&mut self) -> &mut ZstdCompressor {
+        &mut self.zstd
+    }
+
+    /// Get the [`Compressor`] for the given `algorithm`.
+    /// Fails if the feature for this algorithm is not enabled.
+    pub fn get(&self, algorithm: CompressionAlgorithm) -> Result<&dyn Compressor, Error> {
+        match algorithm {
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => Ok(&self.gzip),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => Ok(&self.zstd),
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => Ok(&self.lz4),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::illegal_argument(format!(
+                "Compression algorithm {algorithm:?} is not enabled"
+            ))),
+        }
+    }
+
+    /// Force compression using the current [`CompressionAlgorithm`], ignoring the threshold.
+    /// Returns the compressed buffer together with the algorithm used.
+    pub fn compress(&self, buf: &[u8]) -> Result<(CompressionAlgorithm, Vec<u8>), Error> {
+        let compressed = self.get(self.algorithm)?.compress(buf);
+        Ok((self.algorithm, compressed))
+    }
+
+    /// Compression, using the current [`CompressionAlgorithm`].
+    /// If the buffer is smaller than the threshold, `None` will be returned.
+    /// Else, the buffer is compressed, and returned together with the algorithm used.
+    pub fn maybe_compress(
+        &self,
+        buf: &[u8],
+    ) -> Result<Option<(CompressionAlgorithm, Vec<u8>)>, Error> {
+        Ok(self
+            .get(self.algorithm)?
+            .maybe_compress(buf)
+            .map(|compressed| (self.algorithm, compressed)))
+    }
+
+    /// Decompresses a buffer compressed with `algorithm`
+    pub fn decompress(
+        &self,
+        algorithm: CompressionAlgorithm,
+        buf: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.get(algorithm)?.decompress(buf)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "zstd")]
+    use alloc::{format, vec::Vec};
+
+    #[cfg(feature = "gzip")]
     use crate::compress::GzipCompressor;
+    #[cfg(feature = "lz4")]
+    use crate::compress::Lz4Compressor;
+    #[cfg(feature = "zstd")]
+    use crate::compress::ZstdCompressor;
+    use crate::compress::{
+        CompressionAlgorithm, Compressor, CompressorSet, DEFAULT_COMPRESSION_ALGORITHM,
+    };
 
+    #[cfg(feature = "gzip")]
     #[test]
     fn test_compression() {
         let compressor = GzipCompressor::with_threshold(1);
@@ -87,10 +558,104 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "gzip")]
     #[test]
     fn test_threshold() {
         let compressor = GzipCompressor::with_threshold(1024);
         assert!(compressor.maybe_compress(&[1u8; 1023]).is_none());
         assert!(compressor.maybe_compress(&[1u8; 1024]).is_some());
     }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_dictionary() {
+        let samples: Vec<Vec<u8>> = (0..1024_u32)
+            .map(|i| format!("GET /index{i}.html HTTP/1.1\r\nHost: fuzz{}\r\n\r\n", i % 7).into())
+            .collect();
+        let dictionary = ZstdCompressor::train_dictionary(&samples, 4096).unwrap();
+
+        let plain = ZstdCompressor::new();
+        let compressor = ZstdCompressor::new().with_dictionary(dictionary);
+        let compressed = compressor.compress(&samples[3]);
+        assert!(compressed.len() < plain.compress(&samples[3]).len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), samples[3]);
+        // Without the dictionary, the data can not be decompressed.
+        assert!(plain.decompress(&compressed).is_err());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4() {
+        let compressor = Lz4Compressor::with_threshold(1);
+        let compressed = compressor.maybe_compress(&[1u8; 1024]).unwrap();
+        assert!(compressed.len() < 1024);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), vec![1u8; 1024]);
+
+        // A size prefix beyond the limit is rejected before allocating
+        let small = Lz4Compressor::new().with_max_size(1023);
+        assert!(small.decompress(&compressed).is_err());
+        let mut forged = compressed.clone();
+        forged[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(compressor.decompress(&forged).is_err());
+        assert!(compressor.decompress(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_algorithm_ids() {
+        for algorithm in [
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Lz4,
+        ] {
+            assert_eq!(
+                CompressionAlgorithm::from_id(algorithm.id()).unwrap(),
+                algorithm
+            );
+        }
+        assert!(CompressionAlgorithm::from_id(3).is_err());
+    }
+
+    #[test]
+    fn test_default_algorithm_enabled() {
+        let compressors = CompressorSet::with_threshold(1);
+        let (algorithm, compressed) = compressors.compress(&[3u8; 64]).unwrap();
+        assert_eq!(algorithm, DEFAULT_COMPRESSION_ALGORITHM);
+        assert_eq!(
+            compressors.decompress(algorithm, &compressed).unwrap(),
+            vec![3u8; 64]
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_compressor_set() {
+        let mut compressors = CompressorSet::with_threshold(1);
+        compressors
+            .set_algorithm(CompressionAlgorithm::Gzip)
+            .unwrap();
+        let (algorithm, compressed) = compressors.maybe_compress(&[2u8; 1024]).unwrap().unwrap();
+        assert_eq!(algorithm, CompressionAlgorithm::Gzip);
+        // Forced compression ignores the threshold
+        assert!(compressors.maybe_compress(&[]).unwrap().is_none());
+        let (forced_algorithm, forced) = compressors.compress(&[]).unwrap();
+        assert!(compressors
+            .decompress(forced_algorithm, &forced)
+            .unwrap()
+            .is_empty());
+
+        #[cfg(feature = "lz4")]
+        compressors
+            .set_algorithm(CompressionAlgorithm::Lz4)
+            .unwrap();
+        #[cfg(not(feature = "lz4"))]
+        assert!(compressors
+            .set_algorithm(CompressionAlgorithm::Lz4)
+            .is_err());
+
+        // Messages compressed with another algorithm can still be decompressed
+        assert_eq!(
+            compressors.decompress(algorithm, &compressed).unwrap(),
+            vec![2u8; 1024]
+        );
+    }
 }
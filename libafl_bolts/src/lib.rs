@@ -112,7 +112,7 @@ pub mod build_id;
     feature = "std"
 ))]
 pub mod cli;
-#[cfg(feature = "gzip")]
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
 pub mod compress;
 #[cfg(feature = "std")]
 pub mod core_affinity;
@@ -121,6 +121,8 @@ pub mod cpu;
 pub mod fs;
 #[cfg(feature = "alloc")]
 pub mod llmp;
+#[cfg(feature = "std")]
+pub mod llmp_replay;
 pub mod math;
 #[cfg(all(feature = "std", unix))]
 pub mod minibsod;
@@ -147,7 +149,7 @@ pub mod bolts_prelude {
         feature = "std"
     ))]
     pub use super::cli::*;
-    #[cfg(feature = "gzip")]
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
     pub use super::compress::*;
     #[cfg(feature = "std")]
     pub use super::core_affinity::*;
@@ -300,7 +302,7 @@ pub enum Error {
     /// Serialization error
     Serialize(String, ErrorBacktrace),
     /// Compression error
-    #[cfg(feature = "gzip")]
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
     Compression(ErrorBacktrace),
     /// Optional val was supposed to be set, but isn't.
     EmptyOptional(String, ErrorBacktrace),
@@ -338,7 +340,7 @@ impl Error {
     {
         Error::Serialize(arg.into(), ErrorBacktrace::new())
     }
-    #[cfg(feature = "gzip")]
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
     /// Compression error
     #[must_use]
     pub fn compression() -> Self {
@@ -460,7 +462,7 @@ impl Display for Error {
                 write!(f, "Error in Serialization: `{0}`", &s)?;
                 display_error_backtrace(f, b)
             }
-            #[cfg(feature = "gzip")]
+            #[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
             Self::Compression(b) => {
                 write!(f, "Error in decompression")?;
                 display_error_backtrace(f, b)
@@ -91,6 +91,8 @@ use backtrace::Backtrace;
 use nix::sys::socket::{self, sockopt::ReusePort};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "llmp_compression")]
+use crate::compress::CompressionAlgorithm;
 #[cfg(all(unix, not(miri)))]
 use crate::os::unix_signals::setup_signal_handler;
 #[cfg(unix)]
@@ -100,7 +102,7 @@ use crate::os::windows_exceptions::{setup_ctrl_handler, CtrlHandler};
 #[cfg(feature = "std")]
 use crate::{current_time, IP_LOCALHOST};
 use crate::{
-    shmem::{ShMem, ShMemDescription, ShMemId, ShMemProvider},
+    shmem::{ShMem, ShMemDescription, ShMemId, ShMemProvider, ShMemPurpose},
     ClientId, Error,
 };
 
@@ -140,6 +142,12 @@ pub const LLMP_FLAG_INITIALIZED: Flags = Flags(0x0);
 pub const LLMP_FLAG_COMPRESSED: Flags = Flags(0x1);
 /// From another broker.
 pub const LLMP_FLAG_FROM_B2B: Flags = Flags(0x2);
+/// This message was compressed using zstd instead of gzip.
+/// Always set together with [`LLMP_FLAG_COMPRESSED`].
+pub const LLMP_FLAG_ZSTD: Flags = Flags(0x4);
+/// This message was compressed using lz4 instead of gzip.
+/// Always set together with [`LLMP_FLAG_COMPRESSED`].
+pub const LLMP_FLAG_LZ4: Flags = Flags(0x8);
 
 /// Timt the broker 2 broker connection waits for incoming data,
 /// before checking for own data to forward again.
@@ -206,10 +214,43 @@ impl Debug for Flags {
         if *self & LLMP_FLAG_FROM_B2B == LLMP_FLAG_FROM_B2B {
             f.write_str("FROM_B2B")?;
         }
+        if *self & LLMP_FLAG_ZSTD == LLMP_FLAG_ZSTD {
+            f.write_str("ZSTD")?;
+        }
+        if *self & LLMP_FLAG_LZ4 == LLMP_FLAG_LZ4 {
+            f.write_str("LZ4")?;
+        }
         f.write_str(" )")
     }
 }
 
+#[cfg(feature = "llmp_compression")]
+impl Flags {
+    /// The flags marking a message as compressed using `algorithm`
+    #[must_use]
+    pub fn compressed_with(algorithm: CompressionAlgorithm) -> Self {
+        match algorithm {
+            CompressionAlgorithm::Gzip => LLMP_FLAG_COMPRESSED,
+            CompressionAlgorithm::Zstd => LLMP_FLAG_COMPRESSED | LLMP_FLAG_ZSTD,
+            CompressionAlgorithm::Lz4 => LLMP_FLAG_COMPRESSED | LLMP_FLAG_LZ4,
+        }
+    }
+
+    /// The [`CompressionAlgorithm`] a message with these flags was compressed with, if any
+    #[must_use]
+    pub fn compression(self) -> Option<CompressionAlgorithm> {
+        if self & LLMP_FLAG_COMPRESSED != LLMP_FLAG_COMPRESSED {
+            None
+        } else if self & LLMP_FLAG_ZSTD == LLMP_FLAG_ZSTD {
+            Some(CompressionAlgorithm::Zstd)
+        } else if self & LLMP_FLAG_LZ4 == LLMP_FLAG_LZ4 {
+            Some(CompressionAlgorithm::Lz4)
+        } else {
+            Some(CompressionAlgorithm::Gzip)
+        }
+    }
+}
+
 impl BitAnd for Flags {
     type Output = Self;
 
@@ -900,7 +941,8 @@ where
             last_msg_sent: ptr::null_mut(),
             out_shmems: vec![LlmpSharedMap::new(
                 id,
-                shmem_provider.new_shmem(LLMP_CFG_INITIAL_MAP_SIZE)?,
+                shmem_provider
+                    .new_shmem_with_purpose(LLMP_CFG_INITIAL_MAP_SIZE, ShMemPurpose::LlmpPage)?,
             )],
             // drop pages to the broker if it already read them
             keep_pages_forever,
@@ -1300,7 +1342,8 @@ where
             // No cached maps that fit our need, let's allocate a new one.
             Ok(LlmpSharedMap::new(
                 sender_id,
-                self.shmem_provider.new_shmem(next_min_shmem_size)?,
+                self.shmem_provider
+                    .new_shmem_with_purpose(next_min_shmem_size, ShMemPurpose::LlmpPage)?,
             ))
         }
     }
@@ -2080,7 +2123,8 @@ where
                 last_msg_sent: ptr::null_mut(),
                 out_shmems: vec![LlmpSharedMap::new(
                     ClientId(0),
-                    shmem_provider.new_shmem(next_shmem_size(0))?,
+                    shmem_provider
+                        .new_shmem_with_purpose(next_shmem_size(0), ShMemPurpose::LlmpPage)?,
                 )],
                 keep_pages_forever,
                 has_unsent_message: false,
@@ -2356,6 +2400,42 @@ where
         self.llmp_clients.len() > self.listeners.len()
     }
 
+    /// The number of currently connected clients, without the broker's own listener threads
+    #[inline]
+    #[must_use]
+    pub fn num_clients(&self) -> usize {
+        self.llmp_clients.len() - self.listeners.len()
+    }
+
+    /// Returns `true` once the broker should stop brokering: after a shutdown signal,
+    /// or once all clients it waits for (see [`Self::set_exit_cleanly_after`]) connected and left again.
+    ///
+    /// Together with [`Self::once`], this allows to run own code in-between two rounds of brokering,
+    /// for example to send messages from the broker itself.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        if self.is_shutting_down() {
+            return true;
+        }
+        self.exit_cleanly_after.is_some_and(|exit_after_count| {
+            !self.has_clients()
+                && (self.num_clients_seen - self.listeners.len()) > exit_after_count.into()
+        })
+    }
+
+    /// Sets up the signal handlers that make [`Self::is_done`] return `true` on `SIGINT`.
+    /// The `loop_*` functions do this by themselves.
+    pub fn setup_shutdown_handlers() {
+        #[cfg(any(all(unix, not(miri)), all(windows, feature = "std")))]
+        Self::setup_handlers();
+    }
+
+    /// Tell all clients that this broker is exiting.
+    /// Call this after the last round of [`Self::once`], if not using one of the `loop_*` functions.
+    pub fn send_exiting(&mut self) -> Result<(), Error> {
+        self.llmp_out.send_buf(LLMP_TAG_EXITING, &[])
+    }
+
     /// Loops until the last client quits,
     /// forwarding and handling all incoming messages from clients.
     /// Will call `on_timeout` roughly after `timeout`
@@ -2757,7 +2837,8 @@ where
         // Tcp out map sends messages from background thread tcp server to foreground client
         let tcp_out_shmem = LlmpSharedMap::new(
             llmp_tcp_id,
-            self.shmem_provider.new_shmem(LLMP_CFG_INITIAL_MAP_SIZE)?,
+            self.shmem_provider
+                .new_shmem_with_purpose(LLMP_CFG_INITIAL_MAP_SIZE, ShMemPurpose::LlmpPage)?,
         );
         let tcp_out_shmem_description = tcp_out_shmem.shmem.description();
         let listener_id = self.register_client(tcp_out_shmem);
@@ -3141,7 +3222,8 @@ where
                 id: sender_id,
                 last_msg_sent: ptr::null_mut(),
                 out_shmems: vec![LlmpSharedMap::new(sender_id, {
-                    shmem_provider.new_shmem(LLMP_CFG_INITIAL_MAP_SIZE)?
+                    shmem_provider
+                        .new_shmem_with_purpose(LLMP_CFG_INITIAL_MAP_SIZE, ShMemPurpose::LlmpPage)?
                 })],
                 // drop pages to the broker if it already read them
                 keep_pages_forever: false,
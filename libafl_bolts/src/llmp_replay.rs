@@ -0,0 +1,386 @@
+//! A compact, append-only log of [`crate::llmp`] messages, and the means to replay it.
+//!
+//! Plug an [`LlmpMsgLog`] into the message hook of an [`LlmpBroker`] (see [`LlmpMsgLog::hook`])
+//! to record every message that passes the broker: sender [`ClientId`], [`Tag`], [`Flags`], and payload.
+//! The messages can later be fed back into a fresh broker or a single client using [`replay`],
+//! for example to reproduce an event storm offline.
+//!
+//! The log starts with [`LLMP_LOG_MAGIC`], followed by one record per message:
+//! the microseconds since the log was started (`u64`), the sender (`u32`), the tag (`u32`),
+//! the flags (`u32`), the payload length (`u64`), and the payload itself. All numbers are little endian.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, Write},
+    path::Path,
+    thread,
+};
+
+use crate::{
+    current_time,
+    llmp::{Flags, LlmpBroker, LlmpClient, LlmpMsgHookResult, Tag},
+    shmem::ShMemProvider,
+    ClientId, Error,
+};
+
+/// The magic bytes each LLMP message log starts with
+pub const LLMP_LOG_MAGIC: &[u8; 8] = b"LLMPLOG1";
+
+/// A single message, as recorded in the log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LlmpLoggedMsg {
+    /// When the broker saw this message, relative to the start of the log
+    pub time: Duration,
+    /// The original sender of this message
+    pub client_id: ClientId,
+    /// The tag of this message
+    pub tag: Tag,
+    /// The flags of this message, i.e., if it's compressed
+    pub flags: Flags,
+    /// The (possibly compressed) payload
+    pub buf: Vec<u8>,
+}
+
+/// An append-only log of LLMP messages
+#[derive(Debug)]
+pub struct LlmpMsgLog {
+    writer: BufWriter<File>,
+    start: Duration,
+}
+
+impl LlmpMsgLog {
+    /// Create a new log at the given path, or append to an existing one.
+    ///
+    /// Times of appended messages are relative to this call, not to the start of the existing log.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(path)?;
+        if file.seek(io::SeekFrom::End(0))? == 0 {
+            file.write_all(LLMP_LOG_MAGIC)?;
+        }
+        Ok(Self {
+            writer: BufWriter::new(file),
+            start: current_time(),
+        })
+    }
+
+    /// Append a message to the log.
+    ///
+    /// Each record is flushed right away, so that the log is complete up to the last message
+    /// even if the broker gets killed.
+    pub fn record(
+        &mut self,
+        client_id: ClientId,
+        tag: Tag,
+        flags: Flags,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        let micros = current_time().saturating_sub(self.start).as_micros() as u64;
+        self.writer.write_all(&micros.to_le_bytes())?;
+        self.writer.write_all(&client_id.0.to_le_bytes())?;
+        self.writer.write_all(&tag.0.to_le_bytes())?;
+        self.writer.write_all(&flags.0.to_le_bytes())?;
+        self.writer.write_all(&(buf.len() as u64).to_le_bytes())?;
+        self.writer.write_all(buf)?;
+        self.flush()
+    }
+
+    /// Flush all buffered records to disk
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Wrap a broker message hook, recording every message before passing it on.
+    ///
+    /// Failing to write the log is only reported, so that debugging never takes down the broker.
+    pub fn hook<'a, F>(
+        &'a mut self,
+        on_new_msg: &'a mut F,
+    ) -> impl FnMut(ClientId, Tag, Flags, &[u8]) -> Result<LlmpMsgHookResult, Error> + 'a
+    where
+        F: FnMut(ClientId, Tag, Flags, &[u8]) -> Result<LlmpMsgHookResult, Error>,
+    {
+        move |client_id, tag, flags, buf| {
+            if let Err(err) = self.record(client_id, tag, flags, buf) {
+                log::warn!("Failed to record LLMP message: {err}");
+            }
+            on_new_msg(client_id, tag, flags, buf)
+        }
+    }
+}
+
+impl Drop for LlmpMsgLog {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::warn!("Failed to flush LLMP message log: {err}");
+        }
+    }
+}
+
+/// Reads the messages of an [`LlmpMsgLog`], one by one.
+///
+/// A truncated last record, for example from a broker that crashed mid-write, ends the iteration.
+#[derive(Debug)]
+pub struct LlmpMsgLogReader<R> {
+    reader: R,
+}
+
+impl LlmpMsgLogReader<BufReader<File>> {
+    /// Open the log at the given path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> LlmpMsgLogReader<R>
+where
+    R: Read,
+{
+    /// Read a log from the given reader, checking the magic bytes first
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0_u8; LLMP_LOG_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != LLMP_LOG_MAGIC {
+            return Err(Error::illegal_argument("Not an LLMP message log"));
+        }
+        Ok(Self { reader })
+    }
+
+    fn read_msg(&mut self) -> io::Result<LlmpLoggedMsg> {
+        let mut header = [0_u8; 28];
+        self.reader.read_exact(&mut header)?;
+        let u32_at = |idx: usize| u32::from_le_bytes(header[idx..idx + 4].try_into().unwrap());
+        let micros = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let len = u64::from_le_bytes(header[20..28].try_into().unwrap());
+
+        let mut buf = Vec::new();
+        let read = (&mut self.reader).take(len).read_to_end(&mut buf)?;
+        if read as u64 != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(LlmpLoggedMsg {
+            time: Duration::from_micros(micros),
+            client_id: ClientId(u32_at(8)),
+            tag: Tag(u32_at(12)),
+            flags: Flags(u32_at(16)),
+            buf,
+        })
+    }
+}
+
+impl<R> Iterator for LlmpMsgLogReader<R>
+where
+    R: Read,
+{
+    type Item = Result<LlmpLoggedMsg, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_msg() {
+            Ok(msg) => Some(Ok(msg)),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+/// How fast to replay a log
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayTiming {
+    /// Send all messages back to back
+    AsFastAsPossible,
+    /// Keep the recorded gaps between messages, sped up by the given factor
+    Recorded {
+        /// `2.0` replays twice as fast as recorded
+        speedup: f64,
+    },
+}
+
+impl ReplayTiming {
+    /// Keep the recorded gaps between messages, sped up by `speedup`.
+    /// Fails if `speedup` is not a finite, positive number.
+    pub fn recorded(speedup: f64) -> Result<Self, Error> {
+        let timing = Self::Recorded { speedup };
+        timing.validate()?;
+        Ok(timing)
+    }
+
+    fn validate(self) -> Result<(), Error> {
+        match self {
+            Self::Recorded { speedup } if !(speedup.is_finite() && speedup > 0.0) => {
+                Err(Error::illegal_argument(format!(
+                    "Replay speedup must be a finite, positive number, got {speedup}"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Something LLMP messages can be replayed into
+pub trait LlmpReplayTarget {
+    /// Send a logged message, keeping its tag and flags
+    fn replay_msg(&mut self, msg: &LlmpLoggedMsg) -> Result<(), Error>;
+}
+
+impl<SP> LlmpReplayTarget for LlmpClient<SP>
+where
+    SP: ShMemProvider,
+{
+    fn replay_msg(&mut self, msg: &LlmpLoggedMsg) -> Result<(), Error> {
+        self.send_buf_with_flags(msg.tag, msg.flags, &msg.buf)
+    }
+}
+
+impl<SP> LlmpReplayTarget for LlmpBroker<SP>
+where
+    SP: ShMemProvider + 'static,
+{
+    /// Broadcasts the message to all connected clients.
+    /// New clients are accepted in-between messages.
+    fn replay_msg(&mut self, msg: &LlmpLoggedMsg) -> Result<(), Error> {
+        self.once(&mut |_, _, _, _| Ok(LlmpMsgHookResult::ForwardToClients))?;
+        self.send_buf_with_flags(msg.tag, msg.flags, &msg.buf)
+    }
+}
+
+/// Replay all messages from a log into the target.
+///
+/// The `filter` decides which messages get sent, for example to only replay a single client.
+/// Returns the number of replayed messages.
+/// Fails before sending anything if `timing` has a speedup that is not finite and positive.
+pub fn replay<I, T, F>(
+    messages: I,
+    target: &mut T,
+    timing: ReplayTiming,
+    mut filter: F,
+) -> Result<usize, Error>
+where
+    I: IntoIterator<Item = Result<LlmpLoggedMsg, Error>>,
+    T: LlmpReplayTarget,
+    F: FnMut(&LlmpLoggedMsg) -> bool,
+{
+    timing.validate()?;
+    let start = current_time();
+    let mut count = 0;
+    for msg in messages {
+        let msg = msg?;
+        if !filter(&msg) {
+            continue;
+        }
+        if let ReplayTiming::Recorded { speedup } = timing {
+            let due = msg.time.div_f64(speedup);
+            let elapsed = current_time().saturating_sub(start);
+            if let Some(wait) = due.checked_sub(elapsed) {
+                thread::sleep(wait);
+            }
+        }
+        target.replay_msg(&msg)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use std::{env, fs, io::Cursor};
+
+    use super::{
+        replay, LlmpLoggedMsg, LlmpMsgLog, LlmpMsgLogReader, LlmpReplayTarget, ReplayTiming,
+    };
+    use crate::{
+        llmp::{Flags, LlmpMsgHookResult, Tag},
+        ClientId, Error,
+    };
+
+    #[derive(Default)]
+    struct Collect(Vec<LlmpLoggedMsg>);
+
+    impl LlmpReplayTarget for Collect {
+        fn replay_msg(&mut self, msg: &LlmpLoggedMsg) -> Result<(), Error> {
+            self.0.push(msg.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_llmp_log_roundtrip() {
+        let path = env::temp_dir().join(format!("libafl_llmp_log_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        {
+            let mut log = LlmpMsgLog::create(&path).unwrap();
+            let mut forwarded = 0;
+            let mut on_new_msg = |_, _, _, _: &[u8]| {
+                forwarded += 1;
+                Ok(LlmpMsgHookResult::ForwardToClients)
+            };
+            let mut hook = log.hook(&mut on_new_msg);
+            hook(ClientId(1), Tag(0x42), Flags(0), b"hello").unwrap();
+            hook(ClientId(2), Tag(0x43), Flags(1), &[]).unwrap();
+            drop(hook);
+            assert_eq!(forwarded, 2);
+        }
+        // Appending keeps the old records
+        let mut log = LlmpMsgLog::create(&path).unwrap();
+        log.record(ClientId(1), Tag(0x44), Flags(0), b"world")
+            .unwrap();
+        // Records reach the file without waiting for the log to be dropped
+        assert_eq!(LlmpMsgLogReader::open(&path).unwrap().count(), 3);
+        drop(log);
+
+        let mut target = Collect::default();
+        let count = replay(
+            LlmpMsgLogReader::open(&path).unwrap(),
+            &mut target,
+            ReplayTiming::AsFastAsPossible,
+            |msg| msg.client_id == ClientId(1),
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(target.0[0].tag, Tag(0x42));
+        assert_eq!(target.0[0].buf, b"hello");
+        assert_eq!(target.0[1].buf, b"world");
+
+        // A truncated last record is dropped
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 2);
+        let reader = LlmpMsgLogReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.count(), 2);
+
+        assert!(LlmpMsgLogReader::new(Cursor::new(b"garbage!".to_vec())).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_speedup() {
+        assert!(ReplayTiming::recorded(2.0).is_ok());
+        for speedup in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(ReplayTiming::recorded(speedup).is_err());
+            let msg = LlmpLoggedMsg {
+                time: Duration::from_secs(1),
+                client_id: ClientId(1),
+                tag: Tag(0x42),
+                flags: Flags(0),
+                buf: Vec::new(),
+            };
+            let mut target = Collect::default();
+            assert!(replay(
+                [Ok(msg)],
+                &mut target,
+                ReplayTiming::Recorded { speedup },
+                |_| true
+            )
+            .is_err());
+            assert!(target.0.is_empty());
+        }
+    }
+}
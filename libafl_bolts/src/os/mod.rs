@@ -7,6 +7,9 @@ use crate::Error;
 #[cfg(all(unix, feature = "std"))]
 pub mod unix_shmem_server;
 
+#[cfg(all(unix, feature = "std"))]
+pub mod shmem_registry;
+
 #[cfg(unix)]
 pub mod unix_signals;
 #[cfg(unix)]
@@ -17,6 +20,8 @@ pub mod pipes;
 
 #[cfg(all(unix, feature = "std"))]
 use alloc::borrow::Cow;
+#[cfg(feature = "std")]
+use alloc::string::String;
 #[cfg(all(unix, feature = "std"))]
 use core::ffi::CStr;
 #[cfg(feature = "std")]
@@ -103,6 +108,13 @@ pub fn startable_self() -> Result<Command, Error> {
     Ok(startable)
 }
 
+/// The hostname of this machine, or `<unknown>` if it can't be determined
+#[cfg(feature = "std")]
+#[must_use]
+pub fn hostname() -> String {
+    hostname::get().map_or_else(|_| "<unknown>".into(), |name| name.to_string_lossy().into())
+}
+
 /// "Safe" wrapper around `dup`, duplicating the given file descriptor
 ///
 /// # Safety
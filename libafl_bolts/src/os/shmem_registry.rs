@@ -0,0 +1,779 @@
+//! A per-campaign registry of shared memory segments, and a reaper for segments leaked by dead processes.
+//!
+//! Crashing clients never get to run the [`Drop`] implementations of their maps,
+//! so the `SysV` or `POSIX` segments they created stay around until the machine reboots.
+//! Wrap any [`ShMemProvider`] in a [`RegisteredShMemProvider`] to record every segment it creates
+//! (owner pid, [`ShMemPurpose`], and size) in a registry file.
+//! Processes attaching to a registered segment record themselves as well.
+//! [`ShMemRegistry::reap`] then frees all segments that no live process uses anymore.
+//!
+//! The registry file is a list of json lines, guarded by an advisory `flock`,
+//! so that all processes of a campaign can share it.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display},
+    ops::{Deref, DerefMut},
+    ptr,
+};
+use std::{
+    collections::HashMap,
+    env,
+    ffi::CString,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    shmem::{ShMem, ShMemId, ShMemProvider, ShMemPurpose},
+    Error,
+};
+
+/// The env variable used to hand the registry path to (re)spawned clients.
+pub const SHMEM_REGISTRY_ENV: &str = "LIBAFL_SHMEM_REGISTRY";
+
+/// The OS-level handle needed to free a shared memory segment from another process.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShMemOsHandle {
+    /// A `SysV` segment, freed with `shmctl(IPC_RMID)`
+    SysV(i32),
+    /// A `POSIX` segment, freed with `shm_unlink`
+    Posix(String),
+}
+
+impl Display for ShMemOsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SysV(id) => write!(f, "sysv:{id}"),
+            Self::Posix(name) => write!(f, "posix:{name}"),
+        }
+    }
+}
+
+/// A single segment recorded in the [`ShMemRegistry`], for one process using it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ShMemRegistryEntry {
+    /// The pid of the process that created, or attached to, the segment
+    pub pid: i32,
+    /// The handle to free the segment
+    pub handle: ShMemOsHandle,
+    /// The size of the segment, in bytes
+    pub size: usize,
+    /// What the segment is used for
+    pub purpose: ShMemPurpose,
+}
+
+impl ShMemRegistryEntry {
+    /// Returns `true` if the process of this entry is still alive
+    #[must_use]
+    pub fn owner_alive(&self) -> bool {
+        process_alive(self.pid)
+    }
+}
+
+/// Number of segments and bytes, as reported by [`ShMemRegistry::usage`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShMemUsageCount {
+    /// The number of segments
+    pub segments: usize,
+    /// The accumulated size of all segments, in bytes
+    pub bytes: usize,
+}
+
+impl ShMemUsageCount {
+    fn add(&mut self, size: usize) {
+        self.segments += 1;
+        self.bytes += size;
+    }
+}
+
+/// A snapshot of the shared memory usage of a campaign
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShMemUsage {
+    /// All registered segments
+    pub total: ShMemUsageCount,
+    /// Registered segments without any live process, i.e., segments [`ShMemRegistry::reap`] may free
+    pub leaked: ShMemUsageCount,
+    /// Registered segments, by purpose
+    pub by_purpose: HashMap<ShMemPurpose, ShMemUsageCount>,
+}
+
+impl Display for ShMemUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} segments ({} bytes), {} leaked ({} bytes)",
+            self.total.segments, self.total.bytes, self.leaked.segments, self.leaked.bytes
+        )?;
+        let mut purposes: Vec<_> = self.by_purpose.iter().collect();
+        purposes.sort_by_key(|(purpose, _)| purpose.to_string());
+        for (purpose, count) in purposes {
+            write!(f, ", {purpose}: {} ({} bytes)", count.segments, count.bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// A registry file keeping track of all shared memory segments of a campaign.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShMemRegistry {
+    path: PathBuf,
+}
+
+impl ShMemRegistry {
+    /// Use (or create) the registry file at the given path
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Get the registry from the [`SHMEM_REGISTRY_ENV`] env variable, if it is set.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        match env::var_os(SHMEM_REGISTRY_ENV) {
+            Some(path) if !path.is_empty() => Ok(Some(Self::new(path)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Write the path of this registry to the [`SHMEM_REGISTRY_ENV`] env variable,
+    /// so that child processes register their segments in the same file.
+    pub fn write_to_env(&self) {
+        env::set_var(SHMEM_REGISTRY_ENV, &self.path);
+    }
+
+    /// The path of the registry file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a new segment
+    pub fn register(&self, entry: &ShMemRegistryEntry) -> Result<(), Error> {
+        let mut file = self.lock()?;
+        file.seek(SeekFrom::End(0))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Record that the process `pid` attached to the already registered segment with the given handle.
+    /// Returns `false`, and records nothing, if the segment is not registered or `pid` already uses it.
+    pub fn attach(&self, handle: &ShMemOsHandle, pid: i32) -> Result<bool, Error> {
+        let mut file = self.lock()?;
+        let entries = read_entries(&mut file)?;
+        if entries
+            .iter()
+            .any(|entry| &entry.handle == handle && entry.pid == pid)
+        {
+            return Ok(false);
+        }
+        let Some(entry) = entries.iter().find(|entry| &entry.handle == handle) else {
+            return Ok(false);
+        };
+        let attached = ShMemRegistryEntry {
+            pid,
+            ..entry.clone()
+        };
+        file.seek(SeekFrom::End(0))?;
+        writeln!(file, "{}", serde_json::to_string(&attached)?)?;
+        Ok(true)
+    }
+
+    /// Remove all records of the segment with the given handle, after it has been freed
+    pub fn unregister(&self, handle: &ShMemOsHandle) -> Result<(), Error> {
+        self.remove_entries(|entry| &entry.handle == handle)
+    }
+
+    /// Remove the record of the process `pid` for the segment with the given handle,
+    /// once it detached from the segment.
+    pub fn detach(&self, handle: &ShMemOsHandle, pid: i32) -> Result<(), Error> {
+        self.remove_entries(|entry| &entry.handle == handle && entry.pid == pid)
+    }
+
+    /// All entries currently recorded in this registry.
+    /// A segment has one entry for its creator, and one for every process attached to it.
+    pub fn entries(&self) -> Result<Vec<ShMemRegistryEntry>, Error> {
+        read_entries(&mut self.lock()?)
+    }
+
+    /// Report the current shared memory usage of this campaign
+    pub fn usage(&self) -> Result<ShMemUsage, Error> {
+        let mut usage = ShMemUsage::default();
+        for (_, segment) in group_by_segment(self.entries()?) {
+            let size = segment[0].size;
+            usage.total.add(size);
+            usage
+                .by_purpose
+                .entry(segment[0].purpose)
+                .or_default()
+                .add(size);
+            if !segment.iter().any(ShMemRegistryEntry::owner_alive) {
+                usage.leaked.add(size);
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Free all segments no live process is registered for, and remove them from the registry.
+    ///
+    /// `SysV` segments that are still attached, for example by a process outside of the registry, are kept.
+    /// Processes that still have a reaped `POSIX` segment mapped keep their mapping, the OS only releases
+    /// the memory once the last mapping is gone. No new process can attach to it, though.
+    /// Returns the entries that were reaped, with the entries of each segment next to each other.
+    pub fn reap(&self) -> Result<Vec<ShMemRegistryEntry>, Error> {
+        let mut file = self.lock()?;
+        let mut kept = Vec::new();
+        let mut reaped = Vec::new();
+        for (handle, segment) in group_by_segment(read_entries(&mut file)?) {
+            if segment.iter().any(ShMemRegistryEntry::owner_alive) || segment_attached(&handle) {
+                kept.extend(segment);
+            } else {
+                free_segment(&handle, &segment);
+                reaped.extend(segment);
+            }
+        }
+        if !reaped.is_empty() {
+            write_entries(&mut file, &kept)?;
+        }
+        Ok(reaped)
+    }
+
+    /// Remove all entries matching `predicate`
+    fn remove_entries<F>(&self, predicate: F) -> Result<(), Error>
+    where
+        F: Fn(&ShMemRegistryEntry) -> bool,
+    {
+        let mut file = self.lock()?;
+        let mut entries = read_entries(&mut file)?;
+        let len = entries.len();
+        entries.retain(|entry| !predicate(entry));
+        if entries.len() != len {
+            write_entries(&mut file, &entries)?;
+        }
+        Ok(())
+    }
+
+    /// Open the registry file and take an exclusive lock on it.
+    /// The lock is released when the returned [`File`] is dropped.
+    fn lock(&self) -> Result<File, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&self.path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(Error::last_os_error(format!(
+                "Failed to lock shmem registry {}",
+                self.path.display()
+            )));
+        }
+        Ok(file)
+    }
+}
+
+fn read_entries(file: &mut File) -> Result<Vec<ShMemRegistryEntry>, Error> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(&*file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            // A writer may have died mid-line, skip what we can't parse.
+            Err(err) => log::warn!("Skipping malformed shmem registry line {line:?}: {err}"),
+        }
+    }
+    Ok(entries)
+}
+
+fn write_entries(file: &mut File, entries: &[ShMemRegistryEntry]) -> Result<(), Error> {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&serde_json::to_string(entry)?);
+        out.push('\n');
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(out.as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Groups the entries of each segment, in the order the segments were registered
+fn group_by_segment(
+    entries: Vec<ShMemRegistryEntry>,
+) -> Vec<(ShMemOsHandle, Vec<ShMemRegistryEntry>)> {
+    let mut segments: Vec<(ShMemOsHandle, Vec<ShMemRegistryEntry>)> = Vec::new();
+    for entry in entries {
+        match segments
+            .iter_mut()
+            .find(|(handle, _)| handle == &entry.handle)
+        {
+            Some((_, segment)) => segment.push(entry),
+            None => segments.push((entry.handle.clone(), vec![entry])),
+        }
+    }
+    segments
+}
+
+/// Checks if a process with the given pid exists
+fn process_alive(pid: i32) -> bool {
+    // Signal 0 only checks for existence. `EPERM` means it exists, but belongs to someone else.
+    let ret = unsafe { libc::kill(pid, 0) };
+    ret == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Checks if a `SysV` segment is still mapped by any process.
+/// The OS does not keep track of this for `POSIX` segments.
+fn segment_attached(handle: &ShMemOsHandle) -> bool {
+    match handle {
+        ShMemOsHandle::SysV(id) => unsafe {
+            let mut ds: libc::shmid_ds = core::mem::zeroed();
+            libc::shmctl(*id, libc::IPC_STAT, &mut ds) == 0 && ds.shm_nattch > 0
+        },
+        ShMemOsHandle::Posix(_) => false,
+    }
+}
+
+/// Free a segment, after all processes in `segment` died.
+fn free_segment(handle: &ShMemOsHandle, segment: &[ShMemRegistryEntry]) {
+    match handle {
+        ShMemOsHandle::SysV(id) => unsafe {
+            // `SysV` ids get recycled: make sure the segment was created by one of our dead processes.
+            let mut ds: libc::shmid_ds = core::mem::zeroed();
+            if libc::shmctl(*id, libc::IPC_STAT, &mut ds) == 0
+                && segment.iter().any(|entry| entry.pid == ds.shm_cpid)
+            {
+                libc::shmctl(*id, libc::IPC_RMID, ptr::null_mut());
+            }
+        },
+        ShMemOsHandle::Posix(name) => {
+            if let Ok(name) = CString::new(name.as_bytes()) {
+                unsafe {
+                    libc::shm_unlink(name.as_ptr());
+                }
+            }
+        }
+    }
+}
+
+/// The record of a [`RegisteredShMem`] in the [`ShMemRegistry`], for the process that created or attached to it
+#[derive(Debug)]
+struct ShMemRegistration {
+    registry: ShMemRegistry,
+    handle: ShMemOsHandle,
+    pid: i32,
+    /// If this process created the segment, and not just attached to it
+    owner: bool,
+}
+
+/// A [`ShMem`] created by a [`RegisteredShMemProvider`].
+/// Removes itself from the [`ShMemRegistry`] when the inner map gets dropped.
+///
+/// Only the map originally created or attached to in a process does so:
+/// clones, and copies inherited by forked children, leave the registry alone.
+#[derive(Debug)]
+pub struct RegisteredShMem<SH>
+where
+    SH: ShMem,
+{
+    inner: SH,
+    registration: Option<ShMemRegistration>,
+}
+
+impl<SH> Clone for RegisteredShMem<SH>
+where
+    SH: ShMem,
+{
+    fn clone(&self) -> Self {
+        // The segment stays registered until the original is dropped
+        Self {
+            inner: self.inner.clone(),
+            registration: None,
+        }
+    }
+}
+
+impl<SH> ShMem for RegisteredShMem<SH>
+where
+    SH: ShMem,
+{
+    fn id(&self) -> ShMemId {
+        self.inner.id()
+    }
+
+    fn os_handle(&self) -> Option<ShMemOsHandle> {
+        self.inner.os_handle()
+    }
+}
+
+impl<SH> Deref for RegisteredShMem<SH>
+where
+    SH: ShMem,
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.inner
+    }
+}
+
+impl<SH> DerefMut for RegisteredShMem<SH>
+where
+    SH: ShMem,
+{
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.inner
+    }
+}
+
+impl<SH> Drop for RegisteredShMem<SH>
+where
+    SH: ShMem,
+{
+    fn drop(&mut self) {
+        if let Some(registration) = &self.registration {
+            // A forked child still has the segment of its parent mapped
+            if registration.pid != unsafe { libc::getpid() } {
+                return;
+            }
+            let res = if registration.owner {
+                registration.registry.unregister(&registration.handle)
+            } else {
+                registration
+                    .registry
+                    .detach(&registration.handle, registration.pid)
+            };
+            if let Err(err) = res {
+                log::warn!("Failed to unregister shmem {}: {err}", registration.handle);
+            }
+        }
+    }
+}
+
+/// A [`ShMemProvider`] that records all segments created by the wrapped provider in a [`ShMemRegistry`].
+///
+/// If no registry is set, this acts exactly like the inner provider.
+/// [`ShMemProvider::new`] picks up the registry from the [`SHMEM_REGISTRY_ENV`] env variable,
+/// so respawned clients keep registering in the same file.
+#[derive(Clone, Debug, Default)]
+pub struct RegisteredShMemProvider<SP> {
+    inner: SP,
+    registry: Option<ShMemRegistry>,
+}
+
+impl<SP> RegisteredShMemProvider<SP>
+where
+    SP: ShMemProvider,
+{
+    /// Wrap a provider, registering its segments in the given registry.
+    /// Also writes the registry to the env, for child processes.
+    #[must_use]
+    pub fn with_registry(inner: SP, registry: ShMemRegistry) -> Self {
+        registry.write_to_env();
+        Self {
+            inner,
+            registry: Some(registry),
+        }
+    }
+
+    /// The registry segments are recorded in, if any
+    #[must_use]
+    pub fn registry(&self) -> Option<&ShMemRegistry> {
+        self.registry.as_ref()
+    }
+
+    /// The wrapped provider
+    #[must_use]
+    pub fn inner(&self) -> &SP {
+        &self.inner
+    }
+
+    /// The wrapped provider (mutable)
+    pub fn inner_mut(&mut self) -> &mut SP {
+        &mut self.inner
+    }
+
+    /// Record that this process attached to `inner`, if the segment is registered
+    fn attach(&self, inner: SP::ShMem) -> Result<RegisteredShMem<SP::ShMem>, Error> {
+        let registration = match (&self.registry, inner.os_handle()) {
+            (Some(registry), Some(handle)) => {
+                let pid = unsafe { libc::getpid() };
+                registry.attach(&handle, pid)?.then(|| ShMemRegistration {
+                    registry: registry.clone(),
+                    handle,
+                    pid,
+                    owner: false,
+                })
+            }
+            _ => None,
+        };
+        Ok(RegisteredShMem {
+            inner,
+            registration,
+        })
+    }
+}
+
+impl<SP> ShMemProvider for RegisteredShMemProvider<SP>
+where
+    SP: ShMemProvider,
+{
+    type ShMem = RegisteredShMem<SP::ShMem>;
+
+    fn new() -> Result<Self, Error> {
+        Ok(Self {
+            inner: SP::new()?,
+            registry: ShMemRegistry::from_env()?,
+        })
+    }
+
+    fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
+        self.new_shmem_with_purpose(map_size, ShMemPurpose::Other)
+    }
+
+    fn new_shmem_with_purpose(
+        &mut self,
+        map_size: usize,
+        purpose: ShMemPurpose,
+    ) -> Result<Self::ShMem, Error> {
+        let inner = self.inner.new_shmem_with_purpose(map_size, purpose)?;
+        let registration = match (&self.registry, inner.os_handle()) {
+            (Some(registry), Some(handle)) => {
+                let pid = unsafe { libc::getpid() };
+                registry.register(&ShMemRegistryEntry {
+                    pid,
+                    handle: handle.clone(),
+                    size: map_size,
+                    purpose,
+                })?;
+                Some(ShMemRegistration {
+                    registry: registry.clone(),
+                    handle,
+                    pid,
+                    owner: true,
+                })
+            }
+            _ => None,
+        };
+        Ok(RegisteredShMem {
+            inner,
+            registration,
+        })
+    }
+
+    fn shmem_from_id_and_size(&mut self, id: ShMemId, size: usize) -> Result<Self::ShMem, Error> {
+        let inner = self.inner.shmem_from_id_and_size(id, size)?;
+        self.attach(inner)
+    }
+
+    fn shmem_from_received_fd(&mut self, fd: i32, size: usize) -> Result<Self::ShMem, Error> {
+        let inner = self.inner.shmem_from_received_fd(fd, size)?;
+        self.attach(inner)
+    }
+
+    fn clone_ref(&mut self, mapping: &Self::ShMem) -> Result<Self::ShMem, Error> {
+        Ok(RegisteredShMem {
+            inner: self.inner.clone_ref(&mapping.inner)?,
+            registration: None,
+        })
+    }
+
+    fn pre_fork(&mut self) -> Result<(), Error> {
+        self.inner.pre_fork()
+    }
+
+    fn post_fork(&mut self, is_child: bool) -> Result<(), Error> {
+        self.inner.post_fork(is_child)
+    }
+
+    fn release_shmem(&mut self, shmem: &mut Self::ShMem) {
+        self.inner.release_shmem(&mut shmem.inner);
+    }
+}
+
+#[cfg(all(test, not(target_os = "haiku")))]
+mod tests {
+    use serial_test::serial;
+
+    use super::{
+        RegisteredShMemProvider, ShMemOsHandle, ShMemRegistry, ShMemRegistryEntry,
+        SHMEM_REGISTRY_ENV,
+    };
+    #[cfg(target_os = "linux")]
+    use crate::shmem::{ShMem, ShMemId};
+    use crate::shmem::{ShMemProvider, ShMemPurpose, StdShMemProvider};
+
+    fn temp_registry(name: &str) -> ShMemRegistry {
+        let path = std::env::temp_dir().join(format!(
+            "libafl_shmem_registry_{name}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        ShMemRegistry::new(path).unwrap()
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_registry_register_usage() {
+        let registry = temp_registry("usage");
+        let mut provider = RegisteredShMemProvider::with_registry(
+            StdShMemProvider::new().unwrap(),
+            registry.clone(),
+        );
+        let map = provider
+            .new_shmem_with_purpose(4096, ShMemPurpose::LlmpPage)
+            .unwrap();
+        let _other = provider.new_shmem(1024).unwrap();
+
+        #[cfg(not(any(target_os = "android", target_vendor = "apple")))]
+        {
+            let usage = registry.usage().unwrap();
+            assert_eq!(usage.total.segments, 2);
+            assert_eq!(usage.total.bytes, 4096 + 1024);
+            assert_eq!(usage.leaked.segments, 0);
+            assert_eq!(usage.by_purpose[&ShMemPurpose::LlmpPage].bytes, 4096);
+
+            drop(map);
+            assert_eq!(registry.entries().unwrap().len(), 1);
+        }
+        #[cfg(any(target_os = "android", target_vendor = "apple"))]
+        drop(map);
+
+        std::env::remove_var(SHMEM_REGISTRY_ENV);
+        let _ = std::fs::remove_file(registry.path());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(not(any(target_os = "android", target_vendor = "apple")))]
+    fn test_registry_clone_keeps_entry() {
+        let registry = temp_registry("clone");
+        let mut provider = RegisteredShMemProvider::with_registry(
+            StdShMemProvider::new().unwrap(),
+            registry.clone(),
+        );
+        let map = provider.new_shmem(4096).unwrap();
+        assert_eq!(registry.entries().unwrap().len(), 1);
+
+        // The original is still alive, so the segment must stay registered
+        drop(map.clone());
+        assert_eq!(registry.entries().unwrap().len(), 1);
+
+        drop(map);
+        assert!(registry.entries().unwrap().is_empty());
+
+        std::env::remove_var(SHMEM_REGISTRY_ENV);
+        let _ = std::fs::remove_file(registry.path());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_registry_reap() {
+        let registry = temp_registry("reap");
+        // A pid that can't exist on any sane system
+        let dead = ShMemRegistryEntry {
+            pid: i32::MAX - 1,
+            handle: ShMemOsHandle::Posix("/libafl_registry_test_nonexistent".into()),
+            size: 1234,
+            purpose: ShMemPurpose::CoverageMap,
+        };
+        let alive = ShMemRegistryEntry {
+            pid: unsafe { libc::getpid() },
+            handle: ShMemOsHandle::Posix("/libafl_registry_test_alive".into()),
+            size: 42,
+            purpose: ShMemPurpose::StateRestorer,
+        };
+        registry.register(&dead).unwrap();
+        registry.register(&alive).unwrap();
+
+        let usage = registry.usage().unwrap();
+        assert_eq!(usage.leaked.segments, 1);
+        assert_eq!(usage.leaked.bytes, 1234);
+
+        assert_eq!(registry.reap().unwrap(), vec![dead]);
+        assert_eq!(registry.entries().unwrap(), vec![alive.clone()]);
+
+        registry.unregister(&alive.handle).unwrap();
+        assert!(registry.entries().unwrap().is_empty());
+
+        let _ = std::fs::remove_file(registry.path());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(target_os = "linux")]
+    fn test_registry_reap_keeps_attached() {
+        let registry = temp_registry("attached");
+        let mut provider = RegisteredShMemProvider::with_registry(
+            StdShMemProvider::new().unwrap(),
+            registry.clone(),
+        );
+        let mut pipe = [0; 2];
+        assert_eq!(unsafe { libc::pipe(pipe.as_mut_ptr()) }, 0);
+
+        let owner = unsafe { libc::fork() };
+        assert!(owner >= 0);
+        if owner == 0 {
+            // The owner creates the segment, hands its id to the parent, and waits to get killed
+            let mut map = provider
+                .new_shmem_with_purpose(4096, ShMemPurpose::CoverageMap)
+                .unwrap();
+            map[0] = 0x42;
+            let id: i32 = map.id().into();
+            unsafe {
+                libc::write(pipe[1], (&id as *const i32).cast(), 4);
+                loop {
+                    libc::pause();
+                }
+            }
+        }
+
+        let mut id = 0_i32;
+        assert_eq!(
+            unsafe { libc::read(pipe[0], (&mut id as *mut i32).cast(), 4) },
+            4
+        );
+        let map = provider
+            .shmem_from_id_and_size(ShMemId::from_int(id), 4096)
+            .unwrap();
+        assert_eq!(registry.entries().unwrap().len(), 2);
+
+        unsafe {
+            libc::kill(owner, libc::SIGKILL);
+            libc::waitpid(owner, core::ptr::null_mut(), 0);
+            libc::close(pipe[0]);
+            libc::close(pipe[1]);
+        }
+
+        // The owner died without cleaning up, but we still use the segment
+        assert!(registry.reap().unwrap().is_empty());
+        assert_eq!(registry.usage().unwrap().leaked.segments, 0);
+        assert_eq!(map[0], 0x42);
+
+        // Once we are gone as well, only the dead owner is left
+        drop(map);
+        let reaped = registry.reap().unwrap();
+        assert_eq!(reaped.len(), 1);
+        assert_eq!(reaped[0].pid, owner);
+        assert_eq!(reaped[0].purpose, ShMemPurpose::CoverageMap);
+        assert!(registry.entries().unwrap().is_empty());
+
+        std::env::remove_var(SHMEM_REGISTRY_ENV);
+        let _ = std::fs::remove_file(registry.path());
+    }
+}
@@ -203,12 +203,7 @@ where
         let (server_fd, client_fd) = self.send_receive(ServedShMemRequest::NewMap(map_size))?;
 
         Ok(ServedShMem {
-            inner: ManuallyDrop::new(
-                self.inner.shmem_from_id_and_size(
-                    ShMemId::from_string(&format!("{client_fd}")),
-                    map_size,
-                )?,
-            ),
+            inner: ManuallyDrop::new(self.inner.shmem_from_received_fd(client_fd, map_size)?),
             server_fd,
         })
     }
@@ -220,10 +215,7 @@ where
             ShMemDescription::from_string_and_size(server_id_str, size),
         ))?;
         Ok(ServedShMem {
-            inner: ManuallyDrop::new(
-                self.inner
-                    .shmem_from_id_and_size(ShMemId::from_string(&format!("{client_fd}")), size)?,
-            ),
+            inner: ManuallyDrop::new(self.inner.shmem_from_received_fd(client_fd, size)?),
             server_fd,
         })
     }
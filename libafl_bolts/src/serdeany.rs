@@ -23,30 +23,126 @@ pub type TypeRepr = u128;
 #[cfg(feature = "unsafe_stable_anymap")]
 pub type TypeRepr = String;
 
+/// The stable name and schema version of a [`SerdeAny`] type.
+///
+/// By default, [`SerdeAny`] types are identified by their [`core::any::TypeId`] (or their type name,
+/// with `unsafe_stable_anymap`), which changes as soon as the type is renamed or moved, or the
+/// compiler is updated. Types with a [`crate::serdeany::SerdeAnySchema`] are identified by their stable `name`
+/// instead, and serialized together with their schema `version`. Older versions are upgraded
+/// on deserialization, using the migrations registered with
+/// [`RegistryBuilder::register_migration`].
+///
+/// Use `impl_serdeany!(MyType, name = "my_crate::MyType", version = 2)` to assign a schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerdeAnySchema {
+    name: &'static str,
+    version: u32,
+    #[cfg(not(feature = "unsafe_stable_anymap"))]
+    repr: TypeRepr,
+}
+
+impl SerdeAnySchema {
+    /// Create a new schema with the given stable `name` and `version`
+    #[must_use]
+    pub const fn new(name: &'static str, version: u32) -> Self {
+        Self {
+            name,
+            version,
+            #[cfg(not(feature = "unsafe_stable_anymap"))]
+            repr: stable_name_repr(name),
+        }
+    }
+
+    /// The stable name of this type
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The current schema version of this type
+    #[must_use]
+    pub const fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The [`TypeRepr`] this type is stored and serialized as
+    #[cfg(not(feature = "unsafe_stable_anymap"))]
+    #[must_use]
+    pub const fn type_repr(&self) -> TypeRepr {
+        self.repr
+    }
+
+    /// The [`TypeRepr`] this type is stored and serialized as
+    #[cfg(feature = "unsafe_stable_anymap")]
+    #[must_use]
+    pub const fn type_repr(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Hashes a stable type name to a [`TypeRepr`], using 128 bit FNV-1a.
+/// Other than `TypeId`s, the result never changes between builds.
 #[cfg(not(feature = "unsafe_stable_anymap"))]
-fn type_repr<T>() -> TypeRepr
+const fn stable_name_repr(name: &str) -> TypeRepr {
+    const FNV_OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+    const FNV_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+    let bytes = name.as_bytes();
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u128;
+        hash = hash.wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+    hash
+}
+
+/// The [`TypeRepr`] a type would have without a [`SerdeAnySchema`]
+#[cfg(not(feature = "unsafe_stable_anymap"))]
+fn legacy_type_repr<T>() -> TypeRepr
 where
     T: 'static,
 {
     unpack_type_id(TypeId::of::<T>())
 }
 
+/// The [`TypeRepr`] a type would have without a [`SerdeAnySchema`]
+#[cfg(feature = "unsafe_stable_anymap")]
+fn legacy_type_repr<T>() -> TypeRepr {
+    type_name::<T>().to_string()
+}
+
+#[cfg(not(feature = "unsafe_stable_anymap"))]
+fn type_repr<T>() -> TypeRepr
+where
+    T: SerdeAny,
+{
+    T::schema().map_or_else(legacy_type_repr::<T>, |schema| schema.type_repr())
+}
+
 #[cfg(not(feature = "unsafe_stable_anymap"))]
 fn type_repr_owned<T>() -> TypeRepr
 where
-    T: 'static,
+    T: SerdeAny,
 {
-    unpack_type_id(TypeId::of::<T>())
+    type_repr::<T>()
 }
 
 #[cfg(feature = "unsafe_stable_anymap")]
-fn type_repr_owned<T>() -> TypeRepr {
-    type_name::<T>().to_string()
+fn type_repr_owned<T>() -> TypeRepr
+where
+    T: SerdeAny,
+{
+    type_repr::<T>().to_string()
 }
 
 #[cfg(feature = "unsafe_stable_anymap")]
-fn type_repr<T>() -> &'static str {
-    type_name::<T>()
+fn type_repr<T>() -> &'static str
+where
+    T: SerdeAny,
+{
+    T::schema().map_or_else(type_name::<T>, |schema| schema.type_repr())
 }
 
 /// A (de)serializable Any trait
@@ -57,6 +153,21 @@ pub trait SerdeAny: Any + erased_serde::Serialize + Debug {
     fn as_any_mut(&mut self) -> &mut dyn Any;
     /// returns this as boxed Any trait
     fn as_any_boxed(self: Box<Self>) -> Box<dyn Any>;
+
+    /// The stable name and schema version of this type, if it has one
+    #[must_use]
+    fn schema() -> Option<SerdeAnySchema>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The stable name and schema version of this object's type, if it has one
+    #[must_use]
+    fn dyn_schema(&self) -> Option<SerdeAnySchema> {
+        None
+    }
 }
 
 /// Wrap a type for serialization
@@ -111,20 +222,23 @@ pub mod serdeany_registry {
 
     use alloc::{
         boxed::Box,
+        format,
         string::{String, ToString},
     };
-    use core::{any::TypeId, fmt, hash::BuildHasherDefault};
+    use core::{any::TypeId, fmt, hash::BuildHasherDefault, marker::PhantomData};
 
     use hashbrown::{
         hash_map::{Values, ValuesMut},
         HashMap,
     };
-    use serde::{Deserialize, Serialize};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+    #[cfg(not(feature = "unsafe_stable_anymap"))]
+    use crate::{anymap::unpack_type_id, serdeany::SerdeAnySchema};
     use crate::{
         serdeany::{
-            type_repr, type_repr_owned, DeserializeCallback, DeserializeCallbackSeed, SerdeAny,
-            TypeRepr,
+            legacy_type_repr, type_repr, type_repr_owned, DeserializeCallback,
+            DeserializeCallbackSeed, SerdeAny, TypeRepr,
         },
         Error,
     };
@@ -132,6 +246,10 @@ pub mod serdeany_registry {
     /// A [`HashMap`] that maps from [`TypeRepr`] to a deserializer and its [`TypeId`].
     type DeserializeCallbackMap = HashMap<TypeRepr, (DeserializeCallback<dyn SerdeAny>, TypeId)>;
 
+    /// A [`HashMap`] that maps from a [`TypeRepr`] and an old schema version to a deserializer
+    /// upgrading it to the current version.
+    type MigrationMap = HashMap<(TypeRepr, u32), DeserializeCallback<dyn SerdeAny>>;
+
     /// Visitor object used internally for the [`crate::serdeany::SerdeAny`] registry.
     #[derive(Debug)]
     pub struct BoxDynVisitor {}
@@ -163,9 +281,150 @@ pub mod serdeany_registry {
         }
     }
 
+    /// Visitor for the `(version, value)` pairs [`SerdeAny`] types with a [`crate::serdeany::SerdeAnySchema`]
+    /// are serialized as.
+    struct VersionedVisitor<T> {
+        phantom: PhantomData<T>,
+    }
+
+    impl<'de, T> serde::de::Visitor<'de> for VersionedVisitor<T>
+    where
+        T: SerdeAny + DeserializeOwned,
+    {
+        type Value = Box<dyn SerdeAny>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("Expecting a versioned serialized trait object")
+        }
+
+        fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+        where
+            V: serde::de::SeqAccess<'de>,
+        {
+            let version: u32 = visitor
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+            let cb = versioned_deserializer::<T>(version).map_err(serde::de::Error::custom)?;
+            let seed = DeserializeCallbackSeed::<dyn SerdeAny> { cb };
+            visitor
+                .next_element_seed(seed)?
+                .ok_or_else(|| serde::de::Error::invalid_length(1, &self))
+        }
+    }
+
+    /// Returns the deserializer for version `version` of the type `T`,
+    /// either `T` itself or a registered migration.
+    fn versioned_deserializer<T>(version: u32) -> Result<DeserializeCallback<dyn SerdeAny>, String>
+    where
+        T: SerdeAny + DeserializeOwned,
+    {
+        let schema = T::schema().expect("Versioned deserialization of a type without schema");
+        if version == schema.version() {
+            return Ok(|de| Ok(Box::new(erased_serde::deserialize::<T>(de)?)));
+        }
+        if version > schema.version() {
+            return Err(format!(
+                "{} version {version} was written by a newer build, the latest known version is {}",
+                schema.name(),
+                schema.version()
+            ));
+        }
+        unsafe {
+            REGISTRY
+                .migrations
+                .as_ref()
+                .and_then(|migrations| migrations.get(&(type_repr_owned::<T>(), version)))
+                .copied()
+        }
+        .ok_or_else(|| {
+            format!(
+                "No migration registered for {} from version {version} to version {}",
+                schema.name(),
+                schema.version()
+            )
+        })
+    }
+
+    /// Deserializes a type with a [`crate::serdeany::SerdeAnySchema`] from its `(version, value)` pair.
+    fn deserialize_versioned<T>(
+        de: &mut dyn erased_serde::Deserializer,
+    ) -> Result<Box<dyn SerdeAny>, erased_serde::Error>
+    where
+        T: SerdeAny + DeserializeOwned,
+    {
+        serde::Deserializer::deserialize_tuple(
+            de,
+            2,
+            VersionedVisitor::<T> {
+                phantom: PhantomData,
+            },
+        )
+    }
+
+    /// Deserializes a type with a [`crate::serdeany::SerdeAnySchema`] that was serialized before it had a schema.
+    /// Such data is treated as version `0`, if a migration from version `0` is registered,
+    /// and as the current version otherwise.
+    fn deserialize_unversioned<T>(
+        de: &mut dyn erased_serde::Deserializer,
+    ) -> Result<Box<dyn SerdeAny>, erased_serde::Error>
+    where
+        T: SerdeAny + DeserializeOwned,
+    {
+        let current: DeserializeCallback<dyn SerdeAny> =
+            |de| Ok(Box::new(erased_serde::deserialize::<T>(de)?));
+        let cb = versioned_deserializer::<T>(0).unwrap_or(current);
+        cb(de)
+    }
+
+    /// The [`TypeRepr`] of the stable name `name`
+    #[cfg(not(feature = "unsafe_stable_anymap"))]
+    fn stable_type_repr(name: &'static str) -> TypeRepr {
+        SerdeAnySchema::new(name, 0).type_repr()
+    }
+
+    /// The [`TypeRepr`] of the stable name `name`
+    #[cfg(feature = "unsafe_stable_anymap")]
+    fn stable_type_repr(name: &'static str) -> TypeRepr {
+        name.to_string()
+    }
+
+    /// The [`TypeRepr`] the concrete type of `obj` is stored and serialized as
+    #[cfg(not(feature = "unsafe_stable_anymap"))]
+    #[allow(clippy::unnecessary_wraps)]
+    pub(crate) fn dyn_type_repr(obj: &dyn SerdeAny) -> Result<TypeRepr, Error> {
+        Ok(obj.dyn_schema().map_or_else(
+            || unpack_type_id(obj.as_any().type_id()),
+            |schema| schema.type_repr(),
+        ))
+    }
+
+    /// The [`TypeRepr`] the concrete type of `obj` is stored and serialized as.
+    ///
+    /// Without a schema, this is the type name it was registered with,
+    /// so types that are not registered cannot be serialized.
+    #[cfg(feature = "unsafe_stable_anymap")]
+    pub(crate) fn dyn_type_repr(obj: &dyn SerdeAny) -> Result<TypeRepr, Error> {
+        if let Some(schema) = obj.dyn_schema() {
+            return Ok(schema.type_repr().to_string());
+        }
+        unsafe {
+            REGISTRY
+                .type_reprs
+                .as_ref()
+                .and_then(|type_reprs| type_reprs.get(&obj.as_any().type_id()))
+                .cloned()
+                .ok_or_else(|| {
+                    Error::key_not_found(format!("Cannot serialize an unregistered type: {obj:?}"))
+                })
+        }
+    }
+
     #[allow(unused_qualifications)]
     struct Registry {
         deserializers: Option<DeserializeCallbackMap>,
+        migrations: Option<MigrationMap>,
+        #[cfg(feature = "unsafe_stable_anymap")]
+        type_reprs: Option<HashMap<TypeId, TypeRepr>>,
         finalized: bool,
     }
 
@@ -178,17 +437,75 @@ pub mod serdeany_registry {
             assert!(!self.finalized, "Registry is already finalized!");
 
             let deserializers = self.deserializers.get_or_insert_with(HashMap::default);
-            let _entry = deserializers
+            let entry = deserializers
                 .entry(type_repr_owned::<T>())
                 .or_insert_with(|| {
-                    (
-                        |de| Ok(Box::new(erased_serde::deserialize::<T>(de)?)),
-                        TypeId::of::<T>(),
-                    )
+                    if T::schema().is_some() {
+                        (deserialize_versioned::<T>, TypeId::of::<T>())
+                    } else {
+                        (
+                            |de| Ok(Box::new(erased_serde::deserialize::<T>(de)?)),
+                            TypeId::of::<T>(),
+                        )
+                    }
                 });
+            assert_eq!(entry.1, TypeId::of::<T>(), "Fatal safety error: TypeId of type {} is not equals to the deserializer's TypeId for this type! Two registered types have the same type_name or stable name!", core::any::type_name::<T>());
+
+            if T::schema().is_some() {
+                // Keep loading data that was serialized before this type had a schema
+                deserializers
+                    .entry(legacy_type_repr::<T>())
+                    .or_insert_with(|| (deserialize_unversioned::<T>, TypeId::of::<T>()));
+            }
 
             #[cfg(feature = "unsafe_stable_anymap")]
-            assert_eq!(_entry.1, TypeId::of::<T>(), "Fatal safety error: TypeId of type {} is not equals to the deserializer's TypeId for this type! Two registered types have the same type_name!", type_repr::<T>());
+            self.type_reprs
+                .get_or_insert_with(HashMap::default)
+                .insert(TypeId::of::<T>(), type_repr_owned::<T>());
+        }
+
+        pub fn register_alias<T>(&mut self, old_name: &'static str)
+        where
+            T: crate::serdeany::SerdeAny + Serialize + serde::de::DeserializeOwned,
+        {
+            assert!(!self.finalized, "Registry is already finalized!");
+            assert!(
+                T::schema().is_some(),
+                "Aliases can only be registered for types with a SerdeAnySchema"
+            );
+
+            let entry = self
+                .deserializers
+                .get_or_insert_with(HashMap::default)
+                .entry(stable_type_repr(old_name))
+                .or_insert_with(|| (deserialize_versioned::<T>, TypeId::of::<T>()));
+            assert_eq!(
+                entry.1,
+                TypeId::of::<T>(),
+                "The name {old_name} is already used by a different type!"
+            );
+        }
+
+        pub fn register_migration<T, O>(&mut self, from_version: u32)
+        where
+            T: crate::serdeany::SerdeAny + From<O>,
+            O: serde::de::DeserializeOwned,
+        {
+            assert!(!self.finalized, "Registry is already finalized!");
+            let schema = T::schema()
+                .expect("Migrations can only be registered for types with a SerdeAnySchema");
+            assert!(
+                from_version < schema.version(),
+                "Migrations must upgrade {} from a version older than {}",
+                schema.name(),
+                schema.version()
+            );
+
+            self.migrations
+                .get_or_insert_with(HashMap::default)
+                .insert((type_repr_owned::<T>(), from_version), |de| {
+                    Ok(Box::new(T::from(erased_serde::deserialize::<O>(de)?)))
+                });
         }
 
         pub fn finalize(&mut self) {
@@ -198,6 +515,9 @@ pub mod serdeany_registry {
 
     static mut REGISTRY: Registry = Registry {
         deserializers: None,
+        migrations: None,
+        #[cfg(feature = "unsafe_stable_anymap")]
+        type_reprs: None,
         finalized: false,
     };
 
@@ -222,6 +542,42 @@ pub mod serdeany_registry {
             }
         }
 
+        /// Register `old_name` as a former stable name of the type `T`,
+        /// so that data serialized under the old name can still be deserialized.
+        ///
+        /// `T` needs a [`crate::serdeany::SerdeAnySchema`].
+        ///
+        /// # Safety
+        /// This may never be called concurrently or at the same time as `finalize`.
+        /// It dereferences the `REGISTRY` hashmap and adds the given alias to it.
+        pub unsafe fn register_alias<T>(old_name: &'static str)
+        where
+            T: crate::serdeany::SerdeAny + Serialize + serde::de::DeserializeOwned,
+        {
+            unsafe {
+                REGISTRY.register_alias::<T>(old_name);
+            }
+        }
+
+        /// Register a migration of the type `T` from schema version `from_version`.
+        ///
+        /// Data serialized as version `from_version` is deserialized as `O`, the old layout of `T`,
+        /// and then converted to `T` using [`From`].
+        /// Data serialized before `T` had a [`crate::serdeany::SerdeAnySchema`] counts as version `0`.
+        ///
+        /// # Safety
+        /// This may never be called concurrently or at the same time as `finalize`.
+        /// It dereferences the `REGISTRY` hashmap and adds the given migration to it.
+        pub unsafe fn register_migration<T, O>(from_version: u32)
+        where
+            T: crate::serdeany::SerdeAny + From<O>,
+            O: serde::de::DeserializeOwned,
+        {
+            unsafe {
+                REGISTRY.register_migration::<T, O>(from_version);
+            }
+        }
+
         /// Finalize the registry, no more registrations are allowed after this call
         ///
         /// # Safety
@@ -238,10 +594,31 @@ pub mod serdeany_registry {
     /// in the registry
     #[allow(clippy::unsafe_derive_deserialize)]
     #[derive(Debug, Serialize, Deserialize)]
+    #[serde(try_from = "RawSerdeAnyMap")]
     pub struct SerdeAnyMap {
         map: HashMap<TypeRepr, Box<dyn SerdeAny>>,
     }
 
+    /// A [`SerdeAnyMap`] as it was serialized.
+    /// Its keys may be outdated, if entries were renamed or migrated.
+    #[derive(Deserialize)]
+    struct RawSerdeAnyMap {
+        map: HashMap<TypeRepr, Box<dyn SerdeAny>>,
+    }
+
+    impl TryFrom<RawSerdeAnyMap> for SerdeAnyMap {
+        type Error = Error;
+
+        fn try_from(raw: RawSerdeAnyMap) -> Result<Self, Error> {
+            let map = raw
+                .map
+                .into_values()
+                .map(|value| Ok((dyn_type_repr(value.as_ref())?, value)))
+                .collect::<Result<_, Error>>()?;
+            Ok(Self { map })
+        }
+    }
+
     // Cloning by serializing and deserializing. It ain't fast, but it's honest work.
     // We unwrap postcard, it should not have a reason to fail.
     impl Clone for SerdeAnyMap {
@@ -435,10 +812,32 @@ pub mod serdeany_registry {
     #[allow(clippy::unsafe_derive_deserialize)]
     #[allow(unused_qualifications)]
     #[derive(Debug, Serialize, Deserialize)]
+    #[serde(try_from = "RawNamedSerdeAnyMap")]
     pub struct NamedSerdeAnyMap {
         map: HashMap<TypeRepr, HashMap<String, Box<dyn crate::serdeany::SerdeAny>>>,
     }
 
+    /// A [`NamedSerdeAnyMap`] as it was serialized.
+    /// Its keys may be outdated, if entries were renamed or migrated.
+    #[derive(Deserialize)]
+    struct RawNamedSerdeAnyMap {
+        map: HashMap<TypeRepr, HashMap<String, Box<dyn SerdeAny>>>,
+    }
+
+    impl TryFrom<RawNamedSerdeAnyMap> for NamedSerdeAnyMap {
+        type Error = Error;
+
+        fn try_from(raw: RawNamedSerdeAnyMap) -> Result<Self, Error> {
+            let mut map: HashMap<TypeRepr, HashMap<String, Box<dyn SerdeAny>>> = HashMap::default();
+            for (name, value) in raw.map.into_values().flatten() {
+                map.entry(dyn_type_repr(value.as_ref())?)
+                    .or_default()
+                    .insert(name, value);
+            }
+            Ok(Self { map })
+        }
+    }
+
     // Cloning by serializing and deserializing. It ain't fast, but it's honest work.
     // We unwrap postcard, it should not have a reason to fail.
     impl Clone for NamedSerdeAnyMap {
@@ -768,10 +1167,14 @@ impl Serialize for dyn crate::serdeany::SerdeAny {
     {
         use serde::ser::SerializeSeq;
 
-        let id = crate::anymap::unpack_type_id(self.type_id());
+        let id = serdeany_registry::dyn_type_repr(self).map_err(serde::ser::Error::custom)?;
         let mut seq = se.serialize_seq(Some(2))?;
         seq.serialize_element(&id)?;
-        seq.serialize_element(&crate::serdeany::Wrap(self))?;
+        if let Some(schema) = self.dyn_schema() {
+            seq.serialize_element(&(schema.version(), crate::serdeany::Wrap(self)))?;
+        } else {
+            seq.serialize_element(&crate::serdeany::Wrap(self))?;
+        }
         seq.end()
     }
 }
@@ -819,8 +1222,71 @@ macro_rules! create_register {
 }
 
 /// Implement a [`SerdeAny`], registering it in the [`RegistryBuilder`] when on std
+///
+/// Use `impl_serdeany!(MyType, name = "my_crate::MyType", version = 1)` to give the type a
+/// [`SerdeAnySchema`], so that it can still be deserialized after it was renamed or changed.
+///
+/// Generic types get a schema for each listed instantiation, named after the instantiation:
+/// `impl_serdeany!(MyType<T: Debug>, <u8>, <u16>, name = "my_crate::MyType", version = 1)` names
+/// `MyType<u8>` `"my_crate::MyType<u8>"`. Other instantiations keep working without a schema.
 #[macro_export]
 macro_rules! impl_serdeany {
+    ($struct_name:ident < $( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+ > $(, < $opt:tt $(, $opt_rest:tt )* >)*, name = $name:literal, version = $version:expr) =>
+    {
+        impl < $( $lt $( : $clt $(+ $dlt )* )? ),+ >
+            $crate::serdeany::SerdeAny
+            for $struct_name < $( $lt ),+ >
+        {
+            fn as_any(&self) -> &dyn ::core::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::core::any::Any {
+                self
+            }
+
+            fn as_any_boxed(
+                self: $crate::alloc::boxed::Box<$struct_name < $( $lt ),+ >>,
+            ) -> $crate::alloc::boxed::Box<dyn ::core::any::Any> {
+                self
+            }
+
+            fn schema() -> ::core::option::Option<$crate::serdeany::SerdeAnySchema> {
+                let type_id = ::core::any::TypeId::of::<Self>();
+                $(
+                    if type_id == ::core::any::TypeId::of::<$struct_name < $opt $(, $opt_rest )* >>() {
+                        const SCHEMA: $crate::serdeany::SerdeAnySchema =
+                            $crate::serdeany::SerdeAnySchema::new(
+                                concat!($name, "<", stringify!($opt) $(, ",", stringify!($opt_rest) )*, ">"),
+                                $version,
+                            );
+                        return ::core::option::Option::Some(SCHEMA);
+                    }
+                )*
+                ::core::option::Option::None
+            }
+
+            fn dyn_schema(&self) -> ::core::option::Option<$crate::serdeany::SerdeAnySchema> {
+                <Self as $crate::serdeany::SerdeAny>::schema()
+            }
+        }
+
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        impl< $( $lt $( : $clt $(+ $dlt )* )? ),+ > $struct_name < $( $lt ),+ > {
+
+            /// Manually register this type at a later point in time
+            ///
+            /// # Safety
+            /// This may never be called concurrently as it dereferences the `RegistryBuilder` without acquiring a lock.
+            pub unsafe fn register() {
+                $crate::serdeany::RegistryBuilder::register::<$struct_name < $( $lt ),+ >>();
+            }
+        }
+
+        $(
+            $crate::create_register!($struct_name < $opt $(, $opt_rest )* >);
+        )*
+    };
     ($struct_name:ident < $( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+ > $(, < $( $opt:tt ),+ >)*) =>
     {
         impl < $( $lt $( : $clt $(+ $dlt )* )? ),+ >
@@ -858,6 +1324,51 @@ macro_rules! impl_serdeany {
             $crate::create_register!($struct_name < $( $opt ),+ >);
         )*
     };
+    ($struct_name:ident, name = $name:expr, version = $version:expr) =>
+    {
+        impl
+            $crate::serdeany::SerdeAny
+            for $struct_name
+        {
+            fn as_any(&self) -> &dyn ::core::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::core::any::Any {
+                self
+            }
+
+            fn as_any_boxed(
+                self: $crate::alloc::boxed::Box<$struct_name>,
+            ) -> $crate::alloc::boxed::Box<dyn ::core::any::Any> {
+                self
+            }
+
+            fn schema() -> ::core::option::Option<$crate::serdeany::SerdeAnySchema> {
+                const SCHEMA: $crate::serdeany::SerdeAnySchema =
+                    $crate::serdeany::SerdeAnySchema::new($name, $version);
+                ::core::option::Option::Some(SCHEMA)
+            }
+
+            fn dyn_schema(&self) -> ::core::option::Option<$crate::serdeany::SerdeAnySchema> {
+                <Self as $crate::serdeany::SerdeAny>::schema()
+            }
+        }
+
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        impl $struct_name {
+            /// Manually register this type at a later point in time
+            ///
+            /// # Safety
+            /// This may never be called concurrently as it dereferences the `RegistryBuilder` without acquiring a lock.
+            #[allow(unused)]
+            pub unsafe fn register() {
+                $crate::serdeany::RegistryBuilder::register::<$struct_name>();
+            }
+        }
+
+        $crate::create_register!($struct_name);
+    };
     ($struct_name:ident) =>
     {
         impl
@@ -897,9 +1408,18 @@ macro_rules! impl_serdeany {
 
 #[cfg(test)]
 mod tests {
-    use serde::{Deserialize, Serialize};
+    use alloc::{boxed::Box, string::String};
+    use core::fmt::Debug;
 
-    use crate::serdeany::RegistryBuilder;
+    use hashbrown::HashMap;
+    use serde::{de::DeserializeOwned, ser::SerializeSeq, Deserialize, Serialize, Serializer};
+    #[cfg(feature = "std")]
+    use serial_test::serial;
+
+    use crate::serdeany::{
+        legacy_type_repr, NamedSerdeAnyMap, RegistryBuilder, SerdeAny, SerdeAnyMap, SerdeAnySchema,
+        TypeRepr,
+    };
 
     #[derive(Debug, Serialize, Deserialize)]
     struct MyType(u32);
@@ -914,6 +1434,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(feature = "std", serial)]
     fn test_deserialize_serialize() {
         unsafe {
             RegistryBuilder::register::<MyType>();
@@ -929,4 +1450,251 @@ mod tests {
         );
         assert!(postcard::from_bytes::<inner::MyType>(&serialized).is_err());
     }
+
+    /// The layout of [`HitsMetadata`] in an older build, where it was called `OldHitsMetadata`
+    #[derive(Debug, Serialize, Deserialize)]
+    struct OldHitsMetadata {
+        hits: u32,
+    }
+
+    /// Mimics the [`SerdeAny`] impl of the older build, without registering it in this one.
+    impl SerdeAny for OldHitsMetadata {
+        fn as_any(&self) -> &dyn core::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn core::any::Any {
+            self
+        }
+
+        fn as_any_boxed(self: Box<Self>) -> Box<dyn core::any::Any> {
+            self
+        }
+
+        fn dyn_schema(&self) -> Option<SerdeAnySchema> {
+            Some(SerdeAnySchema::new(
+                "libafl_bolts::tests::OldHitsMetadata",
+                1,
+            ))
+        }
+    }
+
+    /// [`HitsMetadata`] as written by a newer build
+    #[derive(Debug, Serialize)]
+    struct NewerHitsMetadata {
+        hits: u32,
+    }
+
+    impl SerdeAny for NewerHitsMetadata {
+        fn as_any(&self) -> &dyn core::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn core::any::Any {
+            self
+        }
+
+        fn as_any_boxed(self: Box<Self>) -> Box<dyn core::any::Any> {
+            self
+        }
+
+        fn dyn_schema(&self) -> Option<SerdeAnySchema> {
+            Some(SerdeAnySchema::new("libafl_bolts::tests::HitsMetadata", 3))
+        }
+    }
+
+    /// The renamed and extended metadata of the current build
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct HitsMetadata {
+        hits: u32,
+        last_hit: Option<usize>,
+    }
+    impl_serdeany!(
+        HitsMetadata,
+        name = "libafl_bolts::tests::HitsMetadata",
+        version = 2
+    );
+
+    impl From<OldHitsMetadata> for HitsMetadata {
+        fn from(old: OldHitsMetadata) -> Self {
+            Self {
+                hits: old.hits,
+                last_hit: None,
+            }
+        }
+    }
+
+    /// A [`SerdeAnyMap`], as written by the older build
+    #[derive(Serialize)]
+    struct OldSerdeAnyMap {
+        map: HashMap<TypeRepr, Box<dyn SerdeAny>>,
+    }
+
+    /// A [`NamedSerdeAnyMap`], as written by the older build
+    #[derive(Serialize)]
+    struct OldNamedSerdeAnyMap {
+        map: HashMap<TypeRepr, HashMap<String, Box<dyn SerdeAny>>>,
+    }
+
+    /// A [`SerdeAnyMap`] written before [`HitsMetadata`] had a schema
+    #[derive(Serialize)]
+    struct UnversionedSerdeAnyMap {
+        map: HashMap<TypeRepr, UnversionedEntry>,
+    }
+
+    /// A [`HitsMetadata`] entry written before the type had a schema
+    struct UnversionedEntry(TypeRepr, OldHitsMetadata);
+
+    impl Serialize for UnversionedEntry {
+        fn serialize<S>(&self, se: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut seq = se.serialize_seq(Some(2))?;
+            seq.serialize_element(&self.0)?;
+            seq.serialize_element(&self.1)?;
+            seq.end()
+        }
+    }
+
+    fn register_hits_metadata() {
+        unsafe {
+            RegistryBuilder::register::<HitsMetadata>();
+            RegistryBuilder::register_alias::<HitsMetadata>("libafl_bolts::tests::OldHitsMetadata");
+            RegistryBuilder::register_migration::<HitsMetadata, OldHitsMetadata>(0);
+            RegistryBuilder::register_migration::<HitsMetadata, OldHitsMetadata>(1);
+        }
+    }
+
+    /// A [`SerdeAnyMap`] containing `old`, as written by another build
+    fn old_map(old: Box<dyn SerdeAny>) -> OldSerdeAnyMap {
+        let mut map = HashMap::default();
+        map.insert(crate::serdeany::dyn_type_repr(old.as_ref()).unwrap(), old);
+        OldSerdeAnyMap { map }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "std", serial)]
+    fn test_schema_roundtrip() {
+        register_hits_metadata();
+
+        let mut map = SerdeAnyMap::new();
+        map.insert(HitsMetadata {
+            hits: 3,
+            last_hit: Some(7),
+        });
+
+        let serialized = postcard::to_allocvec(&map).unwrap();
+        let map: SerdeAnyMap = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(
+            map.get::<HitsMetadata>().unwrap(),
+            &HitsMetadata {
+                hits: 3,
+                last_hit: Some(7)
+            }
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "std", serial)]
+    fn test_schema_migration() {
+        register_hits_metadata();
+
+        // The type was renamed and got a new field in version 2
+        let serialized =
+            postcard::to_allocvec(&old_map(Box::new(OldHitsMetadata { hits: 1 }))).unwrap();
+        let map: SerdeAnyMap = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(
+            map.get::<HitsMetadata>().unwrap(),
+            &HitsMetadata {
+                hits: 1,
+                last_hit: None
+            }
+        );
+
+        let mut inner = HashMap::default();
+        let old: Box<dyn SerdeAny> = Box::new(OldHitsMetadata { hits: 1 });
+        let repr = crate::serdeany::dyn_type_repr(old.as_ref()).unwrap();
+        inner.insert(String::from("feedback"), old);
+        let mut named = OldNamedSerdeAnyMap {
+            map: HashMap::default(),
+        };
+        named.map.insert(repr, inner);
+        let serialized = postcard::to_allocvec(&named).unwrap();
+        let map: NamedSerdeAnyMap = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(map.get::<HitsMetadata>("feedback").unwrap().hits, 1);
+        assert!(map.contains_type::<HitsMetadata>());
+
+        // Data written by a newer build can not be loaded
+        let serialized =
+            postcard::to_allocvec(&old_map(Box::new(NewerHitsMetadata { hits: 1 }))).unwrap();
+        assert!(postcard::from_bytes::<SerdeAnyMap>(&serialized).is_err());
+    }
+
+    #[test]
+    #[cfg_attr(feature = "std", serial)]
+    fn test_schema_unversioned() {
+        register_hits_metadata();
+
+        // Written before `HitsMetadata` had a schema, keyed by its `TypeId`
+        let mut old = UnversionedSerdeAnyMap {
+            map: HashMap::default(),
+        };
+        old.map.insert(
+            legacy_type_repr::<HitsMetadata>(),
+            UnversionedEntry(
+                legacy_type_repr::<HitsMetadata>(),
+                OldHitsMetadata { hits: 5 },
+            ),
+        );
+        let serialized = postcard::to_allocvec(&old).unwrap();
+        let map: SerdeAnyMap = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(
+            map.get::<HitsMetadata>().unwrap(),
+            &HitsMetadata {
+                hits: 5,
+                last_hit: None
+            }
+        );
+    }
+
+    /// A generic metadata with a schema for some of its instantiations
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct GenericMetadata<T> {
+        value: T,
+    }
+    impl_serdeany!(
+        GenericMetadata<T: Debug + 'static + Serialize + DeserializeOwned>,
+        <u8>, <u32>,
+        name = "libafl_bolts::tests::GenericMetadata",
+        version = 1
+    );
+
+    #[test]
+    #[cfg_attr(feature = "std", serial)]
+    fn test_schema_generic() {
+        assert_eq!(
+            GenericMetadata::<u8>::schema().unwrap(),
+            SerdeAnySchema::new("libafl_bolts::tests::GenericMetadata<u8>", 1)
+        );
+        assert_eq!(
+            GenericMetadata::<u32>::schema().unwrap().name(),
+            "libafl_bolts::tests::GenericMetadata<u32>"
+        );
+        assert!(GenericMetadata::<u16>::schema().is_none());
+
+        unsafe {
+            RegistryBuilder::register::<GenericMetadata<u8>>();
+            RegistryBuilder::register::<GenericMetadata<u32>>();
+        }
+
+        let mut map = SerdeAnyMap::new();
+        map.insert(GenericMetadata { value: 1_u8 });
+        map.insert(GenericMetadata { value: 2_u32 });
+
+        let serialized = postcard::to_allocvec(&map).unwrap();
+        let map: SerdeAnyMap = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(map.get::<GenericMetadata<u8>>().unwrap().value, 1);
+        assert_eq!(map.get::<GenericMetadata<u32>>().unwrap().value, 2);
+    }
 }
@@ -4,11 +4,9 @@
 #[cfg(feature = "alloc")]
 use alloc::{rc::Rc, string::ToString};
 #[cfg(feature = "alloc")]
-use core::fmt::Display;
-#[cfg(feature = "alloc")]
-use core::{cell::RefCell, fmt, mem::ManuallyDrop};
+use core::{cell::RefCell, mem::ManuallyDrop};
 use core::{
-    fmt::Debug,
+    fmt::{self, Debug, Display},
     mem,
     ops::{Deref, DerefMut},
 };
@@ -20,6 +18,8 @@ use std::io::Read;
 use std::io::Write;
 
 use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use unix_shmem::memfd::{MemfdShMem, MemfdShMemProvider};
 #[cfg(all(
     feature = "std",
     unix,
@@ -33,6 +33,11 @@ pub use win32_shmem::{Win32ShMem, Win32ShMemProvider};
 
 #[cfg(all(unix, feature = "std", not(target_os = "haiku")))]
 use crate::os::pipes::Pipe;
+#[cfg(all(unix, feature = "std"))]
+pub use crate::os::shmem_registry::{
+    RegisteredShMem, RegisteredShMemProvider, ShMemOsHandle, ShMemRegistry, ShMemRegistryEntry,
+    ShMemUsage, SHMEM_REGISTRY_ENV,
+};
 #[cfg(all(feature = "std", unix, not(target_os = "haiku")))]
 pub use crate::os::unix_shmem_server::{ServedShMemProvider, ShMemService};
 use crate::Error;
@@ -194,6 +199,31 @@ impl Display for ShMemId {
     }
 }
 
+/// What a shared memory segment is used for, for bookkeeping in a [`ShMemRegistry`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ShMemPurpose {
+    /// A page of an [`crate::llmp`] sender
+    LlmpPage,
+    /// The map of a [`crate::staterestore::StateRestorer`]
+    StateRestorer,
+    /// A coverage map shared with the target
+    CoverageMap,
+    /// Anything else
+    #[default]
+    Other,
+}
+
+impl Display for ShMemPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LlmpPage => write!(f, "llmp_page"),
+            Self::StateRestorer => write!(f, "state_restorer"),
+            Self::CoverageMap => write!(f, "coverage_map"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
 /// A [`ShMem`] is an interface to shared maps.
 /// They are the backbone of [`crate::llmp`] for inter-process communication.
 /// All you need for scaling on a new target is to implement this interface, as well as the respective [`ShMemProvider`].
@@ -201,6 +231,13 @@ pub trait ShMem: Sized + Debug + Clone + DerefMut<Target = [u8]> {
     /// Get the id of this shared memory mapping
     fn id(&self) -> ShMemId;
 
+    /// The OS handle another process needs to free this segment, if it is known to this mapping.
+    /// Used to record segments in a [`ShMemRegistry`].
+    #[cfg(all(unix, feature = "std"))]
+    fn os_handle(&self) -> Option<ShMemOsHandle> {
+        None
+    }
+
     /// Convert to a ptr of a given type, checking the size.
     /// If the map is too small, returns `None`
     fn as_ptr_of<T: Sized>(&self) -> Option<*const T> {
@@ -253,9 +290,26 @@ pub trait ShMemProvider: Clone + Default + Debug {
     /// Create a new shared memory mapping
     fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error>;
 
+    /// Create a new shared memory mapping for the given [`ShMemPurpose`].
+    /// Providers that don't keep track of their segments simply call [`Self::new_shmem`].
+    fn new_shmem_with_purpose(
+        &mut self,
+        map_size: usize,
+        _purpose: ShMemPurpose,
+    ) -> Result<Self::ShMem, Error> {
+        self.new_shmem(map_size)
+    }
+
     /// Get a mapping given its id and size
     fn shmem_from_id_and_size(&mut self, id: ShMemId, size: usize) -> Result<Self::ShMem, Error>;
 
+    /// Map a `fd` received from another process, for example by the [`ServedShMemProvider`].
+    /// The returned map takes ownership of the `fd`, so it gets closed once the map is dropped.
+    #[cfg(all(unix, feature = "std"))]
+    fn shmem_from_received_fd(&mut self, fd: i32, size: usize) -> Result<Self::ShMem, Error> {
+        self.shmem_from_id_and_size(ShMemId::from_string(&format!("{fd}")), size)
+    }
+
     /// Create a new shared memory mapping to hold an object of the given type, and initializes it with the given value.
     fn new_on_shmem<T: Sized + 'static>(&mut self, value: T) -> Result<Self::ShMem, Error> {
         self.uninit_on_shmem::<T>().map(|mut shmem| {
@@ -336,6 +390,11 @@ where
     fn id(&self) -> ShMemId {
         self.internal.id()
     }
+
+    #[cfg(all(unix, feature = "std"))]
+    fn os_handle(&self) -> Option<ShMemOsHandle> {
+        self.internal.os_handle()
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -413,6 +472,21 @@ where
         })
     }
 
+    fn new_shmem_with_purpose(
+        &mut self,
+        map_size: usize,
+        purpose: ShMemPurpose,
+    ) -> Result<Self::ShMem, Error> {
+        Ok(Self::ShMem {
+            internal: ManuallyDrop::new(
+                self.internal
+                    .borrow_mut()
+                    .new_shmem_with_purpose(map_size, purpose)?,
+            ),
+            provider: self.internal.clone(),
+        })
+    }
+
     fn shmem_from_id_and_size(&mut self, id: ShMemId, size: usize) -> Result<Self::ShMem, Error> {
         Ok(Self::ShMem {
             internal: ManuallyDrop::new(
@@ -582,6 +656,7 @@ pub mod unix_shmem {
     mod default {
         use alloc::string::ToString;
         use core::{
+            ffi::CStr,
             ops::{Deref, DerefMut},
             ptr, slice,
         };
@@ -594,7 +669,7 @@ pub mod unix_shmem {
 
         use crate::{
             rands::{Rand, StdRand},
-            shmem::{ShMem, ShMemId, ShMemProvider},
+            shmem::{ShMem, ShMemId, ShMemOsHandle, ShMemProvider},
             Error,
         };
 
@@ -764,6 +839,12 @@ pub mod unix_shmem {
             fn id(&self) -> ShMemId {
                 self.id
             }
+
+            fn os_handle(&self) -> Option<ShMemOsHandle> {
+                let filename_path = self.filename_path.as_ref()?;
+                let name = CStr::from_bytes_until_nul(filename_path).ok()?;
+                Some(ShMemOsHandle::Posix(name.to_string_lossy().into_owned()))
+            }
         }
 
         impl Deref for MmapShMem {
@@ -872,6 +953,10 @@ pub mod unix_shmem {
             fn id(&self) -> ShMemId {
                 self.id
             }
+
+            fn os_handle(&self) -> Option<ShMemOsHandle> {
+                Some(ShMemOsHandle::SysV(self.id.into()))
+            }
         }
 
         impl Deref for CommonUnixShMem {
@@ -1157,6 +1242,240 @@ pub mod unix_shmem {
             }
         }
     }
+
+    /// Module containing `memfd` shared memory support for Linux.
+    ///
+    /// The maps are anonymous files created with `memfd_create`: they need neither `/dev/shm`
+    /// nor `SysV` IPC, and go away as soon as the last fd and mapping are gone.
+    ///
+    /// The [`ShMemId`] is the fd number. It is only valid in the process that created the map,
+    /// and in children that inherited the fd on `fork` or `exec`.
+    /// Unrelated processes, for example the clients of a multi-process LLMP setup started separately,
+    /// can't use it: they need to exchange maps through a [`crate::shmem::ServedShMemProvider`].
+    #[cfg(target_os = "linux")]
+    pub mod memfd {
+        use alloc::{rc::Rc, string::ToString};
+        use core::{
+            mem,
+            ops::{Deref, DerefMut},
+            ptr, slice,
+        };
+
+        use libc::{
+            c_char, c_int, close, dup, fcntl, fstat, ftruncate, memfd_create, mmap, munmap,
+        };
+
+        use crate::{
+            shmem::{ShMem, ShMemId, ShMemProvider},
+            Error,
+        };
+
+        /// The seals we put on each map, so that nobody can resize it under our feet
+        const MEMFD_SEALS: c_int = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+
+        /// A mapped memfd, unmapped and closed on [`Drop`]
+        #[derive(Debug)]
+        struct MemfdMapping {
+            /// The map ptr
+            map: *mut u8,
+            /// The size of this map
+            map_size: usize,
+            /// The fd backing this map, owned by this mapping
+            fd: c_int,
+        }
+
+        impl MemfdMapping {
+            /// Map the given `fd`, taking ownership of it.
+            /// The `fd` gets closed if mapping fails.
+            unsafe fn new(fd: c_int, map_size: usize) -> Result<Self, Error> {
+                let map = mmap(
+                    ptr::null_mut(),
+                    map_size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    0,
+                );
+                if map == libc::MAP_FAILED || map.is_null() {
+                    let err = Error::last_os_error(format!("mmap() failed for memfd {fd}"));
+                    close(fd);
+                    return Err(err);
+                }
+
+                Ok(Self {
+                    map: map as *mut u8,
+                    map_size,
+                    fd,
+                })
+            }
+        }
+
+        impl Drop for MemfdMapping {
+            fn drop(&mut self) {
+                unsafe {
+                    munmap(self.map as *mut _, self.map_size);
+                    // The memory is freed once the last fd and mapping are gone.
+                    close(self.fd);
+                }
+            }
+        }
+
+        /// A [`ShMem`] backed by an anonymous `memfd` file
+        ///
+        /// Clones share the same mapping, which goes away once the last of them is dropped.
+        #[derive(Clone, Debug)]
+        pub struct MemfdShMem {
+            /// The id, i.e., the fd number, to send over the wire
+            id: ShMemId,
+            mapping: Rc<MemfdMapping>,
+        }
+
+        impl MemfdShMem {
+            /// Create a new sealed [`MemfdShMem`] of the given size
+            pub fn new(map_size: usize) -> Result<Self, Error> {
+                unsafe {
+                    // No `MFD_CLOEXEC`: child processes are supposed to inherit the map
+                    let fd = memfd_create(
+                        b"libafl\0".as_ptr() as *const c_char,
+                        libc::MFD_ALLOW_SEALING,
+                    );
+                    if fd == -1 {
+                        return Err(Error::last_os_error("memfd_create() failed"));
+                    }
+
+                    if ftruncate(fd, map_size.try_into()?) != 0 {
+                        let err = Error::last_os_error(format!(
+                            "ftruncate() failed for memfd {fd} of size {map_size}"
+                        ));
+                        close(fd);
+                        return Err(err);
+                    }
+
+                    if fcntl(fd, libc::F_ADD_SEALS, MEMFD_SEALS) != 0 {
+                        let err = Error::last_os_error(format!("Failed to seal memfd {fd}"));
+                        close(fd);
+                        return Err(err);
+                    }
+
+                    Self::from_owned_fd(fd, map_size)
+                }
+            }
+
+            /// Map an existing memfd, given its fd as id.
+            ///
+            /// The fd is duplicated, the one named by `id` stays open and belongs to whoever created it.
+            pub fn shmem_from_id_and_size(id: ShMemId, map_size: usize) -> Result<Self, Error> {
+                let fd: c_int = id.to_string().parse()?;
+                let fd = unsafe { dup(fd) };
+                if fd == -1 {
+                    return Err(Error::last_os_error(format!("Failed to dup memfd {id}")));
+                }
+                let mut shmem = Self::shmem_from_fd(fd, map_size)?;
+                // Keep handing out the original id, the dup is a private detail of this map
+                shmem.id = id;
+                Ok(shmem)
+            }
+
+            /// Map an existing memfd, taking ownership of `fd`: it gets closed when the last clone of the map is dropped,
+            /// or right away if mapping fails.
+            pub fn shmem_from_fd(fd: c_int, map_size: usize) -> Result<Self, Error> {
+                unsafe {
+                    // Mapping past the end of the file would `SIGBUS` on first access, fail early instead.
+                    let mut stat: libc::stat = mem::zeroed();
+                    if fstat(fd, &mut stat) != 0 {
+                        let err = Error::last_os_error(format!("fstat() failed for memfd {fd}"));
+                        close(fd);
+                        return Err(err);
+                    }
+                    if usize::try_from(stat.st_size).map_or(true, |size| size < map_size) {
+                        close(fd);
+                        return Err(Error::illegal_argument(format!(
+                            "memfd {fd} has size {}, but {map_size} bytes were requested",
+                            stat.st_size
+                        )));
+                    }
+                    Self::from_owned_fd(fd, map_size)
+                }
+            }
+
+            unsafe fn from_owned_fd(fd: c_int, map_size: usize) -> Result<Self, Error> {
+                Ok(Self {
+                    id: ShMemId::from_string(&format!("{fd}")),
+                    mapping: Rc::new(MemfdMapping::new(fd, map_size)?),
+                })
+            }
+
+            /// The fd backing this map
+            #[must_use]
+            pub fn fd(&self) -> c_int {
+                self.mapping.fd
+            }
+        }
+
+        impl ShMem for MemfdShMem {
+            fn id(&self) -> ShMemId {
+                self.id
+            }
+        }
+
+        impl Deref for MemfdShMem {
+            type Target = [u8];
+
+            fn deref(&self) -> &[u8] {
+                unsafe { slice::from_raw_parts(self.mapping.map, self.mapping.map_size) }
+            }
+        }
+
+        impl DerefMut for MemfdShMem {
+            fn deref_mut(&mut self) -> &mut [u8] {
+                unsafe { slice::from_raw_parts_mut(self.mapping.map, self.mapping.map_size) }
+            }
+        }
+
+        /// A [`ShMemProvider`] handing out sealed `memfd` maps.
+        ///
+        /// Its ids are fd numbers, so on its own it only shares maps with forked or spawned children.
+        /// For multi-process LLMP, wrap it in a [`crate::shmem::ServedShMemProvider`].
+        #[derive(Clone, Debug)]
+        pub struct MemfdShMemProvider {}
+
+        unsafe impl Send for MemfdShMemProvider {}
+
+        impl Default for MemfdShMemProvider {
+            fn default() -> Self {
+                Self::new().unwrap()
+            }
+        }
+
+        /// Implement [`ShMemProvider`] for [`MemfdShMemProvider`]
+        impl ShMemProvider for MemfdShMemProvider {
+            type ShMem = MemfdShMem;
+
+            fn new() -> Result<Self, Error> {
+                Ok(Self {})
+            }
+
+            fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
+                MemfdShMem::new(map_size)
+            }
+
+            fn shmem_from_id_and_size(
+                &mut self,
+                id: ShMemId,
+                size: usize,
+            ) -> Result<Self::ShMem, Error> {
+                MemfdShMem::shmem_from_id_and_size(id, size)
+            }
+
+            fn shmem_from_received_fd(
+                &mut self,
+                fd: i32,
+                size: usize,
+            ) -> Result<Self::ShMem, Error> {
+                MemfdShMem::shmem_from_fd(fd, size)
+            }
+        }
+    }
 }
 
 /// Then `win32` implementation for shared memory.
@@ -1463,4 +1782,52 @@ mod tests {
         map.as_slice_mut()[0] = 1;
         assert!(map.as_slice()[0] == 1);
     }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(target_os = "linux")]
+    fn test_memfd_shmem() {
+        use crate::shmem::{MemfdShMemProvider, ShMem};
+
+        let mut provider = MemfdShMemProvider::new().unwrap();
+        let mut map = provider.new_shmem(1024).unwrap();
+        map.as_slice_mut()[0] = 1;
+
+        let mut other = provider.shmem_from_description(map.description()).unwrap();
+        assert_eq!(other.as_slice()[0], 1);
+        other.as_slice_mut()[1] = 2;
+        // `other` mapped a dup of the fd, dropping it leaves `map` intact
+        assert_ne!(other.fd(), map.fd());
+        drop(other);
+
+        let cloned = map.clone();
+        drop(map);
+        assert_eq!(cloned.as_slice()[1], 2);
+
+        // Asking for more than there is must fail, not crash later.
+        assert!(provider.shmem_from_id_and_size(cloned.id(), 4096).is_err());
+
+        // Received fds are owned, and closed, by the map
+        let received = unsafe { libc::dup(cloned.fd()) };
+        let owner = provider.shmem_from_received_fd(received, 1024).unwrap();
+        assert_eq!(owner.fd(), received);
+        assert_eq!(owner.as_slice()[1], 2);
+        drop(owner);
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(target_os = "linux")]
+    fn test_memfd_shmem_service() {
+        use crate::shmem::{MemfdShMemProvider, RcShMemProvider, ServedShMemProvider};
+
+        let mut provider =
+            RcShMemProvider::<ServedShMemProvider<MemfdShMemProvider>>::new().unwrap();
+        let mut map = provider.new_shmem(1024).unwrap();
+        map.as_slice_mut()[0] = 1;
+        let other = provider.clone_ref(&map).unwrap();
+        assert_eq!(other.as_slice()[0], 1);
+    }
 }
@@ -57,9 +57,14 @@
     )
 )]
 
+extern crate alloc;
+
+use alloc::{string::ToString, vec::Vec};
+
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index, Member, Type};
 
 /// Derive macro to implement `SerdeAny`, to use a type in a `SerdeAnyMap`
 #[proc_macro_derive(SerdeAny)]
@@ -69,3 +74,211 @@ pub fn libafl_serdeany_derive(input: TokenStream) -> TokenStream {
         libafl_bolts::impl_serdeany!(#name);
     })
 }
+
+/// Derive macro to use a struct of heterogeneous parts as `Input`, e.g.:
+///
+/// ```rust,ignore
+/// #[derive(CompositeInput, Clone, Debug, Serialize, Deserialize)]
+/// struct ProtocolInput {
+///     header: BytesInput,
+///     body: NautilusInput,
+///     flags: u32,
+/// }
+/// ```
+///
+/// This implements `Input`, `libafl::inputs::CompositeInput`, and `HasTargetBytes`,
+/// concatenating the `libafl::inputs::CompositeInputPart` bytes of all fields.
+/// `NautilusInput` fields are unparsed with the grammar set by `libafl::inputs::register_nautilus_context`.
+/// For fields without target bytes, opt out with `#[composite_input(no_target_bytes)]`
+/// and implement `HasTargetBytes` manually.
+///
+/// The parts are mutated by a `libafl::mutators::CompositeMutator`,
+/// with a `tuple_list` of one mutator per field, in declaration order,
+/// e.g. a `NautilusRandomMutator` for `body` and a `libafl::mutators::IntPartMutator` for `flags`.
+/// Crossover only happens between fields of the same type, see `libafl::mutators::CompositeCrossoverMutator`.
+#[proc_macro_derive(CompositeInput, attributes(composite_input))]
+pub fn libafl_composite_input_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    composite_input_impl(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn composite_input_impl(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let mut target_bytes = true;
+    for attr in &input.attrs {
+        if attr.path().is_ident("composite_input") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("no_target_bytes") {
+                    target_bytes = false;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported composite_input attribute"))
+                }
+            })?;
+        }
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            Span::call_site(),
+            "CompositeInput can only be derived for structs",
+        ));
+    };
+    let (members, types): (Vec<Member>, Vec<_>) = match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| (Member::Named(field.ident.clone().unwrap()), &field.ty))
+            .unzip(),
+        Fields::Unnamed(fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| (Member::Unnamed(Index::from(idx)), &field.ty))
+            .unzip(),
+        Fields::Unit => (Vec::new(), Vec::new()),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let part_names = members.iter().map(|member| match member {
+        Member::Named(ident) => ident.to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    });
+
+    // Crossover partners of each part: all parts with the same type
+    let type_names: Vec<_> = types.iter().map(|ty| quote!(#ty).to_string()).collect();
+    let crossover_arms = members.iter().enumerate().map(|(idx, member)| {
+        let partners: Vec<_> = members
+            .iter()
+            .zip(&type_names)
+            .filter(|(_, type_name)| **type_name == type_names[idx])
+            .map(|(partner, _)| partner)
+            .collect();
+        let count = partners.len();
+        let choices = 0..count;
+        quote! {
+            #idx => match libafl_bolts::rands::Rand::below(rand, #count) {
+                #(#choices => libafl::mutators::PartCrossover::crossover(
+                    &mut self.#member,
+                    rand,
+                    &other.#partners,
+                ),)*
+                _ => unreachable!(),
+            }
+        }
+    });
+
+    let target_bytes_impl = target_bytes.then(|| {
+        quote! {
+            impl #impl_generics libafl::inputs::HasTargetBytes for #name #ty_generics #where_clause {
+                fn target_bytes(&self) -> libafl_bolts::ownedref::OwnedSlice<u8> {
+                    let mut bytes = libafl::alloc::vec::Vec::new();
+                    #(libafl::inputs::CompositeInputPart::append_target_bytes(&self.#members, &mut bytes);)*
+                    libafl_bolts::ownedref::OwnedSlice::from(bytes)
+                }
+            }
+        }
+    });
+
+    let part_mutators_impl = part_mutators_impl(input, &members, &types);
+
+    Ok(quote! {
+        impl #impl_generics libafl::inputs::Input for #name #ty_generics #where_clause {
+            fn generate_name(&self, _idx: usize) -> libafl::alloc::string::String {
+                libafl::inputs::composite::composite_input_name(self)
+            }
+        }
+
+        impl #impl_generics libafl::inputs::CompositeInput for #name #ty_generics #where_clause {
+            const PART_NAMES: &'static [&'static str] = &[#(#part_names),*];
+
+            fn crossover_part<R>(
+                &mut self,
+                rand: &mut R,
+                other: &Self,
+                part: usize,
+            ) -> Result<libafl::mutators::MutationResult, libafl::Error>
+            where
+                R: libafl_bolts::rands::Rand,
+            {
+                match part {
+                    #(#crossover_arms,)*
+                    _ => Ok(libafl::mutators::MutationResult::Skipped),
+                }
+            }
+        }
+
+        #target_bytes_impl
+
+        #part_mutators_impl
+    })
+}
+
+/// Implements `libafl::mutators::PartMutatorsTuple` for a `tuple_list` of one mutator per part
+fn part_mutators_impl(input: &DeriveInput, members: &[Member], types: &[&Type]) -> TokenStream2 {
+    let name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let parts = members.len();
+
+    // The mutators are a `tuple_list`, so the mutator of part `n` is at `self.1.1 ... .0`
+    let mutator_params: Vec<_> = (0..parts).map(|idx| format_ident!("__M{}", idx)).collect();
+    let mutators_ty = mutator_params
+        .iter()
+        .rev()
+        .fold(quote!(()), |tail, param| quote!((#param, #tail)));
+    let mutator_paths: Vec<_> = (0..parts)
+        .map(|idx| {
+            let nested = (0..idx).map(|_| Index::from(1));
+            quote!(self #(.#nested)* .0)
+        })
+        .collect();
+    let part_idxs = 0..parts;
+    let mut mutators_generics = input.generics.clone();
+    mutators_generics.params.push(syn::parse_quote!(__S));
+    for param in &mutator_params {
+        mutators_generics.params.push(syn::parse_quote!(#param));
+    }
+    {
+        let where_clause = mutators_generics.make_where_clause();
+        for (param, ty) in mutator_params.iter().zip(types) {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#param: libafl::mutators::Mutator<#ty, __S>));
+        }
+    }
+    let (mutators_impl_generics, _, mutators_where_clause) = mutators_generics.split_for_impl();
+
+    quote! {
+        impl #mutators_impl_generics libafl::mutators::PartMutatorsTuple<#name #ty_generics, __S>
+            for #mutators_ty #mutators_where_clause
+        {
+            fn mutate_part(
+                &mut self,
+                state: &mut __S,
+                input: &mut #name #ty_generics,
+                part: usize,
+            ) -> Result<libafl::mutators::MutationResult, libafl::Error> {
+                match part {
+                    #(#part_idxs => libafl::mutators::Mutator::mutate(
+                        &mut #mutator_paths,
+                        state,
+                        &mut input.#members,
+                    ),)*
+                    _ => Ok(libafl::mutators::MutationResult::Skipped),
+                }
+            }
+
+            fn post_exec_parts(
+                &mut self,
+                state: &mut __S,
+                new_corpus_idx: Option<libafl::corpus::CorpusId>,
+            ) -> Result<(), libafl::Error> {
+                #(libafl::mutators::Mutator::post_exec(&mut #mutator_paths, state, new_corpus_idx)?;)*
+                Ok(())
+            }
+        }
+    }
+}
@@ -49,7 +49,10 @@ use crate::utils::{instruction_width, writer_register};
 use crate::utils::{operand_details, AccessType};
 use crate::{
     alloc::Allocator,
-    asan::errors::{AsanError, AsanErrors, AsanReadWriteError, ASAN_ERRORS},
+    asan::{
+        errors::{AsanError, AsanErrors, AsanReadWriteError, ASAN_ERRORS},
+        hook_funcs::checked_libc_functions,
+    },
     helper::{FridaRuntime, SkipRange},
     utils::disas_count,
 };
@@ -761,177 +764,20 @@ impl AsanRuntime {
         hook_func!(None, munmap, (addr: *const c_void, length: usize), i32);
 
         // Hook libc functions which may access allocated memory
-        hook_func!(
-            None,
-            write,
-            (fd: i32, buf: *const c_void, count: usize),
-            usize
-        );
-        hook_func!(None, read, (fd: i32, buf: *mut c_void, count: usize), usize);
-        hook_func!(
-            None,
-            fgets,
-            (s: *mut c_void, size: u32, stream: *mut c_void),
-            *mut c_void
-        );
-        hook_func!(
-            None,
-            memcmp,
-            (s1: *const c_void, s2: *const c_void, n: usize),
-            i32
-        );
-        hook_func!(
-            None,
-            memcpy,
-            (dest: *mut c_void, src: *const c_void, n: usize),
-            *mut c_void
-        );
-        #[cfg(not(target_vendor = "apple"))]
-        hook_func!(
-            None,
-            mempcpy,
-            (dest: *mut c_void, src: *const c_void, n: usize),
-            *mut c_void
-        );
-        hook_func!(
-            None,
-            memmove,
-            (dest: *mut c_void, src: *const c_void, n: usize),
-            *mut c_void
-        );
-        hook_func!(
-            None,
-            memset,
-            (s: *mut c_void, c: i32, n: usize),
-            *mut c_void
-        );
-        hook_func!(
-            None,
-            memchr,
-            (s: *mut c_void, c: i32, n: usize),
-            *mut c_void
-        );
-        #[cfg(not(target_vendor = "apple"))]
-        hook_func!(
-            None,
-            memrchr,
-            (s: *mut c_void, c: i32, n: usize),
-            *mut c_void
-        );
-        hook_func!(
-            None,
-            memmem,
-            (
-                haystack: *const c_void,
-                haystacklen: usize,
-                needle: *const c_void,
-                needlelen: usize
-            ),
-            *mut c_void
-        );
-        #[cfg(not(target_os = "android"))]
-        hook_func!(None, bzero, (s: *mut c_void, n: usize), ());
-        #[cfg(not(any(target_os = "android", target_vendor = "apple")))]
-        hook_func!(None, explicit_bzero, (s: *mut c_void, n: usize), ());
-        #[cfg(not(target_os = "android"))]
-        hook_func!(
-            None,
-            bcmp,
-            (s1: *const c_void, s2: *const c_void, n: usize),
-            i32
-        );
-        hook_func!(None, strchr, (s: *mut c_char, c: i32), *mut c_char);
-        hook_func!(None, strrchr, (s: *mut c_char, c: i32), *mut c_char);
-        hook_func!(
-            None,
-            strcasecmp,
-            (s1: *const c_char, s2: *const c_char),
-            i32
-        );
-        hook_func!(
-            None,
-            strncasecmp,
-            (s1: *const c_char, s2: *const c_char, n: usize),
-            i32
-        );
-        hook_func!(
-            None,
-            strcat,
-            (dest: *mut c_char, src: *const c_char),
-            *mut c_char
-        );
-        hook_func!(None, strcmp, (s1: *const c_char, s2: *const c_char), i32);
-        hook_func!(
-            None,
-            strncmp,
-            (s1: *const c_char, s2: *const c_char, n: usize),
-            i32
-        );
-        hook_func!(
-            None,
-            strcpy,
-            (dest: *mut c_char, src: *const c_char),
-            *mut c_char
-        );
-        hook_func!(
-            None,
-            strncpy,
-            (dest: *mut c_char, src: *const c_char, n: usize),
-            *mut c_char
-        );
-        hook_func!(
-            None,
-            stpcpy,
-            (dest: *mut c_char, src: *const c_char),
-            *mut c_char
-        );
-        hook_func!(None, strdup, (s: *const c_char), *mut c_char);
-        hook_func!(None, strlen, (s: *const c_char), usize);
-        hook_func!(None, strnlen, (s: *const c_char, n: usize), usize);
-        hook_func!(
-            None,
-            strstr,
-            (haystack: *const c_char, needle: *const c_char),
-            *mut c_char
-        );
-        hook_func!(
-            None,
-            strcasestr,
-            (haystack: *const c_char, needle: *const c_char),
-            *mut c_char
-        );
-        hook_func!(None, atoi, (nptr: *const c_char), i32);
-        hook_func!(None, atol, (nptr: *const c_char), i32);
-        hook_func!(None, atoll, (nptr: *const c_char), i64);
-        hook_func!(None, wcslen, (s: *const wchar_t), usize);
-        hook_func!(
-            None,
-            wcscpy,
-            (dest: *mut wchar_t, src: *const wchar_t),
-            *mut wchar_t
-        );
-        hook_func!(None, wcscmp, (s1: *const wchar_t, s2: *const wchar_t), i32);
-        #[cfg(target_vendor = "apple")]
-        hook_func!(
-            None,
-            memset_pattern4,
-            (s: *mut c_void, c: *const c_void, n: usize),
-            ()
-        );
-        #[cfg(target_vendor = "apple")]
-        hook_func!(
-            None,
-            memset_pattern8,
-            (s: *mut c_void, c: *const c_void, n: usize),
-            ()
-        );
-        #[cfg(target_vendor = "apple")]
-        hook_func!(
-            None,
-            memset_pattern16,
-            (s: *mut c_void, c: *const c_void, n: usize),
-            ()
-        );
+        macro_rules! hook_checked_funcs {
+            ($(
+                $(#[$attr:meta])*
+                fn $name:ident($($param:ident : $param_type:ty),*) -> $return_type:ty {
+                    $($access:ident($ptr:expr, $len:expr);)*
+                }
+            )*) => {
+                $(
+                    $(#[$attr])*
+                    hook_func!(None, $name, ($($param : $param_type),*), $return_type);
+                )*
+            };
+        }
+        checked_libc_functions!(hook_checked_funcs);
     }
 
     #[cfg(target_arch = "x86_64")]
@@ -1,5 +1,5 @@
 //! The allocator hooks for address sanitizer.
-use std::ffi::c_void;
+use std::{ffi::c_void, mem::size_of};
 
 use backtrace::Backtrace;
 use libc::{c_char, wchar_t};
@@ -338,580 +338,13 @@ impl AsanRuntime {
         res
     }
 
-    #[inline]
-    pub fn hook_write(&mut self, fd: i32, buf: *const c_void, count: usize) -> usize {
-        extern "C" {
-            fn write(fd: i32, buf: *const c_void, count: usize) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(buf, count) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "write".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                buf as usize,
-                count,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { write(fd, buf, count) }
-    }
-
-    #[inline]
-    pub fn hook_read(&mut self, fd: i32, buf: *mut c_void, count: usize) -> usize {
-        extern "C" {
-            fn read(fd: i32, buf: *mut c_void, count: usize) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(buf, count) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "read".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                buf as usize,
-                count,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { read(fd, buf, count) }
-    }
-
-    #[inline]
-    pub fn hook_fgets(&mut self, s: *mut c_void, size: u32, stream: *mut c_void) -> *mut c_void {
-        extern "C" {
-            fn fgets(s: *mut c_void, size: u32, stream: *mut c_void) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(s, size as usize) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "fgets".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                size as usize,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { fgets(s, size, stream) }
-    }
-
-    #[inline]
-    pub fn hook_memcmp(&mut self, s1: *const c_void, s2: *const c_void, n: usize) -> i32 {
-        extern "C" {
-            fn memcmp(s1: *const c_void, s2: *const c_void, n: usize) -> i32;
-        }
-        if !(self.shadow_check_func().unwrap())(s1, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memcmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memcmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memcmp(s1, s2, n) }
-    }
-
-    #[inline]
-    pub fn hook_memcpy(&mut self, dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void {
-        extern "C" {
-            fn memcpy(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(dest, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memcpy(dest, src, n) }
-    }
-
-    #[inline]
-    #[cfg(not(target_vendor = "apple"))]
-    pub fn hook_mempcpy(&mut self, dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void {
-        extern "C" {
-            fn mempcpy(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(dest, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "mempcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "mempcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { mempcpy(dest, src, n) }
-    }
-
-    #[inline]
-    pub fn hook_memmove(&mut self, dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void {
-        extern "C" {
-            fn memmove(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(dest, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memmove".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memmove".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memmove(dest, src, n) }
-    }
-
-    #[inline]
-    pub fn hook_memset(&mut self, dest: *mut c_void, c: i32, n: usize) -> *mut c_void {
-        extern "C" {
-            fn memset(dest: *mut c_void, c: i32, n: usize) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(dest, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memset(dest, c, n) }
-    }
-
-    #[inline]
-    pub fn hook_memchr(&mut self, s: *mut c_void, c: i32, n: usize) -> *mut c_void {
-        extern "C" {
-            fn memchr(s: *mut c_void, c: i32, n: usize) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memchr".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memchr(s, c, n) }
-    }
-
-    #[inline]
-    #[cfg(not(target_vendor = "apple"))]
-    pub fn hook_memrchr(&mut self, s: *mut c_void, c: i32, n: usize) -> *mut c_void {
-        extern "C" {
-            fn memrchr(s: *mut c_void, c: i32, n: usize) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memrchr".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memrchr(s, c, n) }
-    }
-
-    #[inline]
-    pub fn hook_memmem(
-        &mut self,
-        haystack: *const c_void,
-        haystacklen: usize,
-        needle: *const c_void,
-        needlelen: usize,
-    ) -> *mut c_void {
-        extern "C" {
-            fn memmem(
-                haystack: *const c_void,
-                haystacklen: usize,
-                needle: *const c_void,
-                needlelen: usize,
-            ) -> *mut c_void;
-        }
-        if !(self.shadow_check_func().unwrap())(haystack, haystacklen) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memmem".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                haystack as usize,
-                haystacklen,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(needle, needlelen) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "memmem".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                needle as usize,
-                needlelen,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memmem(haystack, haystacklen, needle, needlelen) }
-    }
-
-    #[cfg(not(target_os = "android"))]
-    #[inline]
-    pub fn hook_bzero(&mut self, s: *mut c_void, n: usize) {
-        extern "C" {
-            fn bzero(s: *mut c_void, n: usize);
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "bzero".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { bzero(s, n) }
-    }
-
-    #[cfg(all(not(target_os = "android"), not(target_vendor = "apple")))]
-    #[inline]
-    pub fn hook_explicit_bzero(&mut self, s: *mut c_void, n: usize) {
-        extern "C" {
-            fn explicit_bzero(s: *mut c_void, n: usize);
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "explicit_bzero".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { explicit_bzero(s, n) }
-    }
-
-    #[cfg(not(target_os = "android"))]
-    #[inline]
-    pub fn hook_bcmp(&mut self, s1: *const c_void, s2: *const c_void, n: usize) -> i32 {
-        extern "C" {
-            fn bcmp(s1: *const c_void, s2: *const c_void, n: usize) -> i32;
-        }
-        if !(self.shadow_check_func().unwrap())(s1, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "bcmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "bcmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { bcmp(s1, s2, n) }
-    }
-
-    #[inline]
-    pub fn hook_strchr(&mut self, s: *mut c_char, c: i32) -> *mut c_char {
-        extern "C" {
-            fn strchr(s: *mut c_char, c: i32) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, unsafe { strlen(s) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strchr".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                unsafe { strlen(s) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strchr(s, c) }
-    }
-
-    #[inline]
-    pub fn hook_strrchr(&mut self, s: *mut c_char, c: i32) -> *mut c_char {
-        extern "C" {
-            fn strrchr(s: *mut c_char, c: i32) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, unsafe { strlen(s) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strrchr".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                unsafe { strlen(s) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strrchr(s, c) }
-    }
-
-    #[inline]
-    pub fn hook_strcasecmp(&mut self, s1: *const c_char, s2: *const c_char) -> i32 {
-        extern "C" {
-            fn strcasecmp(s1: *const c_char, s2: *const c_char) -> i32;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s1 as *const c_void, unsafe { strlen(s1) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcasecmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                unsafe { strlen(s1) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2 as *const c_void, unsafe { strlen(s2) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcasecmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                unsafe { strlen(s2) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strcasecmp(s1, s2) }
-    }
-
-    #[inline]
-    pub fn hook_strncasecmp(&mut self, s1: *const c_char, s2: *const c_char, n: usize) -> i32 {
-        extern "C" {
-            fn strncasecmp(s1: *const c_char, s2: *const c_char, n: usize) -> i32;
-        }
-        if !(self.shadow_check_func().unwrap())(s1 as *const c_void, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strncasecmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2 as *const c_void, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strncasecmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strncasecmp(s1, s2, n) }
-    }
-
-    #[inline]
-    pub fn hook_strcat(&mut self, s1: *mut c_char, s2: *const c_char) -> *mut c_char {
-        extern "C" {
-            fn strcat(s1: *mut c_char, s2: *const c_char) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s1 as *const c_void, unsafe { strlen(s1) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcat".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                unsafe { strlen(s1) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2 as *const c_void, unsafe { strlen(s2) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcat".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                unsafe { strlen(s2) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strcat(s1, s2) }
-    }
-
-    #[inline]
-    pub fn hook_strcmp(&mut self, s1: *const c_char, s2: *const c_char) -> i32 {
-        extern "C" {
-            fn strcmp(s1: *const c_char, s2: *const c_char) -> i32;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s1 as *const c_void, unsafe { strlen(s1) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                unsafe { strlen(s1) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2 as *const c_void, unsafe { strlen(s2) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                unsafe { strlen(s2) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strcmp(s1, s2) }
-    }
-
-    #[inline]
-    pub fn hook_strncmp(&mut self, s1: *const c_char, s2: *const c_char, n: usize) -> i32 {
-        extern "C" {
-            fn strncmp(s1: *const c_char, s2: *const c_char, n: usize) -> i32;
-            fn strnlen(s: *const c_char, n: usize) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s1 as *const c_void, unsafe { strnlen(s1, n) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strncmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2 as *const c_void, unsafe { strnlen(s2, n) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strncmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strncmp(s1, s2, n) }
-    }
-
-    #[inline]
-    pub fn hook_strcpy(&mut self, dest: *mut c_char, src: *const c_char) -> *mut c_char {
-        extern "C" {
-            fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(dest as *const c_void, unsafe { strlen(src) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "strcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                unsafe { strlen(src) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src as *const c_void, unsafe { strlen(src) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                unsafe { strlen(src) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strcpy(dest, src) }
-    }
-
-    #[inline]
-    pub fn hook_strncpy(&mut self, dest: *mut c_char, src: *const c_char, n: usize) -> *mut c_char {
-        extern "C" {
-            fn strncpy(dest: *mut c_char, src: *const c_char, n: usize) -> *mut c_char;
-        }
-        if !(self.shadow_check_func().unwrap())(dest as *const c_void, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "strncpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src as *const c_void, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strncpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { strncpy(dest, src, n) }
-    }
-
-    #[inline]
-    pub fn hook_stpcpy(&mut self, dest: *mut c_char, src: *const c_char) -> *mut c_char {
-        extern "C" {
-            fn stpcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(dest as *const c_void, unsafe { strlen(src) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "stpcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                unsafe { strlen(src) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src as *const c_void, unsafe { strlen(src) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "stpcpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                unsafe { strlen(src) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { stpcpy(dest, src) }
-    }
-
     #[inline]
     pub fn hook_strdup(&mut self, s: *const c_char) -> *mut c_char {
         extern "C" {
-            fn strlen(s: *const c_char) -> usize;
             fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char;
         }
-        let size = unsafe { strlen(s) };
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, size) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strdup".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                unsafe { strlen(s) },
-                Backtrace::new(),
-            )));
-        }
+        let size = unsafe { str_size(s) };
+        self.check_func_arg_read("strdup", s as *const c_void, size);
 
         unsafe {
             let ret = self.allocator_mut().alloc(size, 8) as *mut c_char;
@@ -920,323 +353,583 @@ impl AsanRuntime {
         }
     }
 
+    /// Reports a [`AsanError::BadFuncArgRead`] if `len` bytes at `ptr` are not all accessible,
+    /// or if they wrap around the address space.
     #[inline]
-    pub fn hook_strlen(&mut self, s: *const c_char) -> usize {
-        extern "C" {
-            fn strlen(s: *const c_char) -> usize;
-        }
-        let size = unsafe { strlen(s) };
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, size) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strlen".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                size,
-                Backtrace::new(),
-            )));
-        }
-        size
-    }
-
-    #[inline]
-    pub fn hook_strnlen(&mut self, s: *const c_char, n: usize) -> usize {
-        extern "C" {
-            fn strnlen(s: *const c_char, n: usize) -> usize;
-        }
-        let size = unsafe { strnlen(s, n) };
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, size) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strnlen".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                size,
-                Backtrace::new(),
-            )));
-        }
-        size
-    }
-
-    #[inline]
-    pub fn hook_strstr(&mut self, haystack: *const c_char, needle: *const c_char) -> *mut c_char {
-        extern "C" {
-            fn strstr(haystack: *const c_char, needle: *const c_char) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(haystack as *const c_void, unsafe {
-            strlen(haystack)
-        }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strstr".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                haystack as usize,
-                unsafe { strlen(haystack) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(needle as *const c_void, unsafe { strlen(needle) })
+    fn check_func_arg_read(&mut self, name: &str, ptr: *const c_void, len: usize) {
+        if (ptr as usize).checked_add(len).is_none()
+            || !(self.shadow_check_func().unwrap())(ptr, len)
         {
             AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strstr".to_string(),
+                name.to_string(),
                 self.real_address_for_stalked(AsanRuntime::pc()),
-                needle as usize,
-                unsafe { strlen(needle) },
+                ptr as usize,
+                len,
                 Backtrace::new(),
             )));
         }
-        unsafe { strstr(haystack, needle) }
     }
 
+    /// Reports a [`AsanError::BadFuncArgWrite`] if `len` bytes at `ptr` are not all accessible,
+    /// or if they wrap around the address space.
     #[inline]
-    pub fn hook_strcasestr(
-        &mut self,
-        haystack: *const c_char,
-        needle: *const c_char,
-    ) -> *mut c_char {
-        extern "C" {
-            fn strcasestr(haystack: *const c_char, needle: *const c_char) -> *mut c_char;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(haystack as *const c_void, unsafe {
-            strlen(haystack)
-        }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcasestr".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                haystack as usize,
-                unsafe { strlen(haystack) },
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(needle as *const c_void, unsafe { strlen(needle) })
+    fn check_func_arg_write(&mut self, name: &str, ptr: *const c_void, len: usize) {
+        if (ptr as usize).checked_add(len).is_none()
+            || !(self.shadow_check_func().unwrap())(ptr, len)
         {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "strcasestr".to_string(),
+            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
+                name.to_string(),
                 self.real_address_for_stalked(AsanRuntime::pc()),
-                needle as usize,
-                unsafe { strlen(needle) },
+                ptr as usize,
+                len,
                 Backtrace::new(),
             )));
         }
-        unsafe { strcasestr(haystack, needle) }
     }
+}
 
-    #[inline]
-    pub fn hook_atoi(&mut self, s: *const c_char) -> i32 {
-        extern "C" {
-            fn atoi(s: *const c_char) -> i32;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, unsafe { strlen(s) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "atoi".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                unsafe { strlen(s) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { atoi(s) }
-    }
+extern "C" {
+    fn strlen(s: *const c_char) -> usize;
+    fn strnlen(s: *const c_char, n: usize) -> usize;
+    fn wcslen(s: *const wchar_t) -> usize;
+    fn wcsnlen(s: *const wchar_t, n: usize) -> usize;
+    fn memchr(s: *mut c_void, c: i32, n: usize) -> *mut c_void;
+}
 
-    /// Hooks `atol`
-    #[inline]
-    pub fn hook_atol(&mut self, s: *const c_char) -> i32 {
-        extern "C" {
-            fn atol(s: *const c_char) -> i32;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, unsafe { strlen(s) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "atol".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                unsafe { strlen(s) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { atol(s) }
-    }
+/// The size of a wide character in bytes
+const WCHAR_SIZE: usize = size_of::<wchar_t>();
 
-    /// Hooks `atoll`
-    #[inline]
-    pub fn hook_atoll(&mut self, s: *const c_char) -> i64 {
-        extern "C" {
-            fn atoll(s: *const c_char) -> i64;
-            fn strlen(s: *const c_char) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, unsafe { strlen(s) }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "atoll".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                unsafe { strlen(s) },
-                Backtrace::new(),
-            )));
-        }
-        unsafe { atoll(s) }
-    }
+/// The number of bytes occupied by the string `s`, including its terminator.
+unsafe fn str_size(s: *const c_char) -> usize {
+    strlen(s) + 1
+}
 
-    /// Hooks `wcslen`
-    #[inline]
-    pub fn hook_wcslen(&mut self, s: *const wchar_t) -> usize {
-        extern "C" {
-            fn wcslen(s: *const wchar_t) -> usize;
-        }
-        let size = unsafe { wcslen(s) };
-        if !(self.shadow_check_func().unwrap())(s as *const c_void, (size + 1) * 2) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "wcslen".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                (size + 1) * 2,
-                Backtrace::new(),
-            )));
-        }
-        size
+/// The number of bytes read from `s` by a function bounded to `n` characters.
+unsafe fn strn_size(s: *const c_char, n: usize) -> usize {
+    let len = strnlen(s, n);
+    if len < n {
+        len + 1
+    } else {
+        len
     }
+}
 
-    /// Hooks `wcscpy`
-    #[inline]
-    pub fn hook_wcscpy(&mut self, dest: *mut wchar_t, src: *const wchar_t) -> *mut wchar_t {
-        extern "C" {
-            fn wcscpy(dest: *mut wchar_t, src: *const wchar_t) -> *mut wchar_t;
-            fn wcslen(s: *const wchar_t) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(dest as *const c_void, unsafe {
-            (wcslen(src) + 1) * 2
-        }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "wcscpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                dest as usize,
-                (unsafe { wcslen(src) } + 1) * 2,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(src as *const c_void, unsafe {
-            (wcslen(src) + 1) * 2
-        }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "wcscpy".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                src as usize,
-                (unsafe { wcslen(src) } + 1) * 2,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { wcscpy(dest, src) }
-    }
+/// The number of bytes occupied by the wide string `s`, including its terminator.
+unsafe fn wcs_size(s: *const wchar_t) -> usize {
+    (wcslen(s) + 1) * WCHAR_SIZE
+}
 
-    /// Hooks `wcscmp`
-    #[inline]
-    pub fn hook_wcscmp(&mut self, s1: *const wchar_t, s2: *const wchar_t) -> i32 {
-        extern "C" {
-            fn wcscmp(s1: *const wchar_t, s2: *const wchar_t) -> i32;
-            fn wcslen(s: *const wchar_t) -> usize;
-        }
-        if !(self.shadow_check_func().unwrap())(s1 as *const c_void, unsafe {
-            (wcslen(s1) + 1) * 2
-        }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "wcscmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s1 as usize,
-                (unsafe { wcslen(s1) } + 1) * 2,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(s2 as *const c_void, unsafe {
-            (wcslen(s2) + 1) * 2
-        }) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgRead((
-                "wcscmp".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s2 as usize,
-                (unsafe { wcslen(s2) } + 1) * 2,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { wcscmp(s1, s2) }
+/// The number of bytes read from `s` by a function bounded to `n` wide characters.
+unsafe fn wcsn_size(s: *const wchar_t, n: usize) -> usize {
+    let len = wcsnlen(s, n);
+    if len < n {
+        (len + 1) * WCHAR_SIZE
+    } else {
+        len * WCHAR_SIZE
     }
+}
 
-    #[cfg(target_vendor = "apple")]
-    #[inline]
-    pub fn hook_memset_pattern4(&mut self, s: *mut c_void, p4: *const c_void, n: usize) {
-        extern "C" {
-            fn memset_pattern4(s: *mut c_void, p4: *const c_void, n: usize);
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset_pattern4".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(p4, n / 4) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset_pattern4".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                p4 as usize,
-                n / 4,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memset_pattern4(s, p4, n) }
+/// The number of bytes `memccpy` copies from `src`, up to and including the first `c`.
+unsafe fn memccpy_size(src: *const c_void, c: i32, n: usize) -> usize {
+    let found = memchr(src as *mut c_void, c, n);
+    if found.is_null() {
+        n
+    } else {
+        found as usize - src as usize + 1
     }
+}
 
-    #[cfg(target_vendor = "apple")]
-    #[inline]
-    pub fn hook_memset_pattern8(&mut self, s: *mut c_void, p8: *const c_void, n: usize) {
-        extern "C" {
-            fn memset_pattern8(s: *mut c_void, p8: *const c_void, n: usize);
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset_pattern8".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
-        }
-        if !(self.shadow_check_func().unwrap())(p8, n / 8) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset_pattern8".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                p8 as usize,
-                n / 8,
-                Backtrace::new(),
-            )));
-        }
-        unsafe { memset_pattern8(s, p8, n) }
-    }
+/// The libc functions which are hooked only to check the memory they access.
+///
+/// Each entry is the signature of the function, followed by the `(pointer, length in bytes)`
+/// ranges it will `read` from or `write` to. The lengths are evaluated in an `unsafe` context,
+/// before the real function is called.
+///
+/// The table is handed to `$callback`, so that the same entries generate both the `hook_*`
+/// methods on [`AsanRuntime`] and their registration with the interceptor.
+macro_rules! checked_libc_functions {
+    ($callback:ident) => {
+        $callback! {
+            fn write(fd: i32, buf: *const c_void, count: usize) -> usize {
+                read(buf, count);
+            }
+            fn read(fd: i32, buf: *mut c_void, count: usize) -> usize {
+                write(buf, count);
+            }
+            fn fgets(s: *mut c_void, size: u32, stream: *mut c_void) -> *mut c_void {
+                write(s, size as usize);
+            }
+            fn memcmp(s1: *const c_void, s2: *const c_void, n: usize) -> i32 {
+                read(s1, n);
+                read(s2, n);
+            }
+            fn memcpy(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void {
+                write(dest, n);
+                read(src, n);
+            }
+            #[cfg(not(target_vendor = "apple"))]
+            fn mempcpy(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void {
+                write(dest, n);
+                read(src, n);
+            }
+            fn memmove(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void {
+                write(dest, n);
+                read(src, n);
+            }
+            fn memccpy(dest: *mut c_void, src: *const c_void, c: i32, n: usize) -> *mut c_void {
+                write(dest, memccpy_size(src, c, n));
+                read(src, memccpy_size(src, c, n));
+            }
+            fn memset(dest: *mut c_void, c: i32, n: usize) -> *mut c_void {
+                write(dest, n);
+            }
+            fn memchr(s: *mut c_void, c: i32, n: usize) -> *mut c_void {
+                read(s, n);
+            }
+            #[cfg(not(target_vendor = "apple"))]
+            fn memrchr(s: *mut c_void, c: i32, n: usize) -> *mut c_void {
+                read(s, n);
+            }
+            fn memmem(
+                haystack: *const c_void,
+                haystacklen: usize,
+                needle: *const c_void,
+                needlelen: usize
+            ) -> *mut c_void {
+                read(haystack, haystacklen);
+                read(needle, needlelen);
+            }
+            #[cfg(not(target_os = "android"))]
+            fn bzero(s: *mut c_void, n: usize) -> () {
+                write(s, n);
+            }
+            #[cfg(not(any(target_os = "android", target_vendor = "apple")))]
+            fn explicit_bzero(s: *mut c_void, n: usize) -> () {
+                write(s, n);
+            }
+            #[cfg(not(target_os = "android"))]
+            fn bcmp(s1: *const c_void, s2: *const c_void, n: usize) -> i32 {
+                read(s1, n);
+                read(s2, n);
+            }
+            fn strchr(s: *mut c_char, c: i32) -> *mut c_char {
+                read(s, str_size(s));
+            }
+            fn strrchr(s: *mut c_char, c: i32) -> *mut c_char {
+                read(s, str_size(s));
+            }
+            fn strcasecmp(s1: *const c_char, s2: *const c_char) -> i32 {
+                read(s1, str_size(s1));
+                read(s2, str_size(s2));
+            }
+            fn strncasecmp(s1: *const c_char, s2: *const c_char, n: usize) -> i32 {
+                read(s1, strn_size(s1, n));
+                read(s2, strn_size(s2, n));
+            }
+            fn strcat(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+                write(dest, strlen(dest) + str_size(src));
+                read(src, str_size(src));
+            }
+            fn strncat(dest: *mut c_char, src: *const c_char, n: usize) -> *mut c_char {
+                write(dest, strlen(dest) + strnlen(src, n) + 1);
+                read(src, strn_size(src, n));
+            }
+            fn strcmp(s1: *const c_char, s2: *const c_char) -> i32 {
+                read(s1, str_size(s1));
+                read(s2, str_size(s2));
+            }
+            fn strncmp(s1: *const c_char, s2: *const c_char, n: usize) -> i32 {
+                read(s1, strn_size(s1, n));
+                read(s2, strn_size(s2, n));
+            }
+            fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+                write(dest, str_size(src));
+                read(src, str_size(src));
+            }
+            fn strncpy(dest: *mut c_char, src: *const c_char, n: usize) -> *mut c_char {
+                write(dest, n);
+                read(src, strn_size(src, n));
+            }
+            fn stpcpy(dest: *mut c_char, src: *const c_char) -> *mut c_char {
+                write(dest, str_size(src));
+                read(src, str_size(src));
+            }
+            fn stpncpy(dest: *mut c_char, src: *const c_char, n: usize) -> *mut c_char {
+                write(dest, n);
+                read(src, strn_size(src, n));
+            }
+            fn strlen(s: *const c_char) -> usize {
+                read(s, str_size(s));
+            }
+            fn strnlen(s: *const c_char, n: usize) -> usize {
+                read(s, strn_size(s, n));
+            }
+            fn strstr(haystack: *const c_char, needle: *const c_char) -> *mut c_char {
+                read(haystack, str_size(haystack));
+                read(needle, str_size(needle));
+            }
+            fn strcasestr(haystack: *const c_char, needle: *const c_char) -> *mut c_char {
+                read(haystack, str_size(haystack));
+                read(needle, str_size(needle));
+            }
+            fn atoi(s: *const c_char) -> i32 {
+                read(s, str_size(s));
+            }
+            fn atol(s: *const c_char) -> i32 {
+                read(s, str_size(s));
+            }
+            fn atoll(s: *const c_char) -> i64 {
+                read(s, str_size(s));
+            }
+            fn wcslen(s: *const wchar_t) -> usize {
+                read(s, wcs_size(s));
+            }
+            fn wcsnlen(s: *const wchar_t, n: usize) -> usize {
+                read(s, wcsn_size(s, n));
+            }
+            fn wcscpy(dest: *mut wchar_t, src: *const wchar_t) -> *mut wchar_t {
+                write(dest, wcs_size(src));
+                read(src, wcs_size(src));
+            }
+            fn wcsncpy(dest: *mut wchar_t, src: *const wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, wcsn_size(src, n));
+            }
+            fn wcpcpy(dest: *mut wchar_t, src: *const wchar_t) -> *mut wchar_t {
+                write(dest, wcs_size(src));
+                read(src, wcs_size(src));
+            }
+            fn wcpncpy(dest: *mut wchar_t, src: *const wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, wcsn_size(src, n));
+            }
+            fn wcscat(dest: *mut wchar_t, src: *const wchar_t) -> *mut wchar_t {
+                write(dest, wcslen(dest) * WCHAR_SIZE + wcs_size(src));
+                read(src, wcs_size(src));
+            }
+            fn wcsncat(dest: *mut wchar_t, src: *const wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, (wcslen(dest) + wcsnlen(src, n) + 1) * WCHAR_SIZE);
+                read(src, wcsn_size(src, n));
+            }
+            fn wcscmp(s1: *const wchar_t, s2: *const wchar_t) -> i32 {
+                read(s1, wcs_size(s1));
+                read(s2, wcs_size(s2));
+            }
+            fn wcsncmp(s1: *const wchar_t, s2: *const wchar_t, n: usize) -> i32 {
+                read(s1, wcsn_size(s1, n));
+                read(s2, wcsn_size(s2, n));
+            }
+            fn wcschr(s: *const wchar_t, c: wchar_t) -> *mut wchar_t {
+                read(s, wcs_size(s));
+            }
+            fn wcsrchr(s: *const wchar_t, c: wchar_t) -> *mut wchar_t {
+                read(s, wcs_size(s));
+            }
+            fn wcsstr(haystack: *const wchar_t, needle: *const wchar_t) -> *mut wchar_t {
+                read(haystack, wcs_size(haystack));
+                read(needle, wcs_size(needle));
+            }
+            fn wmemcpy(dest: *mut wchar_t, src: *const wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, n * WCHAR_SIZE);
+            }
+            fn wmemmove(dest: *mut wchar_t, src: *const wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, n * WCHAR_SIZE);
+            }
+            #[cfg(not(target_vendor = "apple"))]
+            fn wmempcpy(dest: *mut wchar_t, src: *const wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, n * WCHAR_SIZE);
+            }
+            fn wmemset(dest: *mut wchar_t, c: wchar_t, n: usize) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+            }
+            fn wmemcmp(s1: *const wchar_t, s2: *const wchar_t, n: usize) -> i32 {
+                read(s1, n * WCHAR_SIZE);
+                read(s2, n * WCHAR_SIZE);
+            }
+            fn wmemchr(s: *const wchar_t, c: wchar_t, n: usize) -> *mut wchar_t {
+                read(s, n * WCHAR_SIZE);
+            }
+            #[cfg(target_vendor = "apple")]
+            fn memset_pattern4(s: *mut c_void, p4: *const c_void, n: usize) -> () {
+                write(s, n);
+                read(p4, 4);
+            }
+            #[cfg(target_vendor = "apple")]
+            fn memset_pattern8(s: *mut c_void, p8: *const c_void, n: usize) -> () {
+                write(s, n);
+                read(p8, 8);
+            }
+            #[cfg(target_vendor = "apple")]
+            fn memset_pattern16(s: *mut c_void, p16: *const c_void, n: usize) -> () {
+                write(s, n);
+                read(p16, 16);
+            }
 
-    #[cfg(target_vendor = "apple")]
-    #[inline]
-    pub fn hook_memset_pattern16(&mut self, s: *mut c_void, p16: *const c_void, n: usize) {
-        extern "C" {
-            fn memset_pattern16(s: *mut c_void, p16: *const c_void, n: usize);
-        }
-        if !(self.shadow_check_func().unwrap())(s, n) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset_pattern16".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                s as usize,
-                n,
-                Backtrace::new(),
-            )));
+            // The glibc `_FORTIFY_SOURCE` variants. The real functions only abort if the
+            // compiler-known object size is exceeded, which is unknown for heap objects.
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __memcpy_chk(
+                dest: *mut c_void,
+                src: *const c_void,
+                len: usize,
+                destlen: usize
+            ) -> *mut c_void {
+                write(dest, len);
+                read(src, len);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __memmove_chk(
+                dest: *mut c_void,
+                src: *const c_void,
+                len: usize,
+                destlen: usize
+            ) -> *mut c_void {
+                write(dest, len);
+                read(src, len);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __mempcpy_chk(
+                dest: *mut c_void,
+                src: *const c_void,
+                len: usize,
+                destlen: usize
+            ) -> *mut c_void {
+                write(dest, len);
+                read(src, len);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __memset_chk(dest: *mut c_void, c: i32, len: usize, destlen: usize) -> *mut c_void {
+                write(dest, len);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __explicit_bzero_chk(dest: *mut c_void, len: usize, destlen: usize) -> () {
+                write(dest, len);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __strcpy_chk(dest: *mut c_char, src: *const c_char, destlen: usize) -> *mut c_char {
+                write(dest, str_size(src));
+                read(src, str_size(src));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __stpcpy_chk(dest: *mut c_char, src: *const c_char, destlen: usize) -> *mut c_char {
+                write(dest, str_size(src));
+                read(src, str_size(src));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __strncpy_chk(
+                dest: *mut c_char,
+                src: *const c_char,
+                n: usize,
+                destlen: usize
+            ) -> *mut c_char {
+                write(dest, n);
+                read(src, strn_size(src, n));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __stpncpy_chk(
+                dest: *mut c_char,
+                src: *const c_char,
+                n: usize,
+                destlen: usize
+            ) -> *mut c_char {
+                write(dest, n);
+                read(src, strn_size(src, n));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __strcat_chk(dest: *mut c_char, src: *const c_char, destlen: usize) -> *mut c_char {
+                write(dest, strlen(dest) + str_size(src));
+                read(src, str_size(src));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __strncat_chk(
+                dest: *mut c_char,
+                src: *const c_char,
+                n: usize,
+                destlen: usize
+            ) -> *mut c_char {
+                write(dest, strlen(dest) + strnlen(src, n) + 1);
+                read(src, strn_size(src, n));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wmemcpy_chk(
+                dest: *mut wchar_t,
+                src: *const wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, n * WCHAR_SIZE);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wmemmove_chk(
+                dest: *mut wchar_t,
+                src: *const wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, n * WCHAR_SIZE);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wmempcpy_chk(
+                dest: *mut wchar_t,
+                src: *const wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, n * WCHAR_SIZE);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wmemset_chk(
+                dest: *mut wchar_t,
+                c: wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wcscpy_chk(dest: *mut wchar_t, src: *const wchar_t, destlen: usize) -> *mut wchar_t {
+                write(dest, wcs_size(src));
+                read(src, wcs_size(src));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wcpcpy_chk(dest: *mut wchar_t, src: *const wchar_t, destlen: usize) -> *mut wchar_t {
+                write(dest, wcs_size(src));
+                read(src, wcs_size(src));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wcsncpy_chk(
+                dest: *mut wchar_t,
+                src: *const wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, wcsn_size(src, n));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wcpncpy_chk(
+                dest: *mut wchar_t,
+                src: *const wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, n * WCHAR_SIZE);
+                read(src, wcsn_size(src, n));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wcscat_chk(dest: *mut wchar_t, src: *const wchar_t, destlen: usize) -> *mut wchar_t {
+                write(dest, wcslen(dest) * WCHAR_SIZE + wcs_size(src));
+                read(src, wcs_size(src));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __wcsncat_chk(
+                dest: *mut wchar_t,
+                src: *const wchar_t,
+                n: usize,
+                destlen: usize
+            ) -> *mut wchar_t {
+                write(dest, (wcslen(dest) + wcsnlen(src, n) + 1) * WCHAR_SIZE);
+                read(src, wcsn_size(src, n));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __read_chk(fd: i32, buf: *mut c_void, nbytes: usize, buflen: usize) -> isize {
+                write(buf, nbytes);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __pread_chk(
+                fd: i32,
+                buf: *mut c_void,
+                nbytes: usize,
+                offset: i64,
+                buflen: usize
+            ) -> isize {
+                write(buf, nbytes);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __fgets_chk(buf: *mut c_char, size: usize, n: i32, stream: *mut c_void) -> *mut c_char {
+                // A size of zero or less reads nothing, libc rejects it
+                write(buf, n.max(0) as usize);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __fgets_unlocked_chk(
+                buf: *mut c_char,
+                size: usize,
+                n: i32,
+                stream: *mut c_void
+            ) -> *mut c_char {
+                write(buf, n.max(0) as usize);
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __fread_chk(
+                ptr: *mut c_void,
+                ptrlen: usize,
+                size: usize,
+                n: usize,
+                stream: *mut c_void
+            ) -> usize {
+                // An overflowing size wraps around the address space, which is reported
+                write(ptr, size.checked_mul(n).unwrap_or(usize::MAX));
+            }
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            fn __readlink_chk(
+                path: *const c_char,
+                buf: *mut c_char,
+                len: usize,
+                buflen: usize
+            ) -> isize {
+                read(path, str_size(path));
+                write(buf, len);
+            }
         }
-        if !(self.shadow_check_func().unwrap())(p16, n / 16) {
-            AsanErrors::get_mut_blocking().report_error(AsanError::BadFuncArgWrite((
-                "memset_pattern16".to_string(),
-                self.real_address_for_stalked(AsanRuntime::pc()),
-                p16 as usize,
-                n / 16,
-                Backtrace::new(),
-            )));
+    };
+}
+pub(crate) use checked_libc_functions;
+
+/// Generates a `hook_*` method for every entry of [`checked_libc_functions`], which checks the
+/// accessed memory before calling the real function.
+macro_rules! impl_checked_hooks {
+    ($(
+        $(#[$attr:meta])*
+        fn $name:ident($($param:ident : $param_type:ty),*) -> $return_type:ty {
+            $($access:ident($ptr:expr, $len:expr);)*
+        }
+    )*) => {
+        paste::paste! {
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
+            impl AsanRuntime {
+                $(
+                    #[doc = concat!("Hooks `", stringify!($name), "`")]
+                    $(#[$attr])*
+                    #[allow(
+                        non_snake_case,
+                        unused_unsafe,
+                        clippy::unused_unit,
+                        clippy::cast_sign_loss
+                    )]
+                    #[inline]
+                    pub fn [<hook_ $name>](&mut self, $($param: $param_type),*) -> $return_type {
+                        extern "C" {
+                            fn $name($($param: $param_type),*) -> $return_type;
+                        }
+                        $(
+                            self.[<check_func_arg_ $access>](
+                                stringify!($name),
+                                $ptr as *const c_void,
+                                unsafe { $len },
+                            );
+                        )*
+                        unsafe { $name($($param),*) }
+                    }
+                )*
+            }
         }
-        unsafe { memset_pattern16(s, p16, n) }
-    }
+    };
 }
+
+checked_libc_functions!(impl_checked_hooks);
@@ -6,8 +6,12 @@
 
 #[cfg(target_arch = "aarch64")]
 use core::ffi::c_void;
+#[cfg(feature = "cmplog_extended_instrumentation")]
+use core::slice;
 #[cfg(all(feature = "cmplog", target_arch = "x86_64"))]
 use std::collections::HashMap;
+#[cfg(feature = "cmplog_extended_instrumentation")]
+use std::collections::HashSet;
 use std::rc::Rc;
 
 use dynasmrt::dynasm;
@@ -21,6 +25,8 @@ use frida_gum::{
     instruction_writer::{Aarch64Register, IndexMode, InstructionWriter},
     stalker::StalkerOutput,
 };
+#[cfg(feature = "cmplog_extended_instrumentation")]
+use frida_gum::{stalker::Instruction as StalkerInstruction, Module};
 use frida_gum_sys::Insn;
 #[cfg(all(feature = "cmplog", target_arch = "x86_64"))]
 use iced_x86::{
@@ -31,7 +37,13 @@ use libafl::{
     inputs::{HasTargetBytes, Input},
     Error,
 };
-use libafl_targets::{cmps::__libafl_targets_cmplog_instructions, CMPLOG_MAP_W};
+#[cfg(not(feature = "cmplog_extended_instrumentation"))]
+use libafl_targets::cmps::__libafl_targets_cmplog_instructions;
+#[cfg(feature = "cmplog_extended_instrumentation")]
+use libafl_targets::cmps::{
+    __libafl_targets_cmplog_instructions_extended, __libafl_targets_cmplog_routines_extended,
+};
+use libafl_targets::CMPLOG_MAP_W;
 use rangemap::RangeMap;
 
 use crate::helper::FridaRuntime;
@@ -54,10 +66,204 @@ pub enum SpecialCmpLogCase {
 pub enum SpecialCmpLogCase {}
 
 #[cfg(target_arch = "aarch64")]
-use yaxpeax_arm::armv8::a64::{InstDecoder, Opcode, Operand, ShiftStyle};
+use yaxpeax_arm::armv8::a64::{InstDecoder, Opcode, Operand, ShiftStyle, SizeCode};
 #[cfg(target_arch = "x86_64")]
 use yaxpeax_x86::long_mode::InstDecoder;
 
+// The AFL++ comparison attributes, as consumed by `AFLppRedQueen`
+#[cfg(feature = "cmplog_extended_instrumentation")]
+const CMP_ATTRIBUTE_IS_EQUAL: u8 = 1;
+#[cfg(feature = "cmplog_extended_instrumentation")]
+const CMP_ATTRIBUTE_IS_GREATER: u8 = 2;
+#[cfg(feature = "cmplog_extended_instrumentation")]
+const CMP_ATTRIBUTE_IS_LESSER: u8 = 4;
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+const CMP_ATTRIBUTE_IS_FP: u8 = 8;
+
+/// The longest x86 instruction, in bytes
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+const MAX_INSTRUCTION_LEN: u64 = 15;
+
+/// The smallest page size on `x86_64`
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+const MIN_PAGE_SIZE: u64 = 0x1000;
+
+/// Decodes the instruction at `address` and the one following it,
+/// like a compare and the instruction consuming its flags.
+///
+/// The code is only read up to the end of the page, and up to the end of the next page only if an
+/// instruction continues there, so the last instruction of a mapping can be decoded as well.
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+fn decode_two_instructions(address: u64) -> (Instruction, Instruction) {
+    let max_end = address + 2 * MAX_INSTRUCTION_LEN;
+    let mut end = ((address | (MIN_PAGE_SIZE - 1)) + 1).min(max_end);
+    loop {
+        let bytes =
+            unsafe { slice::from_raw_parts(address as *const u8, (end - address) as usize) };
+        let mut decoder = iced_x86::Decoder::with_ip(64, bytes, address, DecoderOptions::NONE);
+        let cmp = decoder.decode();
+        let consumer = decoder.decode();
+        if decoder.last_error() != iced_x86::DecoderError::NoMoreBytes || end == max_end {
+            return (cmp, consumer);
+        }
+        // The executed instruction continues on the next page, so that one is mapped, too
+        end = (end + MIN_PAGE_SIZE).min(max_end);
+    }
+}
+
+/// The compare routines whose calls are logged, taking two pointers as first arguments
+#[cfg(feature = "cmplog_extended_instrumentation")]
+const CMPLOG_ROUTINES: [&str; 8] = [
+    "memcmp",
+    "bcmp",
+    "strcmp",
+    "strncmp",
+    "strcasecmp",
+    "strncasecmp",
+    "strstr",
+    "strcasestr",
+];
+
+/// The function a call jumps to, following PLT stubs and calls through the GOT.
+///
+/// Calls through registers have no known target. Imports that are bound lazily and were not
+/// called yet resolve to their PLT stub.
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+fn call_target(call: &Instruction) -> Option<u64> {
+    match call.op0_kind() {
+        OpKind::NearBranch64 => Some(plt_target(call.near_branch_target())),
+        OpKind::Memory if call.is_ip_rel_memory_operand() => {
+            Some(unsafe { (call.ip_rel_memory_address() as *const u64).read_unaligned() })
+        }
+        _ => None,
+    }
+}
+
+/// The function a PLT stub at `target` jumps to through the GOT, or `target` if it is no stub
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+fn plt_target(target: u64) -> u64 {
+    use iced_x86::Mnemonic;
+
+    let (first, second) = decode_two_instructions(target);
+    // Stubs in `.plt.sec` start with an `endbr64`
+    let jmp = if first.mnemonic() == Mnemonic::Endbr64 {
+        second
+    } else {
+        first
+    };
+    if jmp.mnemonic() == Mnemonic::Jmp
+        && jmp.op0_kind() == OpKind::Memory
+        && jmp.is_ip_rel_memory_operand()
+    {
+        unsafe { (jmp.ip_rel_memory_address() as *const u64).read_unaligned() }
+    } else {
+        target
+    }
+}
+
+/// The function a `bl` at `address` calls, following PLT stubs.
+///
+/// Calls through registers (`blr`) have no known target. Imports that are bound lazily and were
+/// not called yet resolve to their PLT stub.
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "aarch64"))]
+fn call_target(address: u64, bytes: &[u8]) -> Option<u64> {
+    let word = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap());
+    // bl imm26
+    if word & 0xfc00_0000 != 0x9400_0000 {
+        return None;
+    }
+    let offset = (((word & 0x03ff_ffff) << 6) as i32 >> 4) as i64;
+    Some(plt_target(address.wrapping_add_signed(offset)))
+}
+
+/// The function a PLT stub at `target` jumps to through the GOT, or `target` if it is no stub.
+///
+/// A stub starts with `adrp x16, page` and `ldr x17, [x16, offset]`, after an optional `bti c`.
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "aarch64"))]
+fn plt_target(target: u64) -> u64 {
+    const BTI_C: u32 = 0xd503_245f;
+
+    let word = |address: u64| unsafe { (address as *const u32).read_unaligned() };
+    let mut pc = target;
+    if word(pc) == BTI_C {
+        pc += 4;
+    }
+    let (adrp, ldr) = (word(pc), word(pc + 4));
+    if adrp & 0x9f00_0000 != 0x9000_0000 || ldr & 0xffc0_0000 != 0xf940_0000 {
+        return target;
+    }
+    // The `ldr` has to load from the page the `adrp` computed
+    if (ldr >> 5) & 0x1f != adrp & 0x1f {
+        return target;
+    }
+    let imm = (((adrp >> 5) & 0x7_ffff) << 2) | ((adrp >> 29) & 0x3);
+    let page_offset = (((imm << 11) as i32 >> 11) as i64) << 12;
+    let page = (pc & !0xfff).wrapping_add_signed(page_offset);
+    let slot = page + u64::from((ldr >> 10) & 0xfff) * 8;
+    unsafe { (slot as *const u64).read_unaligned() }
+}
+
+/// The AFL++ attribute of the compare at `address`, derived from the instruction consuming its
+/// flags. Like AFL++, `!=` and everything we cannot tell apart gets no attribute at all.
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+fn cmp_attribute(address: u64) -> u8 {
+    use iced_x86::{ConditionCode, Mnemonic};
+
+    let (cmp, consumer) = decode_two_instructions(address);
+    let fp = match cmp.mnemonic() {
+        Mnemonic::Comiss | Mnemonic::Ucomiss | Mnemonic::Comisd | Mnemonic::Ucomisd => {
+            CMP_ATTRIBUTE_IS_FP
+        }
+        _ => 0,
+    };
+    let attr = match consumer.condition_code() {
+        ConditionCode::e => CMP_ATTRIBUTE_IS_EQUAL,
+        ConditionCode::a | ConditionCode::g => CMP_ATTRIBUTE_IS_GREATER,
+        ConditionCode::ae | ConditionCode::ge => CMP_ATTRIBUTE_IS_GREATER | CMP_ATTRIBUTE_IS_EQUAL,
+        ConditionCode::b | ConditionCode::l => CMP_ATTRIBUTE_IS_LESSER,
+        ConditionCode::be | ConditionCode::le => CMP_ATTRIBUTE_IS_LESSER | CMP_ATTRIBUTE_IS_EQUAL,
+        _ => 0,
+    };
+    attr | fp
+}
+
+/// The AFL++ shape (the operand size minus one) and attribute of the compare at `address`.
+///
+/// The attribute is derived from the instruction consuming its flags. Like AFL++, `!=` and
+/// everything we cannot tell apart gets no attribute at all.
+#[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "aarch64"))]
+fn cmp_shape_and_attribute(address: u64) -> (u8, u8) {
+    // The compare and the branch following it, both part of the same basic block
+    let bytes = unsafe { slice::from_raw_parts(address as *const u8, 8) };
+    let instrs = disas_count(&InstDecoder::default(), bytes, 2);
+    let Some(cmp) = instrs.first() else {
+        return (7, 0);
+    };
+    // The compared registers all have the size of the first one
+    let shape = match cmp.operands[0] {
+        Operand::Register(SizeCode::W, _) | Operand::RegisterOrSP(SizeCode::W, _) => 3,
+        _ => 7,
+    };
+    let attr = match cmp.opcode {
+        Opcode::CBZ | Opcode::TBZ => CMP_ATTRIBUTE_IS_EQUAL,
+        Opcode::CBNZ | Opcode::TBNZ => 0,
+        _ => match instrs.get(1).map(|consumer| consumer.opcode) {
+            // eq
+            Some(Opcode::Bcc(0)) => CMP_ATTRIBUTE_IS_EQUAL,
+            // hi, gt
+            Some(Opcode::Bcc(8 | 12)) => CMP_ATTRIBUTE_IS_GREATER,
+            // hs, ge
+            Some(Opcode::Bcc(2 | 10)) => CMP_ATTRIBUTE_IS_GREATER | CMP_ATTRIBUTE_IS_EQUAL,
+            // lo, lt
+            Some(Opcode::Bcc(3 | 11)) => CMP_ATTRIBUTE_IS_LESSER,
+            // ls, le
+            Some(Opcode::Bcc(9 | 13)) => CMP_ATTRIBUTE_IS_LESSER | CMP_ATTRIBUTE_IS_EQUAL,
+            _ => 0,
+        },
+    };
+    (shape, attr)
+}
+
 /// The [`frida_gum_sys::GUM_RED_ZONE_SIZE`] casted to [`i32`]
 ///
 /// # Panic
@@ -95,6 +301,8 @@ pub enum CmplogOperandType {
     Imm(u64),
     /// A memory operand
     Mem(Register, Register, i64, u32, MemorySize), // base, index, disp, scale, mem_size
+    /// A scalar float in an SSE register, with its size in bytes
+    Xmm(Register, usize),
 }
 
 /// `Frida`-based binary-only innstrumentation that logs compares to the fuzzer
@@ -105,6 +313,9 @@ pub struct CmpLogRuntime {
     ops_save_register_and_blr_to_populate: Option<Box<[u8]>>,
     ops_handle_tbz_masking: Option<Box<[u8]>>,
     ops_handle_tbnz_masking: Option<Box<[u8]>>,
+    /// The addresses of the [`CMPLOG_ROUTINES`] the target links
+    #[cfg(feature = "cmplog_extended_instrumentation")]
+    routines: HashSet<u64>,
 }
 
 /// `Frida`-based binary-only innstrumentation that logs compares to the fuzzer
@@ -114,6 +325,9 @@ pub struct CmpLogRuntime {
 pub struct CmpLogRuntime {
     save_registers: Option<Box<[u8]>>,
     restore_registers: Option<Box<[u8]>>,
+    /// The addresses of the [`CMPLOG_ROUTINES`] the target links
+    #[cfg(feature = "cmplog_extended_instrumentation")]
+    routines: HashSet<u64>,
 }
 
 impl FridaRuntime for CmpLogRuntime {
@@ -126,6 +340,15 @@ impl FridaRuntime for CmpLogRuntime {
         _module_map: &Rc<ModuleMap>,
     ) {
         self.generate_instrumentation_blobs();
+
+        #[cfg(feature = "cmplog_extended_instrumentation")]
+        {
+            self.routines = CMPLOG_ROUTINES
+                .iter()
+                .filter_map(|name| Module::find_export_by_name(None, name))
+                .map(|routine| routine.0 as u64)
+                .collect();
+        }
     }
 
     fn pre_exec<I: Input + HasTargetBytes>(&mut self, _input: &I) -> Result<(), Error> {
@@ -146,6 +369,8 @@ impl CmpLogRuntime {
             ops_save_register_and_blr_to_populate: None,
             ops_handle_tbz_masking: None,
             ops_handle_tbnz_masking: None,
+            #[cfg(feature = "cmplog_extended_instrumentation")]
+            routines: HashSet::new(),
         }
     }
 
@@ -156,13 +381,21 @@ impl CmpLogRuntime {
         Self {
             save_registers: None,
             restore_registers: None,
+            #[cfg(feature = "cmplog_extended_instrumentation")]
+            routines: HashSet::new(),
         }
     }
 
-    /// Call the external function that populates the `cmplog_map` with the relevant values
+    /// Call the external function that populates the `cmplog_map` with the relevant values.
+    /// The lowest byte of `shape_and_attr` is the AFL++ comparison attribute of this compare,
+    /// the second one its AFL++ shape, both used for the extended map.
     #[allow(clippy::unused_self)]
+    #[cfg_attr(
+        not(feature = "cmplog_extended_instrumentation"),
+        allow(unused_variables)
+    )]
     #[cfg(target_arch = "aarch64")]
-    extern "C" fn populate_lists(&mut self, op1: u64, op2: u64, retaddr: u64) {
+    extern "C" fn populate_lists(&mut self, op1: u64, op2: u64, retaddr: u64, shape_and_attr: u64) {
         // log::trace!(
         //     "entered populate_lists with: {:#02x}, {:#02x}, {:#02x}",
         //     op1, op2, retaddr
@@ -171,14 +404,29 @@ impl CmpLogRuntime {
 
         k &= (CMPLOG_MAP_W as u64) - 1;
 
+        #[cfg(feature = "cmplog_extended_instrumentation")]
+        unsafe {
+            __libafl_targets_cmplog_instructions_extended(
+                k as usize,
+                (shape_and_attr >> 8) as u8,
+                op1,
+                op2,
+                shape_and_attr as u8,
+            );
+        }
+
+        #[cfg(not(feature = "cmplog_extended_instrumentation"))]
         unsafe {
             __libafl_targets_cmplog_instructions(k as usize, 8, op1, op2);
         }
     }
 
+    /// Call the external function that populates the `cmplog_map` with the relevant values.
+    /// The lowest byte of `size_and_attr` is the operand size, the second one the AFL++
+    /// comparison attribute of this compare, used for the extended map.
     #[allow(clippy::unused_self)]
     #[cfg(target_arch = "x86_64")]
-    extern "C" fn populate_lists(size: u8, op1: u64, op2: u64, retaddr: u64) {
+    extern "C" fn populate_lists(size_and_attr: u32, op1: u64, op2: u64, retaddr: u64) {
         // log::trace!(
         //     "entered populate_lists with: {:#02x}, {:#02x}, {:#02x}",
         //     op1, op2, retaddr
@@ -187,11 +435,46 @@ impl CmpLogRuntime {
 
         k &= (CMPLOG_MAP_W as u64) - 1;
 
+        let size = size_and_attr as u8;
+
+        // AFL++ stores the operand size minus one as shape
+        #[cfg(feature = "cmplog_extended_instrumentation")]
+        unsafe {
+            __libafl_targets_cmplog_instructions_extended(
+                k as usize,
+                size.saturating_sub(1),
+                op1,
+                op2,
+                (size_and_attr >> 8) as u8,
+            );
+        }
+
+        #[cfg(not(feature = "cmplog_extended_instrumentation"))]
         unsafe {
             __libafl_targets_cmplog_instructions(k as usize, size, op1, op2);
         }
     }
 
+    /// Emit a callout in front of a call to a compare routine, logging the first two arguments as
+    /// routine operands to the extended map, if both point to readable memory.
+    #[cfg(feature = "cmplog_extended_instrumentation")]
+    #[allow(clippy::unused_self)]
+    pub fn emit_routine_callout(&self, address: u64, instruction: &StalkerInstruction) {
+        let k = (((address >> 4) ^ (address << 8)) & (CMPLOG_MAP_W as u64 - 1)) as usize;
+        instruction.put_callout(move |context| {
+            #[cfg(all(unix, target_arch = "x86_64"))]
+            let (arg1, arg2) = (context.rdi(), context.rsi());
+            #[cfg(all(windows, target_arch = "x86_64"))]
+            let (arg1, arg2) = (context.rcx(), context.rdx());
+            #[cfg(target_arch = "aarch64")]
+            let (arg1, arg2) = (context.reg(0), context.reg(1));
+
+            unsafe {
+                __libafl_targets_cmplog_routines_extended(k, arg1 as *const u8, arg2 as *const u8);
+            }
+        });
+    }
+
     /// Generate the instrumentation blobs for the current arch.
     #[allow(clippy::similar_names)]
     #[cfg(target_arch = "aarch64")]
@@ -218,9 +501,10 @@ impl CmpLogRuntime {
                 // jump to rust based population of the lists
                 ; mov x2, x0
                 ; adr x3, >done
-                ; ldr x4, >populate_lists
+                ; ldr x4, >shape_and_attr
+                ; ldr x5, >populate_lists
                 ; ldr x0, >self_addr
-                ; blr x4
+                ; blr x5
                 // restore the reg state before returning to the caller
                 ; .dword 0xd51b4218u32 as i32 // msr nzcv, x24
                 ; ldp x30, xzr, [sp], #0x10
@@ -243,6 +527,9 @@ impl CmpLogRuntime {
                 ; .qword core::ptr::from_mut(self) as *mut c_void as i64
                 ; populate_lists:
                 ; .qword  CmpLogRuntime::populate_lists as *mut c_void as i64
+                // patched for each compare, see `emit_comparison_handling`
+                ; shape_and_attr:
+                ; .qword 0
                 ; done:
             );};
         }
@@ -437,14 +724,14 @@ impl CmpLogRuntime {
 
         #[cfg(windows)]
         {
-            arg_reg_1 = Register::CL;
+            arg_reg_1 = Register::ECX;
             arg_reg_2 = Register::RDX;
             arg_reg_3 = Register::R8;
             arg_reg_4 = Register::R9;
         }
         #[cfg(unix)]
         {
-            arg_reg_1 = Register::DL;
+            arg_reg_1 = Register::EDI;
             arg_reg_2 = Register::RSI;
             arg_reg_3 = Register::RDX;
             arg_reg_4 = Register::RCX;
@@ -495,6 +782,14 @@ impl CmpLogRuntime {
                             size = 2;
                             inst = Code::Mov_r16_rm16;
                         }
+                        MemorySize::Float64 => {
+                            size = 8;
+                            inst = Code::Mov_r64_rm64;
+                        }
+                        MemorySize::Float32 => {
+                            size = 4;
+                            inst = Code::Mov_r32_rm32;
+                        }
                         _ => {
                             println!("Invalid memory size");
                             size = 4;
@@ -539,10 +834,46 @@ impl CmpLogRuntime {
                 CmplogOperandType::Imm(imm) => {
                     insts.push(Instruction::with1(Code::Pushq_imm32, *imm as i32).unwrap());
                 }
+                CmplogOperandType::Xmm(reg, size) => {
+                    set_size(*size);
+                    // there is no push for xmm registers, so make room and move it there instead
+                    insts.push(Instruction::with2(Code::Sub_rm64_imm8, Register::RSP, 8).unwrap());
+                    insts.push(
+                        Instruction::with2(
+                            Code::Movq_rm64_xmm,
+                            MemoryOperand::with_base(Register::RSP),
+                            *reg,
+                        )
+                        .unwrap(),
+                    );
+                    // the upper half of the register is not part of a single precision float
+                    if *size == 4 {
+                        insts.push(
+                            Instruction::with2(
+                                Code::Mov_rm32_imm32,
+                                MemoryOperand::with_base_displ(Register::RSP, 4),
+                                0u32,
+                            )
+                            .unwrap(),
+                        );
+                    }
+                }
             }
         }
 
-        insts.push(Instruction::with2(Code::Mov_r8_imm8, arg_reg_1, size_op as u64).unwrap());
+        #[cfg(feature = "cmplog_extended_instrumentation")]
+        let attr = cmp_attribute(address);
+        #[cfg(not(feature = "cmplog_extended_instrumentation"))]
+        let attr = 0;
+
+        insts.push(
+            Instruction::with2(
+                Code::Mov_r32_imm32,
+                arg_reg_1,
+                size_op as u32 | (u32::from(attr) << 8),
+            )
+            .unwrap(),
+        );
         insts.push(Instruction::with1(Code::Pop_r64, arg_reg_2).unwrap());
         insts.push(Instruction::with1(Code::Pop_r64, arg_reg_3).unwrap());
         insts.push(Instruction::with2(Code::Mov_r64_imm64, arg_reg_4, address).unwrap());
@@ -560,7 +891,7 @@ impl CmpLogRuntime {
     #[inline]
     pub fn emit_comparison_handling(
         &self,
-        _address: u64,
+        address: u64,
         output: &StalkerOutput,
         op1: &CmplogOperandType, //first operand of the comparsion
         op2: &CmplogOperandType, //second operand of the comparsion
@@ -624,8 +955,17 @@ impl CmpLogRuntime {
             },
         }
 
-        //call cmplog runtime to populate the values map
-        writer.put_bytes(self.ops_save_register_and_blr_to_populate());
+        #[cfg(feature = "cmplog_extended_instrumentation")]
+        let (shape, attr) = cmp_shape_and_attribute(address);
+        #[cfg(not(feature = "cmplog_extended_instrumentation"))]
+        let (shape, attr) = (7, 0);
+
+        //call cmplog runtime to populate the values map, the last qword of the blob is this compare's shape and attribute
+        let mut populate = self.ops_save_register_and_blr_to_populate().to_vec();
+        let attr_offset = populate.len() - 8;
+        populate[attr_offset..]
+            .copy_from_slice(&(u64::from(attr) | (u64::from(shape) << 8)).to_le_bytes());
+        writer.put_bytes(&populate);
 
         // Restore x0, x1
         assert!(writer.put_ldp_reg_reg_reg_offset(
@@ -660,10 +1000,13 @@ impl CmpLogRuntime {
         }
         let mut instruction = Instruction::default();
         decoder.decode_out(&mut instruction);
-        match instruction.mnemonic() {
-            iced_x86::Mnemonic::Cmp | iced_x86::Mnemonic::Sub => {} // continue
+        // scalar float compares only set the flags
+        let float_size = match instruction.mnemonic() {
+            iced_x86::Mnemonic::Cmp | iced_x86::Mnemonic::Sub => 0, // continue
+            iced_x86::Mnemonic::Comiss | iced_x86::Mnemonic::Ucomiss => 4,
+            iced_x86::Mnemonic::Comisd | iced_x86::Mnemonic::Ucomisd => 8,
             _ => return None,
-        }
+        };
 
         if instruction.op_count() != 2 {
             return None;
@@ -683,6 +1026,9 @@ impl CmpLogRuntime {
         }
 
         let op1 = match instruction.op0_kind() {
+            OpKind::Register if instruction.op0_register().is_xmm() => {
+                CmplogOperandType::Xmm(instruction.op0_register(), float_size)
+            }
             OpKind::Register => CmplogOperandType::Reg(instruction.op0_register()),
             OpKind::Immediate16
             | OpKind::Immediate32
@@ -706,6 +1052,9 @@ impl CmpLogRuntime {
         };
 
         let op2 = match instruction.op1_kind() {
+            OpKind::Register if instruction.op1_register().is_xmm() => {
+                CmplogOperandType::Xmm(instruction.op1_register(), float_size)
+            }
             OpKind::Register => CmplogOperandType::Reg(instruction.op1_register()),
             OpKind::Immediate16
             | OpKind::Immediate32
@@ -745,6 +1094,41 @@ impl CmpLogRuntime {
         Some((op1, op2, None, None))
     }
 
+    #[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "x86_64"))]
+    #[inline]
+    /// Check if the current instruction calls one of the [`CMPLOG_ROUTINES`], whose arguments we
+    /// log as routine operands
+    #[must_use]
+    pub fn cmplog_is_interesting_call(
+        &self,
+        _decoder: InstDecoder,
+        _address: u64,
+        instr: &Insn,
+    ) -> bool {
+        let mut decoder =
+            iced_x86::Decoder::with_ip(64, instr.bytes(), instr.address(), DecoderOptions::NONE);
+        if !decoder.can_decode() {
+            return false;
+        }
+        let call = decoder.decode();
+        call.mnemonic() == iced_x86::Mnemonic::Call
+            && call_target(&call).is_some_and(|target| self.routines.contains(&target))
+    }
+
+    #[cfg(all(feature = "cmplog_extended_instrumentation", target_arch = "aarch64"))]
+    #[inline]
+    /// Check if the current instruction calls one of the [`CMPLOG_ROUTINES`], whose arguments we
+    /// log as routine operands
+    #[must_use]
+    pub fn cmplog_is_interesting_call(
+        &self,
+        _decoder: InstDecoder,
+        address: u64,
+        instr: &Insn,
+    ) -> bool {
+        call_target(address, instr.bytes()).is_some_and(|target| self.routines.contains(&target))
+    }
+
     #[cfg(all(feature = "cmplog", target_arch = "aarch64"))]
     #[allow(clippy::similar_names, clippy::type_complexity)]
     #[inline]
@@ -879,3 +1263,87 @@ impl Default for CmpLogRuntime {
         Self::new()
     }
 }
+
+#[cfg(all(
+    test,
+    unix,
+    feature = "cmplog_extended_instrumentation",
+    target_arch = "x86_64"
+))]
+mod tests {
+    use super::{
+        cmp_attribute, CMP_ATTRIBUTE_IS_EQUAL, CMP_ATTRIBUTE_IS_FP, CMP_ATTRIBUTE_IS_GREATER,
+        CMP_ATTRIBUTE_IS_LESSER,
+    };
+
+    /// Copies `code` to new memory, so that it ends `end` bytes after the start of the mapping.
+    /// The mapping is followed by an inaccessible page. Returns the address of the code.
+    fn map_code(code: &[u8], end: usize) -> u64 {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let len = end.next_multiple_of(page_size);
+        unsafe {
+            let mapping = libc::mmap(
+                core::ptr::null_mut(),
+                len + page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            ) as *mut u8;
+            assert_ne!(mapping, libc::MAP_FAILED as *mut u8);
+            assert_eq!(
+                libc::mprotect(mapping.add(len).cast(), page_size, libc::PROT_NONE),
+                0
+            );
+            let start = mapping.add(end - code.len());
+            start.copy_from_nonoverlapping(code.as_ptr(), code.len());
+            start as u64
+        }
+    }
+
+    #[test]
+    fn test_cmp_attribute_at_end_of_mapping() {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        // cmp eax, ebx
+        let cmp = [0x39, 0xd8];
+        // ucomiss xmm0, xmm1
+        let ucomiss = [0x0f, 0x2e, 0xc1];
+        // comisd xmm0, xmm1
+        let comisd = [0x66, 0x0f, 0x2f, 0xc1];
+
+        for (code, attr) in [
+            // je
+            ([&cmp[..], &[0x74, 0x00]].concat(), CMP_ATTRIBUTE_IS_EQUAL),
+            // jg
+            ([&cmp[..], &[0x7f, 0x00]].concat(), CMP_ATTRIBUTE_IS_GREATER),
+            // jbe
+            (
+                [&cmp[..], &[0x76, 0x00]].concat(),
+                CMP_ATTRIBUTE_IS_LESSER | CMP_ATTRIBUTE_IS_EQUAL,
+            ),
+            // jne
+            ([&cmp[..], &[0x75, 0x00]].concat(), 0),
+            // ja
+            (
+                [&ucomiss[..], &[0x77, 0x00]].concat(),
+                CMP_ATTRIBUTE_IS_GREATER | CMP_ATTRIBUTE_IS_FP,
+            ),
+            // jb
+            (
+                [&comisd[..], &[0x72, 0x00]].concat(),
+                CMP_ATTRIBUTE_IS_LESSER | CMP_ATTRIBUTE_IS_FP,
+            ),
+        ] {
+            // The consumer is the last instruction before the inaccessible page
+            assert_eq!(cmp_attribute(map_code(&code, page_size)), attr);
+        }
+    }
+
+    #[test]
+    fn test_cmp_attribute_across_pages() {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        // cmp eax, ebx; je, with the je continuing on the next page
+        let address = map_code(&[0x39, 0xd8, 0x74, 0x00], page_size + 1);
+        assert_eq!(cmp_attribute(address), CMP_ATTRIBUTE_IS_EQUAL);
+    }
+}
@@ -1,23 +1,136 @@
 //! Functionality regarding binary-only coverage collection.
-use core::ptr::addr_of_mut;
-use std::{cell::RefCell, marker::PhantomPinned, pin::Pin, rc::Rc};
+use core::{
+    ptr::addr_of_mut,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomPinned,
+    pin::Pin,
+    rc::Rc,
+};
+#[cfg(unix)]
+use std::{ffi::c_void, sync::Mutex};
 
 #[cfg(target_arch = "aarch64")]
 use dynasmrt::DynasmLabelApi;
 use dynasmrt::{dynasm, DynasmApi};
-use frida_gum::{instruction_writer::InstructionWriter, stalker::StalkerOutput, ModuleMap};
+use frida_gum::{
+    instruction_writer::InstructionWriter,
+    stalker::{Instruction, StalkerOutput},
+    ModuleMap,
+};
 use libafl_bolts::hash_std;
 use rangemap::RangeMap;
 
 use crate::helper::FridaRuntime;
+#[cfg(unix)]
+use crate::pthread_hook::{self, EventType};
 
 /// (Default) map size for frida coverage reporting
 pub const MAP_SIZE: usize = 64 * 1024;
 
+/// Incremented before each execution, to tell threads of the current execution apart from
+/// threads left over from earlier ones.
+static EXECUTION_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// The threads spawned by a thread of an execution, and the epoch they inherit from it, until
+/// they start.
+#[cfg(unix)]
+static SPAWNED_THREADS: Mutex<Vec<(usize, u64)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// The previous location of this thread, if locations are tracked per thread, and the
+    /// execution epoch it belongs to
+    static PREVIOUS_PC: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+    /// The execution epoch this thread belongs to, `0` if it never took part in an execution
+    static THREAD_EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns `true` if the current thread is the harness thread of the current execution, or
+/// was (transitively) spawned by it during this execution.
+fn is_execution_thread() -> bool {
+    let epoch = THREAD_EPOCH.with(Cell::get);
+    epoch != 0 && epoch == EXECUTION_EPOCH.load(Ordering::Relaxed)
+}
+
+/// Lets spawned threads inherit the execution epoch of the spawning thread.
+#[cfg(unix)]
+fn track_spawned_thread(
+    event: EventType,
+    thread: libc::pthread_t,
+    _addr: *const c_void,
+    _size: libc::size_t,
+) {
+    match event {
+        // Dispatched on the spawning thread
+        EventType::Create => {
+            let epoch = THREAD_EPOCH.with(Cell::get);
+            if epoch != 0 {
+                SPAWNED_THREADS
+                    .lock()
+                    .unwrap()
+                    .push((thread as usize, epoch));
+            }
+        }
+        // Dispatched on the spawned thread
+        EventType::Start => {
+            let mut spawned = SPAWNED_THREADS.lock().unwrap();
+            if let Some(index) = spawned.iter().position(|(id, _)| *id == thread as usize) {
+                let (_, epoch) = spawned.swap_remove(index);
+                THREAD_EPOCH.with(|thread_epoch| thread_epoch.set(epoch));
+            }
+        }
+        EventType::Terminate | EventType::Destroy => {}
+    }
+}
+
+/// Updates the coverage map for the edge from the previous location to `loc`.
+///
+/// # Safety
+/// `map_ptr` has to point to a map of [`MAP_SIZE`] entries, and `prev_loc_ptr` to the shared
+/// previous location.
+unsafe fn record_edge(
+    map_ptr: *mut u8,
+    prev_loc_ptr: *mut u64,
+    loc: u64,
+    per_thread_previous_pc: bool,
+    ignore_foreign_threads: bool,
+) {
+    if ignore_foreign_threads && !is_execution_thread() {
+        return;
+    }
+    let epoch = EXECUTION_EPOCH.load(Ordering::Relaxed);
+    let previous_pc = if per_thread_previous_pc {
+        // Locations of earlier executions are stale, as if they were reset
+        match PREVIOUS_PC.with(Cell::get) {
+            (pc_epoch, pc) if pc_epoch == epoch => pc,
+            _ => 0,
+        }
+    } else {
+        unsafe { *prev_loc_ptr }
+    };
+
+    // Same as the inline code: increment the edge, skipping zero on overflow
+    unsafe {
+        let entry = map_ptr.add((previous_pc ^ loc) as usize);
+        let (value, overflow) = (*entry).overflowing_add(1);
+        *entry = value + u8::from(overflow);
+    }
+
+    if per_thread_previous_pc {
+        PREVIOUS_PC.with(|pc| pc.set((epoch, loc >> 1)));
+    } else {
+        unsafe { *prev_loc_ptr = loc >> 1 };
+    }
+}
+
 #[derive(Debug)]
 struct CoverageRuntimeInner {
     map: [u8; MAP_SIZE],
     previous_pc: u64,
+    per_thread_previous_pc: bool,
+    ignore_foreign_threads: bool,
     _pinned: PhantomPinned,
 }
 
@@ -40,12 +153,25 @@ impl FridaRuntime for CoverageRuntime {
         _ranges: &RangeMap<usize, (u16, String)>,
         _module_map: &Rc<ModuleMap>,
     ) {
+        #[cfg(unix)]
+        if self.0.borrow().ignore_foreign_threads {
+            Self::follow_spawned_threads(_gum);
+        }
     }
 
     fn pre_exec<I: libafl::inputs::Input + libafl::inputs::HasTargetBytes>(
         &mut self,
         _input: &I,
     ) -> Result<(), libafl::Error> {
+        let mut inner = self.0.borrow_mut();
+        // Every execution starts from the same previous location, regardless of earlier ones
+        inner.previous_pc = 0;
+        // Start a new epoch, which also resets the per-thread previous locations. Only the
+        // harness thread and its children belong to it.
+        let epoch = EXECUTION_EPOCH.fetch_add(1, Ordering::Relaxed) + 1;
+        if inner.ignore_foreign_threads {
+            THREAD_EPOCH.with(|thread_epoch| thread_epoch.set(epoch));
+        }
         Ok(())
     }
 
@@ -64,10 +190,48 @@ impl CoverageRuntime {
         Self(Rc::pin(RefCell::new(CoverageRuntimeInner {
             map: [0_u8; MAP_SIZE],
             previous_pc: 0,
+            per_thread_previous_pc: false,
+            ignore_foreign_threads: false,
             _pinned: PhantomPinned,
         })))
     }
 
+    /// Track the previous location separately for each thread, so that basic blocks of
+    /// concurrently running threads do not get hashed into bogus edges.
+    #[must_use]
+    pub fn with_per_thread_previous_pc(self) -> Self {
+        self.0.borrow_mut().per_thread_previous_pc = true;
+        self
+    }
+
+    /// Ignore coverage of all threads, except for the harness thread and the threads spawned
+    /// (transitively) by it during the current execution.
+    ///
+    /// Threads are followed through a `pthread_hook`, so on other platforms only the harness
+    /// thread is covered.
+    #[must_use]
+    pub fn with_foreign_threads_ignored(self) -> Self {
+        self.0.borrow_mut().ignore_foreign_threads = true;
+        self
+    }
+
+    /// Returns `true` if coverage has to be attributed to threads, which the inline
+    /// instrumentation does not support, see [`Self::emit_coverage_callout`].
+    #[must_use]
+    pub fn is_thread_aware(&self) -> bool {
+        let inner = self.0.borrow();
+        inner.per_thread_previous_pc || inner.ignore_foreign_threads
+    }
+
+    /// Install a [`pthread_hook`], so that spawned threads inherit the execution epoch of the
+    /// spawning thread.
+    #[cfg(unix)]
+    fn follow_spawned_threads(_gum: &frida_gum::Gum) {
+        #[cfg(not(target_vendor = "apple"))]
+        pthread_hook::initialize(_gum);
+        unsafe { pthread_hook::install(track_spawned_thread) };
+    }
+
     /// Retrieve the coverage map pointer
     pub fn map_mut_ptr(&mut self) -> *mut u8 {
         self.0.borrow_mut().map.as_mut_ptr()
@@ -212,4 +376,157 @@ impl CoverageRuntime {
         let code = self.generate_inline_code(h64 & (MAP_SIZE as u64 - 1));
         writer.put_bytes(&code);
     }
+
+    /// Emits a callout updating the coverage map in front of `instruction`, the first
+    /// instruction of the current basic block.
+    ///
+    /// This is slower than the inline code of [`Self::emit_coverage_mapping`], but can look at
+    /// the current thread, as needed for [`Self::with_per_thread_previous_pc`] and
+    /// [`Self::with_foreign_threads_ignored`].
+    pub fn emit_coverage_callout(&mut self, address: u64, instruction: &Instruction) {
+        let loc = hash_std(&address.to_le_bytes()) & (MAP_SIZE as u64 - 1);
+        let mut borrow = self.0.borrow_mut();
+        let map_ptr = borrow.map.as_mut_ptr();
+        let prev_loc_ptr = addr_of_mut!(borrow.previous_pc);
+        let per_thread_previous_pc = borrow.per_thread_previous_pc;
+        let ignore_foreign_threads = borrow.ignore_foreign_threads;
+
+        instruction.put_callout(move |_context| unsafe {
+            record_edge(
+                map_ptr,
+                prev_loc_ptr,
+                loc,
+                per_thread_previous_pc,
+                ignore_foreign_threads,
+            );
+        });
+    }
+}
+
+/// The tests share the execution epoch, so they have to run sequentially.
+#[cfg(test)]
+mod tests {
+    use core::ptr::addr_of_mut;
+    #[cfg(unix)]
+    use std::{sync::mpsc::channel, thread};
+
+    #[cfg(unix)]
+    use frida_gum::Gum;
+    use libafl::inputs::BytesInput;
+    use serial_test::serial;
+
+    use super::{record_edge, CoverageRuntime, MAP_SIZE};
+    use crate::helper::FridaRuntime;
+
+    /// Returns a function recording the edges to the basic block at the given location, like
+    /// the callout of the block, which can be sent to other threads
+    fn edge_recorder(runtime: &CoverageRuntime) -> impl Fn(u64) + Send + Clone {
+        let mut inner = runtime.0.borrow_mut();
+        let map_ptr = inner.map.as_mut_ptr() as usize;
+        let prev_loc_ptr = addr_of_mut!(inner.previous_pc) as usize;
+        let per_thread_previous_pc = inner.per_thread_previous_pc;
+        let ignore_foreign_threads = inner.ignore_foreign_threads;
+        move |loc| unsafe {
+            record_edge(
+                map_ptr as *mut u8,
+                prev_loc_ptr as *mut u64,
+                loc,
+                per_thread_previous_pc,
+                ignore_foreign_threads,
+            );
+        }
+    }
+
+    fn map(runtime: &CoverageRuntime) -> [u8; MAP_SIZE] {
+        runtime.0.borrow().map
+    }
+
+    fn assert_previous_pc_reset(mut runtime: CoverageRuntime) {
+        let record = edge_recorder(&runtime);
+        let input = BytesInput::new(vec![]);
+
+        runtime.pre_exec(&input).unwrap();
+        record(0x10);
+        record(0x22);
+        assert_eq!(map(&runtime)[(0x10 >> 1) ^ 0x22], 1);
+
+        // The first edge of the next execution starts from scratch
+        runtime.pre_exec(&input).unwrap();
+        record(0x22);
+        assert_eq!(map(&runtime)[0x22], 1);
+        assert_eq!(map(&runtime)[(0x22 >> 1) ^ 0x22], 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_previous_pc_reset() {
+        assert_previous_pc_reset(CoverageRuntime::new());
+    }
+
+    #[test]
+    #[serial]
+    fn test_per_thread_previous_pc_reset() {
+        assert_previous_pc_reset(CoverageRuntime::new().with_per_thread_previous_pc());
+    }
+
+    #[test]
+    #[serial]
+    fn test_per_thread_previous_pc() {
+        let mut runtime = CoverageRuntime::new().with_per_thread_previous_pc();
+        let record = edge_recorder(&runtime);
+        runtime.pre_exec(&BytesInput::new(vec![])).unwrap();
+
+        record(0x10);
+        let other_record = record.clone();
+        std::thread::spawn(move || other_record(0x22))
+            .join()
+            .unwrap();
+        record(0x34);
+
+        // The other thread does not see the location of this one, and the other way around
+        assert_eq!(map(&runtime)[0x22], 1);
+        assert_eq!(map(&runtime)[(0x10 >> 1) ^ 0x34], 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_foreign_threads_ignored() {
+        static GUM: std::sync::OnceLock<Gum> = std::sync::OnceLock::new();
+
+        let mut runtime = CoverageRuntime::new()
+            .with_per_thread_previous_pc()
+            .with_foreign_threads_ignored();
+        CoverageRuntime::follow_spawned_threads(GUM.get_or_init(|| unsafe { Gum::obtain() }));
+        let record = edge_recorder(&runtime);
+
+        // A thread left over from before the execution
+        let (start_foreign, foreign_started) = channel::<()>();
+        let foreign_record = record.clone();
+        let foreign = thread::spawn(move || {
+            foreign_started.recv().unwrap();
+            foreign_record(0x10);
+        });
+
+        runtime.pre_exec(&BytesInput::new(vec![])).unwrap();
+        record(0x22);
+        let spawned_record = record.clone();
+        thread::spawn(move || {
+            // Threads spawned by threads of the execution belong to it as well
+            let nested_record = spawned_record.clone();
+            thread::spawn(move || nested_record(0x34)).join().unwrap();
+            spawned_record(0x46);
+        })
+        .join()
+        .unwrap();
+        start_foreign.send(()).unwrap();
+        foreign.join().unwrap();
+
+        unsafe { crate::pthread_hook::reset() };
+        let map = map(&runtime);
+        assert_eq!(map[0x10], 0);
+        assert_eq!(map[0x22], 1);
+        assert_eq!(map[0x34], 1);
+        assert_eq!(map[0x46], 1);
+    }
 }
@@ -481,7 +481,11 @@ where
                     //     output.writer().pc()
                     // );
                     if let Some(rt) = runtimes.match_first_type_mut::<CoverageRuntime>() {
-                        rt.emit_coverage_mapping(address, output);
+                        if rt.is_thread_aware() {
+                            rt.emit_coverage_callout(address, &instruction);
+                        } else {
+                            rt.emit_coverage_mapping(address, output);
+                        }
                     }
 
                     if let Some(_rt) = runtimes.match_first_type_mut::<DrCovRuntime>() {
@@ -539,6 +543,11 @@ where
                             &special_case,
                         );
                     }
+
+                    #[cfg(feature = "cmplog_extended_instrumentation")]
+                    if rt.cmplog_is_interesting_call(decoder, address, instr) {
+                        rt.emit_routine_callout(address, &instruction);
+                    }
                 }
 
                 #[cfg(unix)]
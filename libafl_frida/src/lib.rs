@@ -76,8 +76,8 @@ pub mod windows_hooks;
 
 pub mod coverage_rt;
 
-/// Hooking thread lifecycle events. Seems like this is apple-only for now.
-#[cfg(target_vendor = "apple")]
+/// Hooking thread lifecycle events. Native on Apple platforms, emulated through `pthread_create` elsewhere.
+#[cfg(unix)]
 pub mod pthread_hook;
 
 #[cfg(feature = "cmplog")]
@@ -370,6 +370,7 @@ mod tests {
         asan::{
             asan_rt::AsanRuntime,
             errors::{AsanErrorsFeedback, AsanErrorsObserver},
+            hook_funcs::checked_libc_functions,
         },
         coverage_rt::CoverageRuntime,
         executor::FridaInProcessExecutor,
@@ -379,8 +380,32 @@ mod tests {
     static GUM: OnceLock<Gum> = OnceLock::new();
 
     unsafe fn test_asan(options: &FuzzerOptions) {
-        // The names of the functions to run
-        let tests = vec![
+        /// The `<function>_oob_read` and `<function>_oob_write` harness functions, for every
+        /// function that is checked for reads or writes, without the leading underscores
+        macro_rules! checked_function_tests {
+            ($(
+                $(#[$attr:meta])*
+                fn $name:ident($($param:ident : $param_type:ty),*) -> $return_type:ty {
+                    $($access:ident($ptr:expr, $len:expr);)*
+                }
+            )*) => {{
+                let mut tests = Vec::new();
+                $(
+                    $(#[$attr])*
+                    for access in [$(stringify!($access)),*] {
+                        let name = stringify!($name).trim_start_matches('_');
+                        let test = format!("{name}_oob_{access}");
+                        if !tests.contains(&test) {
+                            tests.push(test);
+                        }
+                    }
+                )*
+                tests
+            }};
+        }
+
+        // The names of the functions to run, and the number of solutions they should find
+        let mut tests: Vec<(String, usize)> = [
             ("LLVMFuzzerTestOneInput", 0),
             ("heap_oob_read", 1),
             ("heap_oob_write", 1),
@@ -390,7 +415,17 @@ mod tests {
             ("malloc_heap_oob_write", 1),
             ("malloc_heap_uaf_write", 1),
             ("malloc_heap_uaf_read", 1),
-        ];
+        ]
+        .into_iter()
+        .map(|(name, err_cnt)| (name.to_string(), err_cnt))
+        .chain(
+            checked_libc_functions!(checked_function_tests)
+                .into_iter()
+                .map(|name| (name, 1)),
+        )
+        .collect();
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        tests.push(("fgets_chk_negative_size".to_string(), 0));
 
         let lib = libloading::Library::new(options.clone().harness.unwrap()).unwrap();
 
@@ -1,10 +1,23 @@
 /// Rust bindings for Apple's [`pthread_introspection`](https://opensource.apple.com/source/libpthread/libpthread-218.20.1/pthread/introspection.h.auto.html) hooks.
 use std::sync::RwLock;
+#[cfg(not(target_vendor = "apple"))]
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver},
+    },
+};
+
+#[cfg(not(target_vendor = "apple"))]
+use frida_gum::{interceptor::Interceptor, Gum, Module, NativePointer};
+
 const PTHREAD_INTROSPECTION_THREAD_CREATE: libc::c_uint = 1;
 const PTHREAD_INTROSPECTION_THREAD_START: libc::c_uint = 2;
 const PTHREAD_INTROSPECTION_THREAD_TERMINATE: libc::c_uint = 3;
 const PTHREAD_INTROSPECTION_THREAD_DESTROY: libc::c_uint = 4;
 
+#[cfg(target_vendor = "apple")]
 #[allow(non_camel_case_types)]
 type pthread_introspection_hook_t = extern "C" fn(
     event: libc::c_uint,
@@ -13,14 +26,17 @@ type pthread_introspection_hook_t = extern "C" fn(
     size: libc::size_t,
 );
 
+#[cfg(target_vendor = "apple")]
 extern "C" {
     fn pthread_introspection_hook_install(
         hook: *const pthread_introspection_hook_t,
     ) -> *const pthread_introspection_hook_t;
 }
 
+#[cfg(target_vendor = "apple")]
 struct PreviousHook(*const pthread_introspection_hook_t);
 
+#[cfg(target_vendor = "apple")]
 impl PreviousHook {
     /// Dispatch to the previous hook, if it is set.
     pub unsafe fn dispatch(
@@ -60,13 +76,16 @@ impl PreviousHook {
 
 // At the time where the inner is called, it will have been set.
 // Mark it as sync.
+#[cfg(target_vendor = "apple")]
 unsafe impl Sync for PreviousHook {}
 
 // TODO: This could use a RwLock as well
+#[cfg(target_vendor = "apple")]
 static mut PREVIOUS_HOOK: PreviousHook = PreviousHook(std::ptr::null());
 
 static CURRENT_HOOK: RwLock<Option<PthreadIntrospectionHook>> = RwLock::new(None);
 
+#[cfg(target_vendor = "apple")]
 extern "C" fn pthread_introspection_hook(
     event: libc::c_uint,
     thread: libc::pthread_t,
@@ -79,6 +98,98 @@ extern "C" fn pthread_introspection_hook(
     unsafe { PREVIOUS_HOOK.dispatch(event, thread, addr, size) };
 }
 
+/// Calls the current hook, if it is set
+#[cfg(not(target_vendor = "apple"))]
+fn dispatch(event: EventType, thread: libc::pthread_t) {
+    if let Some(ref hook) = *CURRENT_HOOK.read().unwrap() {
+        hook(event, thread, std::ptr::null(), 0);
+    }
+}
+
+/// The original `pthread_create`, as returned by the interceptor
+#[cfg(not(target_vendor = "apple"))]
+static ORIGINAL_PTHREAD_CREATE: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(not(target_vendor = "apple"))]
+type PthreadStartRoutine = extern "C" fn(*mut c_void) -> *mut c_void;
+
+#[cfg(not(target_vendor = "apple"))]
+type PthreadCreate = unsafe extern "C" fn(
+    *mut libc::pthread_t,
+    *const libc::pthread_attr_t,
+    PthreadStartRoutine,
+    *mut c_void,
+) -> i32;
+
+/// The start routine of a spawned thread, which waits until the `Create` event was dispatched
+#[cfg(not(target_vendor = "apple"))]
+struct ThreadStart {
+    start_routine: PthreadStartRoutine,
+    arg: *mut c_void,
+    created: Receiver<()>,
+}
+
+#[cfg(not(target_vendor = "apple"))]
+extern "C" fn thread_start_trampoline(data: *mut c_void) -> *mut c_void {
+    let data = unsafe { Box::from_raw(data as *mut ThreadStart) };
+    // The sender is dropped without sending if the spawning thread panicked, start anyway
+    let _ = data.created.recv();
+    let thread = unsafe { libc::pthread_self() };
+    dispatch(EventType::Start, thread);
+    let ret = (data.start_routine)(data.arg);
+    dispatch(EventType::Terminate, thread);
+    ret
+}
+
+#[cfg(not(target_vendor = "apple"))]
+unsafe extern "C" fn replacement_pthread_create(
+    thread: *mut libc::pthread_t,
+    attr: *const libc::pthread_attr_t,
+    start_routine: PthreadStartRoutine,
+    arg: *mut c_void,
+) -> i32 {
+    let original: PthreadCreate =
+        std::mem::transmute(ORIGINAL_PTHREAD_CREATE.load(Ordering::Relaxed));
+    let (created_sender, created) = sync_channel(1);
+    let data = Box::into_raw(Box::new(ThreadStart {
+        start_routine,
+        arg,
+        created,
+    }));
+    let ret = original(thread, attr, thread_start_trampoline, data as *mut c_void);
+    if ret == 0 {
+        dispatch(EventType::Create, *thread);
+        let _ = created_sender.send(());
+    } else {
+        drop(Box::from_raw(data));
+    }
+    ret
+}
+
+/// Replace `pthread_create`, to emulate the `pthread_introspection` hooks on platforms other
+/// than Apple's.
+///
+/// Only the [`EventType::Create`], [`EventType::Start`] and [`EventType::Terminate`] events are
+/// emulated, without addresses and sizes. The `Terminate` event is not dispatched for threads
+/// that call `pthread_exit`. This needs to be called once, before the hooks are [`install`]ed.
+#[cfg(not(target_vendor = "apple"))]
+pub fn initialize(gum: &Gum) {
+    if ORIGINAL_PTHREAD_CREATE.load(Ordering::Relaxed) != 0 {
+        return;
+    }
+    let mut interceptor = Interceptor::obtain(gum);
+    let pthread_create =
+        Module::find_export_by_name(None, "pthread_create").expect("Failed to find pthread_create");
+    match interceptor.replace(
+        pthread_create,
+        NativePointer(replacement_pthread_create as *mut c_void),
+        NativePointer(std::ptr::null_mut()),
+    ) {
+        Ok(original) => ORIGINAL_PTHREAD_CREATE.store(original.0 as usize, Ordering::Relaxed),
+        Err(err) => log::error!("Failed to hook pthread_create: {err:?}"),
+    }
+}
+
 /// Closure type for `pthread_introspection` hooks.
 pub type PthreadIntrospectionHook =
     Box<dyn Fn(EventType, libc::pthread_t, *const libc::c_void, libc::size_t) + Sync + Send>;
@@ -122,6 +233,8 @@ impl From<EventType> for libc::c_uint {
 }
 
 /// Set a `pthread_introspection` hook.
+///
+/// On platforms other than Apple's, [`initialize`] has to be called first.
 /// # Example
 /// ```
 ///# use libafl_frida::pthread_hook;
@@ -154,12 +267,15 @@ where
     let mut new_hook = CURRENT_HOOK.write().unwrap();
     *new_hook = Some(Box::new(hook));
 
-    let prev = unsafe { pthread_introspection_hook_install(pthread_introspection_hook as _) };
+    #[cfg(target_vendor = "apple")]
+    {
+        let prev = unsafe { pthread_introspection_hook_install(pthread_introspection_hook as _) };
 
-    // Allow because we're sure this isn't from a different code generation unit.
-    if !(prev).is_null() && prev != pthread_introspection_hook as _ {
-        unsafe {
-            PREVIOUS_HOOK.set(prev as *const pthread_introspection_hook_t);
+        // Allow because we're sure this isn't from a different code generation unit.
+        if !(prev).is_null() && prev != pthread_introspection_hook as _ {
+            unsafe {
+                PREVIOUS_HOOK.set(prev as *const pthread_introspection_hook_t);
+            }
         }
     }
 }
@@ -176,7 +292,14 @@ where
 /// # Safety
 /// Potential data race when if called at the same time as `install` or `reset` from another thread
 pub unsafe fn reset() {
-    unsafe { PREVIOUS_HOOK.reset() };
+    #[cfg(target_vendor = "apple")]
+    unsafe {
+        PREVIOUS_HOOK.reset();
+    };
+    #[cfg(not(target_vendor = "apple"))]
+    {
+        *CURRENT_HOOK.write().unwrap() = None;
+    }
 }
 
 /// The following tests fail if they are not run sequentially.
@@ -188,8 +311,19 @@ mod test {
         time::Duration,
     };
 
+    #[cfg(not(target_vendor = "apple"))]
+    use frida_gum::Gum;
     use serial_test::serial;
 
+    #[cfg(not(target_vendor = "apple"))]
+    static GUM: std::sync::OnceLock<Gum> = std::sync::OnceLock::new();
+
+    /// Replaces `pthread_create` on the platforms where the hooks are emulated
+    fn initialize() {
+        #[cfg(not(target_vendor = "apple"))]
+        super::initialize(GUM.get_or_init(|| unsafe { Gum::obtain() }));
+    }
+
     #[test]
     #[serial]
     fn test_nohook_thread_create() {
@@ -210,6 +344,7 @@ mod test {
         let triggered: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
 
         let inner_triggered = triggered.clone();
+        initialize();
         unsafe {
             super::install(move |event, _, _, _| {
                 if event == super::EventType::Create {
@@ -234,6 +369,7 @@ mod test {
         let triggered: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
 
         let inner_triggered = triggered.clone();
+        initialize();
         unsafe {
             super::install(move |event, _, _, _| {
                 if event == super::EventType::Start {
@@ -258,6 +394,7 @@ mod test {
         let triggered: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
 
         let inner_triggered = triggered.clone();
+        initialize();
         unsafe {
             super::install(move |event, _, _, _| {
                 if event == super::EventType::Start {
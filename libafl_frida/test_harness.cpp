@@ -1,5 +1,12 @@
+#include <dlfcn.h>
+#include <fcntl.h>
 #include <stdint.h>
+#include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
+#include <strings.h>
+#include <unistd.h>
+#include <wchar.h>
 #include <string>
 
 extern "C" int heap_uaf_read(const uint8_t *_data, size_t _size) {
@@ -57,6 +64,449 @@ extern "C" int malloc_heap_oob_write(const uint8_t *_data, size_t _size) {
   return 0;
 }
 
+// One over-read and one over-write test for every libc function that the ASan
+// runtime checks, named `<function>_oob_read` and `<function>_oob_write`.
+// The heap buffers hold `SIZE` characters, and the functions access twice as
+// many.
+#define SIZE 8
+
+#define HARNESS(name) extern "C" int name(const uint8_t *_data, size_t _size)
+
+// Looks up a libc function at runtime, so the compiler can neither inline it
+// nor replace it with a builtin that bypasses the hooks.
+template <typename F>
+static F *libc(const char *symbol) {
+  return reinterpret_cast<F *>(dlsym(RTLD_DEFAULT, symbol));
+}
+
+// A heap buffer of `n` characters, all set to `c`, and not terminated
+template <typename T>
+static T *heap_buffer(size_t n, T c) {
+  T *buffer = static_cast<T *>(malloc(n * sizeof(T)));
+  for (size_t i = 0; i < n; i++) {
+    buffer[i] = c;
+  }
+  return buffer;
+}
+
+// Fills `buffer` with a string of `n - 1` characters
+template <typename T>
+static void fill_string(T *buffer, size_t n) {
+  for (size_t i = 0; i < n - 1; i++) {
+    buffer[i] = static_cast<T>('A');
+  }
+  buffer[n - 1] = 0;
+}
+
+// Functions taking `(dest, src, n)`, which read and write up to `n` characters
+#define COPY_N_HARNESSES(name, symbol, T)                           \
+  HARNESS(name##_oob_write) {                                       \
+    T *dest = heap_buffer<T>(SIZE, 0);                              \
+    T src[2 * SIZE];                                                \
+    fill_string(src, 2 * SIZE);                                     \
+    libc<T *(T *, const T *, size_t)>(symbol)(dest, src, 2 * SIZE); \
+    free(dest);                                                     \
+    return 0;                                                       \
+  }                                                                 \
+  HARNESS(name##_oob_read) {                                        \
+    T *src = heap_buffer<T>(SIZE, 'A');                             \
+    T dest[4 * SIZE] = {0};                                         \
+    libc<T *(T *, const T *, size_t)>(symbol)(dest, src, 2 * SIZE); \
+    free(src);                                                      \
+    return 0;                                                       \
+  }
+
+// The fortified variants of `COPY_N_HARNESSES`, with an unchecked size
+#define COPY_N_CHK_HARNESSES(name, symbol, T)                        \
+  HARNESS(name##_oob_write) {                                        \
+    T *dest = heap_buffer<T>(SIZE, 0);                               \
+    T src[2 * SIZE];                                                 \
+    fill_string(src, 2 * SIZE);                                      \
+    libc<T *(T *, const T *, size_t, size_t)>(symbol)(dest, src,     \
+                                                      2 * SIZE, -1); \
+    free(dest);                                                      \
+    return 0;                                                        \
+  }                                                                  \
+  HARNESS(name##_oob_read) {                                         \
+    T *src = heap_buffer<T>(SIZE, 'A');                              \
+    T dest[4 * SIZE] = {0};                                          \
+    libc<T *(T *, const T *, size_t, size_t)>(symbol)(dest, src,     \
+                                                      2 * SIZE, -1); \
+    free(src);                                                       \
+    return 0;                                                        \
+  }
+
+// Functions taking `(dest, src)`, which copy or append a string
+#define COPY_HARNESSES(name, symbol, T)           \
+  HARNESS(name##_oob_write) {                     \
+    T *dest = heap_buffer<T>(SIZE, 0);            \
+    T src[2 * SIZE];                              \
+    fill_string(src, 2 * SIZE);                   \
+    libc<T *(T *, const T *)>(symbol)(dest, src); \
+    free(dest);                                   \
+    return 0;                                     \
+  }                                               \
+  HARNESS(name##_oob_read) {                      \
+    T *src = heap_buffer<T>(SIZE, 'A');           \
+    T dest[4 * SIZE] = {0};                       \
+    libc<T *(T *, const T *)>(symbol)(dest, src); \
+    free(src);                                    \
+    return 0;                                     \
+  }
+
+// The fortified variants of `COPY_HARNESSES`, with an unchecked size
+#define COPY_CHK_HARNESSES(name, symbol, T)                   \
+  HARNESS(name##_oob_write) {                                 \
+    T *dest = heap_buffer<T>(SIZE, 0);                        \
+    T src[2 * SIZE];                                          \
+    fill_string(src, 2 * SIZE);                               \
+    libc<T *(T *, const T *, size_t)>(symbol)(dest, src, -1); \
+    free(dest);                                               \
+    return 0;                                                 \
+  }                                                           \
+  HARNESS(name##_oob_read) {                                  \
+    T *src = heap_buffer<T>(SIZE, 'A');                       \
+    T dest[4 * SIZE] = {0};                                   \
+    libc<T *(T *, const T *, size_t)>(symbol)(dest, src, -1); \
+    free(src);                                                \
+    return 0;                                                 \
+  }
+
+// Functions comparing `n` characters of two buffers
+#define COMPARE_N_HARNESS(name, symbol, T)                             \
+  HARNESS(name##_oob_read) {                                           \
+    T *s1 = heap_buffer<T>(SIZE, 'A');                                 \
+    T s2[2 * SIZE];                                                    \
+    fill_string(s2, 2 * SIZE);                                         \
+    libc<int(const T *, const T *, size_t)>(symbol)(s1, s2, 2 * SIZE); \
+    free(s1);                                                          \
+    return 0;                                                          \
+  }
+
+// Functions comparing or searching two strings
+#define COMPARE_HARNESS(name, symbol, T, R)        \
+  HARNESS(name##_oob_read) {                       \
+    T *s1 = heap_buffer<T>(SIZE, 'A');             \
+    T s2[2 * SIZE];                                \
+    fill_string(s2, 2 * SIZE);                     \
+    libc<R(const T *, const T *)>(symbol)(s1, s2); \
+    free(s1);                                      \
+    return 0;                                      \
+  }
+
+// Functions searching a string for a character
+#define SEARCH_HARNESS(name, symbol, T, C)   \
+  HARNESS(name##_oob_read) {                 \
+    T *s = heap_buffer<T>(SIZE, 'A');        \
+    libc<T *(const T *, C)>(symbol)(s, 'Z'); \
+    free(s);                                 \
+    return 0;                                \
+  }
+
+// Functions searching `n` characters of a buffer for a character
+#define SEARCH_N_HARNESS(name, symbol, T, C)                   \
+  HARNESS(name##_oob_read) {                                   \
+    T *s = heap_buffer<T>(SIZE, 'A');                          \
+    libc<T *(const T *, C, size_t)>(symbol)(s, 'Z', 2 * SIZE); \
+    free(s);                                                   \
+    return 0;                                                  \
+  }
+
+// Functions reading a string, like `strlen` and `atoi`
+#define STRING_HARNESS(name, symbol, T, R) \
+  HARNESS(name##_oob_read) {               \
+    T *s = heap_buffer<T>(SIZE, '1');      \
+    libc<R(const T *)>(symbol)(s);         \
+    free(s);                               \
+    return 0;                              \
+  }
+
+// Functions setting `n` characters to a value
+#define SET_HARNESS(name, symbol, T, C)                   \
+  HARNESS(name##_oob_write) {                             \
+    T *dest = heap_buffer<T>(SIZE, 0);                    \
+    libc<T *(T *, C, size_t)>(symbol)(dest, 0, 2 * SIZE); \
+    free(dest);                                           \
+    return 0;                                             \
+  }
+
+// The fortified variants of `SET_HARNESS`, with an unchecked size
+#define SET_CHK_HARNESS(name, symbol, T, C)                           \
+  HARNESS(name##_oob_write) {                                         \
+    T *dest = heap_buffer<T>(SIZE, 0);                                \
+    libc<T *(T *, C, size_t, size_t)>(symbol)(dest, 0, 2 * SIZE, -1); \
+    free(dest);                                                       \
+    return 0;                                                         \
+  }
+
+// Functions zeroing `n` bytes
+#define ZERO_HARNESS(name, symbol)                      \
+  HARNESS(name##_oob_write) {                           \
+    char *dest = heap_buffer<char>(SIZE, 0);            \
+    libc<void(void *, size_t)>(symbol)(dest, 2 * SIZE); \
+    free(dest);                                         \
+    return 0;                                           \
+  }
+
+HARNESS(write_oob_read) {
+  char *buf = heap_buffer<char>(SIZE, 'A');
+  int fd = open("/dev/null", O_WRONLY);
+  libc<ssize_t(int, const void *, size_t)>("write")(fd, buf, 2 * SIZE);
+  close(fd);
+  free(buf);
+  return 0;
+}
+
+HARNESS(read_oob_write) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  int fd = open("/dev/zero", O_RDONLY);
+  libc<ssize_t(int, void *, size_t)>("read")(fd, buf, 2 * SIZE);
+  close(fd);
+  free(buf);
+  return 0;
+}
+
+HARNESS(fgets_oob_write) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  FILE *stream = fopen("/dev/zero", "r");
+  libc<char *(char *, int, FILE *)>("fgets")(buf, 2 * SIZE, stream);
+  fclose(stream);
+  free(buf);
+  return 0;
+}
+
+COMPARE_N_HARNESS(memcmp, "memcmp", char)
+COPY_N_HARNESSES(memcpy, "memcpy", char)
+#if !defined(__APPLE__)
+COPY_N_HARNESSES(mempcpy, "mempcpy", char)
+#endif
+COPY_N_HARNESSES(memmove, "memmove", char)
+
+HARNESS(memccpy_oob_write) {
+  char *dest = heap_buffer<char>(SIZE, 0);
+  char src[2 * SIZE];
+  fill_string(src, 2 * SIZE);
+  libc<void *(void *, const void *, int, size_t)>("memccpy")(dest, src, 'Z',
+                                                             2 * SIZE);
+  free(dest);
+  return 0;
+}
+
+HARNESS(memccpy_oob_read) {
+  char *src = heap_buffer<char>(SIZE, 'A');
+  char dest[4 * SIZE];
+  libc<void *(void *, const void *, int, size_t)>("memccpy")(dest, src, 'Z',
+                                                             2 * SIZE);
+  free(src);
+  return 0;
+}
+
+SET_HARNESS(memset, "memset", char, int)
+SEARCH_N_HARNESS(memchr, "memchr", char, int)
+#if !defined(__APPLE__)
+SEARCH_N_HARNESS(memrchr, "memrchr", char, int)
+#endif
+
+HARNESS(memmem_oob_read) {
+  char *haystack = heap_buffer<char>(SIZE, 'A');
+  libc<void *(const void *, size_t, const void *, size_t)>("memmem")(
+      haystack, 2 * SIZE, "Z", 1);
+  free(haystack);
+  return 0;
+}
+
+#if !defined(__ANDROID__)
+ZERO_HARNESS(bzero, "bzero")
+COMPARE_N_HARNESS(bcmp, "bcmp", char)
+#endif
+#if !defined(__ANDROID__) && !defined(__APPLE__)
+ZERO_HARNESS(explicit_bzero, "explicit_bzero")
+#endif
+
+SEARCH_HARNESS(strchr, "strchr", char, int)
+SEARCH_HARNESS(strrchr, "strrchr", char, int)
+COMPARE_HARNESS(strcasecmp, "strcasecmp", char, int)
+COMPARE_N_HARNESS(strncasecmp, "strncasecmp", char)
+COPY_HARNESSES(strcat, "strcat", char)
+COPY_N_HARNESSES(strncat, "strncat", char)
+COMPARE_HARNESS(strcmp, "strcmp", char, int)
+COMPARE_N_HARNESS(strncmp, "strncmp", char)
+COPY_HARNESSES(strcpy, "strcpy", char)
+COPY_N_HARNESSES(strncpy, "strncpy", char)
+COPY_HARNESSES(stpcpy, "stpcpy", char)
+COPY_N_HARNESSES(stpncpy, "stpncpy", char)
+STRING_HARNESS(strlen, "strlen", char, size_t)
+
+HARNESS(strnlen_oob_read) {
+  char *s = heap_buffer<char>(SIZE, 'A');
+  libc<size_t(const char *, size_t)>("strnlen")(s, 2 * SIZE);
+  free(s);
+  return 0;
+}
+
+COMPARE_HARNESS(strstr, "strstr", char, char *)
+COMPARE_HARNESS(strcasestr, "strcasestr", char, char *)
+STRING_HARNESS(atoi, "atoi", char, int)
+STRING_HARNESS(atol, "atol", char, long)
+STRING_HARNESS(atoll, "atoll", char, long long)
+
+STRING_HARNESS(wcslen, "wcslen", wchar_t, size_t)
+
+HARNESS(wcsnlen_oob_read) {
+  wchar_t *s = heap_buffer<wchar_t>(SIZE, L'A');
+  libc<size_t(const wchar_t *, size_t)>("wcsnlen")(s, 2 * SIZE);
+  free(s);
+  return 0;
+}
+
+COPY_HARNESSES(wcscpy, "wcscpy", wchar_t)
+COPY_N_HARNESSES(wcsncpy, "wcsncpy", wchar_t)
+COPY_HARNESSES(wcpcpy, "wcpcpy", wchar_t)
+COPY_N_HARNESSES(wcpncpy, "wcpncpy", wchar_t)
+COPY_HARNESSES(wcscat, "wcscat", wchar_t)
+COPY_N_HARNESSES(wcsncat, "wcsncat", wchar_t)
+COMPARE_HARNESS(wcscmp, "wcscmp", wchar_t, int)
+COMPARE_N_HARNESS(wcsncmp, "wcsncmp", wchar_t)
+SEARCH_HARNESS(wcschr, "wcschr", wchar_t, wchar_t)
+SEARCH_HARNESS(wcsrchr, "wcsrchr", wchar_t, wchar_t)
+COMPARE_HARNESS(wcsstr, "wcsstr", wchar_t, wchar_t *)
+COPY_N_HARNESSES(wmemcpy, "wmemcpy", wchar_t)
+COPY_N_HARNESSES(wmemmove, "wmemmove", wchar_t)
+#if !defined(__APPLE__)
+COPY_N_HARNESSES(wmempcpy, "wmempcpy", wchar_t)
+#endif
+SET_HARNESS(wmemset, "wmemset", wchar_t, wchar_t)
+COMPARE_N_HARNESS(wmemcmp, "wmemcmp", wchar_t)
+SEARCH_N_HARNESS(wmemchr, "wmemchr", wchar_t, wchar_t)
+
+#if defined(__APPLE__)
+// The `memset_pattern*` functions read a pattern of 4, 8 or 16 bytes
+#define MEMSET_PATTERN_HARNESSES(name, symbol, pattern_size)                   \
+  HARNESS(name##_oob_write) {                                                  \
+    char *dest = heap_buffer<char>(SIZE, 0);                                   \
+    char pattern[pattern_size] = {0};                                          \
+    libc<void(void *, const void *, size_t)>(symbol)(dest, pattern, 2 * SIZE); \
+    free(dest);                                                                \
+    return 0;                                                                  \
+  }                                                                            \
+  HARNESS(name##_oob_read) {                                                   \
+    char *pattern = heap_buffer<char>(pattern_size / 2, 0);                    \
+    char dest[2 * SIZE];                                                       \
+    libc<void(void *, const void *, size_t)>(symbol)(dest, pattern, 2 * SIZE); \
+    free(pattern);                                                             \
+    return 0;                                                                  \
+  }
+
+MEMSET_PATTERN_HARNESSES(memset_pattern4, "memset_pattern4", 4)
+MEMSET_PATTERN_HARNESSES(memset_pattern8, "memset_pattern8", 8)
+MEMSET_PATTERN_HARNESSES(memset_pattern16, "memset_pattern16", 16)
+#endif
+
+// The fortified variants are only exported by glibc
+#if defined(__GLIBC__)
+COPY_N_CHK_HARNESSES(memcpy_chk, "__memcpy_chk", char)
+COPY_N_CHK_HARNESSES(memmove_chk, "__memmove_chk", char)
+COPY_N_CHK_HARNESSES(mempcpy_chk, "__mempcpy_chk", char)
+SET_CHK_HARNESS(memset_chk, "__memset_chk", char, int)
+
+HARNESS(explicit_bzero_chk_oob_write) {
+  char *dest = heap_buffer<char>(SIZE, 0);
+  libc<void(void *, size_t, size_t)>("__explicit_bzero_chk")(dest, 2 * SIZE,
+                                                             -1);
+  free(dest);
+  return 0;
+}
+
+COPY_CHK_HARNESSES(strcpy_chk, "__strcpy_chk", char)
+COPY_CHK_HARNESSES(stpcpy_chk, "__stpcpy_chk", char)
+COPY_N_CHK_HARNESSES(strncpy_chk, "__strncpy_chk", char)
+COPY_N_CHK_HARNESSES(stpncpy_chk, "__stpncpy_chk", char)
+COPY_CHK_HARNESSES(strcat_chk, "__strcat_chk", char)
+COPY_N_CHK_HARNESSES(strncat_chk, "__strncat_chk", char)
+COPY_N_CHK_HARNESSES(wmemcpy_chk, "__wmemcpy_chk", wchar_t)
+COPY_N_CHK_HARNESSES(wmemmove_chk, "__wmemmove_chk", wchar_t)
+COPY_N_CHK_HARNESSES(wmempcpy_chk, "__wmempcpy_chk", wchar_t)
+SET_CHK_HARNESS(wmemset_chk, "__wmemset_chk", wchar_t, wchar_t)
+COPY_CHK_HARNESSES(wcscpy_chk, "__wcscpy_chk", wchar_t)
+COPY_CHK_HARNESSES(wcpcpy_chk, "__wcpcpy_chk", wchar_t)
+COPY_N_CHK_HARNESSES(wcsncpy_chk, "__wcsncpy_chk", wchar_t)
+COPY_N_CHK_HARNESSES(wcpncpy_chk, "__wcpncpy_chk", wchar_t)
+COPY_CHK_HARNESSES(wcscat_chk, "__wcscat_chk", wchar_t)
+COPY_N_CHK_HARNESSES(wcsncat_chk, "__wcsncat_chk", wchar_t)
+
+HARNESS(read_chk_oob_write) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  int fd = open("/dev/zero", O_RDONLY);
+  libc<ssize_t(int, void *, size_t, size_t)>("__read_chk")(fd, buf, 2 * SIZE,
+                                                           -1);
+  close(fd);
+  free(buf);
+  return 0;
+}
+
+HARNESS(pread_chk_oob_write) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  int fd = open("/dev/zero", O_RDONLY);
+  libc<ssize_t(int, void *, size_t, off_t, size_t)>("__pread_chk")(
+      fd, buf, 2 * SIZE, 0, -1);
+  close(fd);
+  free(buf);
+  return 0;
+}
+
+#define FGETS_CHK_HARNESS(name, symbol)                                  \
+  HARNESS(name##_oob_write) {                                            \
+    char *buf = heap_buffer<char>(SIZE, 0);                              \
+    FILE *stream = fopen("/dev/zero", "r");                              \
+    libc<char *(char *, size_t, int, FILE *)>(symbol)(buf, -1, 2 * SIZE, \
+                                                      stream);           \
+    fclose(stream);                                                      \
+    free(buf);                                                           \
+    return 0;                                                            \
+  }
+
+FGETS_CHK_HARNESS(fgets_chk, "__fgets_chk")
+FGETS_CHK_HARNESS(fgets_unlocked_chk, "__fgets_unlocked_chk")
+
+// A negative size is passed on to libc unchecked, it reads nothing
+HARNESS(fgets_chk_negative_size) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  FILE *stream = fopen("/dev/zero", "r");
+  libc<char *(char *, size_t, int, FILE *)>("__fgets_chk")(buf, SIZE, -1,
+                                                          stream);
+  fclose(stream);
+  free(buf);
+  return 0;
+}
+
+HARNESS(fread_chk_oob_write) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  FILE *stream = fopen("/dev/zero", "r");
+  libc<size_t(void *, size_t, size_t, size_t, FILE *)>("__fread_chk")(
+      buf, -1, 2, SIZE, stream);
+  fclose(stream);
+  free(buf);
+  return 0;
+}
+
+HARNESS(readlink_chk_oob_read) {
+  char *path = heap_buffer<char>(SIZE, 'A');
+  char buf[2 * SIZE];
+  libc<ssize_t(const char *, char *, size_t, size_t)>("__readlink_chk")(
+      path, buf, sizeof(buf), -1);
+  free(path);
+  return 0;
+}
+
+HARNESS(readlink_chk_oob_write) {
+  char *buf = heap_buffer<char>(SIZE, 0);
+  libc<ssize_t(const char *, char *, size_t, size_t)>("__readlink_chk")(
+      "/proc/self/exe", buf, 2 * SIZE, -1);
+  free(buf);
+  return 0;
+}
+#endif
+
 extern "C" int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size) {
   // abort();
   return 0;
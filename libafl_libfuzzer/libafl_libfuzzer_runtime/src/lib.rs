@@ -91,7 +91,7 @@ macro_rules! fuzz_with {
             inputs::{BytesInput, HasTargetBytes},
             mutators::{
                 GrimoireExtensionMutator, GrimoireRecursiveReplacementMutator, GrimoireRandomDeleteMutator,
-                GrimoireStringReplacementMutator, havoc_crossover, havoc_mutations, havoc_mutations_no_crossover,
+                GrimoireStringReplacementMutator, arbitrary_mutations, havoc_crossover, havoc_mutations, havoc_mutations_no_crossover,
                 I2SRandReplace, StdScheduledMutator, StringCategoryRandMutator, StringSubcategoryRandMutator,
                 StringCategoryTokenReplaceMutator, StringSubcategoryTokenReplaceMutator, Tokens, tokens_mutations
             },
@@ -284,6 +284,12 @@ macro_rules! fuzz_with {
             let std_power = StdPowerMutationalStage::new(std_mutator);
             let std_power = IfStage::new(|_, _, _, _| Ok(mutator_status.std_mutational.into()), (std_power, ()));
 
+            // Structure-aware mutations for harnesses decoding their input with `arbitrary`, like `fuzz_target!(|x: T| ...)`
+            let arbitrary_used = $options.arbitrary();
+            let arbitrary_mutator = StdScheduledMutator::new(arbitrary_mutations());
+            let arbitrary_power = StdPowerMutationalStage::new(arbitrary_mutator);
+            let arbitrary_power = IfStage::new(|_, _, _, _| Ok((arbitrary_used && mutator_status.std_mutational).into()), (arbitrary_power, ()));
+
             // for custom mutator and crossover, each have access to the LLVMFuzzerMutate -- but it appears
             // that this method doesn't normally offer stacked mutations where one may expect them
             // we offer stacked mutations since this appears to be expected; see:
@@ -422,6 +428,7 @@ macro_rules! fuzz_with {
                 i2s,
                 cm_i2s,
                 std_power,
+                arbitrary_power,
                 cm_power,
                 cm_std_power,
                 cc_std_power,
@@ -108,6 +108,7 @@ pub struct LibfuzzerOptions {
     timeout: Duration,
     grimoire: Option<bool>,
     unicode: bool,
+    arbitrary: bool,
     forks: Option<usize>,
     dict: Option<Tokens>,
     dirs: Vec<PathBuf>,
@@ -167,6 +168,10 @@ impl LibfuzzerOptions {
         self.unicode
     }
 
+    pub fn arbitrary(&self) -> bool {
+        self.arbitrary
+    }
+
     pub fn forks(&self) -> Option<usize> {
         self.forks
     }
@@ -236,6 +241,7 @@ struct LibfuzzerOptionsBuilder<'a> {
     timeout: Option<Duration>,
     grimoire: Option<bool>,
     unicode: Option<bool>,
+    arbitrary: bool,
     forks: Option<usize>,
     dict: Option<&'a str>,
     dirs: Vec<&'a str>,
@@ -299,6 +305,7 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                         }
                         "grimoire" => self.grimoire = Some(parse_or_bail!(name, value, u64) > 0),
                         "unicode" => self.unicode = Some(parse_or_bail!(name, value, u64) > 0),
+                        "arbitrary" => self.arbitrary = parse_or_bail!(name, value, u64) > 0,
                         "artifact_prefix" => {
                             self.artifact_prefix = Some(value);
                         }
@@ -372,6 +379,7 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
             timeout: self.timeout.unwrap_or(Duration::from_secs(1200)),
             grimoire: self.grimoire,
             unicode: self.unicode.unwrap_or(true),
+            arbitrary: self.arbitrary,
             forks: self.forks,
             dict: self.dict.map(|path| {
                 Tokens::from_file(path).expect("Couldn't load tokens from specified dictionary")
@@ -111,6 +111,8 @@ pub fn build() {
             is_bitfield: true,
         })
         .header(libafl_qemu_hdr.display().to_string())
+        // lqprintf pulls stdio in, the fuzzer does not need it
+        .clang_arg("-DLIBAFL_QEMU_NO_LQPRINTF")
         .generate()
         .expect("Exit bindings generation failed.")
         .write_to_file(&runtime_bindings_file)
@@ -44,7 +44,7 @@ typedef UINT64 libafl_word;
 #define LIBAFL_SYNC_EXIT_OPCODE 0x66f23a0f
 #define LIBAFL_BACKDOOR_OPCODE 0x44f23a0f
 
-#define LIBAFL_QEMU_HDR_VERSION_NUMBER 0111  // TODO: find a nice way to set it.
+#define LIBAFL_QEMU_HDR_VERSION_NUMBER 0112  // TODO: find a nice way to set it.
 
 typedef enum LibaflQemuCommand {
   LIBAFL_QEMU_COMMAND_START_VIRT = 0,
@@ -56,6 +56,10 @@ typedef enum LibaflQemuCommand {
   LIBAFL_QEMU_COMMAND_LOAD = 6,
   LIBAFL_QEMU_COMMAND_VERSION = 7,
   LIBAFL_QEMU_COMMAND_VADDR_FILTER_ALLOW = 8,
+  LIBAFL_QEMU_COMMAND_PAGE_RANGE_DENY = 9,
+  LIBAFL_QEMU_COMMAND_LOG = 10,
+  LIBAFL_QEMU_COMMAND_ASSERT = 11,
+  LIBAFL_QEMU_COMMAND_MAX_INPUT_SIZE = 12,
 } LibaflExit;
 
 typedef enum LibaflQemuEndStatus {
@@ -232,6 +236,15 @@ LIBAFL_DEFINE_FUNCTIONS(sync_exit, LIBAFL_SYNC_EXIT_OPCODE)
 // Generates backdoor functions
 LIBAFL_DEFINE_FUNCTIONS(backdoor, LIBAFL_BACKDOOR_OPCODE)
 
+// Avoid depending on string.h, it is not always available in the guest.
+static inline libafl_word _libafl_qemu_strlen(const char *s) {
+  libafl_word len = 0;
+  while (s[len]) {
+    len++;
+  }
+  return len;
+}
+
 /* === The private part ends here === */
 
 /* === The public part starts here === */
@@ -256,7 +269,63 @@ LIBAFL_DEFINE_FUNCTIONS(backdoor, LIBAFL_BACKDOOR_OPCODE)
 
 #define LIBAFL_QEMU_LOAD() _libafl_sync_exit_call0(LIBAFL_QEMU_COMMAND_LOAD)
 
-#define LIBAFL_QEMU_VERSION() _libafl_sync_exit_call0(LIBAFL_QEMU_COMMAND_VERSION)
+#define LIBAFL_QEMU_VERSION() \
+  _libafl_sync_exit_call1(LIBAFL_QEMU_COMMAND_VERSION, LIBAFL_QEMU_HDR_VERSION_NUMBER)
+
+#define LIBAFL_QEMU_VADDR_FILTER_ALLOW(vaddr_start, vaddr_end) \
+  _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_VADDR_FILTER_ALLOW, vaddr_start, vaddr_end)
+
+// Stop instrumenting the pages in [vaddr_start, vaddr_end)
+#define LIBAFL_QEMU_PAGE_RANGE_DENY(vaddr_start, vaddr_end) \
+  _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_PAGE_RANGE_DENY, vaddr_start, vaddr_end)
+
+// Forward a message of len bytes to the fuzzer log
+#define LIBAFL_QEMU_LOG(buf_vaddr, len) \
+  _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_LOG, (libafl_word)(buf_vaddr), len)
+
+// End the run with a crash labelled by reason (a NUL-terminated string) if cond does not hold
+#define LIBAFL_QEMU_ASSERT(cond, reason)                                      \
+  do {                                                                        \
+    if (!(cond)) {                                                            \
+      _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_ASSERT, (libafl_word)(reason), \
+                              _libafl_qemu_strlen(reason));                   \
+    }                                                                         \
+  } while (0)
+
+// Maximum size of the inputs the fuzzer will write to the guest
+#define LIBAFL_QEMU_MAX_INPUT_SIZE() \
+  _libafl_sync_exit_call0(LIBAFL_QEMU_COMMAND_MAX_INPUT_SIZE)
+
+/* printf-like logging to the fuzzer. Define LIBAFL_QEMU_NO_LQPRINTF if
+ * vsnprintf is not available in the guest. */
+#ifndef LIBAFL_QEMU_NO_LQPRINTF
+  #include <stdarg.h>
+  #include <stdio.h>
+
+  #ifndef LIBAFL_QEMU_PRINTF_MAX_SIZE
+    #define LIBAFL_QEMU_PRINTF_MAX_SIZE 4096
+  #endif
+
+static char _lqprintf_buffer[LIBAFL_QEMU_PRINTF_MAX_SIZE] = {0};
+
+static inline void lqprintf(const char *fmt, ...) {
+  va_list args;
+  va_start(args, fmt);
+  int res = vsnprintf(_lqprintf_buffer, LIBAFL_QEMU_PRINTF_MAX_SIZE, fmt, args);
+  va_end(args);
+
+  if (res < 0) {
+    return;
+  }
+
+  if (res >= LIBAFL_QEMU_PRINTF_MAX_SIZE) {
+    // The message got truncated, vsnprintf terminated it anyway
+    res = LIBAFL_QEMU_PRINTF_MAX_SIZE - 1;
+  }
+
+  LIBAFL_QEMU_LOG(_lqprintf_buffer, (libafl_word)res);
+}
+#endif
 
 /* === The public part ends here === */
 
@@ -99,7 +99,7 @@ pub const WINT_MIN: u32 = 0;
 pub const WINT_MAX: u32 = 4294967295;
 pub const LIBAFL_SYNC_EXIT_OPCODE: u32 = 1727150607;
 pub const LIBAFL_BACKDOOR_OPCODE: u32 = 1156725263;
-pub const LIBAFL_QEMU_HDR_VERSION_NUMBER: u32 = 73;
+pub const LIBAFL_QEMU_HDR_VERSION_NUMBER: u32 = 74;
 pub type __u_char = ::std::os::raw::c_uchar;
 pub type __u_short = ::std::os::raw::c_ushort;
 pub type __u_int = ::std::os::raw::c_uint;
@@ -225,6 +225,12 @@ pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_LOAD: LibaflQemuCommand = Libafl
 pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_VERSION: LibaflQemuCommand = LibaflQemuCommand(7);
 pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_VADDR_FILTER_ALLOW: LibaflQemuCommand =
     LibaflQemuCommand(8);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_PAGE_RANGE_DENY: LibaflQemuCommand =
+    LibaflQemuCommand(9);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_LOG: LibaflQemuCommand = LibaflQemuCommand(10);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_ASSERT: LibaflQemuCommand = LibaflQemuCommand(11);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_MAX_INPUT_SIZE: LibaflQemuCommand =
+    LibaflQemuCommand(12);
 impl ::std::ops::BitOr<LibaflQemuCommand> for LibaflQemuCommand {
     type Output = Self;
     #[inline]
@@ -1,19 +1,27 @@
 #[cfg(emulation_mode = "systemmode")]
 use std::collections::HashSet;
 use std::{
+    borrow::Cow,
     fmt::{Debug, Display, Formatter},
+    ops::Range,
     sync::OnceLock,
 };
 
 use enum_map::{enum_map, Enum, EnumMap};
 use libafl::{
+    corpus::Testcase,
+    events::EventFirer,
     executors::ExitKind,
+    feedbacks::Feedback,
     inputs::HasTargetBytes,
+    observers::ObserversTuple,
     state::{HasExecutions, State},
+    Error, HasMetadata,
 };
-use libafl_bolts::AsSlice;
+use libafl_bolts::{AsSlice, Named};
 use libafl_qemu_sys::{GuestAddr, GuestPhysAddr, GuestVirtAddr};
 use num_enum::{TryFromPrimitive, TryFromPrimitiveError};
+use serde::{Deserialize, Serialize};
 
 #[cfg(emulation_mode = "systemmode")]
 use crate::QemuInstrumentationPagingFilter;
@@ -27,6 +35,9 @@ use crate::{
 
 pub const VERSION: u64 = bindings::LIBAFL_QEMU_HDR_VERSION_NUMBER as u64;
 
+/// Messages sent by the guest are truncated to this size.
+pub const MAX_GUEST_MESSAGE_SIZE: GuestReg = 4096;
+
 mod bindings {
     #![allow(non_upper_case_globals)]
     #![allow(non_camel_case_types)]
@@ -54,6 +65,10 @@ pub enum NativeCommand {
     Version = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_VERSION.0 as u64, // Version of the bindings used in the target
     VaddrFilterAllowRange =
         bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_VADDR_FILTER_ALLOW.0 as u64, // Allow given address range
+    PageRangeDeny = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_PAGE_RANGE_DENY.0 as u64, // Deny given address range
+    Log = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_LOG.0 as u64, // Forward a message to the fuzzer log
+    Assert = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_ASSERT.0 as u64, // Crash with the given reason
+    MaxInputSize = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_MAX_INPUT_SIZE.0 as u64, // Maximum size of the inputs
 }
 
 #[derive(Debug, Clone, Enum, TryFromPrimitive)]
@@ -104,10 +119,30 @@ pub enum Command {
     #[cfg(emulation_mode = "systemmode")]
     PagingFilterCommand(PagingFilterCommand),
     AddressRangeFilterCommand(AddressRangeFilterCommand),
+    AddressRangeDenyCommand(AddressRangeDenyCommand),
+    LogCommand(LogCommand),
+    AssertCommand(AssertCommand),
+    MaxInputSizeCommand(MaxInputSizeCommand),
 }
 
 pub static EMU_EXIT_KIND_MAP: OnceLock<EnumMap<NativeExitKind, Option<ExitKind>>> = OnceLock::new();
 
+fn emu_exit_kind_map() -> &'static EnumMap<NativeExitKind, Option<ExitKind>> {
+    EMU_EXIT_KIND_MAP.get_or_init(|| {
+        enum_map! {
+            NativeExitKind::Unknown => None,
+            NativeExitKind::Ok      => Some(ExitKind::Ok),
+            NativeExitKind::Crash   => Some(ExitKind::Crash)
+        }
+    })
+}
+
+/// Turns a message sent by the guest into a [`String`], stopping at the first NUL byte.
+fn guest_message(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
 #[derive(Debug, Clone)]
 pub enum CommandError {
     UnknownCommand(GuestReg),
@@ -169,15 +204,7 @@ impl TryFrom<Qemu> for Command {
                 let native_exit_kind: Result<NativeExitKind, _> =
                     u64::from(native_exit_kind).try_into();
 
-                let exit_kind = native_exit_kind.ok().and_then(|k| {
-                    EMU_EXIT_KIND_MAP.get_or_init(|| {
-                        enum_map! {
-                            NativeExitKind::Unknown => None,
-                            NativeExitKind::Ok      => Some(ExitKind::Ok),
-                            NativeExitKind::Crash   => Some(ExitKind::Crash)
-                        }
-                    })[k]
-                });
+                let exit_kind = native_exit_kind.ok().and_then(|k| emu_exit_kind_map()[k]);
 
                 Command::EndCommand(EndCommand::new(exit_kind))
             }
@@ -217,6 +244,38 @@ impl TryFrom<Qemu> for Command {
                     QemuInstrumentationAddressRangeFilter::AllowList(vec![vaddr_start..vaddr_end]),
                 ))
             }
+            NativeCommand::PageRangeDeny => {
+                let vaddr_start: GuestAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg1])?;
+                let vaddr_end: GuestAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg2])?;
+
+                Command::AddressRangeDenyCommand(AddressRangeDenyCommand::new(
+                    vaddr_start..vaddr_end,
+                ))
+            }
+            NativeCommand::Log => {
+                let buf_addr: GuestVirtAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg1])?;
+                let len: GuestReg = qemu.read_reg(arch_regs_map[ExitArgs::Arg2])?;
+
+                Command::LogCommand(LogCommand::new(EmulatorMemoryChunk::virt(
+                    buf_addr,
+                    len.min(MAX_GUEST_MESSAGE_SIZE),
+                    qemu.current_cpu().unwrap(),
+                )))
+            }
+            NativeCommand::Assert => {
+                let reason_addr: GuestVirtAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg1])?;
+                let len: GuestReg = qemu.read_reg(arch_regs_map[ExitArgs::Arg2])?;
+
+                Command::AssertCommand(AssertCommand::new(
+                    EmulatorMemoryChunk::virt(
+                        reason_addr,
+                        len.min(MAX_GUEST_MESSAGE_SIZE),
+                        qemu.current_cpu().unwrap(),
+                    ),
+                    emu_exit_kind_map()[NativeExitKind::Crash],
+                ))
+            }
+            NativeCommand::MaxInputSize => Command::MaxInputSizeCommand(MaxInputSizeCommand),
         })
     }
 }
@@ -270,6 +329,24 @@ where
                 S,
                 StdEmulatorExitHandler<SM>,
             >>::usable_at_runtime(cmd),
+            Command::AddressRangeDenyCommand(cmd) => <AddressRangeDenyCommand as IsCommand<
+                QT,
+                S,
+                StdEmulatorExitHandler<SM>,
+            >>::usable_at_runtime(cmd),
+            Command::LogCommand(cmd) => {
+                <LogCommand as IsCommand<QT, S, StdEmulatorExitHandler<SM>>>::usable_at_runtime(cmd)
+            }
+            Command::AssertCommand(cmd) => {
+                <AssertCommand as IsCommand<QT, S, StdEmulatorExitHandler<SM>>>::usable_at_runtime(
+                    cmd,
+                )
+            }
+            Command::MaxInputSizeCommand(cmd) => <MaxInputSizeCommand as IsCommand<
+                QT,
+                S,
+                StdEmulatorExitHandler<SM>,
+            >>::usable_at_runtime(cmd),
         }
     }
 
@@ -340,6 +417,36 @@ where
                     ret_reg,
                 )
             }
+            Command::AddressRangeDenyCommand(cmd) => {
+                <AddressRangeDenyCommand as IsCommand<QT, S, StdEmulatorExitHandler<SM>>>::run(
+                    cmd,
+                    emu,
+                    qemu_executor_state,
+                    input,
+                    ret_reg,
+                )
+            }
+            Command::LogCommand(cmd) => <LogCommand as IsCommand<
+                QT,
+                S,
+                StdEmulatorExitHandler<SM>,
+            >>::run(
+                cmd, emu, qemu_executor_state, input, ret_reg
+            ),
+            Command::AssertCommand(cmd) => <AssertCommand as IsCommand<
+                QT,
+                S,
+                StdEmulatorExitHandler<SM>,
+            >>::run(
+                cmd, emu, qemu_executor_state, input, ret_reg
+            ),
+            Command::MaxInputSizeCommand(cmd) => <MaxInputSizeCommand as IsCommand<
+                QT,
+                S,
+                StdEmulatorExitHandler<SM>,
+            >>::run(
+                cmd, emu, qemu_executor_state, input, ret_reg
+            ),
         }
     }
 }
@@ -661,6 +768,161 @@ where
     }
 }
 
+/// Removes an address range from the instrumentation, keeping the ranges set up before.
+#[derive(Debug, Clone)]
+pub struct AddressRangeDenyCommand {
+    range: Range<GuestAddr>,
+}
+
+impl AddressRangeDenyCommand {
+    #[must_use]
+    pub fn new(range: Range<GuestAddr>) -> Self {
+        Self { range }
+    }
+
+    /// Adds the range to the address range filter of `qemu_helpers`.
+    pub fn apply<QT, S>(&self, qemu_helpers: &mut QT)
+    where
+        QT: HasInstrumentationFilter<QemuInstrumentationAddressRangeFilter, S>,
+    {
+        qemu_helpers.filter_mut().deny(self.range.clone());
+    }
+}
+
+impl<SM, QT, S> IsCommand<QT, S, StdEmulatorExitHandler<SM>> for AddressRangeDenyCommand
+where
+    SM: IsSnapshotManager,
+    QT: QemuHelperTuple<S> + StdInstrumentationFilter<S> + Debug,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+{
+    fn usable_at_runtime(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        emu: &Emulator<QT, S, StdEmulatorExitHandler<SM>>,
+        qemu_executor_state: &mut QemuExecutorState<QT, S>,
+        _input: &S::Input,
+        _ret_reg: Option<Regs>,
+    ) -> Result<Option<ExitHandlerResult>, ExitHandlerError> {
+        self.apply::<QT, S>(qemu_executor_state.hooks_mut().helpers_mut());
+        // Blocks of the range may already be translated with instrumentation
+        emu.qemu().flush_jit();
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogCommand {
+    message: EmulatorMemoryChunk,
+}
+
+impl<SM, QT, S> IsCommand<QT, S, StdEmulatorExitHandler<SM>> for LogCommand
+where
+    SM: IsSnapshotManager,
+    QT: QemuHelperTuple<S> + StdInstrumentationFilter<S> + Debug,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+{
+    fn usable_at_runtime(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        emu: &Emulator<QT, S, StdEmulatorExitHandler<SM>>,
+        qemu_executor_state: &mut QemuExecutorState<QT, S>,
+        _input: &S::Input,
+        _ret_reg: Option<Regs>,
+    ) -> Result<Option<ExitHandlerResult>, ExitHandlerError> {
+        let message = guest_message(&self.message.read(emu.qemu()));
+
+        log::info!("Guest: {message}");
+        qemu_executor_state.push_guest_log(message);
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssertCommand {
+    reason: EmulatorMemoryChunk,
+    exit_kind: Option<ExitKind>,
+}
+
+impl<SM, QT, S> IsCommand<QT, S, StdEmulatorExitHandler<SM>> for AssertCommand
+where
+    SM: IsSnapshotManager,
+    QT: QemuHelperTuple<S> + StdInstrumentationFilter<S> + Debug,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+{
+    fn usable_at_runtime(&self) -> bool {
+        false
+    }
+
+    fn run(
+        &self,
+        emu: &Emulator<QT, S, StdEmulatorExitHandler<SM>>,
+        qemu_executor_state: &mut QemuExecutorState<QT, S>,
+        _input: &S::Input,
+        _ret_reg: Option<Regs>,
+    ) -> Result<Option<ExitHandlerResult>, ExitHandlerError> {
+        let exit_kind = self.exit_kind.ok_or(ExitHandlerError::ExitKindNotFound)?;
+
+        // Read the reason before the snapshot gets restored
+        let reason = guest_message(&self.reason.read(emu.qemu()));
+        qemu_executor_state.set_crash_reason(reason);
+
+        let emu_exit_handler = emu.exit_handler().borrow_mut();
+
+        let snapshot_id = emu_exit_handler
+            .snapshot_id()
+            .ok_or(ExitHandlerError::SnapshotNotFound)?;
+
+        emu_exit_handler
+            .snapshot_manager_borrow_mut()
+            .restore(&snapshot_id, emu.qemu())?;
+
+        Ok(Some(ExitHandlerResult::EndOfRun(exit_kind)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MaxInputSizeCommand;
+
+impl<SM, QT, S> IsCommand<QT, S, StdEmulatorExitHandler<SM>> for MaxInputSizeCommand
+where
+    SM: IsSnapshotManager,
+    QT: QemuHelperTuple<S> + StdInstrumentationFilter<S> + Debug,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+{
+    fn usable_at_runtime(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        emu: &Emulator<QT, S, StdEmulatorExitHandler<SM>>,
+        _qemu_executor_state: &mut QemuExecutorState<QT, S>,
+        _input: &S::Input,
+        ret_reg: Option<Regs>,
+    ) -> Result<Option<ExitHandlerResult>, ExitHandlerError> {
+        let max_input_size = emu.exit_handler().borrow().max_input_size();
+
+        if let Some(reg) = ret_reg {
+            let max_input_size: GuestReg = max_input_size.try_into().unwrap_or(GuestReg::MAX);
+            emu.qemu().write_reg(reg, max_input_size).unwrap();
+        }
+
+        Ok(None)
+    }
+}
+
 impl VersionCommand {
     #[must_use]
     pub fn new(version: u64) -> Self {
@@ -700,10 +962,24 @@ impl Display for Command {
             Command::AddressRangeFilterCommand(addr_range_filter) => {
                 write!(f, "Addr range filter: {:?}", addr_range_filter.filter,)
             }
+            Command::AddressRangeDenyCommand(deny_command) => {
+                write!(f, "Deny addr range: {:?}", deny_command.range)
+            }
             #[cfg(emulation_mode = "systemmode")]
             Command::PagingFilterCommand(paging_filter) => {
                 write!(f, "Addr range filter: {:?}", paging_filter.filter,)
             }
+            Command::LogCommand(log_command) => {
+                write!(f, "Log message @{}", log_command.message.addr())
+            }
+            Command::AssertCommand(assert_command) => {
+                write!(
+                    f,
+                    "Assertion failure, reason @{}",
+                    assert_command.reason.addr()
+                )
+            }
+            Command::MaxInputSizeCommand(_) => write!(f, "Get max input size"),
         }
     }
 }
@@ -729,6 +1005,20 @@ impl InputCommand {
     }
 }
 
+impl LogCommand {
+    #[must_use]
+    pub fn new(message: EmulatorMemoryChunk) -> Self {
+        Self { message }
+    }
+}
+
+impl AssertCommand {
+    #[must_use]
+    pub fn new(reason: EmulatorMemoryChunk, exit_kind: Option<ExitKind>) -> Self {
+        Self { reason, exit_kind }
+    }
+}
+
 impl Display for InputCommand {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -739,3 +1029,250 @@ impl Display for InputCommand {
         )
     }
 }
+
+/// The reason the guest gave for crashing, through [`AssertCommand`].
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QemuCrashReasonMetadata {
+    pub reason: String,
+}
+
+impl QemuCrashReasonMetadata {
+    #[must_use]
+    pub fn new(reason: String) -> Self {
+        Self { reason }
+    }
+}
+
+libafl_bolts::impl_serdeany!(QemuCrashReasonMetadata);
+
+/// Moves the [`QemuCrashReasonMetadata`] of the last run to the testcase.
+///
+/// It never reports an input as interesting by itself, combine it with a `CrashFeedback` in the
+/// objective.
+#[derive(Debug, Clone, Default)]
+pub struct QemuCrashReasonFeedback;
+
+impl QemuCrashReasonFeedback {
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Feedback<S> for QemuCrashReasonFeedback
+where
+    S: State + HasMetadata,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if let Some(metadata) = state.metadata_map_mut().remove::<QemuCrashReasonMetadata>() {
+            testcase.add_metadata(*metadata);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        state.metadata_map_mut().remove::<QemuCrashReasonMetadata>();
+        Ok(())
+    }
+}
+
+impl Named for QemuCrashReasonFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("QemuCrashReasonFeedback");
+        &NAME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use libafl::{
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        feedbacks::{ConstFeedback, Feedback},
+        inputs::BytesInput,
+        state::{StdState, DEFAULT_MAX_SIZE},
+        HasMetadata,
+    };
+    use libafl_bolts::rands::StdRand;
+    use libafl_qemu_sys::GuestAddr;
+
+    use super::{
+        guest_message, AddressRangeDenyCommand, NativeCommand, QemuCrashReasonFeedback,
+        QemuCrashReasonMetadata,
+    };
+    use crate::{
+        HasInstrumentationFilter, IsSnapshotManager, Qemu, QemuEdgeCoverageHelper, QemuFilterList,
+        QemuInstrumentationAddressRangeFilter, SnapshotId, SnapshotManagerError,
+        StdEmulatorExitHandler,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    fn deny_all(
+        filter: QemuInstrumentationAddressRangeFilter,
+        ranges: &[Range<GuestAddr>],
+    ) -> QemuInstrumentationAddressRangeFilter {
+        let mut helpers = (QemuEdgeCoverageHelper::default(), ());
+        *HasInstrumentationFilter::<QemuInstrumentationAddressRangeFilter, TestState>::filter_mut(
+            &mut helpers,
+        ) = filter;
+        for range in ranges {
+            AddressRangeDenyCommand::new(range.clone()).apply::<_, TestState>(&mut helpers);
+        }
+        HasInstrumentationFilter::<QemuInstrumentationAddressRangeFilter, TestState>::filter(
+            &helpers,
+        )
+        .clone()
+    }
+
+    #[derive(Debug, Clone)]
+    struct NopSnapshotManager;
+
+    impl IsSnapshotManager for NopSnapshotManager {
+        fn save(&mut self, _qemu: &Qemu) -> SnapshotId {
+            SnapshotId::gen_unique_id()
+        }
+
+        fn restore(
+            &mut self,
+            _snapshot_id: &SnapshotId,
+            _qemu: &Qemu,
+        ) -> Result<(), SnapshotManagerError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_native_commands() {
+        assert!(matches!(
+            NativeCommand::try_from(9).unwrap(),
+            NativeCommand::PageRangeDeny
+        ));
+        assert!(matches!(
+            NativeCommand::try_from(10).unwrap(),
+            NativeCommand::Log
+        ));
+        assert!(matches!(
+            NativeCommand::try_from(11).unwrap(),
+            NativeCommand::Assert
+        ));
+        assert!(matches!(
+            NativeCommand::try_from(12).unwrap(),
+            NativeCommand::MaxInputSize
+        ));
+        assert!(NativeCommand::try_from(13).is_err());
+    }
+
+    #[test]
+    fn test_deny_commands_accumulate() {
+        let filter = deny_all(QemuFilterList::None, &[0x1000..0x2000, 0x4000..0x5000]);
+        let QemuFilterList::DenyList(denied) = &filter else {
+            panic!("expected a deny list, got {filter:?}");
+        };
+        assert_eq!(denied, &[0x1000..0x2000, 0x4000..0x5000]);
+
+        // Denying the same range twice does not grow the list
+        let filter = deny_all(filter, &[0x1000..0x2000]);
+        assert!(matches!(filter, QemuFilterList::DenyList(denied) if denied.len() == 2));
+    }
+
+    #[test]
+    fn test_deny_command_keeps_allow_list() {
+        #[allow(clippy::single_range_in_vec_init)]
+        let filter = deny_all(
+            QemuFilterList::AllowList(vec![0x1000..0x9000]),
+            &[0x2000..0x3000, 0x8000..0xa000],
+        );
+        let QemuFilterList::AllowList(allowed) = &filter else {
+            panic!("expected an allow list, got {filter:?}");
+        };
+        assert_eq!(allowed, &[0x1000..0x2000, 0x3000..0x8000]);
+    }
+
+    #[test]
+    fn test_guest_message() {
+        assert_eq!(guest_message(b"hello"), "hello");
+        assert_eq!(guest_message(b"hello\0garbage"), "hello");
+        assert_eq!(guest_message(b"\0"), "");
+        assert_eq!(guest_message(b"bad \xff byte"), "bad \u{fffd} byte");
+    }
+
+    #[test]
+    fn test_max_input_size() {
+        let exit_handler = StdEmulatorExitHandler::new(NopSnapshotManager);
+        assert_eq!(exit_handler.max_input_size(), DEFAULT_MAX_SIZE);
+
+        let exit_handler = StdEmulatorExitHandler::with_max_input_size(NopSnapshotManager, 0x200);
+        assert_eq!(exit_handler.max_input_size(), 0x200);
+    }
+
+    #[test]
+    fn test_crash_reason_feedback() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state: StdState<BytesInput, _, _, _> = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut crash_reason_feedback = QemuCrashReasonFeedback::new();
+
+        // The reason of the last run ends up in the testcase
+        state.add_metadata(QemuCrashReasonMetadata::new("out of bounds".into()));
+        let mut testcase = Testcase::new(BytesInput::new(vec![0]));
+        crash_reason_feedback
+            .append_metadata(&mut state, &mut mgr, &(), &mut testcase)
+            .unwrap();
+        assert_eq!(
+            testcase
+                .metadata::<QemuCrashReasonMetadata>()
+                .unwrap()
+                .reason,
+            "out of bounds"
+        );
+        assert!(!state.has_metadata::<QemuCrashReasonMetadata>());
+
+        // And does not leak into the next solution
+        state.add_metadata(QemuCrashReasonMetadata::new("stale".into()));
+        crash_reason_feedback
+            .discard_metadata(&mut state, &BytesInput::new(vec![0]))
+            .unwrap();
+        assert!(!state.has_metadata::<QemuCrashReasonMetadata>());
+    }
+}
@@ -15,7 +15,7 @@ use std::{
 use libafl::{
     executors::ExitKind,
     inputs::HasTargetBytes,
-    state::{HasExecutions, State},
+    state::{HasExecutions, State, DEFAULT_MAX_SIZE},
 };
 use libafl_bolts::os::unix_signals::Signal;
 use libafl_qemu_sys::{CPUArchStatePtr, GuestUsize};
@@ -81,6 +81,7 @@ pub enum ExitHandlerError {
     MultipleSnapshotDefinition,
     MultipleInputDefinition,
     SnapshotNotFound,
+    ExitKindNotFound,
 }
 
 #[derive(Debug, Clone)]
@@ -142,6 +143,19 @@ pub struct SnapshotId {
     id: u64,
 }
 
+impl SnapshotId {
+    #[cfg(any(emulation_mode = "systemmode", test))]
+    pub(crate) fn gen_unique_id() -> SnapshotId {
+        use core::sync::atomic::{AtomicU64, Ordering};
+
+        static UNIQUE_ID: AtomicU64 = AtomicU64::new(0);
+
+        let unique_id = UNIQUE_ID.fetch_add(1, Ordering::SeqCst);
+
+        SnapshotId { id: unique_id }
+    }
+}
+
 pub trait IsSnapshotManager: Debug + Clone {
     fn save(&mut self, qemu: &Qemu) -> SnapshotId;
     fn restore(
@@ -224,6 +238,7 @@ where
     snapshot_manager: RefCell<SM>,
     snapshot_id: OnceCell<SnapshotId>,
     input_location: OnceCell<InputLocation>,
+    max_input_size: usize,
 }
 
 impl<SM> StdEmulatorExitHandler<SM>
@@ -231,13 +246,25 @@ where
     SM: IsSnapshotManager,
 {
     pub fn new(snapshot_manager: SM) -> Self {
+        Self::with_max_input_size(snapshot_manager, DEFAULT_MAX_SIZE)
+    }
+
+    /// Create a new exit handler, reporting `max_input_size` to the guest when it asks for it.
+    pub fn with_max_input_size(snapshot_manager: SM, max_input_size: usize) -> Self {
         Self {
             snapshot_manager: RefCell::new(snapshot_manager),
             snapshot_id: OnceCell::new(),
             input_location: OnceCell::new(),
+            max_input_size,
         }
     }
 
+    /// The maximum size of the inputs, as reported to the guest.
+    #[must_use]
+    pub fn max_input_size(&self) -> usize {
+        self.max_input_size
+    }
+
     pub fn set_input_location(&self, input_location: InputLocation) -> Result<(), InputLocation> {
         self.input_location.set(input_location)
     }
@@ -1,8 +1,4 @@
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    sync::atomic::{AtomicU64, Ordering},
-};
+use std::{collections::HashMap, fmt::Debug};
 
 use libafl::state::{HasExecutions, State};
 use libafl_qemu_sys::GuestPhysAddr;
@@ -13,14 +9,6 @@ use crate::{
 };
 
 impl SnapshotId {
-    fn gen_unique_id() -> SnapshotId {
-        static UNIQUE_ID: AtomicU64 = AtomicU64::new(0);
-
-        let unique_id = UNIQUE_ID.fetch_add(1, Ordering::SeqCst);
-
-        SnapshotId { id: unique_id }
-    }
-
     fn inner(&self) -> u64 {
         self.id
     }
@@ -43,6 +43,8 @@ where
 {
     hooks: &'a mut QemuHooks<QT, S>,
     first_exec: bool,
+    guest_logs: Vec<String>,
+    crash_reason: Option<String>,
 }
 
 pub struct QemuExecutor<'a, H, OT, QT, S>
@@ -160,6 +162,8 @@ where
         Ok(QemuExecutorState {
             first_exec: true,
             hooks,
+            guest_logs: Vec::new(),
+            crash_reason: None,
         })
     }
 
@@ -176,6 +180,26 @@ where
     pub fn qemu(&self) -> &Qemu {
         self.hooks.qemu()
     }
+
+    /// Queue a message sent by the guest, to be logged by the fuzzer at the end of the run.
+    pub fn push_guest_log(&mut self, message: String) {
+        self.guest_logs.push(message);
+    }
+
+    /// Take the messages sent by the guest during the current run.
+    pub fn take_guest_logs(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.guest_logs)
+    }
+
+    /// Set the reason the guest gave for crashing during the current run.
+    pub fn set_crash_reason(&mut self, reason: String) {
+        self.crash_reason = Some(reason);
+    }
+
+    /// Take the reason the guest gave for crashing during the current run, if any.
+    pub fn take_crash_reason(&mut self) -> Option<String> {
+        self.crash_reason.take()
+    }
 }
 
 impl<'a, H, OT, QT, S> QemuExecutor<'a, H, OT, QT, S>
@@ -430,6 +454,8 @@ where
             state: QemuExecutorState {
                 first_exec: true,
                 hooks,
+                guest_logs: Vec::new(),
+                crash_reason: None,
             },
         })
     }
@@ -6,7 +6,7 @@ use core::{
 };
 
 use libafl::{
-    events::{EventFirer, EventRestarter},
+    events::{EventFirer, EventRestarter, LogSeverity},
     executors::{
         inprocess::{stateful::StatefulInProcessExecutor, HasInProcessHooks},
         Executor, ExitKind, HasObservers,
@@ -15,7 +15,7 @@ use libafl::{
     fuzzer::HasObjective,
     observers::{ObserversTuple, UsesObservers},
     state::{HasCorpus, HasExecutions, HasSolutions, State, UsesState},
-    Error,
+    Error, HasMetadata,
 };
 use libafl_bolts::tuples::RefIndexable;
 
@@ -23,7 +23,10 @@ use libafl_bolts::tuples::RefIndexable;
 use crate::executor::inproc_qemu_crash_handler;
 #[cfg(emulation_mode = "systemmode")]
 use crate::executor::{inproc_qemu_timeout_handler, BREAK_ON_TMOUT};
-use crate::{executor::QemuExecutorState, helpers::QemuHelperTuple, hooks::QemuHooks, Qemu};
+use crate::{
+    command::QemuCrashReasonMetadata, executor::QemuExecutorState, helpers::QemuHelperTuple,
+    hooks::QemuHooks, Qemu,
+};
 
 pub struct StatefulQemuExecutor<'a, H, OT, QT, S>
 where
@@ -142,7 +145,7 @@ impl<'a, EM, H, OT, OF, QT, S, Z> Executor<EM, Z> for StatefulQemuExecutor<'a, H
 where
     EM: EventFirer<State = S> + EventRestarter<State = S>,
     H: FnMut(&S::Input, &mut QemuExecutorState<'a, QT, S>) -> ExitKind,
-    S: State + HasExecutions + HasCorpus + HasSolutions,
+    S: State + HasExecutions + HasCorpus + HasSolutions + HasMetadata,
     OT: ObserversTuple<S>,
     OF: Feedback<S>,
     QT: QemuHelperTuple<S> + Debug,
@@ -156,6 +159,7 @@ where
         input: &Self::Input,
     ) -> Result<ExitKind, Error> {
         let qemu = Qemu::get().unwrap();
+        state.metadata_map_mut().remove::<QemuCrashReasonMetadata>();
         self.inner
             .exposed_executor_state_mut()
             .pre_exec::<Self, EM, OF, Z>(input, qemu);
@@ -168,6 +172,16 @@ where
                 &mut *self.inner.inner.observers_mut(),
                 &mut exit_kind,
             );
+
+        // Forward what the guest reported during the run
+        let executor_state = self.inner.exposed_executor_state_mut();
+        for message in executor_state.take_guest_logs() {
+            mgr.log(state, LogSeverity::Info, message)?;
+        }
+        if let Some(reason) = executor_state.take_crash_reason() {
+            state.add_metadata(QemuCrashReasonMetadata::new(reason));
+        }
+
         Ok(exit_kind)
     }
 }
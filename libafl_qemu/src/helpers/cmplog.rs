@@ -3,6 +3,8 @@ use capstone::{arch::BuildsCapstone, Capstone, InsnDetail};
 use hashbrown::HashMap;
 use libafl::{inputs::UsesInput, HasMetadata};
 use libafl_qemu_sys::GuestAddr;
+#[cfg(emulation_mode = "systemmode")]
+use libafl_qemu_sys::GuestPhysAddr;
 pub use libafl_targets::{
     cmps::{
         __libafl_targets_cmplog_instructions, __libafl_targets_cmplog_routines, CMPLOG_ENABLED,
@@ -11,6 +13,8 @@ pub use libafl_targets::{
 };
 use serde::{Deserialize, Serialize};
 
+#[cfg(emulation_mode = "systemmode")]
+use crate::helpers::QemuInstrumentationPagingFilter;
 #[cfg(emulation_mode = "usermode")]
 use crate::{capstone, qemu::ArchExtras, CallingConvention, Qemu};
 use crate::{
@@ -43,11 +47,20 @@ impl QemuCmpsMapMetadata {
 
 libafl_bolts::impl_serdeany!(QemuCmpsMapMetadata);
 
+#[cfg(emulation_mode = "usermode")]
+#[derive(Debug)]
+pub struct QemuCmpLogHelper {
+    filter: QemuInstrumentationAddressRangeFilter,
+}
+
+#[cfg(emulation_mode = "systemmode")]
 #[derive(Debug)]
 pub struct QemuCmpLogHelper {
     filter: QemuInstrumentationAddressRangeFilter,
+    paging_filter: QemuInstrumentationPagingFilter,
 }
 
+#[cfg(emulation_mode = "usermode")]
 impl QemuCmpLogHelper {
     #[must_use]
     pub fn new(filter: QemuInstrumentationAddressRangeFilter) -> Self {
@@ -60,12 +73,42 @@ impl QemuCmpLogHelper {
     }
 }
 
+#[cfg(emulation_mode = "systemmode")]
+impl QemuCmpLogHelper {
+    #[must_use]
+    pub fn new(
+        filter: QemuInstrumentationAddressRangeFilter,
+        paging_filter: QemuInstrumentationPagingFilter,
+    ) -> Self {
+        Self {
+            filter,
+            paging_filter,
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, paging_id: Option<GuestPhysAddr>) -> bool {
+        self.filter.allowed(addr) && self.paging_filter.allowed(paging_id)
+    }
+}
+
+#[cfg(emulation_mode = "usermode")]
 impl Default for QemuCmpLogHelper {
     fn default() -> Self {
         Self::new(QemuInstrumentationAddressRangeFilter::None)
     }
 }
 
+#[cfg(emulation_mode = "systemmode")]
+impl Default for QemuCmpLogHelper {
+    fn default() -> Self {
+        Self::new(
+            QemuInstrumentationAddressRangeFilter::None,
+            QemuInstrumentationPagingFilter::None,
+        )
+    }
+}
+
 impl<S: UsesInput> HasInstrumentationFilter<QemuInstrumentationAddressRangeFilter, S>
     for QemuCmpLogHelper
 {
@@ -78,6 +121,19 @@ impl<S: UsesInput> HasInstrumentationFilter<QemuInstrumentationAddressRangeFilte
     }
 }
 
+#[cfg(emulation_mode = "systemmode")]
+impl<S: UsesInput> HasInstrumentationFilter<QemuInstrumentationPagingFilter, S>
+    for QemuCmpLogHelper
+{
+    fn filter(&self) -> &QemuInstrumentationPagingFilter {
+        &self.paging_filter
+    }
+
+    fn filter_mut(&mut self) -> &mut QemuInstrumentationPagingFilter {
+        &mut self.paging_filter
+    }
+}
+
 impl<S> QemuHelper<S> for QemuCmpLogHelper
 where
     S: UsesInput + HasMetadata,
@@ -96,11 +152,20 @@ where
     }
 }
 
+#[cfg(emulation_mode = "usermode")]
+#[derive(Debug)]
+pub struct QemuCmpLogChildHelper {
+    filter: QemuInstrumentationAddressRangeFilter,
+}
+
+#[cfg(emulation_mode = "systemmode")]
 #[derive(Debug)]
 pub struct QemuCmpLogChildHelper {
     filter: QemuInstrumentationAddressRangeFilter,
+    paging_filter: QemuInstrumentationPagingFilter,
 }
 
+#[cfg(emulation_mode = "usermode")]
 impl QemuCmpLogChildHelper {
     #[must_use]
     pub fn new(filter: QemuInstrumentationAddressRangeFilter) -> Self {
@@ -113,12 +178,67 @@ impl QemuCmpLogChildHelper {
     }
 }
 
+#[cfg(emulation_mode = "systemmode")]
+impl QemuCmpLogChildHelper {
+    #[must_use]
+    pub fn new(
+        filter: QemuInstrumentationAddressRangeFilter,
+        paging_filter: QemuInstrumentationPagingFilter,
+    ) -> Self {
+        Self {
+            filter,
+            paging_filter,
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, paging_id: Option<GuestPhysAddr>) -> bool {
+        self.filter.allowed(addr) && self.paging_filter.allowed(paging_id)
+    }
+}
+
+#[cfg(emulation_mode = "usermode")]
 impl Default for QemuCmpLogChildHelper {
     fn default() -> Self {
         Self::new(QemuInstrumentationAddressRangeFilter::None)
     }
 }
 
+#[cfg(emulation_mode = "systemmode")]
+impl Default for QemuCmpLogChildHelper {
+    fn default() -> Self {
+        Self::new(
+            QemuInstrumentationAddressRangeFilter::None,
+            QemuInstrumentationPagingFilter::None,
+        )
+    }
+}
+
+impl<S: UsesInput> HasInstrumentationFilter<QemuInstrumentationAddressRangeFilter, S>
+    for QemuCmpLogChildHelper
+{
+    fn filter(&self) -> &QemuInstrumentationAddressRangeFilter {
+        &self.filter
+    }
+
+    fn filter_mut(&mut self) -> &mut QemuInstrumentationAddressRangeFilter {
+        &mut self.filter
+    }
+}
+
+#[cfg(emulation_mode = "systemmode")]
+impl<S: UsesInput> HasInstrumentationFilter<QemuInstrumentationPagingFilter, S>
+    for QemuCmpLogChildHelper
+{
+    fn filter(&self) -> &QemuInstrumentationPagingFilter {
+        &self.paging_filter
+    }
+
+    fn filter_mut(&mut self) -> &mut QemuInstrumentationPagingFilter {
+        &mut self.paging_filter
+    }
+}
+
 impl<S> QemuHelper<S> for QemuCmpLogChildHelper
 where
     S: UsesInput,
@@ -151,10 +271,23 @@ where
     S: UsesInput,
     QT: QemuHelperTuple<S>,
 {
-    if let Some(h) = hooks.match_helper_mut::<QemuCmpLogHelper>() {
+    if let Some(h) = hooks.match_helper::<QemuCmpLogHelper>() {
+        #[cfg(emulation_mode = "usermode")]
         if !h.must_instrument(pc) {
             return None;
         }
+
+        #[cfg(emulation_mode = "systemmode")]
+        {
+            let paging_id = hooks
+                .qemu()
+                .current_cpu()
+                .and_then(|cpu| cpu.current_paging_id());
+
+            if !h.must_instrument(pc, paging_id) {
+                return None;
+            }
+        }
     }
     let state = state.expect("The gen_unique_cmp_ids hook works only for in-process fuzzing");
     if state.metadata_map().get::<QemuCmpsMapMetadata>().is_none() {
@@ -183,10 +316,23 @@ where
     S: UsesInput,
     QT: QemuHelperTuple<S>,
 {
-    if let Some(h) = hooks.match_helper_mut::<QemuCmpLogChildHelper>() {
+    if let Some(h) = hooks.match_helper::<QemuCmpLogChildHelper>() {
+        #[cfg(emulation_mode = "usermode")]
         if !h.must_instrument(pc) {
             return None;
         }
+
+        #[cfg(emulation_mode = "systemmode")]
+        {
+            let paging_id = hooks
+                .qemu()
+                .current_cpu()
+                .and_then(|cpu| cpu.current_paging_id());
+
+            if !h.must_instrument(pc, paging_id) {
+                return None;
+            }
+        }
     }
     Some(hash_me(pc.into()) & (CMPLOG_MAP_W as u64 - 1))
 }
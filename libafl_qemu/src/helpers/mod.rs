@@ -25,6 +25,13 @@ pub mod cmplog;
 #[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
 pub use cmplog::QemuCmpLogHelper;
 
+#[cfg(emulation_mode = "systemmode")]
+pub mod process_filter;
+#[cfg(emulation_mode = "systemmode")]
+pub use process_filter::{
+    GuestMemoryProbe, ProcessNameProbe, ProcessProbe, QemuProcessFilterHelper,
+};
+
 #[cfg(all(emulation_mode = "usermode", feature = "injections"))]
 pub mod injections;
 #[cfg(all(emulation_mode = "usermode", feature = "injections"))]
@@ -245,6 +252,37 @@ where
 
 pub type QemuInstrumentationAddressRangeFilter = QemuFilterList<Vec<Range<GuestAddr>>>;
 
+impl QemuInstrumentationAddressRangeFilter {
+    /// Excludes `range` from the instrumentation, on top of the ranges filtered so far.
+    ///
+    /// A deny list gets the range added, an allow list gets it cut out of its ranges.
+    pub fn deny(&mut self, range: Range<GuestAddr>) {
+        if range.is_empty() {
+            return;
+        }
+        match self {
+            QemuFilterList::None => *self = QemuFilterList::DenyList(vec![range]),
+            QemuFilterList::DenyList(deny_list) => {
+                if !deny_list.contains(&range) {
+                    deny_list.push(range);
+                }
+            }
+            QemuFilterList::AllowList(allow_list) => {
+                *allow_list = allow_list
+                    .iter()
+                    .flat_map(|allowed| {
+                        [
+                            allowed.start..allowed.end.min(range.start),
+                            allowed.start.max(range.end)..allowed.end,
+                        ]
+                    })
+                    .filter(|allowed| !allowed.is_empty())
+                    .collect();
+            }
+        }
+    }
+}
+
 impl IsFilter for Vec<Range<GuestAddr>> {
     type FilterParameter = GuestAddr;
 
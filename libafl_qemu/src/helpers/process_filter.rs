@@ -0,0 +1,431 @@
+//! Restrict the instrumentation of a system-mode guest to the address space of a single process.
+//!
+//! The target address space (the paging id, i.e. `CR3` on x86) is usually not known upfront.
+//! The [`QemuProcessFilterHelper`] follows the guest while it translates and executes code, asks
+//! a [`ProcessProbe`] whether the current context belongs to the target process, and restricts
+//! the paging filters of the edge and cmplog helpers to the address spaces it found.
+//!
+//! Code shared between processes, like the kernel, is translated once for all of them. The
+//! helper therefore watches for context switches at execution time, and flushes the translated
+//! blocks whenever the guest switches between the target and another process after code was
+//! translated on the other side.
+
+use std::{collections::HashSet, fmt::Debug};
+
+use libafl::{inputs::UsesInput, HasMetadata};
+use libafl_qemu_sys::{GuestAddr, GuestPhysAddr};
+
+#[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
+use crate::helpers::{QemuCmpLogChildHelper, QemuCmpLogHelper};
+use crate::{
+    helpers::{
+        HasInstrumentationFilter, QemuEdgeCoverageChildHelper, QemuEdgeCoverageClassicHelper,
+        QemuEdgeCoverageHelper, QemuHelper, QemuHelperTuple, QemuInstrumentationPagingFilter,
+    },
+    hooks::{Hook, QemuHooks},
+    Qemu, CPU,
+};
+
+/// Decides if the guest is currently running in the context of the target process.
+pub trait ProcessProbe: Debug + 'static {
+    /// Returns `true` if `cpu` currently runs in the address space of the target.
+    fn is_target(&mut self, qemu: Qemu, cpu: CPU) -> bool;
+}
+
+/// Matches the current process by the bytes at a fixed virtual address.
+///
+/// Pick something only the target maps at this address, for example a marker in the data
+/// section of the target binary.
+#[derive(Debug, Clone)]
+pub struct GuestMemoryProbe {
+    addr: GuestAddr,
+    expected: Vec<u8>,
+}
+
+impl GuestMemoryProbe {
+    /// Creates a probe matching the address spaces that map `expected` at the virtual address
+    /// `addr`.
+    #[must_use]
+    pub fn new(addr: GuestAddr, expected: Vec<u8>) -> Self {
+        Self { addr, expected }
+    }
+
+    /// Returns `true` if `mem`, read from the probed address, holds the expected bytes.
+    fn matches(&self, mem: &[u8]) -> bool {
+        mem == self.expected
+    }
+}
+
+impl ProcessProbe for GuestMemoryProbe {
+    fn is_target(&mut self, _qemu: Qemu, cpu: CPU) -> bool {
+        let mut buf = vec![0; self.expected.len()];
+        unsafe {
+            cpu.read_mem(self.addr, &mut buf);
+        }
+        self.matches(&buf)
+    }
+}
+
+/// Matches the current process by its name, as stored by the guest kernel.
+///
+/// `locate` returns the guest address of the NUL-terminated name of the current process, for
+/// example `current->comm` on Linux. Note that the kernel may truncate the name (to 15 bytes on
+/// Linux).
+#[derive(Debug, Clone)]
+pub struct ProcessNameProbe {
+    name: Vec<u8>,
+    locate: fn(Qemu, CPU) -> Option<GuestAddr>,
+}
+
+impl ProcessNameProbe {
+    /// Creates a probe matching the processes called `name`.
+    ///
+    /// `locate` is called on every probe and returns the guest address of the name of the
+    /// current process, or `None` if it cannot be found in the current context.
+    #[must_use]
+    pub fn new(name: &str, locate: fn(Qemu, CPU) -> Option<GuestAddr>) -> Self {
+        Self {
+            name: name.as_bytes().to_vec(),
+            locate,
+        }
+    }
+
+    /// Returns `true` if `buf`, read from the name of the current process, holds the name
+    /// followed by its terminator.
+    fn matches(&self, buf: &[u8]) -> bool {
+        // Also check the terminator, so that we do not match on a prefix
+        buf.len() > self.name.len()
+            && buf[..self.name.len()] == self.name
+            && buf[self.name.len()] == 0
+    }
+}
+
+impl ProcessProbe for ProcessNameProbe {
+    fn is_target(&mut self, qemu: Qemu, cpu: CPU) -> bool {
+        let Some(addr) = (self.locate)(qemu, cpu) else {
+            return false;
+        };
+        let mut buf = vec![0xff; self.name.len() + 1];
+        unsafe {
+            cpu.read_mem(addr, &mut buf);
+        }
+        self.matches(&buf)
+    }
+}
+
+/// Tracks the address spaces of the target and the context switches between them.
+#[derive(Debug, Default)]
+struct TargetSpaces {
+    paging_ids: HashSet<GuestPhysAddr>,
+    /// The address space the guest ran in when we last looked
+    current: Option<GuestPhysAddr>,
+    /// If `current` belongs to the target
+    in_target: bool,
+    /// If blocks were translated outside of (index 0) or inside of (index 1) the target since
+    /// the last flush
+    translated: [bool; 2],
+    flush_pending: bool,
+}
+
+impl TargetSpaces {
+    /// Moves to `paging_id`, returns `true` if `is_target` found it to be a new target address
+    /// space. Only probes if the address space changed, or with `always`.
+    fn enter(
+        &mut self,
+        paging_id: Option<GuestPhysAddr>,
+        always: bool,
+        is_target: impl FnOnce() -> bool,
+    ) -> bool {
+        let switched = paging_id != self.current;
+        self.current = paging_id;
+
+        let mut found = false;
+        if let Some(paging_id) = paging_id {
+            if (switched || always) && !self.paging_ids.contains(&paging_id) && is_target() {
+                log::info!("Found target address space {paging_id:#x}");
+                self.paging_ids.insert(paging_id);
+                // Blocks translated so far were instrumented with the old filters
+                self.flush_pending = true;
+                found = true;
+            }
+        }
+
+        let in_target = paging_id.is_some_and(|paging_id| self.paging_ids.contains(&paging_id));
+        if in_target != self.in_target {
+            self.in_target = in_target;
+            // Shared blocks translated on the other side were instrumented for the other side
+            if self.translated[usize::from(!in_target)] {
+                self.flush_pending = true;
+            }
+        }
+        found
+    }
+
+    /// Records that a block gets translated in the current context
+    fn note_translation(&mut self) {
+        if !self.paging_ids.is_empty() {
+            self.translated[usize::from(self.in_target)] = true;
+        }
+    }
+
+    /// Returns `true` if the translated blocks must be flushed, and resets the flush state
+    fn take_flush(&mut self) -> bool {
+        if !self.flush_pending {
+            return false;
+        }
+        self.flush_pending = false;
+        self.translated = [false; 2];
+        true
+    }
+}
+
+/// Discovers the address spaces of the target process at runtime, and restricts the edge and
+/// cmplog helpers of the same [`QemuHooks`] to them.
+///
+/// The probe runs whenever a block gets translated in an address space that is not known to
+/// belong to the target yet. Once the first address space is found, it also runs whenever the
+/// guest switches to such an address space. Until then, the other helpers keep their own paging
+/// filters, and executed blocks are not hooked.
+#[derive(Debug)]
+pub struct QemuProcessFilterHelper<P> {
+    probe: P,
+    spaces: TargetSpaces,
+}
+
+impl<P> QemuProcessFilterHelper<P>
+where
+    P: ProcessProbe,
+{
+    /// Creates a helper that uses `probe` to find the address spaces of the target.
+    ///
+    /// Add it to the same [`QemuHooks`] as the edge and cmplog helpers it should restrict.
+    #[must_use]
+    pub fn new(probe: P) -> Self {
+        Self {
+            probe,
+            spaces: TargetSpaces::default(),
+        }
+    }
+
+    /// The address spaces found to belong to the target so far
+    #[must_use]
+    pub fn paging_ids(&self) -> &HashSet<GuestPhysAddr> {
+        &self.spaces.paging_ids
+    }
+
+    /// The paging filter allowing the target address spaces only, if any were found yet
+    #[must_use]
+    pub fn paging_filter(&self) -> QemuInstrumentationPagingFilter {
+        if self.spaces.paging_ids.is_empty() {
+            QemuInstrumentationPagingFilter::None
+        } else {
+            QemuInstrumentationPagingFilter::AllowList(self.spaces.paging_ids.clone())
+        }
+    }
+
+    /// Probes the current context, returns `true` if it is a newly found target address space
+    ///
+    /// Without `always`, the probe only runs if the guest switched to another address space
+    /// since the last call.
+    pub fn probe(&mut self, qemu: Qemu, cpu: CPU, always: bool) -> bool {
+        let probe = &mut self.probe;
+        self.spaces.enter(cpu.current_paging_id(), always, || {
+            probe.is_target(qemu, cpu)
+        })
+    }
+}
+
+impl<S, P> QemuHelper<S> for QemuProcessFilterHelper<P>
+where
+    S: UsesInput + HasMetadata,
+    P: ProcessProbe,
+{
+    const HOOKS_DO_SIDE_EFFECTS: bool = false;
+
+    fn first_exec<QT>(&self, hooks: &QemuHooks<QT, S>)
+    where
+        QT: QemuHelperTuple<S>,
+    {
+        hooks.blocks(
+            Hook::Function(gen_probe_process::<QT, S, P>),
+            Hook::Empty,
+            Hook::Function(exec_probe_process::<QT, S, P>),
+        );
+    }
+}
+
+fn set_paging_filter<H, QT, S>(
+    hooks: &mut QemuHooks<QT, S>,
+    filter: &QemuInstrumentationPagingFilter,
+) where
+    H: HasInstrumentationFilter<QemuInstrumentationPagingFilter, S> + 'static,
+    S: UsesInput,
+    QT: QemuHelperTuple<S>,
+{
+    if let Some(h) = hooks.match_helper_mut::<H>() {
+        *h.filter_mut() = filter.clone();
+    }
+}
+
+fn set_paging_filters<QT, S>(hooks: &mut QemuHooks<QT, S>, filter: &QemuInstrumentationPagingFilter)
+where
+    S: UsesInput,
+    QT: QemuHelperTuple<S>,
+{
+    set_paging_filter::<QemuEdgeCoverageHelper, QT, S>(hooks, filter);
+    set_paging_filter::<QemuEdgeCoverageChildHelper, QT, S>(hooks, filter);
+    set_paging_filter::<QemuEdgeCoverageClassicHelper, QT, S>(hooks, filter);
+    #[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
+    {
+        set_paging_filter::<QemuCmpLogHelper, QT, S>(hooks, filter);
+        set_paging_filter::<QemuCmpLogChildHelper, QT, S>(hooks, filter);
+    }
+}
+
+/// The block generation hook of the [`QemuProcessFilterHelper`].
+///
+/// Probes the address space the block is translated in, if it is not known to belong to the
+/// target yet, and restricts the paging filters of the other helpers as soon as a new target
+/// address space is found. It never flushes the translated blocks itself, this is left to
+/// [`exec_probe_process`].
+///
+/// Until a target address space is found, no paging filter is active and the execution hook is
+/// skipped. Afterwards it returns an id for every block, so that the execution hook sees every
+/// context switch. Finding the first target address space flushes all blocks, so none are left
+/// without the execution hook.
+pub fn gen_probe_process<QT, S, P>(
+    hooks: &mut QemuHooks<QT, S>,
+    _state: Option<&mut S>,
+    _pc: GuestAddr,
+) -> Option<u64>
+where
+    S: UsesInput + HasMetadata,
+    QT: QemuHelperTuple<S>,
+    P: ProcessProbe,
+{
+    let qemu = *hooks.qemu();
+    let cpu = qemu.current_cpu()?;
+
+    let h = hooks.match_helper_mut::<QemuProcessFilterHelper<P>>()?;
+    let found = h.probe(qemu, cpu, true);
+    h.spaces.note_translation();
+
+    if h.spaces.paging_ids.is_empty() {
+        return None;
+    }
+    if found {
+        let filter = h.paging_filter();
+        set_paging_filters(hooks, &filter);
+    }
+
+    Some(0)
+}
+
+/// The block execution hook of the [`QemuProcessFilterHelper`].
+///
+/// Detects context switches at execution time, probes address spaces the guest switches to, and
+/// flushes the translated blocks if they were instrumented for a different context.
+pub fn exec_probe_process<QT, S, P>(hooks: &mut QemuHooks<QT, S>, _state: Option<&mut S>, _id: u64)
+where
+    S: UsesInput + HasMetadata,
+    QT: QemuHelperTuple<S>,
+    P: ProcessProbe,
+{
+    let qemu = *hooks.qemu();
+    let Some(cpu) = qemu.current_cpu() else {
+        return;
+    };
+
+    let Some(h) = hooks.match_helper_mut::<QemuProcessFilterHelper<P>>() else {
+        return;
+    };
+    let found = h.probe(qemu, cpu, false);
+    let flush = h.spaces.take_flush();
+
+    if found {
+        let filter = h.paging_filter();
+        set_paging_filters(hooks, &filter);
+    }
+    if flush {
+        // QEMU defers the flush until no block is executing anymore
+        qemu.flush_jit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{GuestMemoryProbe, ProcessNameProbe, TargetSpaces};
+
+    #[test]
+    fn test_memory_probe_matches() {
+        let probe = GuestMemoryProbe::new(0x1000, b"MARK".to_vec());
+        assert!(probe.matches(b"MARK"));
+        assert!(!probe.matches(b"MARX"));
+    }
+
+    #[test]
+    fn test_name_probe_matches() {
+        let probe = ProcessNameProbe::new("target", |_, _| None);
+        assert!(probe.matches(b"target\0"));
+        assert!(!probe.matches(b"targets"));
+        assert!(!probe.matches(b"target"));
+        assert!(!probe.matches(b"other\0\0"));
+    }
+
+    #[test]
+    fn test_target_discovery() {
+        let mut spaces = TargetSpaces::default();
+
+        // Another process, nothing to restrict yet
+        assert!(!spaces.enter(Some(0x1000), true, || false));
+        spaces.note_translation();
+        assert!(!spaces.take_flush());
+
+        // The target shows up
+        assert!(spaces.enter(Some(0x2000), true, || true));
+        assert!(spaces.take_flush());
+        assert_eq!(spaces.paging_ids, HashSet::from([0x2000]));
+
+        // Known target spaces are not probed again
+        assert!(!spaces.enter(Some(0x2000), true, || panic!("probed again")));
+        assert_eq!(spaces.paging_ids.len(), 1);
+    }
+
+    #[test]
+    fn test_probe_only_on_switch() {
+        let mut spaces = TargetSpaces::default();
+        assert!(!spaces.enter(Some(0x1000), false, || false));
+        // Same address space at execution time, the probe does not run
+        assert!(!spaces.enter(Some(0x1000), false, || panic!("probed without a switch")));
+        // At translation time it does
+        assert!(spaces.enter(Some(0x1000), true, || true));
+    }
+
+    #[test]
+    fn test_flush_on_context_switch() {
+        let mut spaces = TargetSpaces::default();
+        assert!(spaces.enter(Some(0x2000), true, || true));
+        assert!(spaces.take_flush());
+
+        // No code translated in the target, leaving it needs no flush
+        assert!(!spaces.enter(Some(0x1000), false, || false));
+        assert!(!spaces.take_flush());
+
+        // Shared code translated in another process is not instrumented for the target
+        spaces.note_translation();
+        assert!(!spaces.enter(Some(0x2000), false, || true));
+        assert!(spaces.take_flush());
+
+        // Shared code translated in the target is instrumented for everyone
+        spaces.note_translation();
+        assert!(!spaces.enter(Some(0x1000), false, || false));
+        assert!(spaces.take_flush());
+
+        // Switching between two other processes keeps the blocks
+        spaces.note_translation();
+        assert!(!spaces.enter(Some(0x3000), false, || false));
+        assert!(!spaces.take_flush());
+    }
+}
@@ -1079,6 +1079,34 @@ impl EmulatorMemoryChunk {
 
         input_sliced.len().try_into().unwrap()
     }
+
+    /// Reads the whole memory chunk.
+    #[must_use]
+    pub fn read(&self, qemu: &Qemu) -> Vec<u8> {
+        let mut buf = vec![0; self.size.try_into().unwrap()];
+
+        match self.addr {
+            GuestAddrKind::Physical(hwaddr) => unsafe {
+                #[cfg(emulation_mode = "usermode")]
+                {
+                    // For now the default behaviour is to fall back to virtual addresses
+                    qemu.read_mem(hwaddr.try_into().unwrap(), &mut buf);
+                }
+                #[cfg(emulation_mode = "systemmode")]
+                {
+                    qemu.read_phys_mem(hwaddr, &mut buf);
+                }
+            },
+            GuestAddrKind::Virtual(vaddr) => unsafe {
+                self.cpu
+                    .as_ref()
+                    .unwrap()
+                    .read_mem(vaddr.try_into().unwrap(), &mut buf);
+            },
+        };
+
+        buf
+    }
 }
 
 #[cfg(feature = "python")]
@@ -24,7 +24,7 @@ use libafl::{
 use libafl_bolts::{
     core_affinity::Cores,
     rands::StdRand,
-    shmem::{ShMem, ShMemProvider, UnixShMemProvider},
+    shmem::{ShMem, ShMemProvider, ShMemPurpose, UnixShMemProvider},
     tuples::{tuple_list, Handled, Merge},
     AsSliceMut,
 };
@@ -123,7 +123,9 @@ impl<'a> ForkserverBytesCoverageSugar<'a> {
             let time_observer = time_observer.clone();
 
             // Coverage map shared between target and fuzzer
-            let mut shmem = shmem_provider_client.new_shmem(MAP_SIZE).unwrap();
+            let mut shmem = shmem_provider_client
+                .new_shmem_with_purpose(MAP_SIZE, ShMemPurpose::CoverageMap)
+                .unwrap();
             shmem.write_to_env("__AFL_SHM_ID").unwrap();
             let shmem_map = shmem.as_slice_mut();
 
@@ -121,6 +121,29 @@ void __libafl_targets_cmplog_routines_len(uintptr_t k, const uint8_t *ptr1,
 
   cmplog_routines_checked(k, ptr1, ptr2, len);
 }
+
+// Very generic cmplog instructions callback, aflpp style (with attributes)
+void __libafl_targets_cmplog_instructions_extended(uintptr_t k, uint8_t shape,
+                                                   uint64_t arg1, uint64_t arg2,
+                                                   uint8_t attr) {
+  cmplog_instructions_extended_checked(k, shape, arg1, arg2, attr);
+}
+
+// Very generic cmplog routines callback, aflpp style
+void __libafl_targets_cmplog_routines_extended(uintptr_t      k,
+                                               const uint8_t *ptr1,
+                                               const uint8_t *ptr2) {
+  if (!libafl_cmplog_enabled) { return; }
+
+  int l1, l2;
+  if ((l1 = area_is_valid(ptr1, CMPLOG_RTN_LEN)) <= 0 ||
+      (l2 = area_is_valid(ptr2, CMPLOG_RTN_LEN)) <= 0) {
+    return;
+  }
+  int len = MIN(l1, l2);
+
+  cmplog_routines_checked_extended(k, ptr1, ptr2, len);
+}
 /*
   CMPLOG Callback for instructions
 */
@@ -61,6 +61,23 @@ extern "C" {
     pub static mut libafl_cmplog_map_ptr: *mut CmpLogMap;
 }
 
+#[cfg(all(feature = "cmplog", feature = "cmplog_extended_instrumentation"))]
+// void __libafl_targets_cmplog_instructions_extended(uintptr_t k, uint8_t shape, uint64_t arg1, uint64_t arg2, uint8_t attr)
+extern "C" {
+    /// Logs an instruction into the AFL++-style extended map, together with its comparison attribute.
+    /// Note that `shape` is the operand size in bytes minus one, as in AFL++.
+    pub fn __libafl_targets_cmplog_instructions_extended(
+        k: usize,
+        shape: u8,
+        arg1: u64,
+        arg2: u64,
+        attr: u8,
+    );
+
+    /// Logs a routine into the AFL++-style extended map, if both pointers are readable
+    pub fn __libafl_targets_cmplog_routines_extended(k: usize, ptr1: *const u8, ptr2: *const u8);
+}
+
 #[cfg(feature = "cmplog")]
 pub use libafl_cmplog_map_ptr as CMPLOG_MAP_PTR;
 
@@ -6,7 +6,8 @@
 #include <string.h>
 #include <signal.h>
 #include <unistd.h>
-#ifndef USEMMAP
+// USEMMAP: the ids are POSIX shm names, USEMEMFD: the ids are inherited memfd fds
+#if !defined(USEMMAP) && !defined(USEMEMFD)
   #include <sys/shm.h>
 #else
   #include <sys/mman.h>
@@ -110,7 +111,21 @@ void __afl_map_shm(void) {
   char *id_str = getenv(SHM_ENV_VAR);
 
   if (id_str) {
-#ifdef USEMMAP
+#if defined(USEMEMFD)
+    /* the fd got inherited from the fuzzer, no need to open anything */
+    unsigned char *shm_base =
+        mmap(0, __afl_map_size, PROT_READ | PROT_WRITE, MAP_SHARED,
+             atoi(id_str), 0);
+
+    if (shm_base == MAP_FAILED) {
+      fprintf(stderr, "mmap() failed\n");
+      perror("mmap for map");
+      send_forkserver_error(FS_ERROR_MMAP);
+      exit(2);
+    }
+
+    __afl_area_ptr = shm_base;
+#elif defined(USEMMAP)
     const char    *shm_file_path = id_str;
     int            shm_fd = -1;
     unsigned char *shm_base = NULL;
@@ -169,7 +184,11 @@ static void map_input_shared_memory() {
   if (id_str) {
     uint8_t *map = NULL;
 
-#ifdef USEMMAP
+#if defined(USEMEMFD)
+    map = (uint8_t *)mmap(0, MAX_FILE + sizeof(uint32_t), PROT_READ, MAP_SHARED,
+                          atoi(id_str), 0);
+
+#elif defined(USEMMAP)
     const char *shm_file_path = id_str;
     int         shm_fd = -1;
 
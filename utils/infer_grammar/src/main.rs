@@ -0,0 +1,155 @@
+//! Infer a grammar from a seed corpus, and write it as Nautilus grammar and as Gramatron automaton.
+//!
+//! Without an oracle, all tokens and groups of the same shape are merged, which overgeneralizes.
+//! With `--oracle`, a candidate is valid if the oracle command exits successfully on it, like a parser of the format would.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode, Stdio},
+};
+
+use clap::Parser;
+use libafl::{generators::GrammarInference, inputs::NaiveTokenizer, Error};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "infer_grammar",
+    about = "Infer a Nautilus grammar and a Gramatron automaton from a seed corpus",
+    author = "Dominik Maier <domenukk@gmail.com>"
+)]
+struct Opt {
+    #[arg(short, long, help = "The directory with the seed corpus")]
+    corpus: PathBuf,
+
+    #[arg(
+        long,
+        help = "Write the grammar to this file, in the JSON format of Nautilus"
+    )]
+    nautilus: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Write the serialized Gramatron automaton to this file, as construct_automata does"
+    )]
+    automaton: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        help = "The command that decides if a candidate is valid. @@ is replaced by a file with the candidate, otherwise it is passed on stdin",
+        num_args = 1..,
+        allow_hyphen_values = true
+    )]
+    oracle: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "16",
+        help = "The max stack size of the automaton, recursion is unrolled up to this depth"
+    )]
+    limit: usize,
+
+    #[arg(
+        long,
+        default_value = "65536",
+        help = "The max number of automaton states"
+    )]
+    max_states: usize,
+
+    #[arg(long, default_value = "4096", help = "The max number of oracle runs")]
+    max_checks: usize,
+
+    #[arg(
+        long,
+        default_value = " ",
+        help = "The separator emitted between tokens"
+    )]
+    separator: String,
+}
+
+/// Run the oracle command on a candidate
+fn run_oracle(oracle: &[String], candidate: &[u8], file: &Path) -> Result<bool, Error> {
+    let uses_file = oracle.iter().any(|arg| arg == "@@");
+    if uses_file {
+        fs::write(file, candidate)?;
+    }
+    let mut child = Command::new(&oracle[0])
+        .args(oracle[1..].iter().map(|arg| {
+            if arg == "@@" {
+                file.to_str().unwrap()
+            } else {
+                arg
+            }
+        }))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    if !uses_file {
+        // The oracle may reject a candidate without reading all of it
+        let _ = stdin.write_all(candidate);
+    }
+    drop(stdin);
+    Ok(child.wait()?.success())
+}
+
+fn main() -> ExitCode {
+    let opt = Opt::parse();
+    match infer(&opt) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Could not infer a grammar: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn infer(opt: &Opt) -> Result<(), Error> {
+    let mut inference = GrammarInference::new(NaiveTokenizer::default())
+        .with_separator(opt.separator.clone())
+        .with_max_checks(opt.max_checks);
+    for entry in fs::read_dir(&opt.corpus)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let bytes = fs::read(&path)?;
+        if inference.add_sample(&bytes).is_err() {
+            println!("Skipping {}, it does not tokenize", path.display());
+        }
+    }
+    println!("Inferring a grammar from {} samples", inference.samples());
+
+    let file = std::env::temp_dir().join(format!("infer_grammar_{}", std::process::id()));
+    let mut runs = 0;
+    let grammar = inference.infer(|_, candidate| {
+        if opt.oracle.is_empty() {
+            return Ok(true);
+        }
+        runs += 1;
+        run_oracle(&opt.oracle, candidate, &file)
+    })?;
+    let _ = fs::remove_file(&file);
+    println!(
+        "Inferred {} nonterminals with {} oracle runs",
+        grammar.rules().len(),
+        runs
+    );
+
+    if let Some(nautilus) = &opt.nautilus {
+        grammar.write_nautilus_grammar(nautilus)?;
+    }
+    if let Some(automaton) = &opt.automaton {
+        grammar.write_automaton(automaton, opt.limit, opt.max_states)?;
+    }
+    if opt.nautilus.is_none() && opt.automaton.is_none() {
+        for rule in grammar.nautilus_rules() {
+            println!("{} -> {}", rule[0], rule[1]);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Compare the speed and ratio of the compressors used for LLMP and on-disk metadata
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use libafl_bolts::{
+    compress::{Compressor, GzipCompressor, Lz4Compressor, ZstdCompressor},
+    rands::{Rand, StdRand},
+};
+
+/// Creates `count` testcase-like samples: mostly structured text, with some random bytes
+fn samples(count: usize, len: usize) -> Vec<Vec<u8>> {
+    let mut rand = StdRand::with_seed(0);
+    (0..count)
+        .map(|i| {
+            let mut sample = format!("GET /fuzz/{i} HTTP/1.1\r\nHost: localhost\r\n").into_bytes();
+            while sample.len() < len {
+                if rand.below(4) == 0 {
+                    sample.push(rand.below(256) as u8);
+                } else {
+                    sample.extend_from_slice(b"X-Header: value\r\n");
+                }
+            }
+            sample.truncate(len);
+            sample
+        })
+        .collect()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let training = samples(1024, 256);
+    let dictionary = ZstdCompressor::train_dictionary(&training, 16 * 1024).unwrap();
+
+    let compressors: Vec<(&str, Box<dyn Compressor>)> = vec![
+        ("gzip", Box::new(GzipCompressor::new())),
+        ("zstd", Box::new(ZstdCompressor::new())),
+        (
+            "zstd_dict",
+            Box::new(ZstdCompressor::new().with_dictionary(dictionary)),
+        ),
+        ("lz4", Box::new(Lz4Compressor::new())),
+    ];
+
+    for len in [256, 64 * 1024] {
+        let buf = samples(1, len).pop().unwrap();
+
+        let mut group = c.benchmark_group(format!("compress_{len}"));
+        group.throughput(Throughput::Bytes(len as u64));
+        for (name, compressor) in &compressors {
+            println!(
+                "{name}: {len} bytes -> {} bytes",
+                compressor.compress(&buf).len()
+            );
+            group.bench_with_input(BenchmarkId::from_parameter(name), &buf, |b, buf| {
+                b.iter(|| compressor.compress(black_box(buf)));
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("decompress_{len}"));
+        group.throughput(Throughput::Bytes(len as u64));
+        for (name, compressor) in &compressors {
+            let compressed = compressor.compress(&buf);
+            group.bench_with_input(
+                BenchmarkId::from_parameter(name),
+                &compressed,
+                |b, compressed| {
+                    b.iter(|| compressor.decompress(black_box(compressed)).unwrap());
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,153 @@
+//! Inspect and replay LLMP message logs, as recorded by an `LlmpMsgLog` in a `LibAFL` broker.
+//!
+//! Replaying into a fresh broker broadcasts the recorded messages to all clients that connect to it,
+//! replaying as client feeds them into an already running broker, and on to its clients.
+
+use std::{collections::BTreeMap, path::PathBuf, process::ExitCode, thread, time::Duration};
+
+use clap::{Parser, Subcommand};
+use libafl_bolts::{
+    llmp::{LlmpBroker, LlmpClient, LlmpMsgHookResult},
+    llmp_replay::{replay, LlmpLoggedMsg, LlmpMsgLogReader, ReplayTiming},
+    shmem::{ShMemProvider, StdShMemProvider},
+    ClientId, Error,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "llmp_replay",
+    about = "Inspect and replay LLMP message logs recorded by a LibAFL broker",
+    author = "Dominik Maier <domenukk@gmail.com>"
+)]
+struct Opt {
+    #[arg(name = "LOG", help = "The message log to read")]
+    log: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+struct ReplayOpt {
+    #[arg(short, long, default_value = "1337", help = "The LLMP broker port")]
+    port: u16,
+
+    #[arg(
+        short,
+        long,
+        default_value = "0",
+        help = "Speedup relative to the recorded timing, 0 replays as fast as possible"
+    )]
+    speed: f64,
+
+    #[arg(short, long, help = "Only replay messages of this original sender")]
+    client: Option<u32>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print all messages and a summary per sender and tag
+    Dump,
+    /// Connect to a running broker as client and send the recorded messages
+    Client {
+        #[command(flatten)]
+        replay: ReplayOpt,
+    },
+    /// Start a fresh broker, wait for clients, and broadcast the recorded messages to them
+    Broker {
+        #[command(flatten)]
+        replay: ReplayOpt,
+
+        #[arg(
+            short,
+            long,
+            default_value = "1",
+            help = "The number of clients to wait for before replaying"
+        )]
+        wait_clients: usize,
+    },
+}
+
+impl ReplayOpt {
+    fn timing(&self) -> Result<ReplayTiming, Error> {
+        if self.speed == 0.0 {
+            Ok(ReplayTiming::AsFastAsPossible)
+        } else {
+            ReplayTiming::recorded(self.speed)
+        }
+    }
+
+    fn filter(&self) -> impl FnMut(&LlmpLoggedMsg) -> bool {
+        let client = self.client.map(ClientId);
+        move |msg| client.is_none() || client == Some(msg.client_id)
+    }
+}
+
+fn dump(log: LlmpMsgLogReader<impl std::io::Read>) -> Result<(), Error> {
+    let mut summary: BTreeMap<(u32, u32), (usize, usize)> = BTreeMap::new();
+    for msg in log {
+        let msg = msg?;
+        println!(
+            "{:>12.6}s client {:>4} tag {:#010x} flags {:#06x} len {}",
+            msg.time.as_secs_f64(),
+            msg.client_id.0,
+            msg.tag.0,
+            msg.flags.0,
+            msg.buf.len()
+        );
+        let entry = summary.entry((msg.client_id.0, msg.tag.0)).or_default();
+        entry.0 += 1;
+        entry.1 += msg.buf.len();
+    }
+    println!("\nclient tag          msgs bytes");
+    for ((client, tag), (msgs, bytes)) in summary {
+        println!("{client:>6} {tag:#010x} {msgs:>5} {bytes}");
+    }
+    Ok(())
+}
+
+fn run(opt: Opt) -> Result<(), Error> {
+    let log = LlmpMsgLogReader::open(&opt.log)?;
+    match opt.command {
+        Command::Dump => dump(log),
+        Command::Client { replay: replay_opt } => {
+            let timing = replay_opt.timing()?;
+            let mut client =
+                LlmpClient::create_attach_to_tcp(StdShMemProvider::new()?, replay_opt.port)?;
+            let count = replay(log, &mut client, timing, replay_opt.filter())?;
+            println!("Replayed {count} messages");
+            Ok(())
+        }
+        Command::Broker {
+            replay: replay_opt,
+            wait_clients,
+        } => {
+            let timing = replay_opt.timing()?;
+            let mut broker =
+                LlmpBroker::create_attach_to_tcp(StdShMemProvider::new()?, replay_opt.port)?;
+            let mut forward = |_, _, _, _: &[u8]| Ok(LlmpMsgHookResult::ForwardToClients);
+            println!(
+                "Waiting for {wait_clients} clients on port {}",
+                replay_opt.port
+            );
+            while broker.num_clients() < wait_clients {
+                broker.once(&mut forward)?;
+                thread::sleep(Duration::from_millis(10));
+            }
+            let count = replay(log, &mut broker, timing, replay_opt.filter())?;
+            println!("Replayed {count} messages, brokering until all clients exit");
+            broker.loop_forever(&mut forward, Some(Duration::from_millis(5)));
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Opt::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
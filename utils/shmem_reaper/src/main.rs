@@ -0,0 +1,98 @@
+//! Free shared memory segments leaked by crashed `LibAFL` processes.
+//!
+//! Reads a registry file written by a `RegisteredShMemProvider`,
+//! and frees all segments whose owner process is gone.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use libafl_bolts::shmem::{ShMemRegistry, SHMEM_REGISTRY_ENV};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "shmem_reaper",
+    about = "Free shared memory segments leaked by dead LibAFL processes",
+    author = "Dominik Maier <domenukk@gmail.com>"
+)]
+struct Opt {
+    #[arg(
+        short,
+        long,
+        env = SHMEM_REGISTRY_ENV,
+        name = "REGISTRY",
+        help = "The shmem registry file of the campaign"
+    )]
+    registry: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Free all segments whose owner is dead (the default)
+    Reap,
+    /// Print the current shared memory usage
+    Usage,
+    /// List all registered segments
+    List,
+}
+
+fn main() -> ExitCode {
+    let opt = Opt::parse();
+
+    let registry = match ShMemRegistry::new(&opt.registry) {
+        Ok(registry) => registry,
+        Err(err) => {
+            eprintln!("Could not open registry {}: {err}", opt.registry.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let res = match opt.command.unwrap_or(Command::Reap) {
+        Command::Reap => registry.reap().map(|reaped| {
+            let mut segments = 0;
+            for (i, entry) in reaped.iter().enumerate() {
+                // Entries of the same segment are next to each other
+                if i > 0 && reaped[i - 1].handle == entry.handle {
+                    continue;
+                }
+                let pids: Vec<String> = reaped
+                    .iter()
+                    .filter(|other| other.handle == entry.handle)
+                    .map(|other| other.pid.to_string())
+                    .collect();
+                println!(
+                    "Freed {} ({} bytes, {}) of dead pids {}",
+                    entry.handle,
+                    entry.size,
+                    entry.purpose,
+                    pids.join(", ")
+                );
+                segments += 1;
+            }
+            println!("Reaped {segments} segments");
+        }),
+        Command::Usage => registry.usage().map(|usage| println!("{usage}")),
+        Command::List => registry.entries().map(|entries| {
+            for entry in entries {
+                println!(
+                    "{}\t{}\t{}\t{}{}",
+                    entry.pid,
+                    entry.handle,
+                    entry.size,
+                    entry.purpose,
+                    if entry.owner_alive() { "" } else { "\t(dead)" }
+                );
+            }
+        }),
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}